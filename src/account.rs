@@ -0,0 +1,444 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{Cancel_Token, Kraken_API, Order, Order_Status, Position,
+             Shared_Store, Ws_Message};
+use  crate::reply;
+use  serde_json  as  JSN;
+use  std::collections::BTreeMap;
+use  std::sync::{Arc, Mutex};
+use  std::time::{Duration, Instant};
+
+
+
+/** A change to the account noticed by an [Account]. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  enum  Account_Event
+{
+    /** The balance of an asset has changed from the first figure to the
+        second; `None` means there was or is no balance at all. */
+    BALANCE  (String, Option<f64>, Option<f64>),
+
+    /** An order has appeared. */
+    ORDER_OPENED  (Order),
+
+    /** An open order has changed, usually by being part-filled. */
+    ORDER_CHANGED  (Order),
+
+    /** The order with this transaction ID is no longer open; it has been
+        filled, cancelled or has expired. */
+    ORDER_GONE  (String),
+
+    /** A margin position has been opened. */
+    POSITION_OPENED  (Position),
+
+    /** Part of a margin position has been closed. */
+    POSITION_CHANGED  (Position),
+
+    /** The margin position with this transaction ID has been closed. */
+    POSITION_CLOSED  (String)
+}
+
+
+
+/** A local copy of the state of the account (balances, open orders and,
+    optionally, margin positions), kept up to date by polling the REST
+    end-points and, if available, from the WebSockets openOrders feed, with a
+    callback for every change.
+
+    Strategy code can then read the local state as often as it likes without
+    making a call to the exchange each time.  To keep the account up to date
+    in a thread of its own, put it in an `Arc<Mutex<...>>` and use
+    [Account::spawn]; the lock is not held while the exchange is being asked
+    for the new state, but it is held while the callbacks are run, so they
+    must not try to take it themselves.
+
+    ```ignore
+    let  A  =  Arc::new (Mutex::new (KKN::Account::new ()));
+    A.lock ().unwrap ().on_event (|E|  println! ("{:?}", E));
+    let  (thread, stop)  =  KKN::Account::spawn (A.clone (), K,
+                                                 Duration::from_secs (10));
+    let  usd  =  A.lock ().unwrap ().balance ("ZUSD");
+    ```
+
+    Given a store with [Account::with_store], the open orders are kept
+    there after every change, so that a restarted program knows which
+    orders it had already seen: those still open are not reported as new,
+    and those which went while it was down are reported gone.  */
+
+#[derive(Default)]
+pub  struct  Account  {  balances:         BTreeMap<String, f64>,
+                         orders:           BTreeMap<String, Order>,
+                         positions:        BTreeMap<String, Position>,
+                         track_positions:  bool,
+                         refreshed:        Option<Instant>,
+                         store:            Option<(Shared_Store, String)>,
+                         on_event:         Vec<Event_Callback>,
+                         on_error:         Vec<Error_Callback>  }
+
+type  Event_Callback  =  Box<dyn FnMut (&Account_Event) + Send>;
+type  Error_Callback  =  Box<dyn FnMut (&str) + Send>;
+
+/*  What one poll of the exchange brings back. */
+type  Snapshot  =  (BTreeMap<String, f64>, Vec<Order>, Option<Vec<Position>>);
+
+
+
+impl  Account
+{
+    /** An empty account view, which has not yet been refreshed and does not
+        track margin positions. */
+    pub  fn  new  ()  ->  Self   {   Self::default ()   }
+
+    /** Whether to ask for margin positions at each refresh; this costs an
+        extra call to the exchange, so is off by default. */
+    pub  fn  track_positions  (&mut self,  track:  bool)  ->  &mut Self
+          {   self.track_positions  =  track;   self   }
+
+    /** Keep the open orders in `store` under `key`, and start from the ones
+        kept there if there are any.  A failure to keep them later on is
+        passed to the `on_error` callbacks. */
+    pub  fn  with_store  (&mut self,  store:  Shared_Store,  key:  &str)
+               ->  Result<&mut Self, String>
+    {
+        if  let  Some (B)  =  store.get (key) ?
+        {
+            let  V : JSN::Value  =  JSN::from_slice (&B)
+                                       .map_err (|E| format! ("{}: {}",
+                                                              key, E)) ?;
+            self.orders  =  V.as_object ()
+                             .ok_or (format! ("{}: not a map of orders", key)) ?
+                             .iter ()
+                             .map (|(txid, O)|  Ok ((txid.clone (),
+                                                     Order::from_json (txid,
+                                                                       O) ?)))
+                             .collect::<Result<_, String>> () ?;
+        }
+        self.store  =  Some ((store, key.to_string ()));
+        Ok (self)
+    }
+
+    /** Register a function to be called with every change to the account. */
+    pub  fn  on_event<F>  (&mut self,  f: F)  ->  &mut Self
+                 where  F:  FnMut (&Account_Event) + Send + 'static
+          {   self.on_event.push (Box::new (f));   self   }
+
+    /** Register a function to be called with the error message when a
+        refresh fails.  */
+    pub  fn  on_error<F>  (&mut self,  f: F)  ->  &mut Self
+                 where  F:  FnMut (&str) + Send + 'static
+          {   self.on_error.push (Box::new (f));   self   }
+
+    /** All non-zero balances, by asset. */
+    pub  fn  balances  (&self)  ->  &BTreeMap<String, f64>
+          {   &self.balances   }
+
+    /** The balance of one asset, by Kraken's name for it (e.g. "ZUSD"). */
+    pub  fn  balance  (&self,  asset:  &str)  ->  f64
+          {   self.balances.get (asset).copied ().unwrap_or (0.0)   }
+
+    /** The open orders, by transaction ID. */
+    pub  fn  orders  (&self)  ->  &BTreeMap<String, Order>
+          {   &self.orders   }
+
+    /** The open margin positions, by transaction ID (empty unless positions
+        are being tracked). */
+    pub  fn  positions  (&self)  ->  &BTreeMap<String, Position>
+          {   &self.positions   }
+
+    /** When the account was last refreshed from the REST end-points. */
+    pub  fn  last_refresh  (&self)  ->  Option<Instant>   {   self.refreshed   }
+
+
+    /*  Ask the exchange for the current state of the account. */
+    fn  fetch  (K:  &mut Kraken_API,  positions:  bool)
+           ->  Result<Snapshot, String>
+    {
+        Ok ((K.balances () ?,
+             K.current_orders () ?,
+             if  positions  {  Some (K.positions () ?)  }  else  {  None  }))
+    }
+
+
+    /** Bring the account up to date with the exchange, and return the
+        changes (having also passed them to the `on_event` callbacks).
+        Errors are passed to the `on_error` callbacks as well as being
+        returned. */
+    pub  fn  refresh  (&mut self,  K:  &mut Kraken_API)
+               ->  Result<Vec<Account_Event>, String>
+    {
+        match  Self::fetch (K, self.track_positions)
+           {   Ok (S)   =>  Ok (self.update (S)),
+               Err (E)  =>  {  self.error (&E);   Err (E)  }   }
+    }
+
+
+    /*  Replace our state with a new snapshot, reporting the differences. */
+    fn  update  (&mut self,  (balances, orders, positions):  Snapshot)
+            ->  Vec<Account_Event>
+    {
+        use  Account_Event  as  AE;
+
+        let  mut  events  =  Vec::new ();
+
+        for  (asset, &new)  in  &balances
+        {   let  old  =  self.balances.get (asset).copied ();
+            if  old != Some (new)
+                {   events.push (AE::BALANCE (asset.clone (),
+                                              old, Some (new)));   }   }
+        for  (asset, &old)  in  &self.balances
+        {   if  ! balances.contains_key (asset)
+                {   events.push (AE::BALANCE (asset.clone (),
+                                              Some (old), None));   }   }
+        self.balances  =  balances;
+
+        let  orders  =  by_txid (orders, |O| &O.txid);
+        for  (txid, O)  in  &orders
+        {   match  self.orders.get (txid)
+               {   None  =>  events.push (AE::ORDER_OPENED (O.clone ())),
+                   Some (P)  if  P != O
+                         =>  events.push (AE::ORDER_CHANGED (O.clone ())),
+                   _  =>  ()   }   }
+        for  txid  in  self.orders.keys ()
+        {   if  ! orders.contains_key (txid)
+                {   events.push (AE::ORDER_GONE (txid.clone ()));   }   }
+        self.orders  =  orders;
+
+        if let Some (positions) = positions
+        {
+            let  positions  =  by_txid (positions, |P| &P.txid);
+            for  (txid, P)  in  &positions
+            {   match  self.positions.get (txid)
+                   {   None  =>  events.push (AE::POSITION_OPENED (P.clone ())),
+                       Some (Q)  if  Q.volume_closed != P.volume_closed
+                          =>  events.push (AE::POSITION_CHANGED (P.clone ())),
+                       _  =>  ()   }   }
+            for  txid  in  self.positions.keys ()
+            {   if  ! positions.contains_key (txid)
+                    {   events.push (AE::POSITION_CLOSED
+                                                      (txid.clone ()));   }   }
+            self.positions  =  positions;
+        }
+
+        self.refreshed  =  Some (Instant::now ());
+        self.report (&events);
+        self.save ();
+        events
+    }
+
+
+    /** Bring the open orders up to date from a message from the WebSockets
+        openOrders feed (anything else is ignored), returning the changes
+        (having also passed them to the `on_event` callbacks).  Balances and
+        positions are only updated by [Account::refresh]. */
+    pub  fn  apply  (&mut self,  message:  &Ws_Message)  ->  Vec<Account_Event>
+    {
+        let  Ws_Message::OPEN_ORDERS (updates, _)  =  message
+                else  {  return  Vec::new ();  };
+
+        let  mut  events  =  Vec::new ();
+
+        for  U  in  updates
+        {
+            let  status  =  U.status.as_deref ()
+                              .map (Order_Status::from_kraken_string);
+            let  gone  =  status.as_ref ().is_some_and (|S| ! S.is_open ());
+
+            if  gone
+            {   if  self.orders.remove (&U.txid).is_some ()
+                    {   events.push (Account_Event::ORDER_GONE
+                                                   (U.txid.clone ()));   }   }
+            else if let Some (O) = self.orders.get_mut (&U.txid)
+            {   if let Some (S) = status   {   O.status = S;   }
+                if let Some (V) = U.vol_exec   {   O.vol_exec = V;   }
+                for  (field, value)  in  [("cost", &mut O.cost),
+                                          ("fee", &mut O.fee)]
+                    {   if let Some (V) = U.fields.get (field)
+                            {   *value  =  reply::number (V)
+                                                .unwrap_or (*value);   }   }
+                events.push (Account_Event::ORDER_CHANGED (O.clone ()));   }
+            else if let Ok (O) = Order::from_json (&U.txid, &U.fields)
+            {   self.orders.insert (U.txid.clone (), O.clone ());
+                events.push (Account_Event::ORDER_OPENED (O));   }
+        }
+
+        self.report (&events);
+        if  ! events.is_empty ()   {   self.save ();   }
+        events
+    }
+
+
+    /*  Keep the open orders in the store, if there is one. */
+    fn  save  (&mut self)
+    {
+        let  Some ((S, key))  =  &self.store   else   {   return   };
+        let  V : JSN::Map<_, _>  =  self.orders.iter ()
+                                       .map (|(txid, O)|  (txid.clone (),
+                                                           O.to_json ()))
+                                       .collect ();
+        if  let  Err (E)  =  S.put (key, JSN::Value::Object (V).to_string ()
+                                                               .as_bytes ())
+            {   self.error (&E);   }
+    }
+
+
+    fn  report  (&mut self,  events:  &[Account_Event])
+    {
+        for  E  in  events
+            {   for  f  in  &mut self.on_event   {   f (E);   }   }
+    }
+
+    fn  error  (&mut self,  E:  &str)
+    {
+        for  f  in  &mut self.on_error   {   f (E);   }
+    }
+
+
+    /** Refresh the `account` every `interval` in a thread of its own, using
+        the given [Kraken_API] handle.  Cancel the returned token to bring the
+        thread to an end. */
+    pub  fn  spawn  (account:  Arc<Mutex<Account>>,  mut K:  Kraken_API,
+                     interval:  Duration)
+               ->  (std::thread::JoinHandle<()>, Cancel_Token)
+    {
+        let  stop  =  Cancel_Token::new ();
+        let  S     =  stop.clone ();
+
+        (std::thread::spawn (move ||
+             while  ! S.is_cancelled ()
+             {
+                 let  positions  =  account.lock ().unwrap ().track_positions;
+                 let  state  =  Self::fetch (&mut K, positions);
+
+                 let  mut  A  =  account.lock ().unwrap ();
+                 match  state
+                    {   Ok (state)  =>  {  A.update (state);  }
+                        Err (E)     =>  A.error (&E)   }
+                 drop (A);
+
+                 S.pause (interval);
+             }),
+         stop)
+    }
+}
+
+
+
+fn  by_txid<T>  (items:  Vec<T>,  txid:  fn (&T) -> &String)
+          ->  BTreeMap<String, T>
+      {   items.into_iter ().map (|I| (txid (&I).clone (), I)).collect ()   }
+
+
+
+impl  Kraken_API
+{
+/** Get the balance of every asset held in the account, as numbers.
+
+    This comes from the [Kraken_API::account_balance] end-point.  */
+
+  pub  fn  balances  (&mut self)  ->  Result<BTreeMap<String, f64>, String>
+    {
+      let  R  =  reply::result_of (&self.account_balance () ?) ?;
+      R.as_object ()
+       .into_iter ()
+       .flatten ()
+       .map (|(asset, V)|  Ok ((asset.clone (), reply::number (V) ?)))
+       .collect ()
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+  {  #[test]  fn  account_changes ()  ->  Result<(), String>
+     {
+         use  super::{Account, Account_Event as E};
+         use  crate::{Order, Ws_Message};
+         use  serde_json::json;
+
+         let  order  =  Order::from_json ("O1", &json! (
+                           {"descr": {"pair": "XBTUSD", "type": "buy",
+                                      "ordertype": "limit", "price": "100.0"},
+                            "vol": "1.0", "vol_exec": "0.0", "cost": "0",
+                            "fee": "0", "status": "open", "opentm": 1.0})) ?;
+
+         let  mut  A  =  Account::new ();
+         let  ev  =  A.update (([("ZUSD".to_string (), 50.0)].into (),
+                                vec! [order],  None));
+         assert_eq! (ev.len (),  2);
+
+         let  ev  =  A.update ((Default::default (), Vec::new (), None));
+         assert_eq! (ev,  vec! [E::BALANCE ("ZUSD".to_string (),
+                                            Some (50.0), None),
+                                E::ORDER_GONE ("O1".to_string ())]);
+
+         let  M  =  Ws_Message::from_json (&json! (
+                       [[{"O2": {"descr": {"pair": "XBT/USD", "type": "sell",
+                                           "ordertype": "limit",
+                                           "price": "200.0"},
+                                 "vol": "1.0", "vol_exec": "0.0", "cost": "0",
+                                 "fee": "0", "status": "pending",
+                                 "opentm": "2.0"}},
+                         {"O2": {"status": "open", "vol_exec": "0.5"}}],
+                        "openOrders", {"sequence": 1}])) ?;
+         assert_eq! (A.apply (&M).len (),  2);
+         assert_eq! (A.orders () ["O2"].vol_exec,  0.5);
+
+         Ok (())
+     }
+
+     #[test]  fn  stored_orders ()  ->  Result<(), String>
+     {
+         use  super::{Account, Account_Event as E};
+         use  crate::{Memory_Store, Order, Shared_Store};
+         use  serde_json::json;
+         use  std::sync::Arc;
+
+         let  order  =  |txid: &str|  Order::from_json (txid, &json! (
+                           {"descr": {"pair": "XBTUSD", "type": "buy",
+                                      "ordertype": "limit", "price": "100.0",
+                                      "order": "buy 1.0 XBTUSD @ limit 100.0"},
+                            "vol": "1.0", "vol_exec": "0.2", "cost": "20",
+                            "fee": "0.05", "status": "open", "userref": 7,
+                            "opentm": 1.5}));
+         let  store : Shared_Store  =  Arc::new (Memory_Store::new ());
+
+         let  mut  A  =  Account::new ();
+         A.with_store (store.clone (), "account") ?;
+         A.update ((Default::default (), vec! [order ("O1") ?, order ("O2") ?],
+                    None));
+
+         /*  After a restart, O1 is still open and so not new, and O2 closed
+          *  while we were away. */
+         let  mut  B  =  Account::new ();
+         B.with_store (store.clone (), "account") ?;
+         assert_eq! (B.orders (),  A.orders ());
+         assert_eq! (B.update ((Default::default (), vec! [order ("O1") ?],
+                                None)),
+                     [E::ORDER_GONE ("O2".into ())]);
+
+         let  mut  C  =  Account::new ();
+         C.with_store (store, "account") ?;
+         assert_eq! (C.orders ().keys ().collect::<Vec<_>> (),  ["O1"]);
+         Ok (())
+     }  }
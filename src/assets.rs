@@ -0,0 +1,412 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::Kraken_API;
+use  crate::reply;
+use  crate::strictness;
+use  serde_json  as  JSN;
+
+
+
+/** The facts about an asset given by the [Kraken_API::asset_info]
+    end-point. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Asset_Info
+{
+    /** Kraken's canonical name for the asset, e.g. "XXBT". */
+    pub  name:              String,
+
+    /** The alternative name, e.g. "XBT". */
+    pub  altname:           String,
+
+    /** The asset class, e.g. "currency". */
+    pub  aclass:            String,
+
+    /** Number of decimal places used for record keeping. */
+    pub  decimals:          usize,
+
+    /** Number of decimal places used for display. */
+    pub  display_decimals:  usize,
+
+    /** The fraction of the asset's value which counts towards margin
+        collateral, if it can be used as collateral at all. */
+    pub  collateral_value:  Option<f64>,
+
+    /** The funding status, one of "enabled", "deposit_only",
+        "withdrawal_only" or "funding_temporarily_disabled", if the exchange
+        gave one. */
+    pub  status:            Option<String>
+}
+
+
+
+impl  Asset_Info
+{
+    /** Interpret one entry of the 'result' section of an Assets reply; `name`
+        is the key under which the entry was found. */
+    pub  fn  from_json  (name:  &str,  V:  &JSN::Value)
+              ->  Result<Self, String>
+    {
+        strictness::check_fields ("Asset_Info", V,
+                                  &["aclass", "altname", "decimals",
+                                    "display_decimals", "collateral_value",
+                                    "margin_rate", "status"]) ?;
+
+        let  decimals  =  |f: &str|  V [f].as_u64 ()
+                                          .map (|n| n as usize)
+                                          .ok_or (format! ("no '{}' for {}",
+                                                           f, name));
+        Ok (Asset_Info {
+                name:              name.to_string (),
+                altname:           reply::string (V, "altname") ?,
+                aclass:            reply::string (V, "aclass") ?,
+                decimals:          decimals ("decimals") ?,
+                display_decimals:  decimals ("display_decimals") ?,
+                collateral_value:  V.get ("collateral_value")
+                                    .map (reply::number).transpose () ?,
+                status:            V ["status"].as_str ()
+                                               .map (str::to_string)  })
+    }
+
+    /*  The status, taking its absence to mean that all is well. */
+    fn  status  (&self)  ->  &str
+          {   self.status.as_deref ().unwrap_or ("enabled")   }
+
+    /** Whether the asset may currently be deposited. */
+    pub  fn  can_deposit  (&self)  ->  bool
+          {   matches! (self.status (),  "enabled" | "deposit_only")   }
+
+    /** Whether the asset may currently be withdrawn. */
+    pub  fn  can_withdraw  (&self)  ->  bool
+          {   matches! (self.status (),  "enabled" | "withdrawal_only")   }
+
+    /** Whether the asset may currently be both deposited and withdrawn. */
+    pub  fn  is_fundable  (&self)  ->  bool
+          {   self.status () == "enabled"   }
+
+    /** Whether the asset may be traded.  The asset's status only restricts
+        funding, so this is true for any status the crate knows about; an
+        unknown status is taken, cautiously, to mean no.  (Whether a
+        particular pair is open for trading is a separate matter.) */
+    pub  fn  is_tradable  (&self)  ->  bool
+          {   matches! (self.status (),  "enabled" | "deposit_only"
+                                           | "withdrawal_only"
+                                           | "funding_temporarily_disabled")   }
+
+    /** Whether the asset counts towards margin collateral. */
+    pub  fn  is_collateral  (&self)  ->  bool
+          {   self.collateral_value.is_some_and (|c| c > 0.0)   }
+}
+
+
+
+/** The name of an asset which is known to exist on the exchange, having
+    been found with [Kraken_API::asset] (or taken from an [Asset_Info]).  It
+    holds Kraken's canonical name for the asset, e.g. "XXBT", which is the
+    name the balance, ledger and funding end-points use, so that however the
+    asset was first written ("btc", "XBT", "XXBT") it can be compared with
+    them directly.
+
+    It can be handed as `&A` to any method which takes an asset as `&str`,
+    such as [Account::balance](crate::Account::balance) or
+    [Kraken_API::withdrawal_keys](crate::Kraken_API::withdrawal_keys).
+
+    ```ignore
+    K.set_cache_lifetime ("Assets", Some (Duration::from_secs (3600)));
+    let  A  =  K.asset ("btc") ?;
+    assert_eq! (A.as_str (), "XXBT");
+    let  held  =  K.balances () ?.get (A.as_str ()).copied ();
+    ```  */
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub  struct  Asset  (String);
+
+
+
+impl  Asset
+{
+    /** Take `name` as an asset without checking it with the exchange; for
+        names which are known to be good, such as those in the exchange's own
+        replies. */
+    pub  fn  unchecked  (name:  impl Into<String>)  ->  Self
+          {   Asset (name.into ())   }
+
+    /** The asset's canonical name. */
+    pub  fn  as_str  (&self)  ->  &str   {   &self.0   }
+
+    /** The ISO 4217 code or common ticker symbol for the asset, e.g. "BTC"
+        for "XXBT"; see [common_symbol]. */
+    pub  fn  common_symbol  (&self)  ->  String
+          {   common_symbol (&self.0)   }
+
+    /** Whether this is a staked, bonded or otherwise ring-fenced variant of
+        some other asset, such as "ETH2.S", "DOT.S", "XBT.M" or "ETH2". */
+    pub  fn  is_variant  (&self)  ->  bool
+          {   self.0.contains ('.')  ||  self.0 == "ETH2"   }
+}
+
+
+
+impl  From<&Asset_Info>  for  Asset
+{
+    fn  from  (A:  &Asset_Info)  ->  Self   {   Asset (A.name.clone ())   }
+}
+
+impl  From<Asset>  for  String
+{
+    fn  from  (A:  Asset)  ->  Self   {   A.0   }
+}
+
+impl  From<&Asset>  for  String
+{
+    fn  from  (A:  &Asset)  ->  Self   {   A.0.clone ()   }
+}
+
+impl  std::ops::Deref  for  Asset
+{
+    type  Target  =  str;
+    fn  deref  (&self)  ->  &str   {   &self.0   }
+}
+
+impl  AsRef<str>  for  Asset
+{
+    fn  as_ref  (&self)  ->  &str   {   &self.0   }
+}
+
+impl  std::fmt::Display  for  Asset
+{
+    fn  fmt  (&self,  f:  &mut std::fmt::Formatter)  ->  std::fmt::Result
+          {   f.write_str (&self.0)   }
+}
+
+
+
+/*  Kraken's own codes for the assets which it names differently from
+ *  everyone else, with the ISO 4217 code or common ticker symbol for each.
+ *  The canonical names come first, so that looking up a common symbol finds
+ *  the name the balance and ledger end-points use. */
+
+const  SYMBOLS:  [(&str, &str); 19]
+        =  [("XXBT", "BTC"),  ("XETH", "ETH"),  ("XXDG", "DOGE"),
+            ("XLTC", "LTC"),  ("XXRP", "XRP"),  ("XXLM", "XLM"),
+            ("XXMR", "XMR"),  ("XZEC", "ZEC"),  ("XETC", "ETC"),
+            ("XREP", "REP"),  ("XMLN", "MLN"),
+            ("ZUSD", "USD"),  ("ZEUR", "EUR"),  ("ZGBP", "GBP"),
+            ("ZJPY", "JPY"),  ("ZCAD", "CAD"),  ("ZAUD", "AUD"),
+            ("XBT",  "BTC"),  ("XDG",  "DOGE")];
+
+
+
+/** The ISO 4217 code or common ticker symbol for the asset Kraken calls
+    `code`, e.g. "BTC" for "XXBT" or "XBT", and "USD" for "ZUSD"; any suffix
+    is kept, so "XBT.M" gives "BTC.M".  Codes which Kraken shares with
+    everyone else, such as "SOL" or "USDT", are given back as they are. */
+
+pub  fn  common_symbol  (code:  &str)  ->  String
+{
+    let  (stem, suffix)  =  code.split_at (code.find ('.')
+                                               .unwrap_or (code.len ()));
+    SYMBOLS.iter ()
+           .find (|(kraken, _)| *kraken == stem)
+           .map_or (stem, |(_, common)| common)
+           .to_string ()  +  suffix
+}
+
+
+
+/** Kraken's canonical code for the asset with the ISO 4217 code or common
+    ticker symbol `symbol`, in any case, e.g. "XXBT" for "btc" and "ZUSD" for
+    "USD"; any suffix is kept, so "BTC.M" gives "XXBT.M".  Symbols Kraken
+    uses as they are, such as "SOL", are given back in upper case.
+
+    This needs no call to the exchange, but so only knows the assets in the
+    crate's own table; [Kraken_API::asset] checks the name with the exchange
+    and knows every form the exchange does.  */
+
+pub  fn  kraken_code  (symbol:  &str)  ->  String
+{
+    let  upper  =  symbol.trim ().to_uppercase ();
+    let  (stem, suffix)  =  upper.split_at (upper.find ('.')
+                                                 .unwrap_or (upper.len ()));
+    SYMBOLS.iter ()
+           .find (|(_, common)| *common == stem)
+           .map_or (stem, |(kraken, _)| kraken)
+           .to_string ()  +  suffix
+}
+
+
+
+/*  The names in common use for assets which Kraken calls something else. */
+
+const  ALIASES:  [(&str, &str); 2]  =  [("BTC", "XBT"), ("DOGE", "XDG")];
+
+
+
+/*  Find the asset `name` refers to among `assets`, by its canonical name or
+ *  its altname, in any case, and allowing the common names in ALIASES for
+ *  the part before any suffix (so that "btc.m" finds "XBT.M"). */
+
+fn  find_asset  (assets:  &[Asset_Info],  name:  &str)
+          ->  Result<Asset, String>
+{
+    let  upper  =  name.trim ().to_uppercase ();
+    let  (stem, suffix)  =  match  upper.find ('.')
+                               {   Some (i)  =>  upper.split_at (i),
+                                   None  =>  (upper.as_str (), "")   };
+    let  stem  =  ALIASES.iter ()
+                         .find (|(common, _)| *common == stem)
+                         .map_or (stem, |(_, kraken)| kraken);
+    let  wanted  =  stem.to_string () + suffix;
+
+    assets.iter ()
+          .find (|A|  A.name.to_uppercase () == wanted
+                        ||  A.altname.to_uppercase () == wanted)
+          .map (Asset::from)
+          .ok_or (format! ("unknown asset '{}'", name))
+}
+
+
+
+/*  The name of the asset of which `name` is a variant: the part before any
+ *  suffix, with ETH2 standing for ETH. */
+
+fn  underlying_name  (name:  &str)  ->  &str
+{
+    match  name.split ('.').next ().unwrap_or (name)
+       {   "ETH2"  =>  "ETH",
+           stem    =>  stem   }
+}
+
+
+
+impl  Kraken_API
+{
+/** Find the asset the exchange knows by `name`, which may be its canonical
+    name ("XXBT"), its alternative name ("XBT"), or a common name the
+    exchange does not use ("BTC", "DOGE"), in any case, and may carry a
+    suffix such as ".S" or ".M".
+
+    The search is made in the whole list from [Kraken_API::assets], clearing
+    any [API_Option::ASSET](crate::API_Option::ASSET) and
+    [API_Option::ACLASS](crate::API_Option::ACLASS) setting; code which looks
+    up many assets will do well to have the "Assets" end-point cached with
+    [Kraken_API::set_cache_lifetime].  */
+
+  pub  fn  asset  (&mut self,  name:  &str)  ->  Result<Asset, String>
+    {
+      self.clear_opt (crate::API_Option::ASSET);
+      self.clear_opt (crate::API_Option::ACLASS);
+      find_asset (&self.assets () ?, name)
+    }
+
+
+
+/** The asset of which `asset` is a staked or otherwise ring-fenced variant
+    (see [Asset::is_variant]), e.g. XETH for "ETH2.S" and "ETH2", or XXBT
+    for "XBT.M"; this is the one to price it by.  An asset which is not a
+    variant is given back as it is, without calling the exchange.  */
+
+  pub  fn  underlying_asset  (&mut self,  asset:  &Asset)
+               ->  Result<Asset, String>
+    {
+      if  ! asset.is_variant ()   {   return  Ok (asset.clone ());   }
+      self.asset (underlying_name (asset))
+    }
+
+
+
+/** Get the [Asset_Info] of every asset, or of those named in any
+    [API_Option::ASSET](crate::API_Option::ASSET) setting.
+
+    This calls the [Kraken_API::asset_info] end-point, which also respects
+    any asset class setting.  */
+
+  pub  fn  assets  (&mut self)  ->  Result<Vec<Asset_Info>, String>
+    {
+      let  R  =  reply::result_of (&self.asset_info () ?) ?;
+      R.as_object ()
+       .into_iter ()
+       .flatten ()
+       .map (|(name, V)| Asset_Info::from_json (name, V))
+       .collect ()
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+  {  #[test]  fn  asset_status ()  ->  Result<(), String>
+     {
+         let  A  =  super::Asset_Info::from_json ("XXBT", &serde_json::json! (
+                       {"aclass": "currency", "altname": "XBT",
+                        "decimals": 10, "display_decimals": 5,
+                        "collateral_value": 1.0,
+                        "status": "deposit_only"})) ?;
+
+         assert! (A.can_deposit ()  &&  ! A.can_withdraw ());
+         assert! (! A.is_fundable ()  &&  A.is_tradable ());
+         assert! (A.is_collateral ());
+
+         Ok (())
+     }
+
+     #[test]  fn  aliases ()
+     {
+         use  super::{Asset, Asset_Info, find_asset, underlying_name};
+
+         let  info  =  |name: &str, altname: &str|
+                          Asset_Info {  name:  name.into (),
+                                        altname:  altname.into (),
+                                        aclass:  "currency".into (),
+                                        decimals:  10,
+                                        display_decimals:  5,
+                                        collateral_value:  None,
+                                        status:  None  };
+         let  assets  =  [info ("XXBT", "XBT"),  info ("XBT.M", "XBT.M"),
+                          info ("XXDG", "XDG"),  info ("ETH2.S", "ETH2.S")];
+
+         let  found  =  |name: &str| find_asset (&assets, name)
+                                       .map (|A| A.as_str ().to_string ());
+         assert_eq! (found ("btc"), Ok ("XXBT".into ()));
+         assert_eq! (found ("XXBT"), Ok ("XXBT".into ()));
+         assert_eq! (found ("Doge"), Ok ("XXDG".into ()));
+         assert_eq! (found ("btc.m"), Ok ("XBT.M".into ()));
+         assert_eq! (found ("eth2.s"), Ok ("ETH2.S".into ()));
+         assert! (found ("BTCC").is_err ());
+
+         assert! (Asset::unchecked ("ETH2.S").is_variant ());
+         assert! (! Asset::unchecked ("XXBT").is_variant ());
+         assert_eq! (underlying_name ("ETH2.S"), "ETH");
+         assert_eq! (underlying_name ("XBT.M"), "XBT");
+
+         use  super::{common_symbol, kraken_code};
+         assert_eq! (common_symbol ("XXBT"),  "BTC");
+         assert_eq! (common_symbol ("XBT.M"),  "BTC.M");
+         assert_eq! (common_symbol ("ZUSD"),  "USD");
+         assert_eq! (common_symbol ("USDT"),  "USDT");
+         assert_eq! (kraken_code ("btc"),  "XXBT");
+         assert_eq! (kraken_code ("Doge"),  "XXDG");
+         assert_eq! (kraken_code ("EUR"),  "ZEUR");
+         assert_eq! (kraken_code ("sol.s"),  "SOL.S");
+         assert_eq! (Asset::unchecked ("XETH").common_symbol (),  "ETH");
+     }  }
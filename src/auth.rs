@@ -0,0 +1,306 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option, Instruction, Kraken_API, Order_Type, Report_Type};
+use  std::collections::BTreeMap;
+use  std::sync::atomic::{AtomicU64, Ordering};
+use  std::time::{SystemTime, UNIX_EPOCH};
+
+
+
+/** The ways in which the exchange may refuse the credentials on a private
+    request.  They look much alike but each needs a different fix, which
+    [Auth_Error::hint] describes.
+
+    ```ignore
+    let  reply  =  K.account_balance () ?;
+    if let Some (E) = KKN::Auth_Error::from_message (&reply)
+        {   eprintln! ("{}", E);   }
+    ```  */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub  enum  Auth_Error
+{
+    /** "EAPI:Invalid key": the exchange does not know the API key. */
+    INVALID_KEY,
+    /** "EAPI:Invalid signature": the request was not signed with the
+        key's secret. */
+    INVALID_SIGNATURE,
+    /** "EAPI:Invalid nonce": the nonce was not larger than the last one
+        the exchange saw with the key. */
+    INVALID_NONCE,
+    /** "EGeneral:Permission denied": the key is good but is not allowed to
+        use the end-point. */
+    PERMISSION_DENIED
+}
+
+
+
+impl  Auth_Error
+{
+    /** Look for one of these errors in an error message, or in the whole
+        of a raw reply from the exchange. */
+    pub  fn  from_message  (message:  &str)  ->  Option<Self>
+    {
+        [Auth_Error::INVALID_KEY,   Auth_Error::INVALID_SIGNATURE,
+         Auth_Error::INVALID_NONCE, Auth_Error::PERMISSION_DENIED]
+            .iter ().copied ()
+            .find (|E| message.contains (E.as_kraken_string ()))
+    }
+
+    /** The exact error string the exchange sends. */
+    pub  fn  as_kraken_string  (&self)  ->  &'static str
+    {
+        match  self
+        {
+            Auth_Error::INVALID_KEY        =>  "EAPI:Invalid key",
+            Auth_Error::INVALID_SIGNATURE  =>  "EAPI:Invalid signature",
+            Auth_Error::INVALID_NONCE      =>  "EAPI:Invalid nonce",
+            Auth_Error::PERMISSION_DENIED  =>  "EGeneral:Permission denied"
+        }
+    }
+
+    /** What the operator should do about it. */
+    pub  fn  hint  (&self)  ->  &'static str
+    {
+        match  self
+        {
+            Auth_Error::INVALID_KEY
+                =>  "check that the API key was copied whole, or that it has \
+                     not been deleted; otherwise create a new key and rotate \
+                     it in",
+            Auth_Error::INVALID_SIGNATURE
+                =>  "check that the secret is the one which belongs to the \
+                     API key, and that it was copied whole",
+            Auth_Error::INVALID_NONCE
+                =>  "check that the system clock is right, and that no other \
+                     program is using the same API key; failing that, widen \
+                     the key's nonce window on the Kraken web site",
+            Auth_Error::PERMISSION_DENIED
+                =>  "give the API key the permission this end-point needs, \
+                     on the Kraken web site"
+        }
+    }
+}
+
+
+
+impl  std::fmt::Display  for  Auth_Error
+{
+    fn  fmt  (&self,  f:  &mut std::fmt::Formatter)  ->  std::fmt::Result
+          {   write! (f, "{} ({})", self.as_kraken_string (), self.hint ())   }
+}
+
+
+
+/** The permissions an API key may be given on the Kraken web site, as far
+    as [Kraken_API::probe_permissions] can tell them apart. */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub  enum  Permission
+{
+    /** "Query Funds": balances and trade balances. */
+    QUERY_FUNDS,
+    /** "Query Open Orders & Trades". */
+    QUERY_OPEN_ORDERS,
+    /** "Query Ledger Entries". */
+    QUERY_LEDGER,
+    /** "Create & Modify Orders". */
+    MODIFY_ORDERS,
+    /** "Cancel & Close Orders". */
+    CANCEL_ORDERS,
+    /** "Export Data". */
+    EXPORT_DATA,
+    /** "Access WebSockets API". */
+    WEBSOCKETS
+}
+
+impl  Permission
+{
+    /** The name the permission goes by on the Kraken web site. */
+    pub  fn  name  (&self)  ->  &'static str
+    {
+        match  self
+        {
+            Permission::QUERY_FUNDS        =>  "Query Funds",
+            Permission::QUERY_OPEN_ORDERS  =>  "Query Open Orders & Trades",
+            Permission::QUERY_LEDGER       =>  "Query Ledger Entries",
+            Permission::MODIFY_ORDERS      =>  "Create & Modify Orders",
+            Permission::CANCEL_ORDERS      =>  "Cancel & Close Orders",
+            Permission::EXPORT_DATA        =>  "Export Data",
+            Permission::WEBSOCKETS         =>  "Access WebSockets API"
+        }
+    }
+}
+
+
+
+/*  Whether a probe's reply shows the permission was granted: anything but
+ *  a refusal of permission means the exchange let the request through, even
+ *  if it then found fault with it.  A refusal of the key itself is an
+ *  error. */
+
+fn  granted  (reply:  Result<String, String>)  ->  Result<bool, String>
+{
+    match  Auth_Error::from_message (&reply ?)
+    {
+        Some (Auth_Error::PERMISSION_DENIED)  =>  Ok (false),
+        Some (E)  =>  Err (E.to_string ()),
+        None  =>  Ok (true)
+    }
+}
+
+
+
+impl  Kraken_API
+{
+/** Find out which [Permission]s the API key has, by making one cheap,
+    harmless call needing each: a balance enquiry, an enquiry of open
+    orders, a look-up of a ledger entry and a cancellation of an order which
+    do not exist, a validate-only market order for 0.0001 XBT (which the
+    exchange checks but does not place), the status of trade exports, and a
+    request for a WebSockets token.  Best done when a program starts, so
+    that a key without the scopes it needs is found out before anything is
+    traded.
+
+    The enquiries count against the account's call counter as usual, the
+    order and cancellation against its order rate.  It fails, rather than
+    reporting no permissions, if the key or its secret is refused
+    altogether, or the exchange cannot be reached.  */
+
+  pub  fn  probe_permissions  (&self)
+               ->  Result<BTreeMap<Permission, bool>, String>
+    {
+      const  NO_SUCH_ORDER:  &str  =  "OAAAAA-AAAAA-AAAAAA";
+
+      type  Probe  =  fn (&mut Kraken_API) -> Result<String, String>;
+      let  probes:  [(Permission, Probe); 7]
+         =  [(Permission::QUERY_FUNDS,  |K| K.account_balance ()),
+             (Permission::QUERY_OPEN_ORDERS,  |K| K.open_orders ()),
+             (Permission::QUERY_LEDGER,
+              |K|  {  K.set_opt (API_Option::ID, "LAAAAA-AAAAA-AAAAAA");
+                      K.query_ledgers ()  }),
+             (Permission::MODIFY_ORDERS,
+              |K|  {  K.set_opt (API_Option::VALIDATE, true);
+                      K.add_order (Order_Type::MARKET, Instruction::BUY,
+                                   "0.0001", "XBTUSD")  }),
+             (Permission::CANCEL_ORDERS,  |K| K.cancel_order (NO_SUCH_ORDER)),
+             (Permission::EXPORT_DATA,
+              |K|  K.get_export_report_status (Report_Type::TRADES)),
+             (Permission::WEBSOCKETS,  |K| K.websockets_token ())];
+
+      probes.iter ()
+            .map (|(P, probe)|  Ok ((*P,
+                                     granted (probe (&mut self.share ())) ?)))
+            .collect ()
+    }
+}
+
+
+
+/*  How far ahead of the last nonce we jump when the exchange says a nonce
+ *  was too small: one second's worth. */
+
+const  LEAP:  u64  =  1_000_000;
+
+
+
+/*  The nonces for private requests: the number of microseconds since the
+ *  UNIX epoch, but always more than the last one given out, so that two
+ *  requests in the same microsecond, or a clock stepping back, do not upset
+ *  the exchange.  Handles which share a key share one of these. */
+
+#[derive(Default)]
+pub(crate)  struct  Nonces  (AtomicU64);
+
+impl  Nonces
+{
+    fn  now  ()  ->  u64
+          {   SystemTime::now ().duration_since (UNIX_EPOCH).unwrap ()
+                                .as_micros ()  as  u64   }
+
+    pub(crate)  fn  next  (&self)  ->  u64
+    {
+        let  now  =  Nonces::now ();
+        let  last  =  self.0.fetch_update (Ordering::SeqCst, Ordering::SeqCst,
+                                           |L|  Some (now.max (L + 1)))
+                            .unwrap ();
+        now.max (last + 1)
+    }
+
+    /*  Move well past the last nonce, after the exchange has refused one. */
+    pub(crate)  fn  leap  (&self)
+    {
+        let  now  =  Nonces::now ();
+        let  _  =  self.0.fetch_update (Ordering::SeqCst, Ordering::SeqCst,
+                                        |L|  Some (now.max (L) + LEAP));
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  recognition  ()
+    {
+        use  super::Auth_Error;
+
+        assert_eq! (Auth_Error::from_message
+                                 (r#"{"error":["EAPI:Invalid nonce"]}"#),
+                    Some (Auth_Error::INVALID_NONCE));
+        assert_eq! (Auth_Error::from_message ("EGeneral:Permission denied"),
+                    Some (Auth_Error::PERMISSION_DENIED));
+        assert_eq! (Auth_Error::from_message ("EAPI:Rate limit exceeded"),
+                    None);
+        assert! (Auth_Error::INVALID_KEY.to_string ()
+                    .starts_with ("EAPI:Invalid key (check"));
+    }
+
+
+    #[test]
+    fn  granted  ()
+    {
+        use  super::granted;
+
+        let  reply  =  |E: &str|  Ok (format! (r#"{{"error":[{}]}}"#, E));
+        assert_eq! (granted (reply ("")),  Ok (true));
+        assert_eq! (granted (reply (r#""EOrder:Unknown order""#)),  Ok (true));
+        assert_eq! (granted (reply (r#""EGeneral:Permission denied""#)),
+                    Ok (false));
+        assert! (granted (reply (r#""EAPI:Invalid key""#)).is_err ());
+        assert! (granted (Err ("timed out".into ())).is_err ());
+    }
+
+
+    #[test]
+    fn  nonces  ()
+    {
+        let  N  =  super::Nonces::default ();
+        let  a  =  N.next ();
+        let  b  =  N.next ();
+        assert! (b > a);
+
+        N.leap ();
+        assert! (N.next () >= b + super::LEAP);
+    }
+}
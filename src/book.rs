@@ -0,0 +1,435 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option, Instruction, Kraken_API};
+use  crate::reply;
+use  serde_json  as  JSN;
+
+
+
+/** Which side of the order book a level is on. */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub  enum  Book_Side
+{
+    /** The buying side. */
+    BID,
+
+    /** The selling side. */
+    ASK
+}
+
+
+
+/** One price level of the order book. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Book_Level
+{
+    /** The price of the level. */
+    pub  price:   f64,
+
+    /** The total volume resting at that price. */
+    pub  volume:  f64,
+
+    /** UNIX time of the last change to the level. */
+    pub  time:    f64
+}
+
+impl  Book_Level
+{
+    /** Interpret one `[price, volume, time]` entry of a Depth reply. */
+    pub  fn  from_json  (V:  &JSN::Value)  ->  Result<Self, String>
+    {
+        Ok (Book_Level {  price:   reply::number (&V [0]) ?,
+                          volume:  reply::number (&V [1]) ?,
+                          time:    reply::number (&V [2]) ?  })
+    }
+}
+
+
+
+/** A snapshot of the order book of one pair, as given by the
+    [Kraken_API::order_book] end-point. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Depth
+{
+    /** Kraken's canonical name for the pair. */
+    pub  pair:  String,
+
+    /** The bids, best (highest) first. */
+    pub  bids:  Vec<Book_Level>,
+
+    /** The asks, best (lowest) first. */
+    pub  asks:  Vec<Book_Level>
+}
+
+
+
+/** A difference between two [Depth] snapshots at one price level; see
+    [Depth::diff]. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  enum  Level_Change
+{
+    /** There was no volume at this price before, and now there is. */
+    ADDED
+    {
+        /** The side of the book. */
+        side:    Book_Side,
+        /** The price of the level. */
+        price:   f64,
+        /** The volume now at the level. */
+        volume:  f64
+    },
+
+    /** There was volume at this price before, and now there is not. */
+    REMOVED
+    {
+        /** The side of the book. */
+        side:    Book_Side,
+        /** The price of the level. */
+        price:   f64,
+        /** The volume which was at the level. */
+        volume:  f64
+    },
+
+    /** The volume at this price has changed. */
+    CHANGED
+    {
+        /** The side of the book. */
+        side:    Book_Side,
+        /** The price of the level. */
+        price:   f64,
+        /** The volume which was at the level. */
+        from:    f64,
+        /** The volume now at the level. */
+        to:      f64
+    }
+}
+
+
+
+/** The volume of one side of the book lying within a band of prices; see
+    [Depth::bands]. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Depth_Band
+{
+    /** The price at the edge of the band nearer the middle of the book. */
+    pub  near:        f64,
+
+    /** The price at the edge of the band further from the middle of the
+        book. */
+    pub  far:         f64,
+
+    /** The volume at prices within the band. */
+    pub  volume:      f64,
+
+    /** The volume at prices within this band and all those nearer the middle
+        of the book. */
+    pub  cumulative:  f64
+}
+
+
+
+/** What a market order of a given size would be expected to cost, by
+    walking the book; see [Depth::slippage]. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Slippage
+{
+    /** The volume-weighted average price the order would get. */
+    pub  average_price:  f64,
+
+    /** The price of the last level the order would reach. */
+    pub  worst_price:    f64,
+
+    /** The mid-price, half-way between the best bid and ask. */
+    pub  mid:            f64,
+
+    /** How much worse than the mid-price the average price is, as a fraction
+        of the mid-price (so 0.001 is 0.1%). */
+    pub  slippage:       f64,
+
+    /** How much of the volume the book, as deep as it was fetched, could
+        take; less than asked for if the order would go right through it. */
+    pub  filled:         f64
+}
+
+
+
+impl  Depth
+{
+    /** Interpret the 'result' section of a Depth reply. */
+    pub  fn  from_json  (V:  &JSN::Value)  ->  Result<Self, String>
+    {
+        let  (pair, B)  =  V.as_object ()
+                            .and_then (|M| M.iter ().next ())
+                            .ok_or ("no book in Depth reply") ?;
+
+        let  side  =  |s: &str|  B [s].as_array ()
+                                      .ok_or (format! ("no {} in Depth reply",
+                                                       s)) ?
+                                      .iter ()
+                                      .map (Book_Level::from_json)
+                                      .collect::<Result<Vec<_>, String>> ();
+
+        Ok (Depth {  pair: pair.clone (),
+                     bids: side ("bids") ?,
+                     asks: side ("asks") ?  })
+    }
+
+
+    /** The best bid and ask prices, if both sides of the book have any
+        levels. */
+    pub  fn  best  (&self)  ->  Option<(f64, f64)>
+          {   Some ((self.bids.first () ?.price,
+                     self.asks.first () ?.price))   }
+
+
+    /** List the levels which differ between `self`, taken as the earlier
+        snapshot, and `later`: bids first then asks, each in the order of the
+        later book with removed levels last.  Changes to a level's time alone
+        are not reported.
+
+        Bear in mind that the exchange only sends as many levels as were asked
+        for, so a level which falls off the bottom of a shallow snapshot will
+        show as removed even though it is still in the book.  */
+    pub  fn  diff  (&self,  later:  &Depth)  ->  Vec<Level_Change>
+    {
+        let  mut  changes  =  Vec::new ();
+        diff_side (Book_Side::BID, &self.bids, &later.bids, &mut changes);
+        diff_side (Book_Side::ASK, &self.asks, &later.asks, &mut changes);
+        changes
+    }
+
+
+    /** Gather one `side` of the book into `count` bands of prices, each
+        `width` wide as a fraction of the mid-price (so 0.005 gives bands of
+        0.5%), working outwards from the mid-price.  Levels beyond the last
+        band are left out, and bands with no levels in them are still given.
+        The result is empty if either side of the book is empty.
+
+        Bear in mind that the book only goes as deep as was asked for, so the
+        outer bands may be missing volume which is really there.  */
+    pub  fn  bands  (&self,  side:  Book_Side,  width:  f64,  count:  usize)
+               ->  Vec<Depth_Band>
+    {
+        let  Some ((bid, ask))  =  self.best ()
+                 else  {  return  Vec::new ()  };
+        let  mid   =  (bid + ask) / 2.0;
+        let  step  =  if side == Book_Side::BID  { -mid * width }
+                      else                       {  mid * width };
+
+        let  mut  bands  =  (0 .. count)
+                              .map (|i|  Depth_Band {
+                                             near: mid + step * i as f64,
+                                             far:  mid + step * (i + 1) as f64,
+                                             volume: 0.0,
+                                             cumulative: 0.0  })
+                              .collect::<Vec<_>> ();
+
+        let  levels  =  if side == Book_Side::BID  { &self.bids }
+                        else                       { &self.asks };
+        for  L  in  levels
+        {   let  i  =  ((L.price - mid) / step).floor ();
+            if  i >= 0.0  &&  (i as usize) < count
+                {   bands [i as usize].volume  +=  L.volume;   }   }
+
+        let  mut  total  =  0.0;
+        for  B  in  &mut bands
+            {   total  +=  B.volume;   B.cumulative  =  total;   }
+
+        bands
+    }
+
+
+    /** Estimate how a market order to `side` (buy or sell) `volume` of the
+        pair would be filled, taking the levels on the other side of the book
+        from the best price outwards.  `None` if either side of the book is
+        empty, or the volume is not positive.
+
+        This is only an estimate: the book moves, other orders may get there
+        first, and the fee is not counted.  */
+    pub  fn  slippage  (&self,  side:  Instruction,  volume:  f64)
+               ->  Option<Slippage>
+    {
+        let  (bid, ask)  =  self.best () ?;
+        if  volume <= 0.0  ||  volume.is_nan ()   {   return  None;   }
+        let  mid  =  (bid + ask) / 2.0;
+
+        let  levels  =  match  side   {   Instruction::BUY  =>  &self.asks,
+                                          Instruction::SELL  =>  &self.bids  };
+
+        let  (mut filled, mut cost, mut worst)  =  (0.0, 0.0, mid);
+        for  L  in  levels
+        {
+            if  filled >= volume   {   break;   }
+            let  take  =  L.volume.min (volume - filled);
+            filled  +=  take;
+            cost    +=  take * L.price;
+            worst    =  L.price;
+        }
+
+        if  filled <= 0.0   {   return  None;   }
+        let  average_price  =  cost / filled;
+        let  slippage  =  match  side
+            {   Instruction::BUY   =>  (average_price - mid) / mid,
+                Instruction::SELL  =>  (mid - average_price) / mid   };
+
+        Some (Slippage {  average_price,  worst_price: worst,  mid,
+                          slippage,  filled  })
+    }
+}
+
+
+
+fn  diff_side  (side:  Book_Side,  before:  &[Book_Level],
+                after:  &[Book_Level],  changes:  &mut Vec<Level_Change>)
+{
+    let  find  =  |levels: &[Book_Level], price: f64|
+                      levels.iter ().find (|L| L.price == price)
+                                    .map (|L| L.volume);
+
+    for  L  in  after
+    {   match  find (before, L.price)
+           {   None  =>  changes.push (Level_Change::ADDED
+                                           {  side,  price: L.price,
+                                              volume: L.volume  }),
+               Some (V)  if  V != L.volume
+                     =>  changes.push (Level_Change::CHANGED
+                                           {  side,  price: L.price,
+                                              from: V,  to: L.volume  }),
+               _  =>  ()   }   }
+
+    for  L  in  before
+    {   if  find (after, L.price).is_none ()
+            {   changes.push (Level_Change::REMOVED
+                                  {  side,  price: L.price,
+                                     volume: L.volume  });   }   }
+}
+
+
+
+/*  The most levels the Depth end-point will give on each side. */
+
+const  MAX_DEPTH:  usize  =  500;
+
+
+
+impl  Kraken_API
+{
+/** Get a typed snapshot of the order book for `pair`, to a depth of `count`
+    levels on each side.
+
+    This makes a call to the [Kraken_API::order_book] end-point, and so
+    replaces any [API_Option::COUNT] setting.  */
+
+  pub  fn  depth  (&mut self,  pair: &str,  count: usize)
+             ->  Result<Depth, String>
+    {
+      self.set_opt (API_Option::COUNT, count);
+      Depth::from_json (&reply::result_of (&self.order_book (pair.to_string ())
+                                                                      ?) ?)
+    }
+
+
+
+
+/** Estimate the average price a market order to `side` `volume` of `pair`
+    would get, and how far that is from the mid-price, from a fresh snapshot
+    of the book to the greatest depth the exchange gives; see
+    [Depth::slippage].
+
+    This makes a call to the [Kraken_API::order_book] end-point, and so
+    replaces any [API_Option::COUNT] setting.  */
+
+  pub  fn  estimate_slippage  (&mut self,  pair:  &str,  side:  Instruction,
+                               volume:  f64)
+             ->  Result<Slippage, String>
+    {
+      self.depth (pair, MAX_DEPTH) ?
+          .slippage (side, volume)
+          .ok_or (format! ("cannot estimate slippage of {} on {}",
+                           volume, pair))
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+  {  #[test]  fn  depth_diff ()  ->  Result<(), String>
+     {
+         use  super::{Book_Side as S, Depth, Level_Change as C};
+
+         let  a  =  Depth::from_json (&serde_json::json! (
+                       {"XXBTZUSD": {"bids": [["100.0", "1.0", 1],
+                                              ["99.0", "2.0", 1]],
+                                     "asks": [["101.0", "1.0", 1]]}})) ?;
+         let  b  =  Depth::from_json (&serde_json::json! (
+                       {"XXBTZUSD": {"bids": [["100.0", "1.5", 2],
+                                              ["98.0", "3.0", 2]],
+                                     "asks": [["101.0", "1.0", 2]]}})) ?;
+
+         assert_eq! (a.best (),  Some ((100.0, 101.0)));
+         assert_eq! (a.diff (&b),
+                     vec! [C::CHANGED {  side: S::BID,  price: 100.0,
+                                         from: 1.0,  to: 1.5  },
+                           C::ADDED {  side: S::BID,  price: 98.0,
+                                       volume: 3.0  },
+                           C::REMOVED {  side: S::BID,  price: 99.0,
+                                         volume: 2.0  }]);
+         assert! (b.diff (&b).is_empty ());
+
+         let  B  =  b.bands (S::BID, 0.01, 3);
+         assert_eq! (B.iter ().map (|B| (B.volume, B.cumulative))
+                              .collect::<Vec<_>> (),
+                     vec! [(1.5, 1.5), (0.0, 1.5), (3.0, 4.5)]);
+
+         Ok (())
+     }
+
+
+     #[test]  fn  slippage ()  ->  Result<(), String>
+     {
+         use  super::Depth;
+         use  crate::Instruction;
+
+         let  D  =  Depth::from_json (&serde_json::json! (
+                       {"XXBTZUSD": {"bids": [["99.0", "1.0", 1],
+                                              ["98.0", "2.0", 1]],
+                                     "asks": [["101.0", "1.0", 1],
+                                              ["103.0", "1.0", 1]]}})) ?;
+
+         let  S  =  D.slippage (Instruction::BUY, 1.5).ok_or ("no estimate") ?;
+         assert_eq! ((S.average_price, S.worst_price, S.mid, S.filled),
+                     (152.5 / 1.5, 103.0, 100.0, 1.5));
+         assert! ((S.slippage - 1.0 / 60.0).abs () < 1e-12);
+
+         let  S  =  D.slippage (Instruction::SELL, 5.0).ok_or ("no estimate") ?;
+         assert_eq! ((S.average_price, S.filled),  (295.0 / 3.0, 3.0));
+
+         assert! (D.slippage (Instruction::SELL, 0.0).is_none ());
+         Ok (())
+     }  }
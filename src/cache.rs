@@ -0,0 +1,155 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  std::collections::HashMap;
+use  std::sync::Mutex;
+use  std::time::{Duration, Instant};
+
+
+
+/*  Replies to public enquiries, kept for as long as the user has asked us to
+ *  keep replies from each end-point; end-points with no time set are not
+ *  cached at all.  Entries are keyed on the full query, so that different
+ *  arguments get different entries. */
+
+#[derive(Default)]
+pub(crate)  struct  Cache  (Mutex<Cache_State>);
+
+#[derive(Default)]
+struct  Cache_State  {  lifetimes:  HashMap<String, Duration>,
+                       replies:    HashMap<String, (Instant, String)>  }
+
+
+
+fn  end_point  (query:  &str)  ->  &str
+          {   query.split ('?').next ().unwrap_or ("")   }
+
+
+
+impl  Cache
+{
+    pub(crate)  fn  set_lifetime  (&self,  end_point:  &str,
+                                   lifetime:  Option<Duration>)
+    {
+        let  mut  S  =  self.0.lock ().unwrap ();
+        match  lifetime
+        {
+            Some (T)  =>  {  S.lifetimes.insert (end_point.to_string (), T);  }
+            None      =>  {  S.lifetimes.remove (end_point);  }
+        }
+        S.replies.retain (|Q, _|  self::end_point (Q) != end_point);
+    }
+
+
+    pub(crate)  fn  clear  (&self)
+          {   self.0.lock ().unwrap ().replies.clear ();   }
+
+
+    /*  The reply to the query, if we have one that is young enough; one that
+     *  is too old is thrown away. */
+    pub(crate)  fn  get  (&self,  query:  &str)  ->  Option<String>
+    {
+        let  mut  S  =  self.0.lock ().unwrap ();
+        let  lifetime  =  *S.lifetimes.get (end_point (query)) ?;
+        let  (at, reply)  =  S.replies.get (query) ?;
+        if  at.elapsed () < lifetime   {   return  Some (reply.clone ());   }
+        S.replies.remove (query);
+        None
+    }
+
+
+    /*  Keep the reply, if it is from an end-point we are caching and the
+     *  exchange did not report an error, throwing away any others which have
+     *  grown too old (so that replies to queries which are never asked again
+     *  do not pile up). */
+    pub(crate)  fn  put  (&self,  query:  &str,  reply:  &str)
+    {
+        let  mut  S  =  self.0.lock ().unwrap ();
+        let  Cache_State {  lifetimes,  replies  }  =  &mut *S;
+        replies.retain (|Q, (at, _)|  lifetimes.get (end_point (Q))
+                                               .is_some_and (|L| at.elapsed ()
+                                                                   < *L));
+        if  lifetimes.contains_key (end_point (query))
+              &&  reply.replace (' ', "").contains ("\"error\":[]")
+            {   replies.insert (query.to_string (),
+                                (Instant::now (), reply.to_string ()));   }
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  lifetimes  ()
+    {
+        use  super::Cache;
+        use  std::time::Duration;
+
+        let  C  =  Cache::default ();
+        let  good  =  r#"{"error":[],"result":{"unixtime":1}}"#;
+
+        C.put ("Time", good);
+        assert_eq! (C.get ("Time"), None);
+
+        C.set_lifetime ("Time", Some (Duration::from_secs (60)));
+        C.set_lifetime ("Ticker", Some (Duration::ZERO));
+        C.put ("Time", good);
+        C.put ("Ticker?pair=XBTUSD", good);
+        C.put ("Time?x=1", r#"{"error":["EGeneral:Too many requests"]}"#);
+
+        assert_eq! (C.get ("Time").as_deref (), Some (good));
+        assert_eq! (C.get ("Time?x=1"), None);
+        assert_eq! (C.get ("Ticker?pair=XBTUSD"), None);
+
+        C.clear ();
+        assert_eq! (C.get ("Time"), None);
+    }
+
+
+
+    #[test]
+    fn  expiry  ()
+    {
+        use  super::Cache;
+        use  std::time::Duration;
+
+        let  C  =  Cache::default ();
+        let  good  =  r#"{"error":[],"result":{}}"#;
+        let  count  =  |C: &Cache|  C.0.lock ().unwrap ().replies.len ();
+
+        C.set_lifetime ("Ticker", Some (Duration::from_millis (50)));
+        C.set_lifetime ("Time", Some (Duration::from_secs (60)));
+        C.put ("Ticker?pair=XBTUSD", good);
+        C.put ("Ticker?pair=ETHUSD", good);
+        std::thread::sleep (Duration::from_millis (60));
+
+        //  A stale reply is thrown away when it is looked for ...
+        assert_eq! (C.get ("Ticker?pair=XBTUSD"), None);
+        assert_eq! (count (&C), 1);
+
+        //  ... and any others when a new reply is kept.
+        C.put ("Time", good);
+        assert_eq! (count (&C), 1);
+        assert_eq! (C.get ("Time").as_deref (), Some (good));
+    }
+}
@@ -0,0 +1,238 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option, Kraken_API, Trade};
+use  crate::reply;
+use  serde_json  as  JSN;
+use  std::time::Duration;
+
+
+
+/** One OHLC candle, in the same terms as the exchange's
+    [Kraken_API::ohlc_data](crate::Kraken_API::ohlc_data) end-point. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Candle
+{
+    /** UNIX time of the start of the candle's interval. */
+    pub  time:    f64,
+
+    /** The price of the first trade in the interval. */
+    pub  open:    f64,
+
+    /** The highest price traded in the interval. */
+    pub  high:    f64,
+
+    /** The lowest price traded in the interval. */
+    pub  low:     f64,
+
+    /** The price of the last trade in the interval. */
+    pub  close:   f64,
+
+    /** The volume-weighted average price of the trades in the interval. */
+    pub  vwap:    f64,
+
+    /** The total volume traded in the interval. */
+    pub  volume:  f64,
+
+    /** The number of trades in the interval. */
+    pub  count:   u64
+}
+
+
+
+impl  Candle
+{
+    /** Interpret one `[time, open, high, low, close, vwap, volume, count]`
+        entry of an OHLC reply. */
+    pub  fn  from_json  (V:  &JSN::Value)  ->  Result<Self, String>
+    {
+        Ok (Candle {  time:    reply::number (&V [0]) ?,
+                      open:    reply::number (&V [1]) ?,
+                      high:    reply::number (&V [2]) ?,
+                      low:     reply::number (&V [3]) ?,
+                      close:   reply::number (&V [4]) ?,
+                      vwap:    reply::number (&V [5]) ?,
+                      volume:  reply::number (&V [6]) ?,
+                      count:   V [7].as_u64 ().unwrap_or (0)  })
+    }
+}
+
+
+
+/** Builds OHLC candles of a fixed interval out of a stream of trades, such as
+    that given by [Kraken_API::trade_stream](crate::Kraken_API::trade_stream),
+    so that indicator code can work with candles rather than with individual
+    trades.
+
+    Candle boundaries fall on whole multiples of the interval since the UNIX
+    epoch, as they do in the exchange's own OHLC data.  Trades must be pushed
+    in time order; any which belong to an interval that has already been
+    finished are dropped.  An interval in which there are no trades gives no
+    candle at all.
+
+    ```ignore
+    let  mut  B  =  KKN::Candle_Builder::new (Duration::from_secs (60));
+    for  T  in  K.trade_stream ("XBTUSD")
+        {   if let Some (C) = B.push (&T ?)   {   println! ("{:?}", C);   }   }
+    ```  */
+
+#[derive(Clone, Debug)]
+pub  struct  Candle_Builder  {  interval:  f64,
+                                current:   Option<Candle>,
+                                cost:      f64,
+                                finished:  Option<f64>  }
+
+
+
+impl  Candle_Builder
+{
+    /** A builder of candles `interval` long (which is taken to be at least one
+        second). */
+    pub  fn  new  (interval:  Duration)  ->  Self
+          {   Candle_Builder {  interval: interval.as_secs ().max (1) as f64,
+                                current: None,  cost: 0.0,
+                                finished: None  }   }
+
+    /** The candle in progress, if any trades have been seen in the current
+        interval. */
+    pub  fn  current  (&self)  ->  Option<&Candle>
+          {   self.current.as_ref ()   }
+
+
+    /** Fold the `trade` into the candle in progress, returning the previous
+        candle if this trade is the first of a new interval. */
+    pub  fn  push  (&mut self,  trade:  &Trade)  ->  Option<Candle>
+    {
+        let  start  =  (trade.time / self.interval).floor () * self.interval;
+        if  self.finished.is_some_and (|F| start <= F)   {   return  None;   }
+
+        let  finished  =  match  &self.current
+                            {   Some (C)  if  start < C.time
+                                    =>  return  None,
+                                Some (C)  if  start > C.time
+                                    =>  self.current.take (),
+                                _   =>  None   };
+
+        let  C  =  self.current.get_or_insert
+                          (Candle {  time: start,  open: trade.price,
+                                     high: trade.price,  low: trade.price,
+                                     close: trade.price,  vwap: 0.0,
+                                     volume: 0.0,  count: 0  });
+
+        if  C.count == 0   {   self.cost  =  0.0;   }
+
+        C.high     =  C.high.max (trade.price);
+        C.low      =  C.low.min (trade.price);
+        C.close    =  trade.price;
+        C.volume  +=  trade.volume;
+        C.count   +=  1;
+        self.cost +=  trade.price * trade.volume;
+        C.vwap     =  if C.volume > 0.0  { self.cost / C.volume }
+                      else               { trade.price };
+
+        self.finish (finished)
+    }
+
+
+    /*  Note the start of a candle being handed out as finished, so that no
+     *  later trade can open its interval again. */
+    fn  finish  (&mut self,  candle:  Option<Candle>)  ->  Option<Candle>
+    {
+        if  let  Some (C)  =  &candle   {   self.finished  =  Some (C.time);   }
+        candle
+    }
+
+
+    /** Finish the candle in progress if its interval has ended by UNIX time
+        `now`, even though no trade of a later interval has been seen; this
+        lets candles be emitted on time in quiet markets. */
+    pub  fn  close_until  (&mut self,  now:  f64)  ->  Option<Candle>
+    {
+        let  finished  =  match  &self.current
+                            {   Some (C)  if  now >= C.time + self.interval
+                                    =>  self.current.take (),
+                                _   =>  None   };
+        self.finish (finished)
+    }
+}
+
+
+
+impl  Kraken_API
+{
+/** Get the recent OHLC candles of `pair` as typed data, oldest first, each
+    `interval` minutes long (one of 1, 5, 15, 30, 60, 240, 1440, 10080 or
+    21600).
+
+    This makes one call to the [Kraken_API::ohlc_data] end-point, which gives
+    at most the last 720 candles; it replaces any
+    [API_Option::INTERVAL] setting, and respects [API_Option::SINCE].  */
+
+  pub  fn  candles  (&mut self,  pair:  &str,  interval:  u32)
+               ->  Result<Vec<Candle>, String>
+    {
+      self.set_opt (API_Option::INTERVAL, interval);
+      let  R  =  reply::result_of (&self.ohlc_data (pair) ?) ?;
+      R.as_object ()
+       .into_iter ()
+       .flatten ()
+       .filter (|(key, _)| *key != "last")
+       .filter_map (|(_, V)| V.as_array ())
+       .flatten ()
+       .map (Candle::from_json)
+       .collect ()
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+  {  #[test]  fn  candles ()
+     {
+         use  crate::test_fixtures::trade;
+
+         let  mut  B  =  super::Candle_Builder::new
+                                          (std::time::Duration::from_secs (60));
+
+         assert! (B.push (&trade (120.5, 10.0, 1.0, None)).is_none ());
+         assert! (B.push (&trade (150.0, 12.0, 1.0, None)).is_none ());
+         assert! (B.push (&trade (170.0, 11.0, 2.0, None)).is_none ());
+         assert! (B.push (&trade (100.0, 99.0, 1.0, None)).is_none ());
+
+         let  C  =  B.push (&trade (185.0, 9.0, 1.0, None)).unwrap ();
+         assert_eq! ((C.time, C.open, C.high, C.low, C.close),
+                     (120.0, 10.0, 12.0, 10.0, 11.0));
+         assert_eq! ((C.volume, C.vwap, C.count),  (4.0, 11.0, 3));
+
+         assert! (B.close_until (239.0).is_none ());
+         assert_eq! (B.close_until (240.0).unwrap ().vwap,  9.0);
+         assert! (B.current ().is_none ());
+
+         /*  A trade of the interval just closed, arriving late, must not
+          *  bring its candle back. */
+         assert! (B.push (&trade (230.0, 8.0, 1.0, None)).is_none ());
+         assert! (B.current ().is_none ());
+         assert! (B.close_until (400.0).is_none ());
+         assert_eq! (B.push (&trade (250.0, 7.0, 1.0, None)),  None);
+         assert_eq! (B.close_until (400.0).unwrap ().time,  240.0);
+     }  }
@@ -0,0 +1,288 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  Checks on market data fetched a page at a time, made as the pages are
+ *  joined together, so that holes, repeats and disorder in the data are
+ *  reported rather than passed on silently into whatever is built from
+ *  them. */
+
+
+
+use  crate::{Candle, Trade};
+use  std::cmp::Ordering;
+
+
+
+/** Something wrong, or at least suspicious, in a series of candles or
+    trades, or in the cursors which page through them; reported by
+    [stitch_candles], [stitch_trades] and [check_cursor]. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  enum  Data_Anomaly
+{
+    /** More than one interval passes between two candles.  On a thinly
+        traded pair the exchange may leave out intervals in which nothing
+        was traded, so this need not mean that data were lost. */
+    GAP  {
+        /** UNIX time of the candle before the gap. */
+        after:  f64,
+        /** UNIX time of the candle after it. */
+        before:  f64
+    },
+
+    /** The trade IDs jump, so the trades in between are missing. */
+    MISSING_TRADES  {
+        /** The ID of the trade before the jump. */
+        after:  u64,
+        /** The ID of the trade after it. */
+        before:  u64
+    },
+
+    /** An entry was already in the series, and was dropped. */
+    OVERLAP  {
+        /** UNIX time of the entry. */
+        time:  f64
+    },
+
+    /** An entry came after a later one, and was dropped. */
+    OUT_OF_ORDER  {
+        /** UNIX time of the entry. */
+        time:  f64,
+        /** UNIX time of the entry before it. */
+        previous:  f64
+    },
+
+    /** A candle starts at a time which is not a whole number of
+        intervals. */
+    MISALIGNED  {
+        /** UNIX time of the candle. */
+        time:  f64
+    },
+
+    /** The `last` cursor of a reply was behind that of the one before. */
+    CURSOR_REGRESSED  {
+        /** The cursor before. */
+        previous:  String,
+        /** The cursor which went back. */
+        next:  String
+    }
+}
+
+
+
+impl  std::fmt::Display  for  Data_Anomaly
+{
+    fn  fmt  (&self,  f:  &mut std::fmt::Formatter)  ->  std::fmt::Result
+    {
+        match  self
+        {
+            Data_Anomaly::GAP { after, before }
+                =>  write! (f, "gap between {} and {}", after, before),
+            Data_Anomaly::MISSING_TRADES { after, before }
+                =>  write! (f, "trades missing between IDs {} and {}",
+                            after, before),
+            Data_Anomaly::OVERLAP { time }
+                =>  write! (f, "entry at {} repeated", time),
+            Data_Anomaly::OUT_OF_ORDER { time, previous }
+                =>  write! (f, "entry at {} came after one at {}",
+                            time, previous),
+            Data_Anomaly::MISALIGNED { time }
+                =>  write! (f, "candle at {} is not on an interval boundary",
+                            time),
+            Data_Anomaly::CURSOR_REGRESSED { previous, next }
+                =>  write! (f, "cursor went back from {} to {}",
+                            previous, next)
+        }
+    }
+}
+
+
+
+/** Add a `page` of candles, each `interval` minutes long, as given by
+    [Kraken_API::candles](crate::Kraken_API::candles), to the end of the
+    `series`, reporting anything amiss.
+
+    The exchange gives the candle still in progress at the end of each page,
+    and again at the start of the next page fetched with the `last` cursor;
+    the newer copy replaces the older one.  Any other candle which is not
+    later than the end of the series is dropped, as a [Data_Anomaly::OVERLAP]
+    if it starts at the same time as one already there, else as
+    [Data_Anomaly::OUT_OF_ORDER].  A page can be checked on its own by
+    stitching it to an empty series.  */
+
+pub  fn  stitch_candles  (series:  &mut Vec<Candle>,  page:  &[Candle],
+                          interval:  u32)
+           ->  Vec<Data_Anomaly>
+{
+    let  step  =  interval as f64 * 60.0;
+    let  mut  anomalies  =  Vec::new ();
+
+    for  C  in  page
+    {
+        if  step > 0.0  &&  C.time % step != 0.0
+            {   anomalies.push (Data_Anomaly::MISALIGNED {  time:  C.time  });
+            }
+
+        let  Some (last)  =  series.last ().map (|L| L.time)
+            else   {   series.push (C.clone ());   continue   };
+
+        match  C.time.total_cmp (&last)
+        {
+            Ordering::Equal  =>  *series.last_mut ().unwrap ()  =  C.clone (),
+            Ordering::Less
+                =>  anomalies.push (
+                       if  series.iter ().any (|S| S.time == C.time)
+                           {   Data_Anomaly::OVERLAP {  time:  C.time  }   }
+                       else   {   Data_Anomaly::OUT_OF_ORDER {
+                                      time:  C.time,
+                                      previous:  last  }   }),
+            Ordering::Greater  =>  {
+                if  C.time - last > step
+                    {   anomalies.push (Data_Anomaly::GAP {  after:   last,
+                                                            before:  C.time
+                                                         });   }
+                series.push (C.clone ());
+            }
+        }
+    }
+
+    anomalies
+}
+
+
+
+/** Add a `page` of trades, as given by
+    [Kraken_API::trades](crate::Kraken_API::trades), to the end of the
+    `series`, reporting anything amiss.
+
+    Where the trades carry the exchange's IDs, which run on one by one for
+    each pair, a trade whose ID is not beyond the last in the series is
+    dropped as a [Data_Anomaly::OVERLAP], and a jump in the IDs is reported
+    as [Data_Anomaly::MISSING_TRADES].  A trade earlier than the last in the
+    series is dropped as [Data_Anomaly::OUT_OF_ORDER].  A page can be checked
+    on its own by stitching it to an empty series.  */
+
+pub  fn  stitch_trades  (series:  &mut Vec<Trade>,  page:  &[Trade])
+           ->  Vec<Data_Anomaly>
+{
+    let  mut  anomalies  =  Vec::new ();
+
+    for  T  in  page
+    {
+        let  Some (last)  =  series.last ()
+            else   {   series.push (T.clone ());   continue   };
+
+        if  let  (Some (previous), Some (id))  =  (last.trade_id, T.trade_id)
+        {
+            if  id <= previous
+            {
+                anomalies.push (Data_Anomaly::OVERLAP {  time:  T.time  });
+                continue;
+            }
+            if  id > previous + 1
+                {   anomalies.push (Data_Anomaly::MISSING_TRADES {
+                                        after:   previous,
+                                        before:  id  });   }
+        }
+
+        if  T.time < last.time
+        {
+            anomalies.push (Data_Anomaly::OUT_OF_ORDER {  time:  T.time,
+                                                          previous:  last.time
+                                                       });
+            continue;
+        }
+
+        series.push (T.clone ());
+    }
+
+    anomalies
+}
+
+
+
+/** Check that the `next` cursor (the `last` member of a reply from one of
+    the market data end-points which page with [API_Option::SINCE]) has not
+    gone back from the `previous` one.  Cursors which are not numbers are
+    not judged.
+
+    [API_Option::SINCE]: crate::API_Option::SINCE  */
+
+pub  fn  check_cursor  (previous:  Option<&str>,  next:  &str)
+           ->  Option<Data_Anomaly>
+{
+    let  previous  =  previous ?;
+    let  behind  =  match  (previous.parse::<u128> (), next.parse::<u128> ())
+       {   (Ok (P), Ok (N))  =>  N < P,
+           _  =>  match  (previous.parse::<f64> (), next.parse::<f64> ())
+                     {   (Ok (P), Ok (N))  =>  N < P,
+                         _  =>  false   }   };
+
+    behind.then (||  Data_Anomaly::CURSOR_REGRESSED {
+                         previous:  previous.to_string (),
+                         next:      next.to_string ()  })
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  stitching  ()
+    {
+        use  super::{Data_Anomaly as DA, check_cursor, stitch_candles,
+                     stitch_trades};
+        use  crate::test_fixtures::{candle, trade};
+
+        let  mut  S  =  Vec::new ();
+        assert! (stitch_candles (&mut S, &[candle (0.0, 1.0),
+                                           candle (60.0, 2.0)], 1)
+                     .is_empty ());
+        assert_eq! (stitch_candles (&mut S, &[candle (60.0, 3.0),
+                                              candle (0.0, 1.0),
+                                              candle (240.0, 4.0),
+                                              candle (180.0, 5.0),
+                                              candle (290.0, 6.0)], 1),
+                    [DA::OVERLAP {  time:  0.0  },
+                     DA::GAP {  after:  60.0,  before:  240.0  },
+                     DA::OUT_OF_ORDER {  time:  180.0,  previous:  240.0  },
+                     DA::MISALIGNED {  time:  290.0  }]);
+        assert_eq! (S.iter ().map (|C| C.close).collect::<Vec<_>> (),
+                    [1.0, 3.0, 4.0, 6.0]);
+
+        let  trade  =  |time: f64, id: u64|  trade (time, 1.0, 1.0, Some (id));
+        let  mut  S  =  vec! [trade (10.0, 1)];
+        assert_eq! (stitch_trades (&mut S, &[trade (10.0, 1), trade (11.0, 2),
+                                             trade (12.0, 5), trade (9.0, 6)]),
+                    [DA::OVERLAP {  time:  10.0  },
+                     DA::MISSING_TRADES {  after:  2,  before:  5  },
+                     DA::OUT_OF_ORDER {  time:  9.0,  previous:  12.0  }]);
+        assert_eq! (S.len (),  3);
+
+        assert_eq! (check_cursor (Some ("1688671234567890123"),
+                                  "1688671234567890124"),
+                    None);
+        assert! (check_cursor (Some ("1688671234567890123"),
+                               "1688671234567890122").is_some ());
+        assert_eq! (check_cursor (None, "5"),  None);
+    }
+}
@@ -0,0 +1,256 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option, Kraken_API, Pair_Info};
+use  crate::reply;
+use  std::collections::{BTreeMap, BTreeSet};
+
+
+
+/** One step in converting an amount of one asset into another: a trade on
+    `pair`, from the asset `from` into the asset `to`.  */
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub  struct  Conversion_Leg
+{
+    /** Kraken's canonical name for the pair, e.g. "XXBTZUSD". */
+    pub  pair:  String,
+
+    /** The asset going in. */
+    pub  from:  String,
+
+    /** The asset coming out. */
+    pub  to:    String
+}
+
+
+
+impl  Conversion_Leg
+{
+    /*  Does the leg sell the pair's base asset (so that the rate is the
+     *  pair's price), or buy it (so that the rate is the inverse)? */
+    fn  sells_base  (&self,  P:  &Pair_Info)  ->  bool
+          {   P.base == self.from   }
+}
+
+
+
+/*  The assets, in order of preference, through which we will convert if
+ *  there is no pair joining two assets directly. */
+
+const  INTERMEDIATES:  [&str; 3]  =  ["ZUSD", "XXBT", "ZEUR"];
+
+
+
+/*  The direct leg from one asset to another, if a pair joins them.  Dark-pool
+ *  pairs (with names ending ".d") are passed over, as they have no public
+ *  ticker. */
+
+fn  direct  (pairs:  &[Pair_Info],  from:  &str,  to:  &str)
+          ->  Option<Conversion_Leg>
+{
+    pairs.iter ()
+         .filter (|P| ! P.name.ends_with (".d"))
+         .find (|P|  (P.base == from  &&  P.quote == to)
+                       ||  (P.base == to  &&  P.quote == from))
+         .map (|P|  Conversion_Leg {  pair:  P.name.clone (),
+                                      from:  from.to_string (),
+                                      to:    to.to_string ()  })
+}
+
+
+
+/*  The legs which take `from` to `to`: none if they are the same asset, one
+ *  if a pair joins them, or two via one of the INTERMEDIATES. */
+
+pub(crate)  fn  route  (pairs:  &[Pair_Info],  from:  &str,  to:  &str)
+          ->  Option<Vec<Conversion_Leg>>
+{
+    if  from == to   {   return  Some (Vec::new ());   }
+    if let Some (L) = direct (pairs, from, to)
+        {   return  Some (vec! [L]);   }
+
+    INTERMEDIATES.iter ()
+                 .filter (|I| **I != from  &&  **I != to)
+                 .find_map (|I|  Some (vec! [direct (pairs, from, I) ?,
+                                             direct (pairs, I, to) ?]))
+}
+
+
+
+/*  The rate at which the legs convert, given the mid-market price of each
+ *  pair. */
+
+pub(crate)  fn  rate  (legs:  &[Conversion_Leg],
+                       pairs:  &[Pair_Info],
+                       mids:  &BTreeMap<String, f64>)
+          ->  Result<f64, String>
+{
+    legs.iter ().try_fold (1.0, |R, L|  {
+        let  P  =  pairs.iter ().find (|P| P.name == L.pair)
+                        .ok_or (format! ("no information for pair '{}'",
+                                         L.pair)) ?;
+        let  M  =  mids.get (&L.pair)
+                       .ok_or (format! ("no ticker for pair '{}'", L.pair)) ?;
+        Ok (if  L.sells_base (P)  {  R * M  }  else  {  R / M  })
+    })
+}
+
+
+
+impl  Kraken_API
+{
+/** Get the [Pair_Info] of every trading pair.
+
+    This calls the [Kraken_API::asset_pairs] end-point, clearing any
+    [API_Option::PAIR] and [API_Option::INFO] settings.  Code which calls this
+    often will do well to have the replies cached with
+    [Kraken_API::set_cache_lifetime].  */
+
+  pub  fn  all_pairs  (&mut self)  ->  Result<Vec<Pair_Info>, String>
+    {
+      self.clear_opt (API_Option::PAIR);
+      self.clear_opt (API_Option::INFO);
+      let  R  =  reply::result_of (&self.asset_pairs () ?) ?;
+      R.as_object ()
+       .into_iter ()
+       .flatten ()
+       .map (|(name, V)| Pair_Info::from_json (name, V))
+       .collect ()
+    }
+
+
+
+/*  The mid-market prices of the named pairs, from one call to the ticker. */
+
+  pub(crate)  fn  mid_prices  (&mut self,  pairs:  &BTreeSet<&str>)
+               ->  Result<BTreeMap<String, f64>, String>
+    {
+      if  pairs.is_empty ()   {   return  Ok (BTreeMap::new ());   }
+
+      Ok (self.tickers (&pairs.iter ().copied ().collect::<Vec<_>> ()) ?
+              .into_iter ()
+              .map (|(name, T)|  (name, T.mid ()))
+              .collect ())
+    }
+
+
+
+/** Find how much one unit of each of the `assets` is worth in the `target`
+    asset, at mid-market prices.  Assets are named in Kraken's canonical form,
+    as in the account balance (e.g. "XXBT", "ZUSD").
+
+    Each asset is converted through a pair joining it directly to the target
+    if there is one, and otherwise through USD, XBT or EUR.  Assets for which
+    no such route exists are left out of the result.  The pairs come from
+    [Kraken_API::all_pairs], and all the prices needed are got with a single
+    call to the [Kraken_API::ticker_info] end-point.  */
+
+  pub  fn  conversion_rates  (&mut self,  assets:  &[&str],  target:  &str)
+               ->  Result<BTreeMap<String, f64>, String>
+    {
+      let  pairs  =  self.all_pairs () ?;
+      let  routes  =  assets.iter ()
+                            .filter_map (|A|  Some ((*A, route (&pairs, A,
+                                                                target) ?)))
+                            .collect::<Vec<_>> ();
+
+      let  needed  =  routes.iter ()
+                            .flat_map (|(_, R)| R.iter ())
+                            .map (|L| L.pair.as_str ())
+                            .collect::<BTreeSet<_>> ();
+      let  mids  =  self.mid_prices (&needed) ?;
+
+      routes.iter ()
+            .map (|(A, R)|  Ok ((A.to_string (), rate (R, &pairs, &mids) ?)))
+            .collect ()
+    }
+
+
+
+/** Convert `amount` of the asset `from` into the asset `to`, at mid-market
+    prices, giving the converted amount and the legs of the route taken.
+    Assets are named as for [Kraken_API::conversion_rates] (an
+    [Asset](crate::Asset) from [Kraken_API::asset] will do), and the route is
+    found in the same way: directly if a pair joins the two assets, otherwise
+    through USD, XBT or EUR.  The route is empty if the two assets are the
+    same.
+
+    This calls [Kraken_API::all_pairs], and the [Kraken_API::ticker_info]
+    end-point once for all the pairs on the route.  */
+
+  pub  fn  convert_amount  (&mut self,  from:  &str,  to:  &str,  amount:  f64)
+               ->  Result<(f64, Vec<Conversion_Leg>), String>
+    {
+      let  pairs  =  self.all_pairs () ?;
+      let  legs  =  route (&pairs, from, to)
+                       .ok_or (format! ("no route from {} to {}", from, to)) ?;
+      let  mids  =  self.mid_prices (&legs.iter ()
+                                          .map (|L| L.pair.as_str ())
+                                          .collect ()) ?;
+      Ok ((amount * rate (&legs, &pairs, &mids) ?, legs))
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  routes  ()  ->  Result<(), String>
+    {
+        use  super::{rate, route};
+        use  crate::Pair_Info;
+        use  std::collections::BTreeMap;
+
+        let  pair  =  |name: &str, base: &str, quote: &str|
+                         Pair_Info {  name:  name.into (),
+                                      altname:  name.into (),
+                                      wsname:  String::new (),
+                                      base:  base.into (),
+                                      quote:  quote.into (),
+                                      pair_decimals:  1,
+                                      lot_decimals:  8,
+                                      ordermin:  None,
+                                      taker_fee:  None,
+                                      maker_fee:  None  };
+        let  pairs  =  [pair ("XXBTZUSD", "XXBT", "ZUSD"),
+                        pair ("XETHXXBT", "XETH", "XXBT"),
+                        pair ("XETHZUSD.d", "XETH", "ZUSD"),
+                        pair ("ZUSDZJPY", "ZUSD", "ZJPY")];
+
+        assert_eq! (route (&pairs, "ZUSD", "ZUSD"), Some (vec! []));
+        assert_eq! (route (&pairs, "XETH", "ZUSD").unwrap ().len (), 2);
+        assert_eq! (route (&pairs, "XETH", "ZGBP"), None);
+
+        let  mids  =  BTreeMap::from ([("XXBTZUSD".to_string (), 40000.0),
+                                       ("XETHXXBT".to_string (), 0.05),
+                                       ("ZUSDZJPY".to_string (), 150.0)]);
+
+        let  R  =  route (&pairs, "XETH", "ZUSD").unwrap ();
+        assert_eq! (rate (&R, &pairs, &mids) ?, 2000.0);
+
+        let  R  =  route (&pairs, "ZJPY", "XXBT").unwrap ();
+        assert_eq! (rate (&R, &pairs, &mids) ?, 1.0 / 150.0 / 40000.0);
+        Ok (())
+    }
+}
@@ -0,0 +1,364 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  Which of the exchange's REST end-points the crate has methods for, and
+ *  with which arguments; and, with the `openapi` feature, a comparison of
+ *  that with the exchange's published OpenAPI description of itself. */
+
+
+
+use  crate::{API_Option  as  Opt, kraken_argument};
+
+
+
+/*  The end-points the crate has methods for: the name, whether it is
+ *  private, the options the method passes on, and any arguments it builds
+ *  itself.  This must be kept in step with the methods of Kraken_API. */
+
+const  END_POINTS:  &[(&str, bool, &[Opt], &[&str])]  =  &[
+    ("Time",          false,  &[],  &[]),
+    ("SystemStatus",  false,  &[],  &[]),
+    ("Assets",        false,  &[Opt::ACLASS, Opt::ASSET],  &[]),
+    ("AssetPairs",    false,  &[Opt::INFO, Opt::PAIR],  &[]),
+    ("Ticker",        false,  &[Opt::PAIR],  &[]),
+    ("OHLC",          false,  &[Opt::PAIR, Opt::INTERVAL, Opt::SINCE],  &[]),
+    ("Depth",         false,  &[Opt::PAIR, Opt::COUNT],  &[]),
+    ("Trades",        false,  &[Opt::PAIR, Opt::SINCE],  &[]),
+    ("Spread",        false,  &[Opt::PAIR, Opt::SINCE],  &[]),
+
+    ("Balance",       true,   &[],  &[]),
+    ("TradeBalance",  true,   &[Opt::ASSET],  &[]),
+    ("OpenOrders",    true,   &[Opt::TRADES, Opt::USERREF],  &[]),
+    ("ClosedOrders",  true,   &[Opt::TRADES, Opt::USERREF, Opt::START,
+                                Opt::END, Opt::OFS, Opt::CLOSE_TIME],  &[]),
+    ("QueryOrders",   true,   &[Opt::TXID, Opt::TRADES, Opt::USERREF],  &[]),
+    ("TradesHistory", true,   &[Opt::TYPE, Opt::TRADES, Opt::START,
+                                Opt::END, Opt::OFS],  &[]),
+    ("QueryTrades",   true,   &[Opt::TXID, Opt::TRADES],  &[]),
+    ("OpenPositions", true,   &[Opt::TXID, Opt::DO_CALCS,
+                                Opt::CONSOLIDATION],  &[]),
+    ("Ledgers",       true,   &[Opt::ACLASS, Opt::ASSET, Opt::TYPE,
+                                Opt::START, Opt::END, Opt::OFS],  &[]),
+    ("QueryLedgers",  true,   &[Opt::ID, Opt::TRADES],  &[]),
+    ("TradeVolume",   true,   &[Opt::PAIR, Opt::FEE_INFO],  &[]),
+    ("AddExport",     true,   &[Opt::REPORT, Opt::FORMAT, Opt::DESCRIPTION,
+                                Opt::FIELDS, Opt::START_TIME,
+                                Opt::END_TIME],  &[]),
+    ("ExportStatus",  true,   &[Opt::REPORT],  &[]),
+    ("RetrieveExport",  true,  &[Opt::ID],  &[]),
+    ("RemoveExport",  true,   &[Opt::ID, Opt::TYPE],  &[]),
+    ("AddOrder",      true,   &[Opt::ORDER_TYPE, Opt::TYPE, Opt::VOLUME,
+                                Opt::PAIR, Opt::USERREF, Opt::PRICE,
+                                Opt::PRICE_2, Opt::TRIGGER, Opt::LEVERAGE,
+                                Opt::OFLAGS, Opt::TIME_IN_FORCE,
+                                Opt::START_TIME, Opt::EXPIRE_TIME,
+                                Opt::CLOSE_TYPE, Opt::CLOSE_PRICE_1,
+                                Opt::CLOSE_PRICE_2, Opt::DEADLINE,
+                                Opt::VALIDATE, Opt::CL_ORD_ID],  &[]),
+    ("AddOrderBatch",  true,  &[Opt::PAIR, Opt::DEADLINE, Opt::VALIDATE],
+                              &["orders"]),
+    ("CancelOrder",   true,   &[Opt::TXID],  &[]),
+    ("CancelAll",     true,   &[],  &[]),
+    ("CancelAllOrdersAfter",  true,  &[Opt::TIMEOUT],  &[]),
+    ("DepositMethods",  true,  &[Opt::ASSET, Opt::ACLASS],  &[]),
+    ("DepositAddresses",  true,  &[Opt::ASSET, Opt::METHOD, Opt::NEW],  &[]),
+    ("WithdrawMethods",  true,  &[Opt::ASSET, Opt::ACLASS],  &[]),
+    ("WithdrawAddresses",  true,  &[Opt::ASSET, Opt::ACLASS, Opt::KEY],  &[]),
+    ("WithdrawInfo",  true,   &[Opt::ASSET, Opt::KEY, Opt::AMOUNT],  &[]),
+    ("Withdraw",      true,   &[Opt::ASSET, Opt::KEY, Opt::AMOUNT],  &[]),
+    ("DepositStatus",  true,  &[Opt::ASSET, Opt::METHOD],  &[]),
+    ("WithdrawStatus",  true,  &[Opt::ASSET, Opt::METHOD],  &[]),
+    ("WalletTransfer",  true,  &[Opt::ASSET, Opt::FROM, Opt::TO, Opt::AMOUNT],
+                               &[]),
+    ("Earn/Strategies",  true,  &[Opt::ASSET, Opt::LOCK_TYPE, Opt::CURSOR],
+                                &[]),
+    ("GetWebSocketsToken",  true,  &[],  &[])
+];
+
+
+
+/** One of the exchange's REST end-points which the crate has a method for,
+    as listed by [supported_end_points]. */
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub  struct  Supported_End_Point
+{
+    /** The end-point's name, as in the Kraken documentation, e.g.
+        "AddOrder". */
+    pub  name:       &'static str,
+
+    /** Whether the end-point is private, and so needs the API key. */
+    pub  private:    bool,
+
+    /** The arguments, as the exchange names them, which the crate's method
+        can send, e.g. "pair"; the nonce is not included. */
+    pub  arguments:  Vec<&'static str>
+}
+
+
+
+/** The end-points which the crate has methods for, with the arguments it
+    can pass them.  Anything else has to be reached with the raw
+    `Kraken_API::stream_result` (with the `json` feature), or by
+    building and signing the request with
+    [Kraken_API::sign_offline](crate::Kraken_API::sign_offline).  */
+
+pub  fn  supported_end_points  ()  ->  Vec<Supported_End_Point>
+{
+    END_POINTS.iter ()
+              .map (|(name, private, options, extra)|
+                      Supported_End_Point {
+                          name,
+                          private:    *private,
+                          arguments:  options.iter ().map (kraken_argument)
+                                             .chain (extra.iter ().copied ())
+                                             .collect ()  })
+              .collect ()
+}
+
+
+
+/** How the crate's [supported_end_points] compare with the exchange's
+    OpenAPI description of its REST interface, as given by
+    `compare_with_openapi` (with the `openapi` feature). */
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub  struct  Coverage_Report
+{
+    /** The end-points the exchange describes which the crate has no method
+        for, e.g. "Earn/Allocate". */
+    pub  missing_end_points:  Vec<String>,
+
+    /** The arguments, as (end-point, argument) pairs, which the exchange
+        describes for end-points the crate does have, but which the crate's
+        method cannot send. */
+    pub  missing_arguments:   Vec<(String, String)>,
+
+    /** The end-points the crate has which the exchange no longer
+        describes. */
+    pub  unknown_end_points:  Vec<String>
+}
+
+impl  Coverage_Report
+{
+    /** Whether the crate covers everything the exchange describes. */
+    pub  fn  is_complete  (&self)  ->  bool
+          {   self.missing_end_points.is_empty ()
+                  &&  self.missing_arguments.is_empty ()   }
+}
+
+
+
+#[cfg(feature = "openapi")]
+mod  openapi
+{
+    use  super::{Coverage_Report, supported_end_points};
+    use  serde_json  as  JSN;
+    use  std::collections::{BTreeMap, BTreeSet};
+
+
+    /*  Follow a "$ref" within the document, as far as it goes. */
+    fn  resolve<'a>  (doc:  &'a JSN::Value,  mut V:  &'a JSN::Value)
+              ->  &'a JSN::Value
+    {
+        for  _  in  0 .. 16
+        {
+            match  V ["$ref"].as_str ().and_then (|R| R.strip_prefix ('#'))
+               {   Some (path)  =>  V  =  doc.pointer (path)
+                                            .unwrap_or (&JSN::Value::Null),
+                   None  =>  break   }
+        }
+        V
+    }
+
+
+    /*  The names of the properties of a schema, including those it gets
+     *  from allOf, anyOf and oneOf. */
+    fn  properties  (doc:  &JSN::Value,  schema:  &JSN::Value,
+                     names:  &mut BTreeSet<String>)
+    {
+        let  S  =  resolve (doc, schema);
+        if let Some (P) = S ["properties"].as_object ()
+            {   names.extend (P.keys ().cloned ());   }
+        for  key  in  ["allOf", "anyOf", "oneOf"]
+        {
+            for  sub  in  S [key].as_array ().into_iter ().flatten ()
+                {   properties (doc, sub, names);   }
+        }
+    }
+
+
+    /*  The arguments of each end-point in the document, by end-point name:
+     *  the query parameters and the properties of any request body. */
+    fn  end_points  (doc:  &JSN::Value)
+           ->  Result<BTreeMap<String, BTreeSet<String>>, String>
+    {
+        let  paths  =  doc ["paths"].as_object ()
+                          .ok_or ("no paths in OpenAPI document") ?;
+        let  mut  out  =  BTreeMap::new ();
+
+        for  (path, item)  in  paths
+        {
+            let  name  =  match  path.split_once ("/public/")
+                                     .or_else (|| path.split_once ("/private/"))
+                              {   Some ((_, name))  =>  name.to_string (),
+                                  None  =>  continue   };
+            let  names  :  &mut BTreeSet<String>  =  out.entry (name)
+                                                         .or_default ();
+
+            for  operation  in  resolve (doc, item).as_object ()
+                                                   .into_iter ().flatten ()
+                                                   .map (|(_, O)| O)
+            {
+                for  P  in  operation ["parameters"].as_array ()
+                                                    .into_iter ().flatten ()
+                {
+                    let  P  =  resolve (doc, P);
+                    if  P ["in"] == "query"
+                    {   if let Some (N) = P ["name"].as_str ()
+                            {   names.insert (N.to_string ());   }   }
+                }
+                for  (_, media)  in  resolve (doc, &operation ["requestBody"])
+                                         ["content"].as_object ()
+                                                    .into_iter ().flatten ()
+                    {   properties (doc, &media ["schema"], names);   }
+            }
+            names.remove ("nonce");
+        }
+
+        Ok (out)
+    }
+
+
+    /** Compare the crate's [supported_end_points] with an OpenAPI
+        description of the exchange's REST interface, given as the JSON
+        text of the document.  End-points are matched by the last part of
+        their path, after "/public/" or "/private/".  */
+
+    pub  fn  compare_with_openapi  (spec:  &str)
+               ->  Result<Coverage_Report, String>
+    {
+        let  doc  =  JSN::from_str::<JSN::Value> (spec)
+                        .map_err (|E| format! ("reading OpenAPI document: {}",
+                                               E)) ?;
+        let  theirs  =  end_points (&doc) ?;
+        let  ours  =  supported_end_points ();
+
+        let  mut  report  =  Coverage_Report::default ();
+        for  (name, arguments)  in  &theirs
+        {
+            match  ours.iter ().find (|E| E.name == name)
+            {
+                None  =>  report.missing_end_points.push (name.clone ()),
+                Some (E)  =>  report.missing_arguments.extend
+                                  (arguments.iter ()
+                                       .filter (|A| ! E.arguments
+                                                       .contains (&A.as_str ()))
+                                       .map (|A| (name.clone (), A.clone ())))
+            }
+        }
+        report.unknown_end_points
+            =  ours.iter ()
+                   .filter (|E| ! theirs.contains_key (E.name))
+                   .map (|E| E.name.to_string ())
+                   .collect ();
+
+        Ok (report)
+    }
+
+
+    /** Fetch the OpenAPI description of the exchange's REST interface, in
+        JSON, from `url`, and compare it with the crate's
+        [supported_end_points], as [compare_with_openapi] does.  Kraken
+        publishes its description with its API documentation; the address
+        has moved before, so it is for the caller to give.  */
+
+    pub  fn  openapi_coverage  (url:  &str)  ->  Result<Coverage_Report, String>
+    {
+        let  fail  =  |E: curl::Error|  format! ("fetching {}: {}", url, E);
+        let  mut  C  =  curl::easy::Easy::new ();
+        C.url (url).map_err (fail) ?;
+        C.follow_location (true).map_err (fail) ?;
+        C.fail_on_error (true).map_err (fail) ?;
+
+        let  mut  body  =  Vec::new ();
+        {
+            let  mut  T  =  C.transfer ();
+            T.write_function (|data|  {  body.extend_from_slice (data);
+                                         Ok (data.len ())  })
+             .map_err (fail) ?;
+            T.perform ().map_err (fail) ?;
+        }
+
+        compare_with_openapi (&String::from_utf8_lossy (&body))
+    }
+}
+
+#[cfg(feature = "openapi")]
+pub  use  openapi::{compare_with_openapi, openapi_coverage};
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  registry  ()
+    {
+        let  E  =  super::supported_end_points ();
+        let  add  =  E.iter ().find (|E| E.name == "AddOrder").unwrap ();
+        assert! (add.private);
+        assert! (add.arguments.contains (&"ordertype"));
+        assert! (add.arguments.contains (&"cl_ord_id"));
+        assert! (! E.iter ().find (|E| E.name == "Depth").unwrap ().private);
+    }
+
+
+    #[cfg(feature = "openapi")]
+    #[test]
+    fn  openapi  ()
+    {
+        let  spec  =  r##"{
+          "paths": {
+            "/public/Depth":  {"get": {"parameters": [
+                                  {"name": "pair", "in": "query"},
+                                  {"$ref": "#/components/parameters/asset"}]}},
+            "/private/Earn/Allocate":
+                {"post": {"requestBody": {"content": {"application/json":
+                    {"schema": {"properties": {"nonce": {}, "asset": {}}}}}}}},
+            "/private/Balance":
+                {"post": {"requestBody": {"content": {"application/json":
+                    {"schema": {"$ref": "#/components/schemas/balance"}}}}}}},
+          "components": {
+            "parameters": {"asset": {"name": "asset", "in": "query"}},
+            "schemas": {"balance": {"allOf": [{"properties": {"nonce": {},
+                                                              "rebase": {}}}]}}}
+        }"##;
+
+        let  R  =  super::compare_with_openapi (spec).unwrap ();
+        assert_eq! (R.missing_end_points,  ["Earn/Allocate"]);
+        assert_eq! (R.missing_arguments,
+                    [("Balance".to_string (), "rebase".to_string ()),
+                     ("Depth".to_string (), "asset".to_string ())]);
+        assert! (R.unknown_end_points.contains (&"Time".to_string ()));
+        assert! (! R.is_complete ());
+    }
+}
@@ -0,0 +1,300 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  API keys and secrets kept on disk encrypted under a passphrase.  The file
+ *  is a single line of text:
+ *
+ *      dmbcs-kraken-credentials 1 <iterations> <salt> <iv> <tag> <data>
+ *
+ *  where the last four fields are in base 64; the data are the key and
+ *  secret, on a line each, encrypted with AES-256-GCM under a key derived
+ *  from the passphrase with PBKDF2-HMAC-SHA256, and the first three fields
+ *  are authenticated along with them. */
+
+
+
+use  crate::{Kraken_API, connect};
+use  openssl  as  SSL;
+use  std::path::Path;
+
+
+
+const  MAGIC:       &str   =  "dmbcs-kraken-credentials";
+const  VERSION:     &str   =  "1";
+const  ITERATIONS:  usize  =  600_000;
+
+/*  The iteration counts a file may claim: fewer would make the passphrase
+ *  cheap to guess, and more would keep us busy for minutes on a file made
+ *  to waste our time. */
+const  SANE_ITERATIONS:  std::ops::RangeInclusive<usize>
+                                    =  10_000 ..= 10_000_000;
+
+
+
+/** Where to get the passphrase which unlocks an encrypted credentials file
+    from; see [connect_encrypted]. */
+
+#[derive(Clone, Debug)]
+pub  enum  Passphrase
+{
+    /** This passphrase. */
+    GIVEN  (String),
+
+    /** The value of the named environment variable, such as one set by a
+        service manager from a secret store. */
+    ENVIRONMENT  (String),
+
+    /** Ask on the terminal, with this prompt.  On Unix the passphrase is
+        not echoed; elsewhere it is read from the standard input as
+        typed. */
+    PROMPT  (String)
+}
+
+impl  Passphrase
+{
+    /** Get the passphrase. */
+    pub  fn  resolve  (&self)  ->  Result<String, String>
+    {
+        match  self
+        {
+            Passphrase::GIVEN (P)  =>  Ok (P.clone ()),
+            Passphrase::ENVIRONMENT (V)
+                =>  std::env::var (V)
+                       .map_err (|_| format! ("no passphrase in ${}", V)),
+            Passphrase::PROMPT (prompt)  =>  prompt_for (prompt)
+        }
+    }
+}
+
+
+
+/*  Ask for a line on the terminal, keeping it from being echoed where we
+ *  know how. */
+
+#[cfg(unix)]
+fn  prompt_for  (prompt:  &str)  ->  Result<String, String>
+{
+    use  std::io::{BufRead, Write};
+    use  std::process::Command;
+
+    let  fail  =  |E: std::io::Error|  format! ("asking for passphrase: {}", E);
+    let  tty   =  || std::fs::File::open ("/dev/tty").map_err (fail);
+    let  stty  =  |setting: &str|  Command::new ("stty").arg (setting)
+                                            .stdin (tty () ?)
+                                            .status ()
+                                            .map_err (fail);
+
+    let  mut  out  =  std::fs::OpenOptions::new ().write (true)
+                                                  .open ("/dev/tty")
+                                                  .map_err (fail) ?;
+    write! (out, "{}", prompt).map_err (fail) ?;
+    out.flush ().map_err (fail) ?;
+
+    stty ("-echo") ?;
+    let  mut  line  =  String::new ();
+    let  read  =  std::io::BufReader::new (tty () ?).read_line (&mut line);
+    stty ("echo") ?;
+    writeln! (out).map_err (fail) ?;
+
+    read.map_err (fail) ?;
+    Ok (line.trim_end_matches (['\r', '\n']).to_string ())
+}
+
+#[cfg(not(unix))]
+fn  prompt_for  (prompt:  &str)  ->  Result<String, String>
+{
+    use  std::io::Write;
+
+    let  fail  =  |E: std::io::Error|  format! ("asking for passphrase: {}", E);
+    eprint! ("{}", prompt);
+    std::io::stderr ().flush ().map_err (fail) ?;
+    let  mut  line  =  String::new ();
+    std::io::stdin ().read_line (&mut line).map_err (fail) ?;
+    Ok (line.trim_end_matches (['\r', '\n']).to_string ())
+}
+
+
+
+fn  derive_key  (passphrase:  &str,  salt:  &[u8],  iterations:  usize)
+       ->  Result<[u8; 32], String>
+{
+    let  mut  key  =  [0u8; 32];
+    SSL::pkcs5::pbkdf2_hmac (passphrase.as_bytes (), salt, iterations,
+                             SSL::hash::MessageDigest::sha256 (), &mut key)
+        .map_err (|E| format! ("deriving key: {}", E)) ?;
+    Ok (key)
+}
+
+
+
+/** Encrypt an API `key` and `secret` under `passphrase`, giving the text
+    to be kept in a credentials file; [save_credentials] writes it to one,
+    and [decrypt_credentials] and [connect_encrypted] get the key and secret
+    back.  The encryption is AES-256-GCM, with a key derived from the
+    passphrase by 600,000 rounds of PBKDF2-HMAC-SHA256.  */
+
+pub  fn  encrypt_credentials  (key:  &str,  secret:  &str,  passphrase:  &str)
+           ->  Result<String, String>
+{
+    let  fail  =  |E: SSL::error::ErrorStack|  format! ("encrypting: {}", E);
+    let  mut  salt  =  [0u8; 16];
+    let  mut  iv    =  [0u8; 12];
+    SSL::rand::rand_bytes (&mut salt).map_err (fail) ?;
+    SSL::rand::rand_bytes (&mut iv).map_err (fail) ?;
+
+    let  header  =  format! ("{} {} {}", MAGIC, VERSION, ITERATIONS);
+    let  mut  tag  =  [0u8; 16];
+    let  data  =  SSL::symm::encrypt_aead
+                       (SSL::symm::Cipher::aes_256_gcm (),
+                        &derive_key (passphrase, &salt, ITERATIONS) ?,
+                        Some (&iv),  header.as_bytes (),
+                        format! ("{}\n{}\n", key, secret).as_bytes (),
+                        &mut tag)
+                    .map_err (fail) ?;
+
+    let  B  =  SSL::base64::encode_block;
+    Ok (format! ("{} {} {} {} {}\n", header, B (&salt), B (&iv), B (&tag),
+                 B (&data)))
+}
+
+
+
+/** Get the API key and secret back out of the `text` of a credentials file
+    made by [encrypt_credentials].  A wrong passphrase and a file which has
+    been tampered with are both reported as failure to decrypt. */
+
+pub  fn  decrypt_credentials  (text:  &str,  passphrase:  &str)
+           ->  Result<(String, String), String>
+{
+    let  F  =  text.split_whitespace ().collect::<Vec<_>> ();
+    if  F.len () != 7  ||  F [0] != MAGIC
+        {   Err ("not an encrypted credentials file") ?   }
+    if  F [1] != VERSION
+        {   Err (format! ("credentials file is of unknown version {}",
+                          F [1])) ?   }
+
+    let  iterations  =  F [2].parse::<usize> ()
+                             .map_err (|_| "credentials file is corrupt") ?;
+    if  ! SANE_ITERATIONS.contains (&iterations)
+        {   Err (format! ("credentials file asks for {} key derivation \
+                           iterations, outside the range {} to {}",
+                          iterations, SANE_ITERATIONS.start (),
+                          SANE_ITERATIONS.end ())) ?   }
+
+    let  B  =  |S: &str|  SSL::base64::decode_block (S)
+                             .map_err (|_| "credentials file is corrupt");
+    let  header  =  F [.. 3].join (" ");
+
+    let  plain  =  SSL::symm::decrypt_aead
+                        (SSL::symm::Cipher::aes_256_gcm (),
+                         &derive_key (passphrase, &B (F [3]) ?, iterations) ?,
+                         Some (&B (F [4]) ?),  header.as_bytes (),
+                         &B (F [6]) ?,  &B (F [5]) ?)
+                     .map_err (|_| "cannot decrypt credentials: wrong \
+                                    passphrase, or the file is corrupt") ?;
+
+    let  plain  =  String::from_utf8 (plain)
+                        .map_err (|_| "credentials file is corrupt") ?;
+    match  plain.lines ().collect::<Vec<_>> () [..]
+       {   [key, secret]  =>  Ok ((key.to_string (), secret.to_string ())),
+           _  =>  Err ("credentials file is corrupt".to_string ())   }
+}
+
+
+
+/** Write the API `key` and `secret`, encrypted under `passphrase` as by
+    [encrypt_credentials], to the file at `path`; on Unix the file is
+    readable by its owner alone.  An existing file is replaced. */
+
+pub  fn  save_credentials  (path:  impl AsRef<Path>,  key:  &str,
+                            secret:  &str,  passphrase:  &str)
+           ->  Result<(), String>
+{
+    use  std::io::Write;
+
+    let  text  =  encrypt_credentials (key, secret, passphrase) ?;
+    let  fail  =  |E: std::io::Error|  format! ("writing {}: {}",
+                                                path.as_ref ().display (), E);
+
+    let  mut  O  =  std::fs::OpenOptions::new ();
+    O.write (true).create (true).truncate (true);
+    #[cfg(unix)]
+    {   use  std::os::unix::fs::OpenOptionsExt;   O.mode (0o600);   }
+
+    O.open (path.as_ref ()).map_err (fail) ?
+     .write_all (text.as_bytes ()).map_err (fail)
+}
+
+
+
+/** Get a handle on the exchange, as [connect] does, with the key and secret
+    from the encrypted credentials file at `path` (made with
+    [save_credentials]), unlocked with the given passphrase.
+
+    ```ignore
+    let  mut  K  =  KKN::connect_encrypted
+                         ("/etc/trader/kraken.cred",
+                          KKN::Passphrase::ENVIRONMENT ("KRAKEN_PASS"
+                                                            .into ())) ?;
+    ```  */
+
+pub  fn  connect_encrypted  (path:  impl AsRef<Path>,  passphrase:  Passphrase)
+           ->  Result<Kraken_API, String>
+{
+    let  text  =  std::fs::read_to_string (path.as_ref ())
+                     .map_err (|E| format! ("reading {}: {}",
+                                            path.as_ref ().display (), E)) ?;
+    let  (key, secret)  =  decrypt_credentials (&text,
+                                                &passphrase.resolve () ?) ?;
+    Ok (connect (key, secret))
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  round_trip  ()
+    {
+        use  super::{decrypt_credentials, encrypt_credentials};
+
+        let  T  =  encrypt_credentials ("the key", "the secret", "open sesame")
+                       .unwrap ();
+        assert! (T.starts_with ("dmbcs-kraken-credentials 1 600000 "));
+        assert! (! T.contains ("secret"));
+
+        assert_eq! (decrypt_credentials (&T, "open sesame"),
+                    Ok (("the key".into (), "the secret".into ())));
+        assert! (decrypt_credentials (&T, "open barley").is_err ());
+        assert! (decrypt_credentials (&T.replace (" 600000 ", " 500000 "),
+                                      "open sesame").is_err ());
+        assert! (decrypt_credentials ("key secret", "open sesame").is_err ());
+
+        /*  Absurd iteration counts are refused before any work is done. */
+        for  N  in  ["1", "9999", "10000001", "18446744073709551615"]
+        {   let  E  =  decrypt_credentials
+                           (&T.replace (" 600000 ", &format! (" {} ", N)),
+                            "open sesame")
+                           .unwrap_err ();
+            assert! (E.contains ("iterations"), "{}", E);   }
+    }
+}
@@ -0,0 +1,271 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option as Opt, Cancel_Token, Instruction, Kraken_API,
+             Order_Type, Schedule, System_Status};
+use  crate::reply;
+use  std::time::{Duration, SystemTime};
+
+
+
+/** A purchase to be made over and over again by a [DCA_Scheduler]. */
+
+#[derive(Clone, Debug)]
+pub  struct  DCA_Buy
+{
+    /** The pair to trade, in any form the exchange understands, e.g.
+        "XBTUSD". */
+    pub  pair:            String,
+
+    /** The amount of the base asset to buy each time. */
+    pub  volume:          f64,
+
+    /** If `None`, buy with a market order.  Otherwise place a limit order this
+        many percent below the best asking price at the time. */
+    pub  limit_discount:  Option<f64>
+}
+
+
+
+struct  Plan  {  schedule:  Schedule,
+                buy:       DCA_Buy,
+                next:      Option<SystemTime>  }
+
+
+
+/** Makes recurring purchases on crontab-like [Schedule]s: the classic
+    dollar-cost-averaging bot.
+
+    Before any buy is placed the exchange's [System_Status] is checked, and the
+    buy is skipped (not deferred) if the exchange is not fully on-line; the
+    volume is also checked against the pair's minimum order size.  A run of the
+    scheduler which was missed altogether (because the program was not running,
+    say) is not made up later.
+
+    Every order is placed from a clean slate: [Kraken_API::clear_all_options]
+    is called before each one.
+
+    ```ignore
+    let  mut  D  =  KKN::DCA_Scheduler::new ();
+    D.add (KKN::Schedule::parse ("0 12 * * 5") ?,
+           KKN::DCA_Buy { pair: "XBTUSD".to_string (),
+                          volume: 0.0005,
+                          limit_discount: None })
+     .on_log (|m| eprintln! ("{}", m));
+    D.validate (&mut K) ?;
+    D.run (&mut K, &KKN::Cancel_Token::new ());
+    ```  */
+
+#[derive(Default)]
+pub  struct  DCA_Scheduler  {  plans:    Vec<Plan>,
+                               dry_run:  bool,
+                               log:      Option<Log_Callback>  }
+
+type  Log_Callback  =  Box<dyn FnMut (&str) + Send>;
+
+
+
+impl  DCA_Scheduler
+{
+    /** A scheduler with nothing to do. */
+    pub  fn  new  ()  ->  Self   {   Self::default ()   }
+
+    /** Make the purchase `buy` at every time `schedule` fires. */
+    pub  fn  add  (&mut self,  schedule:  Schedule,  buy:  DCA_Buy)
+              ->  &mut Self
+    {
+        let  next  =  schedule.next_after (SystemTime::now ());
+        self.plans.push (Plan {  schedule,  buy,  next  });
+        self
+    }
+
+    /** If set, orders are sent with [API_Option::VALIDATE](crate::API_Option)
+        so that the exchange checks them but does not act on them. */
+    pub  fn  set_dry_run  (&mut self,  dry_run:  bool)  ->  &mut Self
+          {   self.dry_run  =  dry_run;   self   }
+
+    /** Have a line of text passed to `f` describing everything the scheduler
+        does, or fails to do. */
+    pub  fn  on_log<F>  (&mut self,  f:  F)  ->  &mut Self
+                where  F:  FnMut (&str) + Send + 'static
+          {   self.log  =  Some (Box::new (f));   self   }
+
+    /** The next time at which some purchase is due. */
+    pub  fn  next_due  (&self)  ->  Option<SystemTime>
+          {   self.plans.iter ().filter_map (|P| P.next).min ()   }
+
+
+    /** Check with the exchange that every pair is known and that every volume
+        is acceptable, so that configuration mistakes are found at start-up
+        rather than at the first scheduled purchase.  */
+    pub  fn  validate  (&mut self,  K:  &mut Kraken_API)  ->  Result<(), String>
+    {
+        for  P  in  &self.plans
+            {   K.pair_info (&P.buy.pair) ?.check_volume (P.buy.volume) ?;   }
+        Ok (())
+    }
+
+
+    /** Place all the purchases which have fallen due by `now`, returning the
+        exchange's replies (or our reasons for not placing an order).  */
+    pub  fn  run_due  (&mut self,  K:  &mut Kraken_API,  now:  SystemTime)
+                ->  Vec<Result<String, String>>
+    {
+        let  mut  results  =  Vec::new ();
+        let  mut  status   =  None;
+
+        for  i  in  0 .. self.plans.len ()
+        {
+            match  self.plans [i].next   {   Some (T)  if  T <= now  =>  (),
+                                             _  =>  continue   }
+
+            self.plans [i].next  =  self.plans [i].schedule.next_after (now);
+            let  buy  =  self.plans [i].buy.clone ();
+
+            if  status.is_none ()
+                {   status  =  Some (K.health_check ().map (|H| H.status));   }
+
+            let  outcome  =  match  skip_reason (status.as_ref ().unwrap (),
+                                                 &buy)
+                                {   Some (E)  =>  Err (E),
+                                    None  =>  self.place (K, &buy)   };
+
+            let  line  =  match  &outcome
+                            {   Ok (R)   =>  format! ("DCA order placed: {}",R),
+                                Err (E)  =>  format! ("DCA: {}", E)   };
+            if let Some (f) = &mut self.log   {   f (&line);   }
+
+            results.push (outcome);
+        }
+
+        results
+    }
+
+
+    /** Keep placing purchases as they fall due, until `stop` is cancelled. */
+    pub  fn  run  (&mut self,  K:  &mut Kraken_API,  stop:  &Cancel_Token)
+    {
+        while  ! stop.is_cancelled ()
+        {
+            self.run_due (K, SystemTime::now ());
+
+            stop.pause (self.next_due ()
+                            .and_then (|T| T.duration_since (SystemTime::now ())
+                                            .ok ())
+                            .unwrap_or (Duration::from_secs (60)));
+        }
+    }
+
+
+    fn  place  (&self,  K:  &mut Kraken_API,  buy:  &DCA_Buy)
+            ->  Result<String, String>
+    {
+        let  P  =  K.pair_info (&buy.pair) ?;
+        P.check_volume (buy.volume) ?;
+
+        let  order_type  =  match  buy.limit_discount
+            {
+                None  =>  {  K.clear_all_options ();   Order_Type::MARKET  }
+                Some (D)  =>
+                  {
+                    let  T  =  reply::result_of
+                                      (&K.ticker_info (P.name.clone ()) ?) ?;
+                    let  ask  =  reply::number (&T [&P.name] ["a"] [0]) ?;
+                    K.clear_all_options ();
+                    K.set_opt (Opt::PRICE,
+                               P.format_price (ask * (1.0 - D / 100.0)));
+                    Order_Type::LIMIT
+                  }
+            };
+
+        if  self.dry_run   {   K.set_opt (Opt::VALIDATE, true);   }
+
+        let  R  =  K.add_order (order_type,
+                                Instruction::BUY,
+                                P.format_volume (buy.volume),
+                                &P.name) ?;
+        reply::result_of (&R) ?;
+        Ok (R)
+    }
+}
+
+
+
+/*  Why the `buy` is not to be made, given the exchange's `status`: anything
+ *  but fully on-line, or not knowing, rules it out. */
+
+fn  skip_reason  (status:  &Result<System_Status, String>,  buy:  &DCA_Buy)
+      ->  Option<String>
+{
+    let  why  =  match  status
+                    {   Ok (System_Status::ONLINE)  =>  return  None,
+                        Ok (S)  =>  format! ("exchange is in {} mode",
+                                             S.as_kraken_string ()),
+                        Err (E)  =>  E.clone ()   };
+    Some (format! ("skipped buy of {} {}: {}", buy.volume, buy.pair, why))
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  due  ()  ->  Result<(), String>
+    {
+        use  super::{DCA_Buy, DCA_Scheduler, Plan, skip_reason};
+        use  crate::{Cancel_Token, Kraken_API, Schedule, System_Status};
+        use  std::time::{Duration, UNIX_EPOCH};
+
+        //  2022-12-25T09:30:59Z; the plan is next due at 09:45.
+        let  t  =  UNIX_EPOCH + Duration::from_secs (1671960659);
+        let  due  =  UNIX_EPOCH + Duration::from_secs (1671961500);
+        let  buy  =  DCA_Buy {  pair:  "XBTUSD".into (),  volume:  0.0005,
+                                limit_discount:  None  };
+
+        let  schedule  =  Schedule::parse ("*/15 * * * *") ?;
+        let  mut  D  =  DCA_Scheduler::new ();
+        D.plans.push (Plan {  next:  schedule.next_after (t),  schedule,
+                              buy:  buy.clone ()  });
+        assert_eq! (D.next_due (),  Some (due));
+
+        /*  The request for the status is cancelled before it goes out, so
+         *  nothing reaches the exchange. */
+        let  mut  K  =  Kraken_API::default ();
+        let  stop  =  Cancel_Token::new ();
+        K.set_cancel_token (stop.clone ());
+        stop.cancel ();
+
+        assert! (D.run_due (&mut K, due - Duration::from_secs (1)).is_empty ());
+        assert_eq! (D.run_due (&mut K, due),
+                    [Err ("skipped buy of 0.0005 XBTUSD: request cancelled"
+                              .to_string ())]);
+        assert_eq! (D.next_due (),  Some (due + Duration::from_secs (15 * 60)));
+        assert! (D.run_due (&mut K, due).is_empty ());
+
+        assert_eq! (skip_reason (&Ok (System_Status::ONLINE), &buy),  None);
+        assert_eq! (skip_reason (&Ok (System_Status::MAINTENANCE), &buy),
+                    Some ("skipped buy of 0.0005 XBTUSD: exchange is in \
+                           maintenance mode".to_string ()));
+        assert! (skip_reason (&Ok (System_Status::POST_ONLY), &buy).is_some ());
+        Ok (())
+    }
+}
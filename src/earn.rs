@@ -0,0 +1,235 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option as Opt, Kraken_API};
+use  crate::reply;
+use  crate::strictness;
+use  serde_json  as  JSN;
+use  std::time::Duration;
+
+
+
+/** One of the earn strategies on offer, as given by the
+    [Kraken_API::earn_strategies] end-point. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Earn_Strategy
+{
+    /** The strategy's ID, by which funds are allocated to it. */
+    pub  id:                String,
+
+    /** The asset the strategy takes, e.g. "DOT". */
+    pub  asset:             String,
+
+    /** How the strategy holds the funds: "flex", "bonded", "timed" or
+        "instant". */
+    pub  lock_type:         String,
+
+    /** The low end of the exchange's estimate of the annual yield, in
+        percent. */
+    pub  apr_low:           Option<f64>,
+
+    /** The high end of the exchange's estimate of the annual yield, in
+        percent. */
+    pub  apr_high:          Option<f64>,
+
+    /** How long funds are out of reach in all: the bonding, exit queue and
+        unbonding periods of a bonded strategy, or the duration of a timed
+        one; zero for the others. */
+    pub  lock_period:       Duration,
+
+    /** How often rewards are paid, if the exchange says. */
+    pub  payout_frequency:  Option<Duration>,
+
+    /** The least amount which may be allocated to the strategy, if there is
+        a minimum. */
+    pub  min_allocation:    Option<f64>,
+
+    /** The fee charged on allocating funds, in percent. */
+    pub  allocation_fee:    f64,
+
+    /** The fee charged on taking funds out, in percent. */
+    pub  deallocation_fee:  f64,
+
+    /** Whether the account may allocate funds to the strategy now. */
+    pub  can_allocate:      bool,
+
+    /** Whether the account may take funds out of the strategy now. */
+    pub  can_deallocate:    bool
+}
+
+
+
+impl  Earn_Strategy
+{
+    /** Interpret one member of the 'items' list of a Strategies reply. */
+    pub  fn  from_json  (V:  &JSN::Value)  ->  Result<Self, String>
+    {
+        strictness::check_fields ("Earn_Strategy", V,
+                                  &["id", "asset", "lock_type", "apr_estimate",
+                                    "user_min_allocation", "allocation_fee",
+                                    "deallocation_fee", "auto_compound",
+                                    "yield_source", "can_allocate",
+                                    "can_deallocate",
+                                    "allocation_restriction_info",
+                                    "user_cap"]) ?;
+
+        let  optional  =  |V: &JSN::Value, f: &str|
+                             if  V.is_null ()  {  Ok (None)  }
+                             else  {  reply::number (V)
+                                          .map (Some)
+                                          .map_err (|E| format! ("{}: {}",
+                                                                 f, E))  };
+        let  L  =  &V ["lock_type"];
+        let  seconds  =  |f: &str|  L [f].as_u64 ().unwrap_or (0);
+
+        let  lock_type  =  reply::string (L, "type") ?;
+        let  lock_period  =  match  lock_type.as_str ()
+           {   "bonded"  =>  seconds ("bonding_period")
+                               + seconds ("exit_queue_period")
+                               + seconds ("unbonding_period"),
+               "timed"   =>  seconds ("duration"),
+               _         =>  0   };
+
+        Ok (Earn_Strategy {
+                id:                reply::string (V, "id") ?,
+                asset:             reply::string (V, "asset") ?,
+                lock_type,
+                apr_low:           optional (&V ["apr_estimate"] ["low"],
+                                             "apr_estimate") ?,
+                apr_high:          optional (&V ["apr_estimate"] ["high"],
+                                             "apr_estimate") ?,
+                lock_period:       Duration::from_secs (lock_period),
+                payout_frequency:  L ["payout_frequency"].as_u64 ()
+                                                  .map (Duration::from_secs),
+                min_allocation:    optional (&V ["user_min_allocation"],
+                                             "user_min_allocation") ?,
+                allocation_fee:    optional (&V ["allocation_fee"],
+                                             "allocation_fee") ?
+                                       .unwrap_or (0.0),
+                deallocation_fee:  optional (&V ["deallocation_fee"],
+                                             "deallocation_fee") ?
+                                       .unwrap_or (0.0),
+                can_allocate:      V ["can_allocate"].as_bool ()
+                                                     .unwrap_or (false),
+                can_deallocate:    V ["can_deallocate"].as_bool ()
+                                                       .unwrap_or (false)  })
+    }
+}
+
+
+
+/*  Put the strategies with the best yield first: by the high end of the
+ *  estimate and then the low end, with those which have no estimate last. */
+
+fn  sort_by_apr  (S:  &mut [Earn_Strategy])
+{
+    let  key  =  |E: &Earn_Strategy|  (E.apr_high.unwrap_or (f64::NEG_INFINITY),
+                                       E.apr_low.unwrap_or (f64::NEG_INFINITY));
+    S.sort_by (|a, b|  {  let  (a, b)  =  (key (a), key (b));
+                          b.0.total_cmp (&a.0).then (b.1.total_cmp (&a.1))  });
+}
+
+
+
+impl  Kraken_API
+{
+/** Get all the earn strategies which take `asset` (e.g. "DOT"), best
+    estimated yield first, so that code can choose where to allocate funds.
+
+    This calls the [Kraken_API::earn_strategies] end-point as many times as
+    it takes to read every page, replacing any
+    [API_Option::ASSET](crate::API_Option::ASSET) and
+    [API_Option::CURSOR](crate::API_Option::CURSOR) settings; an
+    [API_Option::LOCK_TYPE](crate::API_Option::LOCK_TYPE) setting is
+    respected.  */
+
+  pub  fn  strategies_by_apr  (&mut self,  asset:  &str)
+               ->  Result<Vec<Earn_Strategy>, String>
+    {
+      self.set_opt (Opt::ASSET, asset);
+      self.clear_opt (Opt::CURSOR);
+      let  mut  S  =  Vec::new ();
+
+      loop
+      {
+          let  R  =  reply::result_of (&self.earn_strategies () ?) ?;
+          for  V  in  R ["items"].as_array ().into_iter ().flatten ()
+              {   S.push (Earn_Strategy::from_json (V) ?);   }
+
+          match  R ["next_cursor"].as_str ()
+             {   Some (C)  if  ! C.is_empty ()
+                     =>  self.set_opt (Opt::CURSOR, C),
+                 _   =>  break   }
+      }
+
+      self.clear_opt (Opt::CURSOR);
+      sort_by_apr (&mut S);
+      Ok (S)
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  strategies  ()  ->  Result<(), String>
+    {
+        use  super::{Earn_Strategy, sort_by_apr};
+        use  std::time::Duration;
+
+        let  bonded  =  serde_json::json! (
+            { "id": "ESDQCOL-WTZEU-NU55QF", "asset": "DOT",
+              "lock_type": { "type": "bonded", "payout_frequency": 604800,
+                             "bonding_period": 0, "exit_queue_period": 0,
+                             "unbonding_period": 2419200 },
+              "apr_estimate": { "low": "12.0000", "high": "15.0000" },
+              "user_min_allocation": "0.01",
+              "allocation_fee": "0.0000", "deallocation_fee": "0.0000",
+              "can_allocate": true, "can_deallocate": true });
+        let  flex  =  serde_json::json! (
+            { "id": "ESRFUO3-Q62XD-WIOIL7", "asset": "DOT",
+              "lock_type": { "type": "flex" },
+              "apr_estimate": { "low": "4.0000", "high": "15.0000" },
+              "can_allocate": true, "can_deallocate": true });
+        let  closed  =  serde_json::json! (
+            { "id": "ESMWVX6-JAPVY-23L3CV", "asset": "DOT",
+              "lock_type": { "type": "instant" },
+              "can_allocate": false, "can_deallocate": true });
+
+        let  mut  S  =  vec! [Earn_Strategy::from_json (&closed) ?,
+                              Earn_Strategy::from_json (&flex) ?,
+                              Earn_Strategy::from_json (&bonded) ?];
+
+        assert_eq! (S [2].lock_period, Duration::from_secs (2419200));
+        assert_eq! (S [2].min_allocation, Some (0.01));
+        assert_eq! ((S [1].lock_period, S [1].min_allocation),
+                    (Duration::ZERO, None));
+
+        sort_by_apr (&mut S);
+        assert_eq! (S.iter ().map (|S| S.lock_type.as_str ())
+                             .collect::<Vec<_>> (),
+                    ["bonded", "flex", "instant"]);
+        Ok (())
+    }
+}
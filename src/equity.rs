@@ -0,0 +1,208 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  The value of the account over time: the balances the ledger shows at each
+ *  moment, priced in one asset with the closing prices of OHLC candles. */
+
+
+
+use  crate::{API_Option, Candle, Kraken_API, Ledger_Entry, Pair_Info};
+use  crate::conversion::{rate, route};
+use  std::collections::{BTreeMap, BTreeSet};
+
+
+
+/** The value of the account at one moment, as given by
+    [Kraken_API::equity_curve]. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Equity_Point
+{
+    /** UNIX time. */
+    pub  time:      f64,
+
+    /** The value of the balances which could be priced, in the target
+        asset. */
+    pub  equity:    f64,
+
+    /** The assets with a balance which could not be priced at this time,
+        for want of a pair or of a price; they are left out of the
+        equity. */
+    pub  unpriced:  Vec<String>
+}
+
+
+
+/*  The close of the last candle to start at or before `time`. */
+
+fn  price_at  (candles:  &[Candle],  time:  f64)  ->  Option<f64>
+{
+    let  n  =  candles.partition_point (|C| C.time <= time);
+    if  n == 0   {   None   }   else   {   Some (candles [n - 1].close)   }
+}
+
+
+
+/*  The equity at each of the `times`, from the balances the `ledger` shows
+ *  (each entry carries the balance of its asset after it) and the candles
+ *  of each pair, oldest first, in `history`. */
+
+pub(crate)  fn  curve  (ledger:  &[Ledger_Entry],
+                        pairs:  &[Pair_Info],
+                        history:  &BTreeMap<String, Vec<Candle>>,
+                        target:  &str,
+                        times:  &[f64])
+          ->  Vec<Equity_Point>
+{
+    let  mut  ledger  =  ledger.iter ().collect::<Vec<_>> ();
+    ledger.sort_by (|A, B| A.time.total_cmp (&B.time));
+
+    let  mut  balances  =  BTreeMap::<&str, f64>::new ();
+    let  mut  next  =  0;
+
+    times.iter ().map (|&time|  {
+        while  next < ledger.len ()  &&  ledger [next].time <= time
+        {
+            balances.insert (&ledger [next].asset, ledger [next].balance);
+            next  +=  1;
+        }
+
+        let  mids  =  history.iter ()
+                             .filter_map (|(P, C)|  Some ((P.clone (),
+                                                           price_at (C, time)
+                                                              ?)))
+                             .collect ();
+
+        let  mut  point  =  Equity_Point {  time,  equity:  0.0,
+                                            unpriced:  Vec::new ()  };
+        for  (asset, balance)  in  &balances
+        {
+            if  *balance == 0.0   {   continue;   }
+            match  route (pairs, asset, target)
+                       .and_then (|R|  rate (&R, pairs, &mids).ok ())
+               {   Some (R)  =>  point.equity  +=  balance * R,
+                   None  =>  point.unpriced.push (asset.to_string ())   }
+        }
+        point
+    }).collect ()
+}
+
+
+
+impl  Kraken_API
+{
+/** Reconstruct the value of the account, in the `target` asset (e.g.
+    "ZUSD"), at the start of every `interval` minutes (one of the intervals
+    OHLC data come in) from the first ledger entry until now, for plotting
+    or for working out returns.  The balances are those the ledger shows at
+    each moment, and they are priced at the close of the OHLC candle then
+    current, converting through USD, XBT or EUR where no pair joins an
+    asset to the target directly (as [Kraken_API::conversion_rates] does).
+
+    The exchange only gives the last 720 candles of any pair, so the
+    interval must be long enough to reach back to the start of the ledger,
+    or the earlier points will leave out the assets which cannot be priced
+    (see [Equity_Point::unpriced]); 1440, daily, reaches back almost two
+    years.  Staked and other variant assets are not priced.
+
+    This pages through the whole ledger 50 entries at a time, respecting
+    the [API_Option::ASSET], [API_Option::START] and [API_Option::END]
+    settings, and calls [Kraken_API::all_pairs] and [Kraken_API::candles]
+    for each pair needed.  It replaces any [API_Option::TYPE],
+    [API_Option::OFS], [API_Option::INTERVAL] and [API_Option::SINCE]
+    settings.  */
+
+  pub  fn  equity_curve  (&mut self,  target:  &str,  interval:  u32)
+               ->  Result<Vec<Equity_Point>, String>
+    {
+      self.clear_opt (API_Option::TYPE);
+      let  ledger  =  self.all_ledger_entries ();
+      self.clear_opt (API_Option::OFS);
+      let  ledger  =  ledger ?;
+
+      let  pairs  =  self.all_pairs () ?;
+      let  needed  =  ledger.iter ()
+                            .filter_map (|L| route (&pairs, &L.asset, target))
+                            .flatten ()
+                            .map (|L| L.pair)
+                            .collect::<BTreeSet<_>> ();
+
+      self.clear_opt (API_Option::SINCE);
+      let  history  =  needed.into_iter ()
+                             .map (|P|  {  let  C  =  self.candles (&P,
+                                                                    interval) ?;
+                                           Ok ((P, C))  })
+                             .collect::<Result<BTreeMap<_, _>, String>> () ?;
+      self.clear_opt (API_Option::INTERVAL);
+
+      let  step  =  interval as f64 * 60.0;
+      let  Some (first)  =  ledger.iter ().map (|L| L.time)
+                                  .reduce (f64::min)
+          else   {   return  Ok (Vec::new ())   };
+      let  now  =  std::time::SystemTime::now ()
+                       .duration_since (std::time::UNIX_EPOCH)
+                       .map_err (|E| E.to_string ()) ?.as_secs_f64 ();
+      let  times  =  (0 ..).map (|n|  ((first / step).ceil () + n as f64)
+                                         * step)
+                           .take_while (|T| *T <= now)
+                           .collect::<Vec<_>> ();
+
+      Ok (curve (&ledger, &pairs, &history, target, &times))
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  curve  ()  ->  Result<(), String>
+    {
+        use  super::curve;
+        use  crate::{Ledger_Type::TRADE, Pair_Info};
+        use  crate::test_fixtures::{candle, ledger_entry};
+        use  serde_json::json;
+        use  std::collections::BTreeMap;
+
+        let  P  =  Pair_Info::from_json ("XXBTZUSD", &json! ({
+                       "altname": "XBTUSD", "wsname": "XBT/USD",
+                       "base": "XXBT", "quote": "ZUSD",
+                       "pair_decimals": 1, "lot_decimals": 8,
+                       "ordermin": "0.0001", "fees": [[0, 0.26]],
+                       "fees_maker": [[0, 0.16]]})) ?;
+        let  ledger  =  [ledger_entry ("L1", 100.0, TRADE, "ZUSD", 0.0, 1000.0),
+                         ledger_entry ("L2", 250.0, TRADE, "XXBT", 0.0, 0.5),
+                         ledger_entry ("L3", 250.0, TRADE, "ZUSD", 0.0, 500.0),
+                         ledger_entry ("L4", 260.0, TRADE, "XETH", 0.0, 2.0)];
+        let  history  =  BTreeMap::from ([("XXBTZUSD".to_string (),
+                                           vec! [candle (200.0, 1000.0),
+                                                 candle (300.0, 1200.0)])]);
+
+        let  C  =  curve (&ledger, &[P], &history, "ZUSD",
+                          &[50.0, 200.0, 300.0]);
+        assert_eq! (C.iter ().map (|P| P.equity).collect::<Vec<_>> (),
+                    [0.0, 1000.0, 1100.0]);
+        assert_eq! (C [2].unpriced,  ["XETH"]);
+        assert! (C [1].unpriced.is_empty ());
+        Ok (())
+    }
+}
@@ -0,0 +1,273 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option, Account, Account_Event, Cancel_Token, Kraken_API,
+             Margin_Alert, Margin_Monitor, Order, Transfer};
+use  crate::reply;
+use  std::collections::{BTreeMap, BTreeSet};
+use  std::sync::{Arc, Mutex};
+use  std::sync::mpsc::{Receiver, Sender, channel};
+use  std::time::Duration;
+
+
+
+/** Something which has happened on the account, as published by an
+    [Event_Bus]. */
+
+#[derive(Clone, Debug)]
+pub  enum  Event
+{
+    /** An order has appeared in the book. */
+    ORDER_PLACED  (Order),
+
+    /** An order has been completely filled; this is the order as last
+        seen. */
+    ORDER_FILLED  (Order),
+
+    /** An order has left the book without having been seen completely
+        filled: it was cancelled or expired.  When the account is only
+        polled, an order which fills completely between two polls looks the
+        same, so code which must know should follow the WebSockets feed with
+        [Account::apply], or look the order up with
+        [Kraken_API::query_orders].  This is the order as last seen. */
+    ORDER_CANCELLED  (Order),
+
+    /** The balance of an asset has changed from the first figure to the
+        second; `None` means there was or is no balance at all. */
+    BALANCE_CHANGED  (String, Option<f64>, Option<f64>),
+
+    /** A deposit has been seen for the first time. */
+    DEPOSIT_DETECTED  (Transfer),
+
+    /** The margin level has crossed one of a [Margin_Monitor]'s thresholds,
+        going down or (see [Margin_Alert::falling]) back up. */
+    MARGIN_WARNING  (Margin_Alert)
+}
+
+
+
+/*  What the bus knows: the subscribers, and the open orders as last seen,
+ *  with whether each has been reported filled. */
+
+#[derive(Default)]
+struct  Bus_State  {  subscribers:  Vec<Sender<Event>>,
+                      orders:       BTreeMap<String, (Order, bool)>  }
+
+
+
+/** Carries [Event]s from the parts of the crate which watch the account (an
+    [Account], a [Margin_Monitor], a deposit watcher) to any number of
+    subscribers, each of which gets every event on a channel of its own; so
+    the parts of an application can react to what happens on the exchange
+    without polling it, or each other.  Clones of a bus are the same bus.
+
+    ```ignore
+    let  bus  =  KKN::Event_Bus::new ();
+    let  fills  =  bus.subscribe ();
+
+    let  mut  A  =  KKN::Account::new ();
+    bus.attach_account (&mut A);
+    let  A  =  Arc::new (Mutex::new (A));
+    let  (_, stop)  =  KKN::Account::spawn (A.clone (), K.share (),
+                                            Duration::from_secs (10));
+
+    for  E  in  fills
+        {   if let KKN::Event::ORDER_FILLED (O) = E
+                {   println! ("filled: {}", O.txid);   }   }
+    ```  */
+
+#[derive(Clone, Default)]
+pub  struct  Event_Bus  (Arc<Mutex<Bus_State>>);
+
+
+
+impl  Event_Bus
+{
+    /** A bus with no subscribers. */
+    pub  fn  new  ()  ->  Self   {   Self::default ()   }
+
+
+    /** Get a channel on which every event published from now on will
+        arrive.  Dropping the receiver is all it takes to unsubscribe. */
+    pub  fn  subscribe  (&self)  ->  Receiver<Event>
+    {
+        let  (S, R)  =  channel ();
+        self.0.lock ().unwrap ().subscribers.push (S);
+        R
+    }
+
+
+    /** Send the event to every subscriber. */
+    pub  fn  publish  (&self,  event:  Event)
+    {
+        self.0.lock ().unwrap ()
+              .subscribers.retain (|S|  S.send (event.clone ()).is_ok ());
+    }
+
+
+    /** Publish the events which an [Account] notices: orders placed,
+        filled and cancelled, and balance changes. */
+    pub  fn  attach_account  (&self,  account:  &mut Account)
+    {
+        let  bus  =  self.clone ();
+        account.on_event (move |E|  for  event  in  bus.translate (E)
+                                        {   bus.publish (event);   });
+    }
+
+
+    /** Publish the alerts a [Margin_Monitor] raises. */
+    pub  fn  attach_margin_monitor  (&self,  monitor:  &mut Margin_Monitor)
+    {
+        let  bus  =  self.clone ();
+        monitor.on_alert (move |A|  bus.publish (Event::MARGIN_WARNING
+                                                        (A.clone ())));
+    }
+
+
+    /*  The events which follow from a change noticed by an Account. */
+    fn  translate  (&self,  E:  &Account_Event)  ->  Vec<Event>
+    {
+        let  mut  S  =  self.0.lock ().unwrap ();
+        let  filled  =  |O: &Order|  O.volume > 0.0  &&  O.vol_exec >= O.volume;
+
+        match  E
+        {
+            Account_Event::BALANCE (asset, old, new)
+                =>  vec! [Event::BALANCE_CHANGED (asset.clone (), *old, *new)],
+
+            Account_Event::ORDER_OPENED (O)
+                =>  {  S.orders.insert (O.txid.clone (), (O.clone (), false));
+                       vec! [Event::ORDER_PLACED (O.clone ())]  },
+
+            Account_Event::ORDER_CHANGED (O)
+                =>  {  let  told  =  S.orders.get (&O.txid)
+                                              .is_some_and (|(_, told)| *told);
+                       S.orders.insert (O.txid.clone (),
+                                        (O.clone (), told || filled (O)));
+                       if  ! told  &&  filled (O)
+                           {   vec! [Event::ORDER_FILLED (O.clone ())]   }
+                       else   {   vec! []   }  },
+
+            Account_Event::ORDER_GONE (txid)
+                =>  match  S.orders.remove (txid)
+                       {   Some ((_, true))  =>  vec! [],
+                           Some ((O, false))  if  filled (&O)
+                               =>  vec! [Event::ORDER_FILLED (O)],
+                           Some ((O, false))
+                               =>  vec! [Event::ORDER_CANCELLED (O)],
+                           None  =>  vec! []   },
+
+            _  =>  vec! []
+        }
+    }
+
+
+    /** Ask the exchange for the deposits of `asset` every `interval`, in a
+        thread of its own using the given handle, and publish each one the
+        first time it is seen; those already there at the first look are
+        taken as known.  A failed look is simply tried again next time.
+        Cancel the returned token to bring the thread to an end. */
+    pub  fn  watch_deposits  (&self,  mut K:  Kraken_API,  asset:  &str,
+                              interval:  Duration)
+               ->  (std::thread::JoinHandle<()>, Cancel_Token)
+    {
+        let  stop   =  Cancel_Token::new ();
+        let  S      =  stop.clone ();
+        let  bus    =  self.clone ();
+        let  asset  =  asset.to_string ();
+
+        (std::thread::spawn (move ||  {
+             let  mut  seen:  Option<BTreeSet<String>>  =  None;
+             while  ! S.is_cancelled ()
+             {
+                 K.set_opt (API_Option::ASSET, &asset);
+                 let  deposits  =  K.deposit_status ()
+                                    .and_then (|R| reply::result_of (&R))
+                                    .and_then (|V|  V.as_array ()
+                                                     .into_iter ().flatten ()
+                                                     .map (Transfer::from_json)
+                                                     .collect::<Result<Vec<_>,
+                                                                       _>> ());
+                 if let Ok (deposits) = deposits
+                 {
+                     let  first  =  seen.is_none ();
+                     let  known  =  seen.get_or_insert_with (BTreeSet::new);
+                     for  T  in  deposits
+                     {
+                         if  known.insert (T.refid.clone ())  &&  ! first
+                             {   bus.publish (Event::DEPOSIT_DETECTED (T));   }
+                     }
+                 }
+                 S.pause (interval);
+             }  }),
+         stop)
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  orders  ()
+    {
+        use  super::{Event, Event_Bus};
+        use  crate::{Account_Event, Instruction, Order, Order_Status};
+
+        const  TXID:  &str  =  "OQCLML-BW3P3-BUCMWZ";
+        let  order  =  |vol_exec|  Order {  txid: TXID.into (),
+                                            pair: "XBTUSD".into (),
+                                            side: Instruction::BUY,
+                                            order_type: "limit".into (),
+                                            price: 30000.0,  volume: 1.0,
+                                            vol_exec,  cost: 0.0,  fee: 0.0,
+                                            status: Order_Status::OPEN,
+                                            userref: None,  open_time: 0.0,
+                                            description: None  };
+
+        let  bus  =  Event_Bus::new ();
+        let  R  =  bus.subscribe ();
+        let  kinds  =  |events: Vec<Account_Event>|  {
+            for  E  in  events
+                {   for  e  in  bus.translate (&E)   {   bus.publish (e);   }  }
+            R.try_iter ().map (|E|  match  E
+                                  {   Event::ORDER_PLACED (_)  =>  "placed",
+                                      Event::ORDER_FILLED (_)  =>  "filled",
+                                      Event::ORDER_CANCELLED (_)  =>  "gone",
+                                      _  =>  "other"   })
+                         .collect::<Vec<_>> ()
+        };
+
+        assert_eq! (kinds (vec! [Account_Event::ORDER_OPENED (order (0.0)),
+                                 Account_Event::ORDER_CHANGED (order (0.4)),
+                                 Account_Event::ORDER_CHANGED (order (1.0)),
+                                 Account_Event::ORDER_GONE (TXID.into ())]),
+                    ["placed", "filled"]);
+
+        assert_eq! (kinds (vec! [Account_Event::ORDER_OPENED (order (0.0)),
+                                 Account_Event::ORDER_CHANGED (order (0.4)),
+                                 Account_Event::ORDER_GONE (TXID.into ()),
+                                 Account_Event::BALANCE ("ZUSD".into (),
+                                                         None, Some (5.0))]),
+                    ["placed", "gone", "other"]);
+    }
+}
@@ -0,0 +1,292 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option as Opt, Cancel_Token, Kraken_API, Report_Type,
+             Schedule, civil_date};
+use  crate::reply;
+use  serde_json  as  JSN;
+use  std::path::PathBuf;
+use  std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+
+
+/** Archives the account's trades and ledgers on a crontab-like [Schedule],
+    for hands-off bookkeeping.
+
+    Each time the schedule fires, an export of every selected report is
+    requested from the exchange ([Kraken_API::request_export_report]), its
+    status is polled until it has been processed, and the ZIP archive is then
+    written into the scheduler's directory
+    ([Kraken_API::retrieve_data_export_to_file]) under a name like
+    `ledgers_20240101-0000_20240201-0000.zip`, after which the export is
+    deleted from the exchange.
+
+    Each archive covers the time from the end of the one before it up to the
+    time the schedule fired, so that successive archives neither overlap nor
+    leave gaps; the first covers the period before it set with
+    [Export_Scheduler::set_period], 31 days unless changed.  As with the
+    [DCA_Scheduler](crate::DCA_Scheduler), a run which was missed altogether
+    is not made up later, but the next archive will stretch back to cover it.
+
+    ```ignore
+    //  Trades and ledgers for the past month, early on the first of every
+    //  month.
+    let  mut  E  =  KKN::Export_Scheduler::new
+                          (KKN::Schedule::parse ("30 2 1 * *") ?,
+                           "/home/me/accounts/kraken");
+    E.on_log (|m| eprintln! ("{}", m));
+    E.run (&mut K, &KKN::Cancel_Token::new ());
+    ```  */
+
+pub  struct  Export_Scheduler  {  schedule:       Schedule,
+                                  directory:      PathBuf,
+                                  reports:        Vec<Report_Type>,
+                                  period:         Duration,
+                                  poll_interval:  Duration,
+                                  patience:       Duration,
+                                  covered_to:     Option<SystemTime>,
+                                  next:           Option<SystemTime>,
+                                  log:            Option<Log_Callback>  }
+
+type  Log_Callback  =  Box<dyn FnMut (&str) + Send>;
+
+
+
+impl  Export_Scheduler
+{
+    /** A scheduler which archives both trades and ledgers into `directory`
+        at every time `schedule` fires. */
+    pub  fn  new  (schedule:  Schedule,  directory:  impl Into<PathBuf>)
+              ->  Self
+    {
+        let  next  =  schedule.next_after (SystemTime::now ());
+        Export_Scheduler {
+            schedule,
+            directory:      directory.into (),
+            reports:        vec! [Report_Type::TRADES, Report_Type::LEDGERS],
+            period:         Duration::from_secs (31 * 86400),
+            poll_interval:  Duration::from_secs (30),
+            patience:       Duration::from_secs (3600),
+            covered_to:     None,
+            next,
+            log:            None  }
+    }
+
+    /** Archive only the given reports. */
+    pub  fn  set_reports  (&mut self,  reports:  &[Report_Type])  ->  &mut Self
+          {   self.reports  =  reports.to_vec ();   self   }
+
+    /** The span of time the first archive reaches back over. */
+    pub  fn  set_period  (&mut self,  period:  Duration)  ->  &mut Self
+          {   self.period  =  period;   self   }
+
+    /** How often to ask the exchange whether an export is ready (every 30
+        seconds unless changed), and how long to wait in all before giving up
+        on it (an hour unless changed). */
+    pub  fn  set_polling  (&mut self,  interval:  Duration,
+                           patience:  Duration)
+              ->  &mut Self
+    {
+        self.poll_interval  =  interval;
+        self.patience       =  patience;
+        self
+    }
+
+    /** Have a line of text passed to `f` describing everything the scheduler
+        does, or fails to do. */
+    pub  fn  on_log<F>  (&mut self,  f:  F)  ->  &mut Self
+                where  F:  FnMut (&str) + Send + 'static
+          {   self.log  =  Some (Box::new (f));   self   }
+
+    /** The next time at which archives are due. */
+    pub  fn  next_due  (&self)  ->  Option<SystemTime>   {   self.next   }
+
+
+    /** If the schedule has fallen due by `now`, make the archives, returning
+        the paths of the files written (or the reasons for not writing them).
+        Polling for the exports to be ready stops early if `stop` is
+        cancelled.  */
+    pub  fn  run_due  (&mut self,  K:  &mut Kraken_API,  now:  SystemTime,
+                       stop:  &Cancel_Token)
+                ->  Vec<Result<PathBuf, String>>
+    {
+        let  to  =  match  self.next   {   Some (T)  if  T <= now  =>  T,
+                                           _  =>  return  Vec::new ()   };
+        self.next  =  self.schedule.next_after (now);
+
+        let  from  =  self.covered_to.unwrap_or (to - self.period);
+        let  mut  all_done  =  true;
+        let  mut  results  =  Vec::new ();
+
+        for  report  in  self.reports.clone ()
+        {
+            let  outcome  =  self.export (K, report, from, to, stop);
+
+            let  line  =  match  &outcome
+                {   Ok (P)   =>  format! ("export written to {}",
+                                          P.display ()),
+                    Err (E)  =>  format! ("export of {}: {}",
+                                          report.as_kraken_string (), E)   };
+            if let Some (f) = &mut self.log   {   f (&line);   }
+
+            all_done  &=  outcome.is_ok ();
+            results.push (outcome);
+        }
+
+        if  all_done   {   self.covered_to  =  Some (to);   }
+
+        results
+    }
+
+
+    /** Keep making archives as they fall due, until `stop` is cancelled. */
+    pub  fn  run  (&mut self,  K:  &mut Kraken_API,  stop:  &Cancel_Token)
+    {
+        while  ! stop.is_cancelled ()
+        {
+            self.run_due (K, SystemTime::now (), stop);
+
+            stop.pause (self.next_due ()
+                            .and_then (|T| T.duration_since (SystemTime::now ())
+                                            .ok ())
+                            .unwrap_or (Duration::from_secs (60)));
+        }
+    }
+
+
+    /** Straight away, run the whole export workflow for one `report` covering
+        the times `from` to `to`, and give the path of the archive written.
+        This is what [Export_Scheduler::run_due] does for each report when
+        the schedule fires.
+
+        Every call to the exchange is made from a clean slate:
+        [Kraken_API::clear_all_options] is called before each one.  */
+    pub  fn  export  (&mut self,  K:  &mut Kraken_API,  report:  Report_Type,
+                      from:  SystemTime,  to:  SystemTime,
+                      stop:  &Cancel_Token)
+               ->  Result<PathBuf, String>
+    {
+        let  name  =  file_name (report, from, to);
+
+        K.clear_all_options ();
+        K.set_opt (Opt::FORMAT, "CSV");
+        K.set_opt (Opt::START_TIME, unix_seconds (from));
+        K.set_opt (Opt::END_TIME, unix_seconds (to));
+        let  R  =  reply::result_of (&K.request_export_report (report,
+                                                               &name) ?) ?;
+        let  id  =  reply::string (&R, "id") ?;
+
+        let  give_up  =  Instant::now () + self.patience;
+        loop
+        {
+            K.clear_all_options ();
+            let  S  =  reply::result_of
+                               (&K.get_export_report_status (report) ?) ?;
+            match  status_of (&S, &id) ?
+            {
+                "Processed"  =>  break,
+                "Queued" | "Processing"  =>  (),
+                S  =>  Err (format! ("export {} has status '{}'", id, S)) ?
+            }
+
+            if  stop.is_cancelled ()
+                {   Err (format! ("gave up waiting for export {}: cancelled",
+                                  id)) ?   }
+            if  Instant::now () >= give_up
+                {   Err (format! ("export {} was not ready in time", id)) ?   }
+            stop.pause (self.poll_interval);
+        }
+
+        let  path  =  self.directory.join (name + ".zip");
+        K.clear_all_options ();
+        K.retrieve_data_export_to_file (&id, &path) ?;
+
+        K.clear_all_options ();
+        if let Err (E) = K.delete_export_report (&id, "delete")
+                          .and_then (|R| reply::result_of (&R))
+        {
+            if let Some (f) = &mut self.log
+                {   f (&format! ("could not delete export {}: {}", id, E));   }
+        }
+
+        Ok (path)
+    }
+}
+
+
+
+/*  The status of the export with the given `id`, in the 'result' section of
+ *  an ExportStatus reply. */
+
+fn  status_of<'a>  (V:  &'a JSN::Value,  id:  &str)  ->  Result<&'a str, String>
+{
+    V.as_array ().into_iter ().flatten ()
+     .find (|E| E ["id"] == id)
+     .and_then (|E| E ["status"].as_str ())
+     .ok_or (format! ("the exchange has no record of export {}", id))
+}
+
+
+
+fn  unix_seconds  (t:  SystemTime)  ->  u64
+      {   t.duration_since (UNIX_EPOCH).unwrap_or_default ().as_secs ()   }
+
+
+
+/*  E.g. "trades_20240101-0000_20240201-0000"; this is also the description
+ *  the export is given on the exchange. */
+
+fn  file_name  (report:  Report_Type,  from:  SystemTime,  to:  SystemTime)
+          ->  String
+{
+    let  stamp  =  |t: SystemTime|  {
+        let  secs  =  unix_seconds (t);
+        let  (year, month, day)  =  civil_date ((secs / 86400) as i64);
+        format! ("{:04}{:02}{:02}-{:02}{:02}",
+                 year, month, day, secs / 3600 % 24, secs / 60 % 60)
+    };
+
+    format! ("{}_{}_{}", report.as_kraken_string (), stamp (from), stamp (to))
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  names_and_status  ()
+    {
+        use  super::{file_name, status_of};
+        use  crate::Report_Type;
+        use  std::time::{Duration, UNIX_EPOCH};
+
+        let  from  =  UNIX_EPOCH + Duration::from_secs (1704067200);
+        let  to    =  UNIX_EPOCH + Duration::from_secs (1706751000);
+        assert_eq! (file_name (Report_Type::LEDGERS, from, to),
+                    "ledgers_20240101-0000_20240201-0130");
+
+        let  V  =  serde_json::json! ([{"id": "TCJA", "status": "Processed"},
+                                       {"id": "VBXN", "status": "Queued"}]);
+        assert_eq! (status_of (&V, "VBXN"), Ok ("Queued"));
+        assert! (status_of (&V, "QQQQ").is_err ());
+    }
+}
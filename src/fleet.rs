@@ -0,0 +1,150 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::Kraken_API;
+use  std::collections::BTreeMap;
+use  std::sync::Mutex;
+
+
+
+/** A set of handles on different accounts, each known by a name, on which
+    the same piece of work can be done all at once.
+
+    [Fleet::run] gives each account to a worker thread of its own (up to the
+    limit set with [Fleet::set_workers]), so that something like cancelling
+    every order on every account takes about as long as doing it on one.
+    Each handle goes on keeping to its own account's rate limit, retries,
+    time limit and so on, as set on it before it was added.
+
+    ```ignore
+    let  mut  F  =  KKN::Fleet::new ();
+    F.add ("main",   KKN::connect (main_key, main_secret));
+    F.add ("hedge",  KKN::connect (hedge_key, hedge_secret));
+
+    for  (name, outcome)  in  F.cancel_all_orders ()
+        {   println! ("{}: {:?}", name, outcome);   }
+    ```  */
+
+#[derive(Default)]
+pub  struct  Fleet
+{
+    accounts:  BTreeMap<String, Kraken_API>,
+    workers:   Option<usize>
+}
+
+
+
+impl  Fleet
+{
+    /** Make an empty fleet. */
+    pub  fn  new  ()  ->  Self   {   Self::default ()   }
+
+
+    /** Add the handle on an account under the given name, replacing any
+        handle already known by that name. */
+    pub  fn  add  (&mut self,  name:  &str,  account:  Kraken_API)
+          {   self.accounts.insert (name.to_string (), account);   }
+
+
+    /** Take the named account out of the fleet, giving back its handle. */
+    pub  fn  remove  (&mut self,  name:  &str)  ->  Option<Kraken_API>
+          {   self.accounts.remove (name)   }
+
+
+    /** The handle on the named account, to set options on, for example. */
+    pub  fn  account  (&mut self,  name:  &str)  ->  Option<&mut Kraken_API>
+          {   self.accounts.get_mut (name)   }
+
+
+    /** The names of the accounts, in order. */
+    pub  fn  names  (&self)  ->  Vec<String>
+          {   self.accounts.keys ().cloned ().collect ()   }
+
+
+    /** Use at most `workers` threads at once in [Fleet::run]; `None` (the
+        default) gives every account a thread. */
+    pub  fn  set_workers  (&mut self,  workers:  Option<usize>)
+          {   self.workers  =  workers;   }
+
+
+    /** Call `work` with the name and handle of every account, on worker
+        threads, and gather up what each call gives back under the account's
+        name.  One account failing does not stop the work on the others.
+        This returns when every call has, so any time limit should be set on
+        the handles themselves. */
+    pub  fn  run<T, F>  (&mut self,  work:  F)
+                  ->  BTreeMap<String, Result<T, String>>
+            where  T:  Send,
+                   F:  Fn (&str, &mut Kraken_API) -> Result<T, String> + Sync
+    {
+        let  workers  =  self.workers.unwrap_or (self.accounts.len ())
+                                     .clamp (1, self.accounts.len ().max (1));
+        let  queue    =  Mutex::new (self.accounts.iter_mut ());
+        let  results  =  Mutex::new (BTreeMap::new ());
+
+        std::thread::scope (|S|  for  _  in  0 .. workers
+           {   S.spawn (||  {
+                   /*  The queue's lock is let go before the work is done. */
+                   let  next  =  || queue.lock ().unwrap ().next ();
+                   while let Some ((name, K)) = next ()
+                   {
+                       let  R  =  work (name, K);
+                       results.lock ().unwrap ().insert (name.clone (), R);
+                   }
+               });   });
+
+        results.into_inner ().unwrap ()
+    }
+
+
+    /** Cancel every open order on every account, with
+        [Kraken_API::cancel_all_orders], giving the exchange's reply for each
+        account. */
+    pub  fn  cancel_all_orders  (&mut self)
+               ->  BTreeMap<String, Result<String, String>>
+          {   self.run (|_, K|  K.cancel_all_orders ())   }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  run  ()
+    {
+        use  super::Fleet;
+
+        let  mut  F  =  Fleet::new ();
+        for  name  in  ["a", "b", "c"]
+            {   F.add (name, Default::default ());   }
+        F.set_workers (Some (2));
+
+        let  R  =  F.run (|name, _|
+                              if  name == "c"  {  Err ("no".to_string ())  }
+                              else  {  Ok (name.to_uppercase ())  });
+
+        assert_eq! (R.keys ().collect::<Vec<_>> (),  ["a", "b", "c"]);
+        assert_eq! (R ["b"],  Ok ("B".to_string ()));
+        assert_eq! (R ["c"],  Err ("no".to_string ()));
+        assert! (Fleet::new ().run (|_, _| Ok (())).is_empty ());
+    }
+}
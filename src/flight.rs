@@ -0,0 +1,222 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::Cancel_Token;
+use  std::collections::HashMap;
+use  std::sync::{Arc, Condvar, Mutex};
+use  std::time::Duration;
+
+
+
+/*  The public requests currently in progress on a set of shared handles,
+ *  keyed on the full query (end-point and arguments), so that a second
+ *  thread asking for the same thing waits for the first thread's answer
+ *  instead of making its own call. */
+
+#[derive(Default)]
+pub(crate)  struct  Flights  (Mutex<HashMap<String, Arc<Flight>>>);
+
+#[derive(Default)]
+struct  Flight  {  state:  Mutex<State>,
+                   done:   Condvar  }
+
+#[derive(Default)]
+enum  State
+{
+    #[default]
+    FLYING,
+
+    /*  The thread making the call has the reply. */
+    LANDED (Result<String, String>),
+
+    /*  The thread making the call was cancelled, and so the others must
+     *  make the call again for themselves. */
+    ABANDONED
+}
+
+
+
+/*  Held by the thread making the call: when it goes, even if the thread
+ *  panics, the flight is taken off the list and its outcome given to the
+ *  threads waiting on it. */
+
+struct  Landing<'a>  {  flights:  &'a Flights,
+                        query:    &'a str,
+                        flight:   Arc<Flight>,
+                        outcome:  Option<State>  }
+
+impl  Drop  for  Landing<'_>
+{
+    fn  drop  (&mut self)
+    {
+        self.flights.0.lock ().unwrap_or_else (|E| E.into_inner ())
+            .remove (self.query);
+        *self.flight.state.lock ().unwrap_or_else (|E| E.into_inner ())
+            =  self.outcome.take ()
+                   .unwrap_or (State::LANDED (Err ("request failed: the \
+                                                    thread making it \
+                                                    panicked".to_string ())));
+        self.flight.done.notify_all ();
+    }
+}
+
+
+
+impl  Flights
+{
+    /*  Get the reply to the query, either by calling `fetch` or, if another
+     *  thread is already doing so, by waiting for its reply.  A thread which
+     *  is waiting gives up if its own token is cancelled; if instead the
+     *  thread making the call is cancelled, the first waiting thread makes
+     *  the call again. */
+    pub(crate)  fn  run  (&self,  query:  &str,
+                          token:  Option<&Cancel_Token>,
+                          fetch:  impl FnOnce () -> Result<String, String>)
+                 ->  Result<String, String>
+    {
+        let  cancelled  =  || token.is_some_and (Cancel_Token::is_cancelled);
+        let  mut  fetch  =  Some (fetch);
+
+        loop
+        {
+            let  (flight, leader)  =  {
+                      let  mut  M  =  self.0.lock ().unwrap ();
+                      match  M.get (query)
+                      {
+                          Some (F)  =>  (F.clone (), false),
+                          None      =>  {
+                              let  F  =  Arc::new (Flight::default ());
+                              M.insert (query.to_string (), F.clone ());
+                              (F, true)  }
+                      }
+                  };
+
+            if  leader
+            {
+                let  mut  L  =  Landing {  flights:  self,  query,
+                                           flight,  outcome:  None  };
+                let  reply  =  (fetch.take ().unwrap ()) ();
+                L.outcome  =  Some (if  reply.is_err ()  &&  cancelled ()
+                                        {  State::ABANDONED  }
+                                    else  {  State::LANDED (reply.clone ())  });
+                return  reply;
+            }
+
+            let  mut  S  =  flight.state.lock ().unwrap ();
+            loop
+            {
+                match  &*S
+                   {   State::LANDED (reply)  =>  return  reply.clone (),
+                       State::ABANDONED  =>  break,
+                       State::FLYING  =>  ()   }
+
+                if  cancelled ()
+                    {   Err ("request cancelled".to_string ()) ?   }
+
+                S  =  flight.done.wait_timeout (S, Duration::from_millis (50))
+                                 .unwrap ().0;
+            }
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  coalesce  ()
+    {
+        use  super::Flights;
+        use  std::sync::Arc;
+        use  std::sync::atomic::{AtomicUsize, Ordering};
+        use  std::time::Duration;
+
+        let  F  =  Arc::new (Flights::default ());
+        let  calls  =  Arc::new (AtomicUsize::new (0));
+
+        let  ask  =  |query: &'static str|  {
+                        let  (F, calls)  =  (F.clone (), calls.clone ());
+                        std::thread::spawn (move ||  F.run (query, None, ||  {
+                            calls.fetch_add (1, Ordering::SeqCst);
+                            std::thread::sleep (Duration::from_millis (200));
+                            Ok (format! ("reply {}", query))  }))  };
+
+        let  A  =  ask ("Ticker?pair=XBTUSD");
+        std::thread::sleep (Duration::from_millis (50));
+        let  B  =  ask ("Ticker?pair=XBTUSD");
+        let  C  =  ask ("Ticker?pair=ETHUSD");
+
+        let  reply  =  |T: std::thread::JoinHandle<Result<String, String>>|
+                            T.join ().unwrap ().unwrap ();
+        assert_eq! (reply (A), "reply Ticker?pair=XBTUSD");
+        assert_eq! (reply (B), "reply Ticker?pair=XBTUSD");
+        assert_eq! (reply (C), "reply Ticker?pair=ETHUSD");
+        assert_eq! (calls.load (Ordering::SeqCst), 2);
+
+        //  Once the reply is in, a new request goes to the exchange again.
+        assert_eq! (F.run ("Ticker?pair=XBTUSD", None,
+                           || Ok ("fresh".to_string ())),
+                    Ok ("fresh".to_string ()));
+    }
+
+
+    #[test]
+    fn  leader_gone  ()
+    {
+        use  super::Flights;
+        use  crate::Cancel_Token;
+        use  std::sync::Arc;
+        use  std::time::Duration;
+
+        let  F  =  Arc::new (Flights::default ());
+        let  pause  =  || std::thread::sleep (Duration::from_millis (100));
+
+        /*  The first caller is cancelled part-way; the second, which was
+         *  waiting on it, makes the call itself. */
+        let  stop  =  Cancel_Token::new ();
+        let  A  =  {  let  (F, stop)  =  (F.clone (), stop.clone ());
+                      std::thread::spawn (move ||
+                          F.run ("Time", Some (&stop),
+                                 ||  {  pause ();  stop.cancel ();
+                                        Err ("request cancelled".into ())  }))
+                   };
+        std::thread::sleep (Duration::from_millis (20));
+        assert_eq! (F.run ("Time", None, || Ok ("B".to_string ())),
+                    Ok ("B".to_string ()));
+        assert! (A.join ().unwrap ().is_err ());
+
+        /*  The first caller panics; the second gets an error rather than
+         *  waiting for ever, and the next starts afresh. */
+        let  A  =  {  let  F  =  F.clone ();
+                      std::thread::spawn (move ||
+                          F.run ("Time", None,
+                                 ||  {  pause ();  panic! ("in fetch")  }))
+                   };
+        std::thread::sleep (Duration::from_millis (20));
+        assert! (F.run ("Time", None, || Ok ("B".to_string ()))
+                  .unwrap_err ().contains ("panicked"));
+        assert! (A.join ().is_err ());
+        assert_eq! (F.run ("Time", None, || Ok ("C".to_string ())),
+                    Ok ("C".to_string ()));
+    }
+}
@@ -0,0 +1,880 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option, Kraken_API, Ledger_Entry, Ledger_Type};
+use  crate::reply;
+use  crate::strictness;
+use  serde_json  as  JSN;
+use  std::collections::BTreeMap;
+use  std::time::{Duration, Instant};
+
+
+
+/** A withdrawal key set up for an asset, as given by the
+    [Kraken_API::withdraw_addresses] end-point, together with the minimum
+    amount its method will take, from [Kraken_API::withdraw_methods].  */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Withdrawal_Key
+{
+    /** The name the key was given on the Kraken web site. */
+    pub  key:      String,
+
+    /** The asset the key is for, as the exchange names it, e.g. "XBT". */
+    pub  asset:    String,
+
+    /** The withdrawal method, e.g. "Bitcoin". */
+    pub  method:   String,
+
+    /** The network the method sends over, e.g. "Ethereum" for an ERC-20
+        token, if the exchange said. */
+    pub  network:  Option<String>,
+
+    /** The address funds are sent to. */
+    pub  address:  String,
+
+    /** The memo, tag or destination ID which goes with the address on
+        networks which need one, e.g. XRP and Stellar. */
+    pub  tag:      Option<String>,
+
+    /** Whether the address has been verified; the exchange will not send
+        funds to one which has not. */
+    pub  verified:  bool,
+
+    /** The smallest amount which may be withdrawn by the key's method, or
+        `None` if the method is not currently offered for the asset. */
+    pub  minimum:   Option<f64>
+}
+
+
+
+/** The terms on which a withdrawal would be made, as given by the
+    [Kraken_API::withdrawal_info] end-point. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Withdrawal_Info
+{
+    /** The withdrawal method, e.g. "Bitcoin". */
+    pub  method:  String,
+
+    /** The most which may be withdrawn now. */
+    pub  limit:   f64,
+
+    /** The amount which would be withdrawn. */
+    pub  amount:  f64,
+
+    /** The fee which would be charged. */
+    pub  fee:     f64
+}
+
+
+
+impl  Withdrawal_Info
+{
+    /** Interpret the 'result' section of a WithdrawInfo reply. */
+    pub  fn  from_json  (V:  &JSN::Value)  ->  Result<Self, String>
+    {
+        strictness::check_fields ("Withdrawal_Info", V,
+                                  &["method", "limit", "amount", "fee"]) ?;
+
+        let  number  =  |f: &str|  reply::number (&V [f])
+                                     .map_err (|E| format! ("{}: {}", f, E));
+        Ok (Withdrawal_Info {  method:  reply::string (V, "method") ?,
+                               limit:   number ("limit") ?,
+                               amount:  number ("amount") ?,
+                               fee:     number ("fee") ?  })
+    }
+}
+
+
+
+/** A deposit or withdrawal, as given by the [Kraken_API::deposit_status]
+    and [Kraken_API::withdraw_status] end-points. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Transfer
+{
+    /** The funding method, e.g. "Bitcoin". */
+    pub  method:       String,
+
+    /** The asset, e.g. "XXBT". */
+    pub  asset:        String,
+
+    /** The exchange's reference ID for the transfer. */
+    pub  refid:        String,
+
+    /** The transaction ID on the blockchain (or the bank's reference);
+        empty until there is one. */
+    pub  txid:         String,
+
+    /** The address or account the funds came from or went to. */
+    pub  info:         String,
+
+    /** The amount moved. */
+    pub  amount:       f64,
+
+    /** The fee charged. */
+    pub  fee:          f64,
+
+    /** UNIX time at which the transfer was requested or seen. */
+    pub  time:         f64,
+
+    /** One of "Initial", "Pending", "Settled", "Success" or "Failure". */
+    pub  status:       String,
+
+    /** A qualification of the status, such as "onhold", "return" or
+        "canceled", if there is one. */
+    pub  status_prop:  Option<String>
+}
+
+
+
+impl  Transfer
+{
+    /** Interpret one member of the 'result' section of a DepositStatus or
+        WithdrawStatus reply. */
+    pub  fn  from_json  (V:  &JSN::Value)  ->  Result<Self, String>
+    {
+        strictness::check_fields ("Transfer", V,
+                                  &["method", "network", "aclass", "asset",
+                                    "refid", "txid", "info", "amount", "fee",
+                                    "time", "status", "status-prop",
+                                    "originators", "key"]) ?;
+
+        let  number  =  |f: &str|  reply::number (&V [f])
+                                     .map_err (|E| format! ("{}: {}", f, E));
+        let  text  =  |f: &str|  V [f].as_str ().unwrap_or ("").to_string ();
+
+        Ok (Transfer {  method:       reply::string (V, "method") ?,
+                        asset:        reply::string (V, "asset") ?,
+                        refid:        reply::string (V, "refid") ?,
+                        txid:         text ("txid"),
+                        info:         text ("info"),
+                        amount:       number ("amount") ?,
+                        fee:          V.get ("fee").map (reply::number)
+                                                   .transpose () ?
+                                                   .unwrap_or (0.0),
+                        time:         number ("time") ?,
+                        status:       reply::string (V, "status") ?,
+                        status_prop:  V ["status-prop"].as_str ()
+                                                       .map (str::to_string)  })
+    }
+
+    /** Whether the transfer has gone through. */
+    pub  fn  succeeded  (&self)  ->  bool   {   self.status == "Success"   }
+
+    /** Whether the transfer has failed, or been cancelled or sent back. */
+    pub  fn  failed  (&self)  ->  bool
+    {
+        self.status == "Failure"
+            ||  matches! (self.status_prop.as_deref (),
+                          Some ("canceled" | "return"))
+    }
+}
+
+
+
+/** Which transfer [Kraken_API::wait_for_deposit] or
+    [Kraken_API::wait_for_withdrawal] is to wait for. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  enum  Transfer_Ref
+{
+    /** The one with this blockchain transaction ID. */
+    TXID (String),
+
+    /** The one with this reference ID on the exchange. */
+    REFID (String),
+
+    /** The first one for this amount made at or after the UNIX time, for
+        when the transaction ID is not known in advance. */
+    AMOUNT (f64, f64)
+}
+
+
+
+impl  Transfer_Ref
+{
+    /*  Does T answer to this reference? */
+    fn  matches  (&self,  T:  &Transfer)  ->  bool
+    {
+        match  self
+        {
+            Transfer_Ref::TXID (X)   =>  T.txid == *X,
+            Transfer_Ref::REFID (X)  =>  T.refid == *X,
+            Transfer_Ref::AMOUNT (amount, since)
+                =>  T.time >= *since  &&  (T.amount - amount).abs () < 1e-9
+        }
+    }
+}
+
+
+
+/** How a wait for a transfer ended. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  enum  Transfer_Outcome
+{
+    /** The transfer went through. */
+    SUCCEEDED (Transfer),
+
+    /** The transfer failed, or was cancelled or sent back. */
+    FAILED (Transfer),
+
+    /** Time ran out first; this holds the transfer as last seen, if it was
+        seen at all. */
+    TIMED_OUT (Option<Transfer>)
+}
+
+
+
+/** Which way funds moved in a [Funding_Record]. */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub  enum  Funding_Direction
+{
+    /** Into the account. */
+    DEPOSIT,
+    /** Out of the account. */
+    WITHDRAWAL
+}
+
+
+
+/** One deposit or withdrawal in a [Kraken_API::funding_history], put
+    together from what the status end-points and the ledger each say about
+    it. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Funding_Record
+{
+    /** Whether this is a deposit or a withdrawal. */
+    pub  direction:  Funding_Direction,
+
+    /** The exchange's reference ID, which the status end-points and the
+        ledger share. */
+    pub  refid:      String,
+
+    /** The asset, e.g. "XXBT". */
+    pub  asset:      String,
+
+    /** UNIX time of the transfer, as the status end-point gives it if it
+        does, or else of the ledger entry. */
+    pub  time:       f64,
+
+    /** The amount moved, always positive. */
+    pub  amount:     f64,
+
+    /** The fee charged. */
+    pub  fee:        f64,
+
+    /** What the [Kraken_API::deposit_status] or
+        [Kraken_API::withdraw_status] end-point says about the transfer; they
+        only go back a few months, so older records have none. */
+    pub  transfer:   Option<Transfer>,
+
+    /** The ledger entry which settled the transfer; a record without one is
+        still under way, or has failed. */
+    pub  ledger:     Option<Ledger_Entry>
+}
+
+
+
+/*  Put the transfers and ledger entries together into records, matching
+ *  them up by reference ID, in time order. */
+
+fn  merge_funding  (deposits:  Vec<Transfer>,  withdrawals:  Vec<Transfer>,
+                    ledger:  Vec<Ledger_Entry>)
+          ->  Vec<Funding_Record>
+{
+    let  mut  M  =  BTreeMap::new ();
+
+    for  (direction, transfers)  in  [(Funding_Direction::DEPOSIT, deposits),
+                                      (Funding_Direction::WITHDRAWAL,
+                                       withdrawals)]
+    {   for  T  in  transfers
+        {   M.insert ((direction, T.refid.clone ()),
+                      Funding_Record {  direction,
+                                        refid:  T.refid.clone (),
+                                        asset:  T.asset.clone (),
+                                        time:  T.time,
+                                        amount:  T.amount.abs (),
+                                        fee:  T.fee,
+                                        transfer:  Some (T),
+                                        ledger:  None  });   }   }
+
+    for  E  in  ledger
+    {
+        let  direction  =  match  E.kind
+            {   Ledger_Type::DEPOSIT  =>  Funding_Direction::DEPOSIT,
+                Ledger_Type::WITHDRAWAL  =>  Funding_Direction::WITHDRAWAL,
+                _  =>  continue   };
+
+        let  R  =  M.entry ((direction, E.refid.clone ()))
+                    .or_insert_with (||  Funding_Record {
+                                             direction,
+                                             refid:  E.refid.clone (),
+                                             asset:  E.asset.clone (),
+                                             time:  E.time,
+                                             amount:  E.amount.abs (),
+                                             fee:  E.fee,
+                                             transfer:  None,
+                                             ledger:  None  });
+        R.ledger  =  Some (E);
+    }
+
+    let  mut  records  =  M.into_values ().collect::<Vec<_>> ();
+    records.sort_by (|a, b| a.time.total_cmp (&b.time));
+    records
+}
+
+
+
+/*  How often to ask after a transfer. */
+
+const  TRANSFER_POLL:  Duration  =  Duration::from_secs (15);
+
+
+
+/*  Find the transfer in the 'result' section of a status reply. */
+
+fn  find_transfer  (V:  &JSN::Value,  which:  &Transfer_Ref)
+          ->  Result<Option<Transfer>, String>
+{
+    for  T  in  V.as_array ().into_iter ().flatten ()
+    {
+        let  T  =  Transfer::from_json (T) ?;
+        if  which.matches (&T)   {   return  Ok (Some (T));   }
+    }
+    Ok (None)
+}
+
+
+
+/*  Keep calling `status` until the transfer has succeeded or failed, telling
+ *  `progress` every time its status changes or it gains a transaction ID. */
+
+fn  wait_for_transfer  (K:  &mut Kraken_API,
+                        status:  fn (&mut Kraken_API) -> Result<String, String>,
+                        which:  &Transfer_Ref,
+                        timeout:  Duration,
+                        mut  progress:  impl FnMut (&Transfer))
+          ->  Result<Transfer_Outcome, String>
+{
+    let  give_up  =  Instant::now () + timeout;
+    let  mut  last:  Option<Transfer>  =  None;
+
+    loop
+    {
+        if let Some (T) = find_transfer (&reply::result_of (&status (K) ?) ?,
+                                         which) ?
+        {
+            if  last.as_ref ().map (|L| (&L.status, &L.status_prop, &L.txid))
+                    != Some ((&T.status, &T.status_prop, &T.txid))
+                {   progress (&T);   }
+
+            if  T.succeeded ()
+                {   return  Ok (Transfer_Outcome::SUCCEEDED (T));   }
+            if  T.failed ()
+                {   return  Ok (Transfer_Outcome::FAILED (T));   }
+            last  =  Some (T);
+        }
+
+        let  now  =  Instant::now ();
+        if  now >= give_up
+            {   return  Ok (Transfer_Outcome::TIMED_OUT (last));   }
+
+        let  delay  =  TRANSFER_POLL.min (give_up - now);
+        match  &K.cancel_token
+        {
+            Some (C)  =>  {  C.pause (delay);
+                             if  C.is_cancelled ()
+                                 {   Err ("request cancelled") ?   }  }
+            None      =>  std::thread::sleep (delay)
+        }
+    }
+}
+
+
+
+/*  Put together the keys in the 'result' section of a WithdrawAddresses
+ *  reply with the minima of the methods in that of a WithdrawMethods
+ *  reply. */
+
+fn  keys_from_json  (addresses:  &JSN::Value,  methods:  &JSN::Value)
+          ->  Result<Vec<Withdrawal_Key>, String>
+{
+    let  method_of  =  |method: &str|
+                         methods.as_array ().into_iter ().flatten ()
+                                .find (|M| M ["method"] == method);
+    let  text  =  |V: &JSN::Value|  V.as_str ().filter (|S| ! S.is_empty ())
+                                              .map (str::to_string);
+
+    addresses.as_array ().into_iter ().flatten ()
+             .map (|A|  {
+                  let  method  =  reply::string (A, "method") ?;
+                  let  M  =  method_of (&method);
+                  Ok (Withdrawal_Key {
+                          key:       reply::string (A, "key") ?,
+                          asset:     reply::string (A, "asset") ?,
+                          network:   M.and_then (|M| text (&M ["network"])),
+                          address:   reply::string (A, "address") ?,
+                          tag:       text (&A ["tag"])
+                                        .or_else (|| text (&A ["memo"])),
+                          verified:  A ["verified"].as_bool ()
+                                        .unwrap_or (A ["verified"] == "true"),
+                          minimum:   M.map (|M| reply::number (&M ["minimum"]))
+                                      .transpose () ?,
+                          method  })  })
+             .collect ()
+}
+
+
+
+/*  The checks which can be made on a withdrawal before asking the exchange
+ *  about it. */
+
+fn  check_key  (keys:  &[Withdrawal_Key],  asset:  &str,  key:  &str,
+                amount:  f64)
+          ->  Result<(), String>
+{
+    if  amount.is_nan ()  ||  amount <= 0.0
+        {   Err (format! ("withdrawal amount {} is not positive", amount)) ?   }
+
+    let  Some (K)  =  keys.iter ().find (|K| K.key == key)
+        else
+        {
+            let  known  =  keys.iter ().map (|K| K.key.as_str ())
+                               .collect::<Vec<_>> ().join (", ");
+            Err (format! ("no withdrawal key '{}' for {} (known keys: {})",
+                          key, asset,
+                          if known.is_empty () { "none" } else { &known })) ?
+        };
+
+    if  ! K.verified
+        {   Err (format! ("withdrawal key '{}' has not been verified",
+                          key)) ?   }
+
+    match  K.minimum
+    {
+        None  =>  Err (format! ("withdrawal method '{}' of key '{}' is not \
+                                 available for {}", K.method, key, asset)) ?,
+        Some (M)  if  amount < M
+              =>  Err (format! ("withdrawal of {} {} is below the minimum of \
+                                 {} for '{}'", amount, asset, M, K.method)) ?,
+        _  =>  Ok (())
+    }
+}
+
+
+
+impl  Withdrawal_Key
+{
+    /** Whether funds sent by this key would go to `address` (with the memo
+        or tag `tag`, on networks which use one).  Addresses are compared
+        exactly, except that the case of Ethereum-style "0x" addresses is
+        ignored, as it only carries a checksum. */
+    pub  fn  sends_to  (&self,  address:  &str,  tag:  Option<&str>)  ->  bool
+    {
+        let  same_address
+                =  if  address.starts_with ("0x")
+                       {   self.address.eq_ignore_ascii_case (address)   }
+                   else   {   self.address == address   };
+        same_address  &&  self.tag.as_deref () == tag
+    }
+
+    /** Whether `text` appears, ignoring case, in the key's name, address,
+        method or network. */
+    pub  fn  mentions  (&self,  text:  &str)  ->  bool
+    {
+        let  text  =  text.to_lowercase ();
+        [Some (&self.key), Some (&self.address), Some (&self.method),
+         self.network.as_ref ()]
+             .iter ().flatten ()
+             .any (|S| S.to_lowercase ().contains (&text))
+    }
+}
+
+
+
+/*  Find the key which is allowed to send to the address. */
+
+fn  whitelisted  (keys:  &[Withdrawal_Key],  asset:  &str,  address:  &str,
+                  tag:  Option<&str>)
+          ->  Result<Withdrawal_Key, String>
+{
+    let  mut  found  =  keys.iter ().filter (|K| K.sends_to (address, tag));
+
+    match  (found.clone ().find (|K| K.verified),  found.next ())
+    {
+        (Some (K), _)  =>  Ok (K.clone ()),
+        (None, Some (K))
+            =>  Err (format! ("the withdrawal key '{}' for {} to {} has not \
+                               been verified", K.key, asset, address)),
+        (None, None)
+            =>  Err (format! ("{} is not a whitelisted withdrawal address for \
+                               {}", address, asset))
+    }
+}
+
+
+
+/*  The checks to be made against the terms the exchange offers. */
+
+fn  check_info  (info:  &Withdrawal_Info,  asset:  &str,  amount:  f64)
+          ->  Result<(), String>
+{
+    if  amount > info.limit
+        {   Err (format! ("withdrawal of {} {} is over the limit of {}",
+                          amount, asset, info.limit)) ?   }
+
+    if  info.fee >= amount
+        {   Err (format! ("the fee of {} {} would take all of the {} \
+                           withdrawn", info.fee, asset, amount)) ?   }
+
+    Ok (())
+}
+
+
+
+impl  Kraken_API
+{
+/** List the withdrawal keys set up for `asset`, with the minimum amount
+    each one's method will take.
+
+    This calls both the [Kraken_API::withdraw_addresses] and
+    [Kraken_API::withdraw_methods] end-points.  */
+
+  pub  fn  withdrawal_keys  (&mut self,  asset:  &str)
+               ->  Result<Vec<Withdrawal_Key>, String>
+    {
+      self.clear_opt (API_Option::KEY);
+      let  addresses  =  self.withdraw_addresses (asset) ?;
+      let  methods    =  self.withdraw_methods (asset) ?;
+      keys_from_json (&reply::result_of (&addresses) ?,
+                      &reply::result_of (&methods) ?)
+    }
+
+
+
+/** List the withdrawal keys set up for `asset` which mention `text`, ignoring
+    case, in their name, address, method or network; see
+    [Withdrawal_Key::mentions].
+
+    This calls the same end-points as [Kraken_API::withdrawal_keys].  */
+
+  pub  fn  search_withdrawal_keys  (&mut self,  asset:  &str,  text:  &str)
+               ->  Result<Vec<Withdrawal_Key>, String>
+    {
+      Ok (self.withdrawal_keys (asset) ?
+              .into_iter ()
+              .filter (|K| K.mentions (text))
+              .collect ())
+    }
+
+
+
+/** Check that `address` (with the memo or tag `tag`, on networks which use
+    one) is whitelisted for withdrawals of `asset`, that is, that a verified
+    withdrawal key has been set up for it on the account, and give back that
+    key; the error says which check failed.  This is meant for compliance
+    checks made before a withdrawal is even put together: the exchange only
+    ever sends funds to a whitelisted address anyway.
+
+    This calls the same end-points as [Kraken_API::withdrawal_keys]; no
+    funds are moved.  */
+
+  pub  fn  whitelisted_key  (&mut self,  asset:  &str,  address:  &str,
+                             tag:  Option<&str>)
+               ->  Result<Withdrawal_Key, String>
+    {
+      whitelisted (&self.withdrawal_keys (asset) ?, asset, address, tag)
+    }
+
+
+
+/** Check that a withdrawal of `amount` of `asset` to the withdrawal `key`
+    would be accepted, before calling [Kraken_API::withdraw]: that the key
+    exists and is verified, that its method is available for the asset, that
+    the amount is at least the method's minimum and no more than the limit
+    the exchange currently allows, and that the fee would not swallow it.
+    The returned terms give the fee which would be charged.
+
+    This calls the [Kraken_API::withdraw_addresses],
+    [Kraken_API::withdraw_methods] and [Kraken_API::withdrawal_info]
+    end-points; no funds are moved.  */
+
+  pub  fn  validate_withdrawal  (&mut self,  asset:  &str,  key:  &str,
+                                 amount:  f64)
+               ->  Result<Withdrawal_Info, String>
+    {
+      check_key (&self.withdrawal_keys (asset) ?, asset, key, amount) ?;
+
+      let  R  =  self.withdrawal_info (asset, key, &amount.to_string ()) ?;
+      let  info  =  Withdrawal_Info::from_json (&reply::result_of (&R) ?) ?;
+      check_info (&info, asset, amount) ?;
+      Ok (info)
+    }
+
+
+
+/** Wait for the deposit of `asset` picked out by `which` to go through, or
+    fail, or for `timeout` to pass, whichever comes first; `progress` is
+    called with the deposit when it is first seen and whenever its status
+    changes after that.  A deposit of a cryptocurrency is marked as a success
+    once it has had the confirmations the exchange needs.
+
+    This asks the [Kraken_API::deposit_status] end-point every 15 seconds,
+    replacing any [API_Option::ASSET] setting; a cancellation token installed
+    with [Kraken_API::set_cancel_token] cuts the wait short with an error.  */
+
+  pub  fn  wait_for_deposit  (&mut self,  asset:  &str,  which:  Transfer_Ref,
+                              timeout:  Duration,
+                              progress:  impl FnMut (&Transfer))
+               ->  Result<Transfer_Outcome, String>
+    {
+      self.set_opt (API_Option::ASSET, asset);
+      wait_for_transfer (self, Kraken_API::deposit_status, &which, timeout,
+                         progress)
+    }
+
+
+
+/** Wait for the withdrawal of `asset` with the reference ID `refid` (as
+    given in the reply to [Kraken_API::withdraw]) to go through, or fail, or
+    be cancelled, or for `timeout` to pass, whichever comes first.
+    `progress` is called with the withdrawal when it is first seen and
+    whenever it moves on after that: when its status changes, and when it
+    goes on-chain and so gains a transaction ID.
+
+    This asks the [Kraken_API::withdraw_status] end-point every 15 seconds,
+    replacing any [API_Option::ASSET] setting; a cancellation token cuts the
+    wait short with an error.  */
+
+  pub  fn  wait_for_withdrawal  (&mut self,  asset:  &str,  refid:  &str,
+                                 timeout:  Duration,
+                                 progress:  impl FnMut (&Transfer))
+               ->  Result<Transfer_Outcome, String>
+    {
+      self.set_opt (API_Option::ASSET, asset);
+      wait_for_transfer (self, Kraken_API::withdraw_status,
+                         &Transfer_Ref::REFID (refid.to_string ()), timeout,
+                         progress)
+    }
+
+
+
+/** Get every deposit and withdrawal, oldest first, putting together what
+    the [Kraken_API::deposit_status] and [Kraken_API::withdraw_status]
+    end-points say about recent transfers, including those still under way,
+    with the deposit and withdrawal entries in the ledger, which go back to
+    the opening of the account.
+
+    The ledger is paged through 50 entries at a time, for deposits and then
+    for withdrawals, so this may take many calls.  Any
+    [API_Option::ASSET] setting is respected by all the end-points, and the
+    [API_Option::START] and [API_Option::END] settings by the ledger, and, if
+    they are times rather than ledger IDs, also by the transfers which are
+    not yet in the ledger.  This replaces any [API_Option::TYPE] and
+    [API_Option::OFS] settings.  */
+
+  pub  fn  funding_history  (&mut self)  ->  Result<Vec<Funding_Record>, String>
+    {
+      let  mut  ledger  =  Vec::new ();
+      for  kind  in  [Ledger_Type::DEPOSIT, Ledger_Type::WITHDRAWAL]
+      {
+          self.set_opt (API_Option::TYPE, kind.as_kraken_string ());
+          let  mut  offset  =  0;
+          loop
+          {
+              self.set_opt (API_Option::OFS, offset);
+              let  page  =  self.ledger_entries () ?;
+              if  page.is_empty ()   {   break;   }
+              offset  +=  page.len ();
+              ledger.extend (page);
+          }
+      }
+      self.clear_opt (API_Option::TYPE);
+      self.clear_opt (API_Option::OFS);
+
+      let  transfers  =  |R: Result<String, String>|
+                            reply::result_of (&R ?) ?
+                                .as_array ().into_iter ().flatten ()
+                                .map (Transfer::from_json)
+                                .collect::<Result<Vec<_>, _>> ();
+      let  deposits     =  transfers (self.deposit_status ()) ?;
+      let  withdrawals  =  transfers (self.withdraw_status ()) ?;
+
+      let  time  =  |opt|  self.option (&opt)
+                                       .and_then (|T| T.parse::<f64> ().ok ());
+      let  (start, end)  =  (time (API_Option::START), time (API_Option::END));
+
+      Ok (merge_funding (deposits, withdrawals, ledger)
+              .into_iter ()
+              .filter (|R|  R.ledger.is_some ()
+                              ||  (start.is_none_or (|S| R.time >= S)
+                                     &&  end.is_none_or (|E| R.time <= E)))
+              .collect ())
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  checks  ()  ->  Result<(), String>
+    {
+        use  super::{Withdrawal_Info, check_info, check_key, keys_from_json,
+                     whitelisted};
+        use  serde_json::json;
+
+        let  keys  =  keys_from_json
+                        (&json! ([{"address": "bc1qxdsh4sdd29h6ldehz0se5c61asq",
+                                   "asset": "XBT", "method": "Bitcoin",
+                                   "key": "cold", "verified": true},
+                                  {"address": "bc1qmuq0ngqh5e2ra6mnqnjkt8dprp",
+                                   "asset": "XBT", "method": "Bitcoin",
+                                   "key": "new", "verified": false},
+                                  {"address": "0x6fb1de36",
+                                   "asset": "XBT", "method": "Lightning",
+                                   "key": "ln", "verified": true}]),
+                         &json! ([{"asset": "XBT", "method": "Bitcoin",
+                                   "network": "Bitcoin",
+                                   "minimum": "0.0004"}])) ?;
+
+        assert_eq! (keys.len (), 3);
+        assert_eq! (keys [0].minimum, Some (0.0004));
+        assert_eq! (keys [0].network.as_deref (), Some ("Bitcoin"));
+        assert_eq! ((keys [2].network.as_ref (), keys [2].tag.as_ref ()),
+                    (None, None));
+
+        let  cold  =  "bc1qxdsh4sdd29h6ldehz0se5c61asq";
+        assert_eq! (whitelisted (&keys, "XBT", cold, None) ?.key,  "cold");
+        assert! (whitelisted (&keys, "XBT", "bc1qmuq0ngqh5e2ra6mnqnjkt8dprp",
+                              None)
+                    .unwrap_err ().contains ("not been verified"));
+        assert! (whitelisted (&keys, "XBT", cold, Some ("1234"))
+                    .unwrap_err ().contains ("not a whitelisted"));
+        assert! (whitelisted (&keys, "XBT", "0x6FB1DE36", None).is_ok ());
+        assert_eq! (keys.iter ().filter (|K| K.mentions ("LIGHT")).count (),
+                    1);
+
+        assert! (check_key (&keys, "XBT", "cold", 0.01).is_ok ());
+        assert! (check_key (&keys, "XBT", "cold", 0.0)
+                    .unwrap_err ().contains ("not positive"));
+        assert! (check_key (&keys, "XBT", "hot", 0.01)
+                    .unwrap_err ().contains ("known keys: cold, new, ln"));
+        assert! (check_key (&keys, "XBT", "new", 0.01)
+                    .unwrap_err ().contains ("not been verified"));
+        assert! (check_key (&keys, "XBT", "ln", 0.01)
+                    .unwrap_err ().contains ("not available"));
+        assert! (check_key (&keys, "XBT", "cold", 0.0001)
+                    .unwrap_err ().contains ("below the minimum"));
+
+        let  info  =  Withdrawal_Info::from_json
+                         (&json! ({"method": "Bitcoin", "limit": "1.5",
+                                   "amount": "2.0", "fee": "0.0002"})) ?;
+        assert! (check_info (&info, "XBT", 2.0)
+                    .unwrap_err ().contains ("over the limit"));
+        assert! (check_info (&info, "XBT", 0.0001)
+                    .unwrap_err ().contains ("take all"));
+        assert! (check_info (&info, "XBT", 1.0).is_ok ());
+        Ok (())
+    }
+
+
+    #[test]
+    fn  transfers  ()  ->  Result<(), String>
+    {
+        use  super::{Transfer_Ref, find_transfer};
+        use  serde_json::json;
+
+        let  V  =  json! ([{"method": "Bitcoin", "aclass": "currency",
+                            "asset": "XXBT", "refid": "FTQcuak-V6Za8q",
+                            "txid": "6544b41b607d8b2512baf801755a3",
+                            "info": "bc1qxdsh4sdd29h6ldehz0se5c61asq",
+                            "amount": "0.1", "fee": "0.0000000000",
+                            "time": 1688992722, "status": "Success"},
+                           {"method": "Bitcoin", "aclass": "currency",
+                            "asset": "XXBT", "refid": "FTQcuak-V6Za8r",
+                            "txid": "", "info": "bc1qxdsh4sdd29h6ldehz0",
+                            "amount": "0.25", "time": 1688992800,
+                            "status": "Pending"}]);
+
+        let  T  =  find_transfer (&V, &Transfer_Ref::TXID
+                                          ("6544b41b607d8b2512baf801755a3"
+                                           .into ())) ?.unwrap ();
+        assert! (T.succeeded ()  &&  ! T.failed ());
+
+        let  T  =  find_transfer (&V, &Transfer_Ref::AMOUNT (0.25,
+                                                            1688992700.0)) ?
+                     .unwrap ();
+        assert_eq! ((T.refid.as_str (), T.fee), ("FTQcuak-V6Za8r", 0.0));
+        assert! (! T.succeeded ()  &&  ! T.failed ());
+
+        assert! (find_transfer (&V, &Transfer_Ref::AMOUNT (0.25, 1688993000.0))
+                    ?.is_none ());
+        Ok (())
+    }
+
+
+    #[test]
+    fn  funding  ()  ->  Result<(), String>
+    {
+        use  super::{Funding_Direction, Transfer, merge_funding};
+        use  crate::Ledger_Type::{DEPOSIT, TRADE, WITHDRAWAL};
+        use  crate::test_fixtures::ledger_entry;
+        use  serde_json::json;
+
+        let  transfer  =  |refid: &str, time: f64, status: &str|
+                             Transfer::from_json (&json! ({
+                                 "method": "Bitcoin", "asset": "XXBT",
+                                 "refid": refid, "txid": "", "info": "",
+                                 "amount": "0.5", "fee": "0.0001",
+                                 "time": time, "status": status  }));
+
+        let  R  =  merge_funding (vec! [transfer ("D2", 300.0, "Success") ?,
+                                        transfer ("D3", 500.0, "Pending") ?],
+                                  vec! [transfer ("W1", 400.0, "Success") ?],
+                                  vec! [ledger_entry ("D1", 100.0, DEPOSIT,
+                                                      "XXBT", 2.0, 1.0),
+                                        ledger_entry ("D2", 310.0, DEPOSIT,
+                                                      "XXBT", 0.5, 1.0),
+                                        ledger_entry ("W1", 410.0, WITHDRAWAL,
+                                                      "XXBT", -0.5, 1.0),
+                                        ledger_entry ("T1", 200.0, TRADE,
+                                                      "XXBT", 1.0, 1.0)]);
+
+        assert_eq! (R.iter ().map (|R| (R.refid.as_str (), R.direction,
+                                        R.transfer.is_some (),
+                                        R.ledger.is_some ()))
+                             .collect::<Vec<_>> (),
+                    [("D1", Funding_Direction::DEPOSIT, false, true),
+                     ("D2", Funding_Direction::DEPOSIT, true, true),
+                     ("W1", Funding_Direction::WITHDRAWAL, true, true),
+                     ("D3", Funding_Direction::DEPOSIT, true, false)]);
+        assert_eq! ((R [0].amount, R [2].amount, R [1].time),
+                    (2.0, 0.5, 300.0));
+        Ok (())
+    }
+}
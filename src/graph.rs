@@ -0,0 +1,203 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{Conversion_Leg, Kraken_API, Pair_Info};
+use  crate::conversion;
+use  std::collections::BTreeMap;
+
+
+
+/** The exchange's trading pairs seen as a graph, with the assets as nodes
+    and the pairs as edges joining them; each edge carries the pair's
+    [Pair_Info], with its fees and precision.  Dark-pool pairs (with names
+    ending ".d") are left out.
+
+    ```ignore
+    let  G  =  K.pair_graph () ?;
+    for  T  in  G.triangles ()
+        {   println! ("{} -> {} -> {}", T [0].from, T [1].from, T [2].from);   }
+    ```  */
+
+#[derive(Clone, Debug)]
+pub  struct  Pair_Graph  {  pairs:  Vec<Pair_Info>,
+                            links:  BTreeMap<String, BTreeMap<String, usize>>  }
+
+
+
+impl  Pair_Graph
+{
+    /** Build the graph from a list of pairs, such as that given by
+        [Kraken_API::all_pairs].  Where more than one pair joins the same two
+        assets, the first is kept. */
+    pub  fn  new  (pairs:  Vec<Pair_Info>)  ->  Self
+    {
+        let  pairs  =  pairs.into_iter ()
+                            .filter (|P| ! P.name.ends_with (".d"))
+                            .collect::<Vec<_>> ();
+        let  mut  links  =  BTreeMap::<String, BTreeMap<String, usize>>::new ();
+
+        for  (i, P)  in  pairs.iter ().enumerate ()
+        {
+            links.entry (P.base.clone ()).or_default ()
+                 .entry (P.quote.clone ()).or_insert (i);
+            links.entry (P.quote.clone ()).or_default ()
+                 .entry (P.base.clone ()).or_insert (i);
+        }
+
+        Pair_Graph {  pairs,  links  }
+    }
+
+    /** All the assets, in order of name. */
+    pub  fn  assets  (&self)  ->  impl Iterator<Item = &str>
+          {   self.links.keys ().map (String::as_str)   }
+
+    /** All the pairs. */
+    pub  fn  pairs  (&self)  ->  &[Pair_Info]   {   &self.pairs   }
+
+    /** The assets which `asset` trades directly against, each with the pair
+        joining them. */
+    pub  fn  neighbours  (&self,  asset:  &str)
+              ->  impl Iterator<Item = (&str, &Pair_Info)>
+    {
+        self.links.get (asset).into_iter ().flatten ()
+                  .map (move |(A, i)|  (A.as_str (), &self.pairs [*i]))
+    }
+
+    /** The pair joining two assets, if there is one, whichever way round
+        they are. */
+    pub  fn  pair_between  (&self,  a:  &str,  b:  &str)  ->  Option<&Pair_Info>
+          {   self.links.get (a)?.get (b).map (|i| &self.pairs [*i])   }
+
+    /** The trade converting `from` into `to` directly, if there is one. */
+    pub  fn  leg  (&self,  from:  &str,  to:  &str)  ->  Option<Conversion_Leg>
+    {
+        self.pair_between (from, to)
+            .map (|P|  Conversion_Leg {  pair:  P.name.clone (),
+                                         from:  from.to_string (),
+                                         to:    to.to_string ()  })
+    }
+
+    /** Every cycle of three trades which leads from an asset back to
+        itself, through two others.  Each cycle is given once in each
+        direction, starting from the asset with the lowest name. */
+    pub  fn  triangles  (&self)  ->  Vec<[Conversion_Leg; 3]>
+    {
+        let  mut  T  =  Vec::new ();
+
+        for  a  in  self.assets ()
+        {
+            for  (b, _)  in  self.neighbours (a).filter (|(b, _)| *b > a)
+            {
+                for  (c, _)  in  self.neighbours (b)
+                                     .filter (|(c, _)|  *c > a  &&  *c != b)
+                {
+                    if let (Some (x), Some (y), Some (z))
+                               =  (self.leg (a, b), self.leg (b, c),
+                                   self.leg (c, a))
+                        {   T.push ([x, y, z]);   }
+                }
+            }
+        }
+
+        T
+    }
+
+    /** What one unit of the first asset of `legs` would become after going
+        through them all, at the mid-market prices in `mids` (keyed by pair
+        name) and paying the taker fee on every trade.  For a cycle, a figure
+        above one suggests an opportunity; the real prices paid will of
+        course be worse than the mid-market ones. */
+    pub  fn  cycle_return  (&self,  legs:  &[Conversion_Leg],
+                            mids:  &BTreeMap<String, f64>)
+              ->  Result<f64, String>
+    {
+        let  fees  =  legs.iter ()
+                          .map (|L|  self.pairs.iter ()
+                                          .find (|P| P.name == L.pair)
+                                          .and_then (|P| P.taker_fee)
+                                          .unwrap_or (0.0))
+                          .fold (1.0, |R, fee| R * (1.0 - fee / 100.0));
+
+        Ok (conversion::rate (legs, &self.pairs, mids) ? * fees)
+    }
+}
+
+
+
+impl  Kraken_API
+{
+/** Get the [Pair_Graph] of all the exchange's trading pairs, from
+    [Kraken_API::all_pairs].  */
+
+  pub  fn  pair_graph  (&mut self)  ->  Result<Pair_Graph, String>
+    {
+      Ok (Pair_Graph::new (self.all_pairs () ?))
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  triangles  ()  ->  Result<(), String>
+    {
+        use  super::Pair_Graph;
+        use  crate::Pair_Info;
+        use  std::collections::BTreeMap;
+
+        let  pair  =  |name: &str, base: &str, quote: &str|
+                         Pair_Info {  name:  name.into (),
+                                      altname:  name.into (),
+                                      wsname:  String::new (),
+                                      base:  base.into (),
+                                      quote:  quote.into (),
+                                      pair_decimals:  1,
+                                      lot_decimals:  8,
+                                      ordermin:  None,
+                                      taker_fee:  Some (0.5),
+                                      maker_fee:  None  };
+        let  G  =  Pair_Graph::new (vec! [pair ("XXBTZUSD", "XXBT", "ZUSD"),
+                                          pair ("XETHXXBT", "XETH", "XXBT"),
+                                          pair ("XETHZUSD", "XETH", "ZUSD"),
+                                          pair ("XETHZUSD.d", "XETH", "ZUSD"),
+                                          pair ("ZUSDZJPY", "ZUSD", "ZJPY")]);
+
+        assert_eq! (G.pairs ().len (), 4);
+        assert_eq! (G.neighbours ("ZUSD").count (), 3);
+        assert! (G.pair_between ("ZJPY", "XETH").is_none ());
+
+        let  T  =  G.triangles ();
+        assert_eq! (T.len (), 2);
+        assert_eq! ((T [0][0].from.as_str (), T [0][1].from.as_str (),
+                     T [0][2].from.as_str ()),
+                    ("XETH", "XXBT", "ZUSD"));
+        assert_eq! (T [1][1].from, "ZUSD");
+
+        let  mids  =  BTreeMap::from ([("XXBTZUSD".to_string (), 40000.0),
+                                       ("XETHXXBT".to_string (), 0.05),
+                                       ("XETHZUSD".to_string (), 2000.0)]);
+        let  R  =  G.cycle_return (&T [0], &mids) ?;
+        assert! ((R - 0.995f64.powi (3)).abs () < 1e-12);
+        Ok (())
+    }
+}
@@ -0,0 +1,251 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option as Opt, Batch_Order, Instruction, Kraken_API,
+             Order_Type};
+use  crate::{reply, Userref};
+use  std::cmp::Ordering;
+
+
+
+/** One rung of a [Grid]. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Grid_Level
+{
+    /** Whether this rung buys or sells. */
+    pub  side:   Instruction,
+
+    /** The limit price, as sent to the exchange. */
+    pub  price:  String
+}
+
+
+
+/** A ladder of limit orders placed by [Kraken_API::place_grid]. */
+
+#[derive(Clone, Debug)]
+pub  struct  Grid
+{
+    /** The pair the grid trades, by Kraken's canonical name. */
+    pub  pair:     String,
+
+    /** The user reference carried by every order in the grid. */
+    pub  userref:  i32,
+
+    /** The rungs of the ladder which were sent to the exchange, lowest price
+        first. */
+    pub  levels:   Vec<Grid_Level>,
+
+    /** The transaction IDs of the orders the exchange accepted. */
+    pub  txids:    Vec<String>,
+
+    /** The exchange's reasons for refusing any of the orders, and the
+        errors of any calls which failed outright (the orders of which may
+        or may not have been placed). */
+    pub  errors:   Vec<String>
+}
+
+
+
+/*  The exchange will not take more than this many orders in one batch. */
+const  BATCH_LIMIT:  usize  =  15;
+
+
+
+/*  Where the grids' user references come from: one generator for the whole
+ *  program, so that no two grids share a reference, and cancelling one
+ *  never touches the orders of another. */
+static  GRID_REFS:  Userref  =  Userref::new ();
+
+
+
+/*  The rungs of a ladder of `levels` prices from `low` to `high`, buying
+ *  below the `bid` and selling above the `ask`, with those in between left
+ *  out; the prices are given as `format` writes them, and the rungs are
+ *  divided into batches the exchange will take. */
+
+fn  ladder  (low:  f64,  high:  f64,  levels:  usize,  bid:  f64,  ask:  f64,
+             format:  impl Fn (f64) -> String)
+      ->  Vec<Vec<Grid_Level>>
+{
+    let  step  =  (high - low) / (levels - 1) as f64;
+    let  rungs  =  (0 .. levels)
+                       .filter_map (|i|  {
+                           let  price  =  low + step * i as f64;
+                           let  side  =  if  price < bid  {  Instruction::BUY  }
+                                         else if  price > ask
+                                             {  Instruction::SELL  }
+                                         else  {  return  None  };
+                           Some (Grid_Level {  side,  price: format (price)  })
+                       })
+                       .collect::<Vec<_>> ();
+    rungs.chunks (BATCH_LIMIT).map (<[_]>::to_vec).collect ()
+}
+
+
+
+impl  Kraken_API
+{
+/** Place a ladder of `levels` limit orders, each of `size`, at prices spaced
+    evenly from `low` to `high` inclusive.
+
+    Rungs below the best bid are buys and those above the best ask are sells;
+    any which would fall inside the spread are left out.  Prices and volumes
+    are rounded to the precision the pair allows, all orders are post-only, and
+    they are sent in as few calls to [Kraken_API::add_order_batch] as possible.
+    Every order carries the same user reference, which is how
+    [Kraken_API::cancel_grid] finds them again.  A call which fails does not
+    stop the rest being made: its error goes into [Grid::errors], so that
+    the grid, and the orders already placed, are never lost track of.
+
+    The optional arguments on this object are cleared before the orders are
+    sent.  */
+
+  pub  fn  place_grid  (&mut self,  pair: &str,  low: f64,  high: f64,
+                        levels: usize,  size: f64)
+               ->  Result<Grid, String>
+    {
+      if  levels < 2  ||  low.partial_cmp (&high) != Some (Ordering::Less)
+          {   Err ("a grid needs at least two levels, and low below high"
+                       .to_string ()) ?   }
+
+      let  P  =  self.pair_info (pair) ?;
+      P.check_volume (size) ?;
+
+      let  T  =  reply::result_of (&self.ticker_info (P.name.clone ()) ?) ?;
+      let  bid  =  reply::number (&T [&P.name] ["b"] [0]) ?;
+      let  ask  =  reply::number (&T [&P.name] ["a"] [0]) ?;
+
+      let  batches  =  ladder (low, high, levels, bid, ask,
+                               |price|  P.format_price (price));
+
+      let  userref  =  GRID_REFS.next ();
+
+      let  mut  grid  =  Grid {  pair: P.name.clone (),  userref,
+                                 levels: Vec::new (),
+                                 txids: Vec::new (),  errors: Vec::new ()  };
+
+      let  volume  =  P.format_volume (size);
+
+      for  chunk  in  &batches
+      {
+          self.clear_all_options ();
+
+          let  R  =  if  chunk.len () == 1
+                     {
+                         self.set_opt (Opt::PRICE, &chunk [0].price);
+                         self.set_opt (Opt::USERREF, userref);
+                         self.set_opt (Opt::OFLAGS, "post");
+                         self.add_order (Order_Type::LIMIT, chunk [0].side,
+                                         &volume, &P.name)
+                     }
+                     else
+                     {
+                         let  order  =  |L: &Grid_Level|
+                                  Batch_Order::new (Order_Type::LIMIT,
+                                                    L.side,  &volume)
+                                     .set_opt (Opt::PRICE, &L.price)
+                                     .set_opt (Opt::USERREF, userref)
+                                     .set_opt (Opt::OFLAGS, "post");
+                         let  orders  =  chunk.iter ().map (order)
+                                                      .collect::<Vec<_>> ();
+                         self.add_order_batch (&P.name, &orders)
+                     };
+
+          match  R.and_then (|R| reply::result_of (&R))
+            {   Ok (R)   =>  note_outcome (&mut grid, &R),
+                Err (E)  =>  grid.errors.push (E)   }
+      }
+
+      self.clear_all_options ();
+      grid.levels  =  batches.into_iter ().flatten ().collect ();
+
+      Ok (grid)
+    }
+
+
+
+/** Cancel all the orders in a grid placed by [Kraken_API::place_grid] which
+    are still open, by way of their common user reference; this also catches
+    any orders placed by calls whose replies were lost.  */
+
+  pub  fn  cancel_grid  (&mut self,  grid:  &Grid)  ->  Result<String, String>
+    {   self.cancel_by_userref (grid.userref)   }
+}
+
+
+
+/*  Pick the transaction IDs or error messages out of the result of an AddOrder
+ *  or AddOrderBatch call. */
+
+fn  note_outcome  (grid:  &mut Grid,  R:  &serde_json::Value)
+{
+    for  T  in  R ["txid"].as_array ().into_iter ().flatten ()
+        {   grid.txids.extend (T.as_str ().map (str::to_string));   }
+
+    for  O  in  R ["orders"].as_array ().into_iter ().flatten ()
+    {   grid.txids.extend (O ["txid"].as_str ().map (str::to_string));
+        grid.errors.extend (O ["error"].as_str ().map (str::to_string));   }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  ladder  ()
+    {
+        use  super::{BATCH_LIMIT, ladder};
+        use  crate::Instruction::{BUY, SELL};
+
+        let  L  =  ladder (100.0, 110.0, 6, 103.5, 104.5,
+                           |P| format! ("{:.1}", P));
+        assert_eq! (L.len (),  1);
+        assert_eq! (L [0].iter ().map (|L| (L.side, L.price.as_str ()))
+                                  .collect::<Vec<_>> (),
+                    [(BUY, "100.0"), (BUY, "102.0"),
+                     (SELL, "106.0"), (SELL, "108.0"), (SELL, "110.0")]);
+
+        /*  A rung on the bid or ask is inside the spread. */
+        let  L  =  ladder (1.0, 3.0, 3, 2.0, 2.0, |P| P.to_string ());
+        assert_eq! (L [0].iter ().map (|L| L.side).collect::<Vec<_>> (),
+                    [BUY, SELL]);
+
+        let  L  =  ladder (1.0, 41.0, 41, 20.5, 20.5,
+                           |P| format! ("{:.0}", P));
+        assert_eq! (L.iter ().map (Vec::len).collect::<Vec<_>> (),
+                    [BATCH_LIMIT, BATCH_LIMIT, 11]);
+        assert_eq! ((L [1] [4].side, L [1] [5].side),  (BUY, SELL));
+        assert_eq! (L [2] [10].price,  "41");
+    }
+
+
+    #[test]
+    fn  userrefs  ()
+    {
+        let  A  =  super::GRID_REFS.next ();
+        let  B  =  super::GRID_REFS.next ();
+        assert! (A > 0  &&  B > 0);
+        assert_ne! (A,  B);
+    }
+}
@@ -0,0 +1,286 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  Limits the user puts on the withdrawals a handle will send, checked
+ *  before the request leaves the process, so that a bug or compromised
+ *  strategy code cannot take more out of the account than was meant to
+ *  go. */
+
+
+
+use  crate::{Kraken_API, UNKNOWN_OUTCOME};
+use  std::collections::{BTreeMap, BTreeSet, VecDeque};
+use  std::sync::Mutex;
+use  std::time::{Duration, Instant};
+
+
+
+/*  The window over which the daily limits are counted. */
+
+const  DAY:  Duration  =  Duration::from_secs (24 * 60 * 60);
+
+
+
+/** Limits on what [Kraken_API::withdraw] will send, installed with
+    [Kraken_API::set_withdrawal_limits].  Assets and keys are named exactly
+    as they are given to [Kraken_API::withdraw]; an asset with no entry in
+    one of the maps is not limited by it. */
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub  struct  Withdrawal_Limits
+{
+    /** The most of each asset which may be withdrawn in a single call. */
+    pub  per_call:  BTreeMap<String, f64>,
+
+    /** The most of each asset which may be withdrawn in any 24 hours,
+        counting the withdrawals sent through this handle and those shared
+        with it. */
+    pub  per_day:   BTreeMap<String, f64>,
+
+    /** If given, the only assets which may be withdrawn at all. */
+    pub  assets:    Option<BTreeSet<String>>,
+
+    /** If given, the only withdrawal keys which may be sent to. */
+    pub  keys:      Option<BTreeSet<String>>
+}
+
+
+
+/*  A withdrawal counted against the daily limits. */
+
+struct  Sent  {  serial:  u64,
+                 at:      Instant,
+                 asset:   String,
+                 amount:  f64  }
+
+
+
+#[derive(Default)]
+struct  Guard_State  {  limits:  Option<Withdrawal_Limits>,
+                        sent:    VecDeque<Sent>,
+                        serial:  u64  }
+
+
+
+/*  The limits, and the withdrawals made under them, of all the handles
+ *  which share a key. */
+
+#[derive(Default)]
+pub(crate)  struct  Withdrawal_Guard  (Mutex<Guard_State>);
+
+
+
+impl  Guard_State
+{
+    fn  sent_within_day  (&mut self,  asset:  &str,  now:  Instant)  ->  f64
+    {
+        while  self.sent.front ().is_some_and (|S| now - S.at >= DAY)
+            {   self.sent.pop_front ();   }
+        self.sent.iter ().filter (|S| S.asset == asset)
+                 .map (|S| S.amount).sum ()
+    }
+
+
+    fn  check  (&mut self,  asset:  &str,  key:  &str,  amount:  &str,
+                now:  Instant)
+          ->  Result<Option<u64>, String>
+    {
+        let  Some (L)  =  self.limits.clone ()
+            else   {   return  Ok (None)   };
+        let  refuse  =  |why: String|
+                           Err (format! ("withdrawal refused by the local \
+                                          limits: {}", why));
+
+        if  L.assets.as_ref ().is_some_and (|A| ! A.contains (asset))
+            {   return  refuse (format! ("{} may not be withdrawn", asset));   }
+        if  L.keys.as_ref ().is_some_and (|K| ! K.contains (key))
+            {   return  refuse (format! ("the key '{}' may not be used",
+                                         key));   }
+
+        let  amount  =  match  amount.trim ().parse::<f64> ()
+                           {   Ok (A)  if  A > 0.0  =>  A,
+                               _  =>  return  refuse (format! ("the amount \
+                                                               '{}' is not \
+                                                               a positive \
+                                                               number",
+                                                              amount))   };
+
+        if  let  Some (M)  =  L.per_call.get (asset)
+        {
+            if  amount > *M
+                {   return  refuse (format! ("{} {} is over the limit of {} \
+                                              per call", amount, asset, M));   }
+        }
+
+        if  let  Some (M)  =  L.per_day.get (asset)
+        {
+            let  so_far  =  self.sent_within_day (asset, now);
+            if  so_far + amount > *M
+                {   return  refuse (format! ("{} {} would take the day's \
+                                              withdrawals over the limit of \
+                                              {} ({} already sent)",
+                                             amount, asset, M, so_far));   }
+        }
+
+        self.serial  +=  1;
+        self.sent.push_back (Sent {  serial:  self.serial,  at:  now,
+                                     asset:  asset.to_string (),  amount  });
+        Ok (Some (self.serial))
+    }
+}
+
+
+
+impl  Withdrawal_Guard
+{
+    /*  Check a withdrawal against the limits; if it passes it is counted
+     *  against the daily limits straight away, and the serial number given
+     *  back lets it be taken off again should the exchange refuse it. */
+    pub(crate)  fn  check  (&self,  asset:  &str,  key:  &str,  amount:  &str)
+                   ->  Result<Option<u64>, String>
+          {   self.0.lock ().unwrap ().check (asset, key, amount,
+                                              Instant::now ())   }
+
+
+    /*  Take a withdrawal off the count again if the reply shows that no
+     *  funds moved: the exchange refused it, or it never got there.  When
+     *  we cannot tell, it stays counted. */
+    pub(crate)  fn  settle  (&self,  serial:  Option<u64>,
+                             reply:  &Result<String, String>)
+    {
+        let  moved  =  match  reply
+                          {   Ok (R)   =>  R.contains ("\"result\""),
+                              Err (E)  =>  E.starts_with (UNKNOWN_OUTCOME)   };
+        if  let  (Some (serial), false)  =  (serial, moved)
+            {   self.0.lock ().unwrap ()
+                    .sent.retain (|S| S.serial != serial);   }
+    }
+}
+
+
+
+impl  Kraken_API
+{
+/** Put `limits` on the withdrawals this handle, and every handle shared
+    with it through [Kraken_API::share], will make: a call to
+    [Kraken_API::withdraw], or a [Kraken_API::submit] of a signed
+    withdrawal, which breaks them fails without anything being sent to the
+    exchange.  This is a second line of defence, under the
+    limits which can be set on the API key itself, against a bug or
+    compromised strategy code sending funds where they should not go.
+    `None` (the default) lifts the limits.
+
+    A withdrawal counts against the daily limit from the moment it is sent,
+    unless the exchange refuses it; the count is kept in memory, so starts
+    again from nothing when the program does.  */
+
+    pub  fn  set_withdrawal_limits  (&self,  limits:  Option<Withdrawal_Limits>)
+    {
+        let  mut  S  =  self.withdrawal_guard.0.lock ().unwrap ();
+        S.limits  =  limits;
+    }
+
+
+
+/** The limits set with [Kraken_API::set_withdrawal_limits], if any. */
+
+    pub  fn  withdrawal_limits  (&self)  ->  Option<Withdrawal_Limits>
+          {   self.withdrawal_guard.0.lock ().unwrap ().limits.clone ()   }
+
+
+
+/** How much of `asset` has been withdrawn, through this handle and those
+    shared with it, in the last 24 hours, as counted against the daily
+    limit.  Only withdrawals made while limits were in place are counted. */
+
+    pub  fn  withdrawn_today  (&self,  asset:  &str)  ->  f64
+    {
+        self.withdrawal_guard.0.lock ().unwrap ()
+            .sent_within_day (asset, Instant::now ())
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  limits  ()
+    {
+        use  super::{DAY, Guard_State, Withdrawal_Limits};
+        use  std::time::Instant;
+
+        let  mut  G  =  Guard_State::default ();
+        let  now  =  Instant::now ();
+        assert_eq! (G.check ("XBT", "cold", "1000", now),  Ok (None));
+
+        G.limits  =  Some (Withdrawal_Limits {
+                             per_call:  [("XBT".into (), 0.5)].into (),
+                             per_day:   [("XBT".into (), 1.0)].into (),
+                             assets:    Some (["XBT".into ()].into ()),
+                             keys:      Some (["cold".into ()].into ())  });
+
+        let  refused  =  |R: Result<Option<u64>, String>, why: &str|
+                            R.unwrap_err ().contains (why);
+        assert! (refused (G.check ("ETH", "cold", "0.1", now),
+                          "ETH may not be withdrawn"));
+        assert! (refused (G.check ("XBT", "hot", "0.1", now),
+                          "'hot' may not be used"));
+        assert! (refused (G.check ("XBT", "cold", "-1", now),
+                          "not a positive number"));
+        assert! (refused (G.check ("XBT", "cold", "0.6", now), "per call"));
+
+        assert_eq! (G.check ("XBT", "cold", "0.5", now),  Ok (Some (1)));
+        assert_eq! (G.check ("XBT", "cold", "0.4", now),  Ok (Some (2)));
+        assert! (refused (G.check ("XBT", "cold", "0.2", now),
+                          "(0.9 already sent)"));
+        assert_eq! (G.check ("XBT", "cold", "0.2", now + DAY),  Ok (Some (3)));
+    }
+
+
+    #[test]
+    fn  settle  ()
+    {
+        use  super::Withdrawal_Limits;
+
+        let  K  =  crate::Kraken_API::default ();
+        K.set_withdrawal_limits
+              (Some (Withdrawal_Limits {
+                         per_day:  [("XBT".into (), 1.0)].into (),
+                         ..Default::default ()  }));
+        let  S  =  K.share ();
+
+        let  G  =  &K.withdrawal_guard;
+        let  serial  =  G.check ("XBT", "cold", "0.3").unwrap ();
+        G.settle (serial, &Ok (r#"{"error":["EFunding:Unknown withdraw key"]}"#
+                                  .into ()));
+        assert_eq! (S.withdrawn_today ("XBT"),  0.0);
+
+        let  serial  =  G.check ("XBT", "cold", "0.3").unwrap ();
+        G.settle (serial, &Ok (r#"{"error":[],"result":{"refid":"A"}}"#
+                                  .into ()));
+        let  serial  =  G.check ("XBT", "cold", "0.2").unwrap ();
+        G.settle (serial, &Err (format! ("{}: Withdraw ...",
+                                         crate::UNKNOWN_OUTCOME)));
+        assert_eq! (S.withdrawn_today ("XBT"),  0.5);
+    }
+}
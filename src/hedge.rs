@@ -0,0 +1,202 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  Hedged public requests: if the exchange has not answered a request within
+ *  the time by which it usually has, the same request is sent again, and the
+ *  first answer to arrive is taken. */
+
+
+
+use  crate::{Cancel_Token, Kraken_API, Reply_Too_Large};
+use  curl::multi::{EasyHandle, Multi};
+use  std::collections::VecDeque;
+use  std::sync::{Arc, Mutex};
+use  std::time::{Duration, Instant};
+
+
+
+/*  How many of the most recent request times we keep, and how many we must
+ *  have before we trust them enough to hedge. */
+
+const  WINDOW:       usize  =  200;
+const  MIN_SAMPLES:  usize  =  20;
+
+
+
+/*  The recent times taken by public requests, and the percentile of them
+ *  after which a request is hedged. */
+
+pub(crate)  struct  Hedger  {  percentile:  f64,
+                               latencies:   Mutex<VecDeque<Duration>>  }
+
+
+
+impl  Hedger
+{
+    pub(crate)  fn  new  (percentile:  f64)  ->  Self
+          {   Hedger {  percentile:  percentile.clamp (0.0, 100.0),
+                        latencies:   Mutex::new (VecDeque::new ())  }   }
+
+
+    /*  How long to wait before sending a second request, if we have seen
+     *  enough requests to say. */
+    fn  delay  (&self)  ->  Option<Duration>
+    {
+        let  L  =  self.latencies.lock ().unwrap ();
+        if  L.len () < MIN_SAMPLES   {   return  None;   }
+
+        let  mut  S  =  L.iter ().copied ().collect::<Vec<_>> ();
+        S.sort ();
+        let  i  =  ((S.len () - 1) as f64 * self.percentile / 100.0).round ();
+        Some (S [i as usize])
+    }
+
+
+    fn  record  (&self,  latency:  Duration)
+    {
+        let  mut  L  =  self.latencies.lock ().unwrap ();
+        if  L.len () == WINDOW   {   L.pop_front ();   }
+        L.push_back (latency);
+    }
+}
+
+
+
+/*  One copy of the request in flight, and the body of its reply so far. */
+
+type  In_Flight  =  (EasyHandle, Arc<Mutex<Vec<u8>>>);
+
+
+
+fn  launch  (K:  &Kraken_API,  M:  &Multi,  url:  &str)
+       ->  Result<In_Flight, String>
+{
+    let  mut  C  =  curl::easy::Easy::new ();
+    C.url (url).map_err (|e| e.to_string ()) ?;
+    if let Some (T) = K.request_timeout
+        {   C.timeout (T).map_err (|e| e.to_string ()) ?;   }
+    K.transport.apply (&mut C) ?;
+
+    /*  A reply which grows too big is cut off by answering curl with
+     *  nothing written, which perform below reports as such. */
+    let  body  =  Arc::new (Mutex::new (Vec::new ()));
+    let  b  =  body.clone ();
+    let  transport  =  K.transport.clone ();
+    C.write_function (move |data|  {  let  mut  B  =  b.lock ().unwrap ();
+                                      let  size  =  B.len () + data.len ();
+                                      if  transport.admit (size).is_err ()
+                                          {   return  Ok (0);   }
+                                      B.extend_from_slice (data);
+                                      Ok (data.len ())  })
+     .map_err (|e| e.to_string ()) ?;
+
+    Ok ((M.add (C).map_err (|e| e.to_string ()) ?,  body))
+}
+
+
+
+/*  GET the url, sending it a second time if the first has not been
+ *  answered by the time given by the hedger, and give the body of the first
+ *  reply to come back.  Only a failure of the last copy in flight is
+ *  reported as a failure. */
+
+pub(crate)  fn  perform  (K:  &Kraken_API,  H:  &Hedger,  url:  &str)
+               ->  Result<String, String>
+{
+    let  cancelled  =  || K.cancel_token.as_ref ()
+                                   .is_some_and (Cancel_Token::is_cancelled);
+    if  cancelled ()   {   Err ("request cancelled".to_string ()) ?   }
+
+    let  start  =  Instant::now ();
+    let  delay  =  H.delay ();
+    let  M  =  Multi::new ();
+    let  mut  copies  =  vec! [launch (K, &M, url) ?];
+    let  mut  hedged  =  false;
+
+    let  finish  =  |M: &Multi, copies: Vec<In_Flight>|
+                      for  (h, _)  in  copies   {   let _ = M.remove (h);   };
+
+    loop
+    {
+        M.perform ().map_err (|e| e.to_string ()) ?;
+
+        let  mut  done  =  Vec::new ();
+        M.messages (|m|  for  (i, (h, _))  in  copies.iter ().enumerate ()
+                            {   if let Some (r) = m.result_for (h)
+                                    {   done.push ((i, r));   }   });
+
+        let  mut  failure  =  None;
+        for  (i, r)  in  done.into_iter ().rev ()
+        {
+            let  (h, body)  =  copies.remove (i);
+            let  _  =  M.remove (h);
+            match  r
+            {
+                Ok (())  =>  {
+                    finish (&M, copies);
+                    H.record (start.elapsed ());
+                    K.latencies.record (&K.query_url, start.elapsed ());
+                    let  body  =  std::mem::take (&mut *body.lock ().unwrap ());
+                    return  String::from_utf8 (body)
+                               .map_err (|_| "reply from exchange is not text"
+                                                 .to_string ());
+                },
+                Err (E)  if  E.is_write_error ()
+                    =>  failure  =  K.transport.max_reply
+                                      .map (|limit|  Reply_Too_Large {  limit  }
+                                                         .to_string ()),
+                Err (E)  =>  failure  =  Some (K.transport.failure (&E))
+            }
+        }
+
+        if  copies.is_empty ()
+            {   Err (failure.unwrap_or ("request failed".to_string ())) ?   }
+
+        if  ! hedged  &&  delay.is_some_and (|D| start.elapsed () >= D)
+        {
+            copies.push (launch (K, &M, url) ?);
+            hedged  =  true;
+        }
+
+        if  cancelled ()
+            {   finish (&M, copies);
+                return  Err ("request cancelled".to_string ());   }
+
+        M.wait (&mut [], Duration::from_millis (5))
+         .map_err (|e| e.to_string ()) ?;
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+  {  #[test]  fn  delay ()
+     {
+         use  std::time::Duration;
+
+         let  H  =  super::Hedger::new (90.0);
+         for  n  in  1 ..= 19   {   H.record (Duration::from_millis (n));   }
+         assert_eq! (H.delay (), None);
+
+         for  n  in  20 ..= 210   {   H.record (Duration::from_millis (n));   }
+         assert_eq! (H.delay (), Some (Duration::from_millis (190)));
+     }  }
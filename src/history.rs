@@ -0,0 +1,145 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option, Kraken_API};
+use  crate::reply;
+use  serde_json  as  JSN;
+use  std::collections::BTreeMap;
+
+
+
+/*  The most IDs the QueryTrades and QueryLedgers end-points will take at
+ *  once. */
+
+const  QUERY_TRADES_LIMIT:   usize  =  20;
+const  QUERY_LEDGERS_LIMIT:  usize  =  20;
+
+
+
+/*  Call an end-point which takes a comma-separated list of IDs with `limit`
+ *  of them at a time until all the `ids` have been asked for, merging the
+ *  'result' sections of the replies into one map. */
+
+pub(crate)  fn  query_chunked  (K:  &mut Kraken_API,
+                                ids:  &[&str],
+                                limit:  usize,
+                                call:  fn (&mut Kraken_API, String)
+                                          ->  Result<String, String>)
+               ->  Result<BTreeMap<String, JSN::Value>, String>
+{
+    let  mut  M  =  BTreeMap::new ();
+    for  chunk  in  ids.chunks (limit)
+    {
+        let  R  =  reply::result_of (&call (K, chunk.join (",")) ?) ?;
+        if let JSN::Value::Object (R) = R   {   M.extend (R);   }
+    }
+    Ok (M)
+}
+
+
+
+impl  Kraken_API
+{
+/** Get the details of any number of trades, by transaction ID, keyed by ID.
+
+    The [Kraken_API::trades_info] end-point only takes 20 IDs at a time, so
+    the list is split up and the end-point called as many times as needed, one
+    call after another.  Any [API_Option::TRADES] setting is respected.  */
+
+  pub  fn  trades_info_all  (&mut self,  txids:  &[&str])
+             ->  Result<BTreeMap<String, JSN::Value>, String>
+    {
+      query_chunked (self, txids, QUERY_TRADES_LIMIT, Kraken_API::trades_info)
+    }
+
+
+
+/** Get any number of ledger entries, by ledger ID, keyed by ID.
+
+    The [Kraken_API::query_ledgers] end-point only takes 20 IDs at a time, so
+    the list is split up and the end-point called as many times as needed, one
+    call after another.  This replaces any [API_Option::ID] setting, and
+    respects [API_Option::TRADES].  */
+
+  pub  fn  query_ledgers_all  (&mut self,  ids:  &[&str])
+             ->  Result<BTreeMap<String, JSN::Value>, String>
+    {
+      query_chunked (self, ids, QUERY_LEDGERS_LIMIT,
+                     |K, ids|  {  K.set_opt (API_Option::ID, ids);
+                                  K.query_ledgers ()  })
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    use  crate::Kraken_API;
+    use  std::cell::RefCell;
+
+    thread_local! {
+        /*  The number of IDs in each call made of the stub. */
+        static  CALLS:  RefCell<Vec<usize>>
+                          =  const {  RefCell::new (Vec::new ())  };
+    }
+
+    /*  Stands in for an end-point, answering every ID it is asked for. */
+    fn  fetch  (_K:  &mut Kraken_API,  ids:  String)  ->  Result<String, String>
+    {
+        let  ids : Vec<&str>  =  ids.split (',').collect ();
+        CALLS.with (|C| C.borrow_mut ().push (ids.len ()));
+        Ok (format! (r#"{{"error":[],"result":{{{}}}}}"#,
+                     ids.iter ()
+                        .map (|I| format! (r#""{}":{{}}"#, I))
+                        .collect::<Vec<_>> ()
+                        .join (",")))
+    }
+
+    #[test]
+    fn  query_chunked  ()  ->  Result<(), String>
+    {
+        let  mut  K  =  Kraken_API::default ();
+
+        for  (n, calls)  in  [(0, vec! []),
+                              (20, vec! [20]),
+                              (41, vec! [20, 20, 1])].iter ()
+        {
+            CALLS.with (|C| C.borrow_mut ().clear ());
+            let  ids : Vec<String>  =  (0..*n).map (|i| format! ("T{}", i))
+                                              .collect ();
+            let  ids : Vec<&str>  =  ids.iter ().map (|I| I.as_str ())
+                                                .collect ();
+
+            let  M  =  super::query_chunked (&mut K, &ids, 20, fetch) ?;
+            assert_eq! (M.len (),  *n);
+            assert! (ids.iter ().all (|I| M.contains_key (*I)));
+            assert_eq! (CALLS.with (|C| C.borrow ().clone ()),  *calls);
+        }
+
+        //  A refusal of any chunk is the refusal of the lot.
+        let  refuse  =  |_: &mut Kraken_API, _|
+                              Ok (r#"{"error":["EQuery:Unknown"]}"#.into ());
+        assert_eq! (super::query_chunked (&mut K, &["T0"], 20, refuse),
+                    Err ("EQuery:Unknown".to_string ()));
+        Ok (())
+    }
+}
@@ -0,0 +1,222 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option as Opt, Cancel_Token, Instruction, Kraken_API,
+             Order_Type, Pair_Info};
+use  crate::reply;
+use  std::time::Duration;
+
+
+
+/** Works a large limit order by keeping only a small slice ('clip') of it
+    resting in the book at any one time, and placing the next slice as each
+    one is filled.
+
+    This is done entirely at our end, by polling the exchange for the state of
+    the resting clip, so it works for any pair and order size, irrespective of
+    the exchange's own support for iceberg orders; the price of that is that
+    there is a gap of up to one polling interval between one clip filling and
+    the next appearing.  It polls, rather than waiting on fills, because the
+    crate holds no connection of its own on which fills could arrive: the
+    `openOrders` feed (see [Subscriptions](crate::Subscriptions)) is only there
+    if the application keeps a WebSocket open, and the one `QueryOrders` call
+    per interval is cheap.
+
+    ```ignore
+    let  mut  I  =  KKN::Iceberg::new ("XBTUSD", KKN::Instruction::SELL,
+                                       2.0, 0.1, 31000.0);
+    I.run (&mut K, Duration::from_secs (5), &KKN::Cancel_Token::new ()) ?;
+    ```  */
+
+pub  struct  Iceberg  {  pair:       String,
+                         side:       Instruction,
+                         total:      f64,
+                         clip:       f64,
+                         price:      f64,
+                         userref:    Option<i32>,
+                         filled:     f64,
+                         resting:    Option<String>,
+                         info:       Option<Pair_Info>,
+                         done:       bool  }
+
+
+
+impl  Iceberg
+{
+    /** Prepare to `side` (buy or sell) a `total` volume of `pair` at limit
+        `price`, showing no more than `clip` of it at once.  Nothing is sent to
+        the exchange until the first [Iceberg::step]. */
+    pub  fn  new  (pair: &str,  side: Instruction,
+                   total: f64,  clip: f64,  price: f64)  ->  Self
+          {   Iceberg {  pair: pair.to_string (),  side,  total,  clip,  price,
+                         userref: None,  filled: 0.0,  resting: None,
+                         info: None,  done: false  }   }
+
+    /** Tag every clip with this user reference, so that they can be found
+        again (and cancelled together) if our program goes away. */
+    pub  fn  with_userref  (mut self,  userref: i32)  ->  Self
+          {   self.userref  =  Some (userref);   self   }
+
+    /** The volume filled so far, not counting any part-fill of the clip
+        currently resting. */
+    pub  fn  filled  (&self)  ->  f64   {   self.filled   }
+
+    /** The transaction ID of the clip currently in the book, if any. */
+    pub  fn  resting_txid  (&self)  ->  Option<&str>
+          {   self.resting.as_deref ()   }
+
+    /** Whether the whole order has been worked (or as much of it as the
+        exchange's minimum order size allows). */
+    pub  fn  is_done  (&self)  ->  bool   {   self.done   }
+
+
+    /** Look at the state of the resting clip, and place the next one if it has
+        gone.  Returns whether the whole order is now done.
+
+        If the clip disappears from the book other than by being filled (it is
+        cancelled by hand, say), the iceberg stops and an error is returned.  */
+    pub  fn  step  (&mut self,  K:  &mut Kraken_API)  ->  Result<bool, String>
+    {
+        if  self.done   {   return  Ok (true);   }
+
+        if  self.info.is_none ()
+            {   self.info  =  Some (K.pair_info (&self.pair) ?);   }
+
+        if let Some (txid) = self.resting.clone ()
+        {
+            K.clear_all_options ();
+            let  R  =  reply::result_of (&K.query_orders (txid.clone ()) ?) ?;
+            let  O  =  &R [&txid];
+            if  self.absorb (&txid,
+                             O ["status"].as_str ().unwrap_or (""),
+                             reply::number (&O ["vol_exec"]) ?) ?
+                {   return  Ok (false);   }
+        }
+
+        let  info  =  self.info.as_ref ().unwrap ();
+        let  volume  =  self.next_clip ();
+
+        if  info.check_volume (volume).is_err ()
+            {   self.done  =  true;   return  Ok (true);   }
+
+        K.clear_all_options ();
+        K.set_opt (Opt::PRICE, info.format_price (self.price));
+        if let Some (U) = self.userref   {   K.set_opt (Opt::USERREF, U);   }
+
+        let  R  =  reply::result_of (&K.add_order (Order_Type::LIMIT,
+                                                   self.side,
+                                                   info.format_volume (volume),
+                                                   &info.name) ?) ?;
+        self.resting  =  Some (R ["txid"] [0].as_str ()
+                                            .ok_or ("no txid in AddOrder reply")
+                                            ? .to_string ());
+
+        Ok (false)
+    }
+
+
+    /*  Take account of the resting clip `txid` being in `status` with
+     *  `executed` volume filled; returns whether it is still resting. */
+    fn  absorb  (&mut self,  txid:  &str,  status:  &str,  executed:  f64)
+            ->  Result<bool, String>
+    {
+        if  status == "pending"  ||  status == "open"
+            {   return  Ok (true);   }
+
+        self.filled  +=  executed;
+        self.resting  =  None;
+
+        if  status != "closed"
+        {
+            self.done  =  true;
+            return  Err (format! ("iceberg clip {} was {}", txid, status));
+        }
+
+        Ok (false)
+    }
+
+
+    /*  The volume of the next clip: a whole one, or whatever is left. */
+    fn  next_clip  (&self)  ->  f64
+          {   self.clip.min (self.total - self.filled)   }
+
+
+    /** Call [Iceberg::step] every `interval` until the order is done, an error
+        occurs, or `stop` is cancelled.  In the last case the resting clip is
+        left in the book; call [Iceberg::cancel] to remove it. */
+    pub  fn  run  (&mut self,  K:  &mut Kraken_API,
+                   interval:  Duration,  stop:  &Cancel_Token)
+              ->  Result<(), String>
+    {
+        while  ! stop.is_cancelled ()  &&  ! self.step (K) ?
+        {
+            stop.pause (interval);
+        }
+        Ok (())
+    }
+
+
+    /** Take the resting clip out of the book and stop working the order. */
+    pub  fn  cancel  (&mut self,  K:  &mut Kraken_API)  ->  Result<(), String>
+    {
+        self.done  =  true;
+        if let Some (txid) = self.resting.take ()
+            {   reply::result_of (&K.cancel_order (&txid) ?) ?;   }
+        Ok (())
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  clips  ()
+    {
+        use  super::Iceberg;
+        use  crate::Instruction;
+
+        let  mut  I  =  Iceberg::new ("XBTUSD", Instruction::SELL,
+                                      1.0, 0.4, 31000.0);
+        assert_eq! (I.next_clip (),  0.4);
+
+        I.resting  =  Some ("O1".into ());
+        assert_eq! (I.absorb ("O1", "open", 0.1),  Ok (true));
+        assert_eq! (I.filled (),  0.0);
+        assert_eq! (I.resting_txid (),  Some ("O1"));
+
+        assert_eq! (I.absorb ("O1", "closed", 0.4),  Ok (false));
+        assert_eq! (I.absorb ("O2", "closed", 0.4),  Ok (false));
+        assert_eq! (I.filled (),  0.8);
+        assert_eq! (I.resting_txid (),  None);
+        assert! ((I.next_clip () - 0.2).abs () < 1e-12);
+        assert! (! I.is_done ());
+
+        //  A part-filled clip which is then cancelled still counts.
+        I.resting  =  Some ("O3".into ());
+        assert_eq! (I.absorb ("O3", "canceled", 0.05),
+                    Err ("iceberg clip O3 was canceled".to_string ()));
+        assert! ((I.filled () - 0.85).abs () < 1e-12);
+        assert_eq! (I.resting_txid (),  None);
+        assert! (I.is_done ());
+    }
+}
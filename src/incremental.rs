@@ -0,0 +1,368 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  Replies parsed as they arrive, rather than gathered into one string and
+ *  then parsed into one tree: the transfer runs in a thread of its own,
+ *  passing pieces of the body down a short channel to the parser, which
+ *  hands each member of the 'result' section to the caller as soon as it is
+ *  complete and then forgets it. */
+
+
+
+use  crate::{Kraken_API, kraken_argument, private_request,
+             reply, retry, transfer, url_host};
+use  crate::warnings::{check_deprecation, is_warning};
+use  serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess,
+                 Visitor};
+use  serde_json  as  JSN;
+use  std::io::{BufReader, Read};
+use  std::sync::mpsc::{Receiver, sync_channel};
+
+
+
+/*  The end-points which are reached without signing. */
+
+const  PUBLIC:  &[&str]  =  &["Time", "SystemStatus", "Assets", "AssetPairs",
+                              "Ticker", "OHLC", "Depth", "Trades", "Spread"];
+
+
+
+/*  How many pieces of the body may be waiting for the parser before the
+ *  transfer is held up. */
+
+const  BACKLOG:  usize  =  16;
+
+
+
+/*  The body of the reply, read from the pieces sent down the channel. */
+
+struct  Pieces  {  channel:  Receiver<Vec<u8>>,
+                   piece:    Vec<u8>,
+                   at:       usize  }
+
+impl  Read  for  Pieces
+{
+    fn  read  (&mut self,  buffer:  &mut [u8])  ->  std::io::Result<usize>
+    {
+        while  self.at == self.piece.len ()
+        {
+            match  self.channel.recv ()
+               {   Ok (P)   =>  {  self.piece  =  P;   self.at  =  0;  }
+                   Err (_)  =>  return  Ok (0)   }
+        }
+
+        let  n  =  buffer.len ().min (self.piece.len () - self.at);
+        buffer [.. n].copy_from_slice (&self.piece [self.at .. self.at + n]);
+        self.at  +=  n;
+        Ok (n)
+    }
+}
+
+
+
+/*  The whole reply: the messages in the 'error' section are checked as soon
+ *  as they are read, and the 'result' section is given to Entries.  Our own
+ *  failures (and the caller's) are kept in `failure`, so that they come out
+ *  as they were rather than dressed up as parse errors. */
+
+struct  Whole<'a, F>  {  visit:     &'a mut F,
+                         warning:   &'a dyn Fn (&str),
+                         failure:   &'a mut Option<String>  }
+
+impl<'de, 'a, F>  Visitor<'de>  for  Whole<'a, F>
+        where  F:  FnMut (&str, JSN::Value) -> Result<(), String>
+{
+    type  Value  =  ();
+
+    fn  expecting  (&self,  f:  &mut std::fmt::Formatter)  ->  std::fmt::Result
+          {   f.write_str ("a reply from the exchange")   }
+
+    fn  visit_map<M: MapAccess<'de>>  (self,  mut  map:  M)
+            ->  Result<(), M::Error>
+    {
+        while let Some (key) = map.next_key::<String> () ?
+        {
+            match  key.as_str ()
+            {
+                "error"  =>  {
+                    let  E  =  map.next_value::<Vec<String>> () ?;
+                    for  W  in  E.iter ().filter (|e| is_warning (e))
+                        {   (self.warning) (W);   }
+                    let  checked  =  reply::check_errors
+                                         (E.iter ().map (String::as_str));
+                    if let Err (E) = checked
+                    {
+                        *self.failure  =  Some (E);
+                        Err (de::Error::custom ("error from exchange")) ?
+                    }
+                },
+                "result"  =>  map.next_value_seed
+                                   (Entries {  visit:    &mut *self.visit,
+                                               failure:  &mut *self.failure  })
+                                 ?,
+                _  =>  {  map.next_value::<IgnoredAny> () ?;  }
+            }
+        }
+        Ok (())
+    }
+}
+
+
+
+/*  The 'result' section, each member of which is passed to the caller with
+ *  its name, or, if the section is a list, its position. */
+
+struct  Entries<'a, F>  {  visit:    &'a mut F,
+                           failure:  &'a mut Option<String>  }
+
+impl<'a, F>  Entries<'a, F>
+        where  F:  FnMut (&str, JSN::Value) -> Result<(), String>
+{
+    fn  give<E: de::Error>  (&mut self,  key:  &str,  value:  JSN::Value)
+            ->  Result<(), E>
+    {
+        (self.visit) (key, value)
+            .map_err (|E|  {  *self.failure  =  Some (E);
+                              de::Error::custom ("stopped by caller")  })
+    }
+}
+
+impl<'de, 'a, F>  DeserializeSeed<'de>  for  Entries<'a, F>
+        where  F:  FnMut (&str, JSN::Value) -> Result<(), String>
+{
+    type  Value  =  ();
+
+    fn  deserialize<D: de::Deserializer<'de>>  (self,  D:  D)
+            ->  Result<(), D::Error>
+          {   D.deserialize_any (self)   }
+}
+
+impl<'de, 'a, F>  Visitor<'de>  for  Entries<'a, F>
+        where  F:  FnMut (&str, JSN::Value) -> Result<(), String>
+{
+    type  Value  =  ();
+
+    fn  expecting  (&self,  f:  &mut std::fmt::Formatter)  ->  std::fmt::Result
+          {   f.write_str ("an object or list of results")   }
+
+    fn  visit_map<M: MapAccess<'de>>  (mut self,  mut  map:  M)
+            ->  Result<(), M::Error>
+    {
+        while let Some (key) = map.next_key::<String> () ?
+        {
+            let  value  =  map.next_value::<JSN::Value> () ?;
+            self.give (&key, value) ?;
+        }
+        Ok (())
+    }
+
+    fn  visit_seq<S: SeqAccess<'de>>  (mut self,  mut  list:  S)
+            ->  Result<(), S::Error>
+    {
+        let  mut  n  =  0;
+        while let Some (value) = list.next_element::<JSN::Value> () ?
+        {
+            self.give (&n.to_string (), value) ?;
+            n  +=  1;
+        }
+        Ok (())
+    }
+}
+
+
+
+impl  Kraken_API
+{
+/** Make an enquiry of the `end_point` (named as in the Kraken documentation,
+    e.g. "AssetPairs"), with every option which has been set on this object,
+    and call `visit` with the name and value of each member of the 'result'
+    section of the reply (or, if the section is a list, with the position and
+    value of each element) as soon as it has arrived.
+
+    Neither the whole reply nor the whole of the parsed result is ever held
+    in memory, as they are by the ordinary end-point methods; this is for
+    replies which can run to many megabytes, such as a deep order book or the
+    full list of asset pairs, on machines with little memory to spare.  Note
+    that each member is still parsed whole: [Kraken_API::asset_pairs] gives
+    one member per pair, but [Kraken_API::order_book] only one for the whole
+    book.
+
+    The 'error' section is checked as it is read, as
+    [Kraken_API::on_warning] and the `_json` methods would check it.  If
+    `visit` fails, the transfer is abandoned and its error returned.  Only
+    enquiries can be made this way, and the reply is not cached, pooled,
+    hedged or retried.
+
+    ```ignore
+    let  mut  names  =  Vec::new ();
+    K.stream_result ("AssetPairs",
+                     |pair, V|  {  names.push ((pair.to_string (),
+                                                V ["wsname"].to_string ()));
+                                   Ok (())  }) ?;
+    ```  */
+
+  pub  fn  stream_result  (&mut self,  end_point:  &str,
+                           mut  visit:  impl FnMut (&str, JSN::Value)
+                                                -> Result<(), String>)
+               ->  Result<(), String>
+    {
+      let  mut  arguments  =  self.all_options ()
+                                  .map (|(O, V)|  format! ("{}={}",
+                                                           kraken_argument (O),
+                                                           V))
+                                  .collect::<Vec<_>> ();
+      arguments.sort ();
+      self.query_url  =  if  arguments.is_empty ()  {  end_point.to_string ()  }
+                         else  {  end_point.to_string () + "?"
+                                      + &arguments.join ("&")  };
+
+      if  ! retry::idempotent (&self.query_url)
+          {   Err (format! ("{} changes the account, and cannot be streamed",
+                            end_point)) ?   }
+
+      check_deprecation (self.warning.as_ref (), end_point);
+      let  public  =  PUBLIC.contains (&end_point);
+      let  C  =  if  public
+                 {   let  mut  C  =  curl::easy::Easy::new ();
+                     C.url (&format! ("{}/{}/public/{}", url_host,
+                                      self.version (), self.query_url))
+                      .map_err (|E| E.to_string ()) ?;
+                     C   }
+                 else   {   private_request (self) ?   };
+
+      let  (sender, channel)  =  sync_channel::<Vec<u8>> (BACKLOG);
+      let  sink  =  move |data: &[u8]|
+                         sender.send (data.to_vec ())
+                               .map_err (|_| "reply no longer wanted"
+                                                 .to_string ());
+
+      let  K  =  &*self;
+      let  warning  =  |W: &str|  if let Some (f) = &K.warning
+                                      {   f (end_point, W);   };
+      let  mut  failure  =  None;
+
+      let  (sent, parsed)  =  std::thread::scope (|S|  {
+          let  sending  =  S.spawn (|| transfer (K, C, sink));
+
+          let  parsed
+              =  de::Deserializer::deserialize_map
+                     (&mut JSN::Deserializer::from_reader
+                               (BufReader::new (Pieces {  channel,
+                                                          piece:  Vec::new (),
+                                                          at:     0  })),
+                      Whole {  visit:    &mut visit,
+                               warning:  &warning,
+                               failure:  &mut failure  });
+
+          (sending.join ().unwrap_or (Err ("transfer failed".to_string ())),
+           parsed)
+      });
+
+      if  ! public
+      {
+          self.rate_limit.observe (failure.as_deref ().unwrap_or (""));
+      }
+
+      if let Some (E) = failure   {   Err (E) ?   }
+      sent ?;
+      parsed.map_err (|E| format! ("reply from exchange: {}", E))
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  entries  ()
+    {
+        use  super::{Pieces, Whole};
+        use  std::io::BufReader;
+        use  std::sync::mpsc::sync_channel;
+
+        let  run  =  |reply: &[&str]|  {
+            let  (sender, channel)  =  sync_channel (8);
+            for  P  in  reply
+                {   sender.send (P.as_bytes ().to_vec ()).unwrap ();   }
+            drop (sender);
+
+            let  mut  seen  =  Vec::new ();
+            let  warned  =  std::cell::RefCell::new (Vec::new ());
+            let  mut  failure  =  None;
+            let  mut  visit  =  |K: &str, V: serde_json::Value|
+                                  {   if  K == "stop"
+                                          {   Err ("stopped".to_string ()) ?   }
+                                      seen.push (format! ("{}={}", K, V));
+                                      Ok (())   };
+            let  warning  =  |W: &str|  warned.borrow_mut ()
+                                              .push (W.to_string ());
+            let  parsed  =  serde::de::Deserializer::deserialize_map
+                               (&mut serde_json::Deserializer::from_reader
+                                         (BufReader::new
+                                              (Pieces {  channel,
+                                                         piece:  Vec::new (),
+                                                         at:  0  })),
+                                Whole {  visit:    &mut visit,
+                                         warning:  &warning,
+                                         failure:  &mut failure  });
+            (seen, failure, warned.into_inner (), parsed.is_ok ())
+        };
+
+        let  (seen, failure, warned, ok)
+             =  run (&["{\"error\":[\"WGeneral:Deprecated\"],\"result\":{\"XBT",
+                       "USD\":{\"lot\":\"unit\"},\"ETHUSD\":[1,2]}}"]);
+        assert_eq! (seen,  ["XBTUSD={\"lot\":\"unit\"}", "ETHUSD=[1,2]"]);
+        assert_eq! ((failure, warned, ok),
+                    (None, vec! ["WGeneral:Deprecated".to_string ()], true));
+
+        let  (seen, failure, _, ok)
+             =  run (&["{\"error\":[\"EGeneral:Invalid arguments\"]}"]);
+        assert! (seen.is_empty ()  &&  ! ok);
+        assert_eq! (failure.as_deref (),  Some ("EGeneral:Invalid arguments"));
+
+        let  (seen, failure, _, _)
+             =  run (&["{\"result\":[\"a\",\"b\"]}"]);
+        assert_eq! ((seen, failure),
+                    (vec! ["0=\"a\"".to_string (), "1=\"b\"".to_string ()],
+                     None));
+
+        let  (seen, failure, _, _)
+             =  run (&["{\"result\":{\"one\":1,\"stop\":2,\"three\":3}}"]);
+        assert_eq! ((seen, failure),
+                    (vec! ["one=1".to_string ()],
+                     Some ("stopped".to_string ())));
+    }
+
+
+    #[test]
+    fn  refused  ()
+    {
+        use  crate::{API_Option, Kraken_API};
+
+        let  mut  K  =  Kraken_API::default ();
+        K.set_opt (API_Option::ASSET, "XBT");
+        K.set_opt (API_Option::NEW, true);
+        assert_eq! (K.stream_result ("DepositAddresses", |_, _| Ok (())),
+                    Err ("DepositAddresses changes the account, and cannot \
+                          be streamed".to_string ()));
+    }
+}
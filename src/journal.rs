@@ -0,0 +1,420 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  A log of the account's completed orders, one JSON object to a line, put
+ *  together from the orders, their trades and the ledger entries the trades
+ *  made. */
+
+
+
+use  crate::{API_Option, Fill_Summary, Instruction, Kraken_API, Ledger_Entry,
+             Ledger_Type, Order, Own_Trade, Userref};
+use  crate::reply;
+use  serde_json  as  JSN;
+use  std::collections::{BTreeMap, BTreeSet};
+use  std::io::Write;
+use  std::path::{Path, PathBuf};
+
+
+
+/** What a [Trade_Journal] records of one completed order. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Journal_Entry
+{
+    /** The order's transaction ID. */
+    pub  order_txid:     String,
+
+    /** The pair, as the exchange describes it (e.g. "XBTUSD"). */
+    pub  pair:           String,
+
+    /** Whether the order bought or sold. */
+    pub  side:           Instruction,
+
+    /** The order type, e.g. "limit". */
+    pub  order_type:     String,
+
+    /** The state the order finished in, e.g. "closed" or "canceled". */
+    pub  status:         String,
+
+    /** The user reference given to the order, if any. */
+    pub  userref:        Option<i64>,
+
+    /** The strategy which placed the order, from the namespace of its user
+        reference (see [Userref]): the name given to
+        [Trade_Journal::name_strategy], or else the namespace's number.
+        `None` if the order has no user reference of ours. */
+    pub  strategy:       Option<String>,
+
+    /** UNIX time at which the order was placed. */
+    pub  opened:         f64,
+
+    /** UNIX time of the first trade; the time the order was placed if no
+        trades were found. */
+    pub  first_fill:     f64,
+
+    /** UNIX time of the last trade; likewise. */
+    pub  last_fill:      f64,
+
+    /** The volume ordered. */
+    pub  volume:         f64,
+
+    /** The volume executed. */
+    pub  executed:       f64,
+
+    /** The volume-weighted average price of the trades. */
+    pub  average_price:  f64,
+
+    /** The total cost of the trades, in the quote currency. */
+    pub  cost:           f64,
+
+    /** The total fee paid, in the quote currency. */
+    pub  fee:            f64,
+
+    /** The transaction IDs of the trades. */
+    pub  trades:         Vec<String>,
+
+    /** The IDs of the ledger entries the trades made. */
+    pub  ledgers:        Vec<String>
+}
+
+
+
+impl  Journal_Entry
+{
+    /** The entry as a JSON object, as it is written to the journal. */
+    pub  fn  to_json  (&self)  ->  JSN::Value
+    {
+        JSN::json! ({ "order_txid":     self.order_txid,
+                      "pair":           self.pair,
+                      "side":           match  self.side
+                                           {   Instruction::BUY   =>  "buy",
+                                               Instruction::SELL  =>  "sell" },
+                      "order_type":     self.order_type,
+                      "status":         self.status,
+                      "userref":        self.userref,
+                      "strategy":       self.strategy,
+                      "opened":         self.opened,
+                      "first_fill":     self.first_fill,
+                      "last_fill":      self.last_fill,
+                      "volume":         self.volume,
+                      "executed":       self.executed,
+                      "average_price":  self.average_price,
+                      "cost":           self.cost,
+                      "fee":            self.fee,
+                      "trades":         self.trades,
+                      "ledgers":        self.ledgers  })
+    }
+}
+
+
+
+/** An automatic log of the account's trading: an entry (a [Journal_Entry])
+    for each completed order, appended as a line of JSON to a file, so that
+    the file can be read back by anything from a spreadsheet import to a
+    notebook.  An order is only ever recorded once, even across runs of the
+    program, as the orders already in the file are noted when it is opened.
+
+    ```ignore
+    let  mut  J  =  KKN::Trade_Journal::open ("trades.jsonl") ?;
+    J.name_strategy (3, "grid");
+    loop
+    {
+        J.record_closed (&mut K) ?;
+        std::thread::sleep (Duration::from_secs (300));
+    }
+    ```  */
+
+pub  struct  Trade_Journal  {  path:        PathBuf,
+                               strategies:  BTreeMap<u8, String>,
+                               recorded:    BTreeSet<String>  }
+
+
+
+impl  Trade_Journal
+{
+    /** A journal kept in the file at `path`, which is made when the first
+        entry is written if it does not yet exist. */
+    pub  fn  open  (path:  impl AsRef<Path>)  ->  Result<Self, String>
+    {
+        let  path  =  path.as_ref ().to_path_buf ();
+        let  text  =  match  std::fs::read_to_string (&path)
+        {
+            Ok (T)  =>  T,
+            Err (E)  if  E.kind () == std::io::ErrorKind::NotFound
+                     =>  String::new (),
+            Err (E)  =>  Err (format! ("{}: {}", path.display (), E)) ?
+        };
+
+        let  recorded  =  text.lines ()
+                              .filter_map (|L| JSN::from_str::<JSN::Value> (L)
+                                                  .ok ())
+                              .filter_map (|V| V ["order_txid"].as_str ()
+                                                   .map (str::to_string))
+                              .collect ();
+
+        Ok (Trade_Journal {  path,  strategies:  BTreeMap::new (),  recorded  })
+    }
+
+
+    /** Record orders whose user reference is in `namespace` as placed by
+        the strategy called `name`. */
+    pub  fn  name_strategy  (&mut self,  namespace:  u8,  name:  &str)
+               ->  &mut Self
+    {
+        self.strategies.insert (namespace, name.to_string ());
+        self
+    }
+
+
+    /** Whether the order with the transaction ID has been recorded. */
+    pub  fn  contains  (&self,  order_txid:  &str)  ->  bool
+          {   self.recorded.contains (order_txid)   }
+
+
+    /** Put together the entry for the `order` from those of the `trades`
+        which belong to it and those of the `ledger` entries which they made;
+        the others are passed over.  If there are no trades, the order's own
+        figures are used. */
+    pub  fn  entry  (&self,  order:  &Order,  trades:  &[Own_Trade],
+                     ledger:  &[Ledger_Entry])
+              ->  Journal_Entry
+    {
+        let  trades  =  trades.iter ().filter (|T| T.order_txid == order.txid)
+                              .cloned ().collect::<Vec<_>> ();
+        let  S  =  Fill_Summary::of (order, &trades);
+        let  times  =  trades.iter ().map (|T| T.time);
+        let  txids  =  trades.iter ().map (|T| T.txid.clone ())
+                             .collect::<Vec<_>> ();
+        let  (executed, cost, fee)
+                 =  if  trades.is_empty ()
+                        {   (order.vol_exec, order.cost, order.fee)   }
+                    else   {   (S.executed, S.cost, S.fees)   };
+
+        Journal_Entry {
+            order_txid:     order.txid.clone (),
+            pair:           order.pair.clone (),
+            side:           order.side,
+            order_type:     order.order_type.clone (),
+            status:         order.status.as_kraken_string ().to_string (),
+            userref:        order.userref,
+            strategy:       order.userref.and_then (Userref::namespace_of)
+                                 .map (|N|  self.strategies.get (&N).cloned ()
+                                                .unwrap_or (N.to_string ())),
+            opened:         order.open_time,
+            first_fill:     times.clone ().reduce (f64::min)
+                                 .unwrap_or (order.open_time),
+            last_fill:      times.reduce (f64::max)
+                                 .unwrap_or (order.open_time),
+            volume:         order.volume,
+            executed,
+            average_price:  if executed > 0.0 { cost / executed } else { 0.0 },
+            cost,
+            fee,
+            ledgers:        ledger.iter ()
+                                  .filter (|L| txids.contains (&L.refid))
+                                  .map (|L| L.id.clone ())
+                                  .collect (),
+            trades:         txids  }
+    }
+
+
+    /** Append the `entry` to the journal, unless its order is already
+        there; gives whether it was written. */
+    pub  fn  append  (&mut self,  entry:  &Journal_Entry)
+               ->  Result<bool, String>
+    {
+        if  self.contains (&entry.order_txid)   {   return  Ok (false);   }
+
+        std::fs::OpenOptions::new ().create (true).append (true)
+            .open (&self.path)
+            .and_then (|mut F| writeln! (F, "{}", entry.to_json ()))
+            .map_err (|E| format! ("{}: {}", self.path.display (), E)) ?;
+
+        self.recorded.insert (entry.order_txid.clone ());
+        Ok (true)
+    }
+
+
+    /*  Fetch the trades with the given IDs, and the ledger entries they
+     *  made, and record the order. */
+    fn  record_with  (&mut self,  K:  &mut Kraken_API,  order:  &Order,
+                      trade_ids:  &[&str])
+           ->  Result<bool, String>
+    {
+        let  trades  =  K.trades_info_all (trade_ids) ?
+                          .iter ()
+                          .map (|(txid, V)| Own_Trade::from_json (txid, V))
+                          .collect::<Result<Vec<_>, _>> () ?;
+
+        let  mut  ledger  =  Vec::new ();
+        if  let  (Some (first), Some (last))
+                  =  (trades.iter ().map (|T| T.time).reduce (f64::min),
+                      trades.iter ().map (|T| T.time).reduce (f64::max))
+        {
+            K.set_opt (API_Option::TYPE,
+                       Ledger_Type::TRADE.as_kraken_string ());
+            K.set_opt (API_Option::START, first - 1.0);
+            K.set_opt (API_Option::END, last + 1.0);
+            let  entries  =  K.all_ledger_entries ();
+            for  O  in  [API_Option::TYPE, API_Option::START, API_Option::END,
+                         API_Option::OFS]
+                {   K.clear_opt (O);   }
+            ledger  =  entries ?;
+        }
+
+        let  entry  =  self.entry (order, &trades, &ledger);
+        self.append (&entry)
+    }
+
+
+    /** Record the `order`, if it is finished with, some of it was executed,
+        and it is not already in the journal; gives whether it was written.
+        The order's trades and the ledger entries they made are fetched from
+        the exchange, with the [Kraken_API::query_orders],
+        [Kraken_API::trades_info] and [Kraken_API::ledgers_info]
+        end-points, the ledger 50 entries at a time until all are had; this
+        replaces any [API_Option::TRADES], [API_Option::TYPE],
+        [API_Option::START], [API_Option::END] and [API_Option::OFS]
+        settings. */
+    pub  fn  record  (&mut self,  K:  &mut Kraken_API,  order:  &Order)
+               ->  Result<bool, String>
+    {
+        if  ! order.is_terminal ()  ||  order.vol_exec <= 0.0
+              ||  self.contains (&order.txid)
+            {   return  Ok (false);   }
+
+        K.set_opt (API_Option::TRADES, true);
+        let  R  =  K.query_orders (order.txid.clone ());
+        K.clear_opt (API_Option::TRADES);
+        let  R  =  reply::result_of (&R ?) ?;
+        let  ids  =  trade_ids (&R [&order.txid]);
+        self.record_with (K, order, &ids)
+    }
+
+
+    /** Record every order among the 50 most recently closed which some of
+        was executed and which is not already in the journal, oldest first;
+        gives how many were written.  Called every so often, this keeps the
+        journal up to date with the account's trading.  It calls the same
+        end-points as [Trade_Journal::record], as well as
+        [Kraken_API::closed_orders], and replaces the same settings. */
+    pub  fn  record_closed  (&mut self,  K:  &mut Kraken_API)
+               ->  Result<usize, String>
+    {
+        K.set_opt (API_Option::TRADES, true);
+        K.clear_opt (API_Option::OFS);
+        let  R  =  K.closed_orders ();
+        K.clear_opt (API_Option::TRADES);
+        let  R  =  reply::result_of (&R ?) ?;
+
+        let  mut  orders  =  R ["closed"].as_object ().into_iter ().flatten ()
+                                .map (|(txid, V)|  Ok ((Order::from_json (txid,
+                                                                          V) ?,
+                                                        trade_ids (V))))
+                                .collect::<Result<Vec<_>, String>> () ?;
+        orders.sort_by (|(A, _), (B, _)|  A.open_time.total_cmp (&B.open_time));
+
+        let  mut  written  =  0;
+        for  (O, ids)  in  &orders
+        {
+            if  O.vol_exec > 0.0  &&  ! self.contains (&O.txid)
+                  &&  self.record_with (K, O, ids) ?
+                {   written  +=  1;   }
+        }
+        Ok (written)
+    }
+}
+
+
+
+/*  The trade IDs listed in an order entry fetched with the TRADES option. */
+
+fn  trade_ids  (V:  &JSN::Value)  ->  Vec<&str>
+{
+    V ["trades"].as_array ().into_iter ().flatten ()
+                .filter_map (|T| T.as_str ())
+                .collect ()
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  journal  ()  ->  Result<(), String>
+    {
+        use  super::Trade_Journal;
+        use  crate::{Instruction::BUY, Ledger_Entry, Ledger_Type::TRADE, Order,
+                     Userref};
+        use  crate::test_fixtures::{ledger_entry, own_trade};
+        use  serde_json::json;
+
+        let  userref  =  Userref::with_namespace (3).next ();
+        let  order  =  Order::from_json ("OQCLML-BW3P3-BUCMWZ", &json! ({
+                           "status": "closed", "opentm": 1688665496.0,
+                           "userref": userref,
+                           "descr": {"pair": "XBTUSD", "type": "buy",
+                                     "ordertype": "limit", "price": "30000"},
+                           "vol": "1.0", "vol_exec": "1.0",
+                           "cost": "30000", "fee": "78", "price": "30000"})) ?;
+        let  trades  =  [own_trade ("TB", "OQCLML-BW3P3-BUCMWZ", 1688665600.0,
+                                    BUY, 30000.0, 0.4),
+                         own_trade ("TA", "OQCLML-BW3P3-BUCMWZ", 1688665500.0,
+                                    BUY, 30000.0, 0.6)];
+        let  ledger  =  [Ledger_Entry {  id:  "LA".into (),
+                                         ..ledger_entry ("TA", 1688665500.0,
+                                                         TRADE, "XXBT",
+                                                         0.6, 0.6)  },
+                         ledger_entry ("TX", 1688665500.0, TRADE, "XXBT",
+                                       0.1, 0.7)];
+
+        let  path  =  std::env::temp_dir ()
+                          .join (format! ("kraken-journal-{}.jsonl",
+                                          std::process::id ()));
+        let  _  =  std::fs::remove_file (&path);
+
+        let  mut  J  =  Trade_Journal::open (&path) ?;
+        J.name_strategy (3, "grid");
+        let  E  =  J.entry (&order, &trades, &ledger);
+        assert_eq! (E.strategy.as_deref (),  Some ("grid"));
+        assert_eq! ((E.first_fill, E.last_fill),
+                    (1688665500.0, 1688665600.0));
+        assert_eq! ((E.executed, E.average_price, E.fee),
+                    (1.0, 30000.0, 30.0));
+        assert_eq! (E.trades,  ["TB", "TA"]);
+        assert_eq! (E.ledgers,  ["LA"]);
+
+        assert! (J.append (&E) ?);
+        assert! (! J.append (&E) ?);
+        let  J  =  Trade_Journal::open (&path) ?;
+        assert! (J.contains ("OQCLML-BW3P3-BUCMWZ"));
+        assert_eq! (J.entry (&order, &[], &[]).strategy.as_deref (),
+                    Some ("3"));
+
+        let  text  =  std::fs::read_to_string (&path).unwrap ();
+        std::fs::remove_file (&path).unwrap ();
+        assert_eq! (text.lines ().count (),  1);
+        assert! (text.contains (r#""side":"buy""#));
+        Ok (())
+    }
+}
@@ -0,0 +1,182 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  Variants of the end-point methods which hand back the 'result' section of
+ *  the exchange's reply as a parsed JSON value, having turned any entries in
+ *  the 'error' section into an Err.  They are all the same shape, so a macro
+ *  writes them. */
+
+
+
+use  crate::{Instruction, Kraken_API, Order_Type, Report_Type, Batch_Order};
+use  crate::reply;
+use  serde_json  as  JSN;
+
+
+
+macro_rules!  json_variants
+{
+    ( $( $name:ident  =>  $base:ident  ( $( $arg:ident : $type:ty ),* ) ; )* )
+    =>
+    {
+        impl  Kraken_API
+        {
+            $(
+                #[doc = concat! ("As [Kraken_API::", stringify! ($base),
+                                 "], but gives the 'result' section of the \
+                                  reply as a [serde_json::Value], or the \
+                                  exchange's error messages as an `Err`.")]
+                pub  fn  $name  (&mut self,  $( $arg: $type ),*)
+                     ->  Result<JSN::Value, String>
+                      {   reply::result_of (&self.$base ($( $arg ),*) ?)   }
+            )*
+        }
+    };
+}
+
+
+
+json_variants!
+{
+    account_balance_json        =>  account_balance ();
+    trade_balance_json          =>  trade_balance ();
+    open_orders_json            =>  open_orders ();
+    closed_orders_json          =>  closed_orders ();
+    query_orders_json           =>  query_orders (txid: String);
+    trades_history_json         =>  trades_history ();
+    trades_info_json            =>  trades_info (txid: String);
+    open_margin_positions_json  =>  open_margin_positions ();
+    ledgers_info_json           =>  ledgers_info ();
+    query_ledgers_json          =>  query_ledgers ();
+    trade_volume_json           =>  trade_volume (pair: &str);
+    request_export_report_json  =>  request_export_report
+                                        (report_type: Report_Type,
+                                         description: &str);
+    get_export_report_status_json
+                                =>  get_export_report_status
+                                        (report_type: Report_Type);
+    delete_export_report_json   =>  delete_export_report (id: &str,
+                                                          type_: &str);
+    add_order_batch_json        =>  add_order_batch (pair: &str,
+                                                     orders: &[Batch_Order]);
+    cancel_order_json           =>  cancel_order (txid: &str);
+    cancel_by_txid_json         =>  cancel_by_txid (txid: &str);
+    cancel_by_userref_json      =>  cancel_by_userref (userref: i32);
+    cancel_all_orders_json      =>  cancel_all_orders ();
+    cancel_all_orders_after_x_json
+                                =>  cancel_all_orders_after_x (timeout: isize);
+    withdraw_methods_json       =>  withdraw_methods (asset: &str);
+    withdraw_addresses_json     =>  withdraw_addresses (asset: &str);
+    withdrawal_info_json        =>  withdrawal_info (asset: &str,  key: &str,
+                                                     amount: &str);
+    withdraw_json               =>  withdraw (asset: &str,  key: &str,
+                                              amount: &str);
+    deposit_status_json         =>  deposit_status ();
+    withdraw_status_json        =>  withdraw_status ();
+    wallet_transfer_json        =>  wallet_transfer (asset: &str,  from: &str,
+                                                     to: &str,  amount: &str);
+    earn_strategies_json        =>  earn_strategies ();
+    websockets_token_json       =>  websockets_token ();
+    server_time_json            =>  server_time ();
+    system_status_json          =>  system_status ();
+    asset_info_json             =>  asset_info ();
+    asset_pairs_json            =>  asset_pairs ();
+    ticker_info_json            =>  ticker_info
+                                        (pair: impl Into<String>);
+    ohlc_data_json              =>  ohlc_data
+                                        (pair: impl Into<String>);
+    order_book_json             =>  order_book
+                                        (pair: impl Into<String>);
+    recent_trades_json          =>  recent_trades
+                                        (pair: impl Into<String>);
+    spread_data_json            =>  spread_data
+                                        (pair: impl Into<String>);
+}
+
+
+
+impl  Kraken_API
+{
+    /** As [Kraken_API::add_order], but gives the 'result' section of the
+        reply as a [serde_json::Value], or the exchange's error messages as an
+        `Err`. */
+    pub  fn  add_order_json<V: std::fmt::Display>  (&mut self,
+                                                   order_type: Order_Type,
+                                                   direction: Instruction,
+                                                   volume:  V,
+                                                   pair:  &str)
+               ->  Result<JSN::Value, String>
+    {
+        reply::result_of (&self.add_order (order_type, direction,
+                                           volume, pair) ?)
+    }
+
+
+    /** As [Kraken_API::edit_order], but gives the 'result' section of the
+        reply as a [serde_json::Value], or the exchange's error messages as an
+        `Err`. */
+    pub  fn  edit_order_json  (&mut self,  tx_id: &str,  pair: &str)
+               ->  Result<JSN::Value, String>
+          {   reply::result_of (&self.edit_order::<String> (tx_id, pair) ?)   }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    use  crate::{Cancel_Token, Kraken_API};
+    use  crate::reply;
+    use  serde_json  as  JSN;
+    use  serde_json::json;
+    use  std::time::Duration;
+
+    /*  An end-point which the exchange always refuses. */
+    impl  Kraken_API
+    {
+        fn  refused  (&mut self)  ->  Result<String, String>
+              {   Ok (r#"{"error":["EGeneral:Invalid arguments",
+                                   "EService:Unavailable"]}"#.into ())   }
+    }
+
+    json_variants!  {  refused_json  =>  refused ();  }
+
+    #[test]
+    fn  errors  ()
+    {
+        let  mut  K  =  Kraken_API::default ();
+        assert_eq! (K.refused_json (),
+                    Err ("EGeneral:Invalid arguments, EService:Unavailable"
+                             .to_string ()));
+
+        //  A good reply, from the cache so that it needs no network.
+        K.set_cache_lifetime ("Time", Some (Duration::from_secs (60)));
+        K.cache.put ("Time", r#"{"error":[],"result":{"unixtime":1}}"#);
+        assert_eq! (K.server_time_json (),  Ok (json! ({"unixtime": 1})));
+
+        //  Failure to get a reply at all is passed straight through.
+        let  stop  =  Cancel_Token::new ();
+        K.set_cancel_token (stop.clone ());
+        stop.cancel ();
+        assert_eq! (K.system_status_json (),
+                    Err ("request cancelled".to_string ()));
+    }
+}
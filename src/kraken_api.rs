@@ -21,7 +21,10 @@
 
 #![allow (non_snake_case,
           non_camel_case_types,
-          non_upper_case_globals)]
+          non_upper_case_globals,
+          clippy::empty_docs,
+          clippy::empty_line_after_doc_comments,
+          clippy::upper_case_acronyms)]
 #![warn (missing_docs)]
 
 
@@ -80,6 +83,39 @@
     Note that we made use of the `serde_json` crate to parse the response from
     the Kraken exchange, but this is absolutely not mandated by this library.
 
+    ##  Features
+
+    * `json` (on by default) brings in `serde_json`, and with it a handful of
+      higher-level helpers which interpret the exchange's replies for you, such
+      as [Kraken_API::health_check], and a `_json` variant of every end-point
+      method, e.g. [Kraken_API::ticker_info_json], which returns the 'result'
+      section of the reply as a `serde_json::Value` after checking the 'error'
+      section for you.  Turn the default features off if all you want is the
+      raw end-points.
+
+    * `http2` builds libcurl with HTTP/2 support, for systems whose own
+      libcurl lacks it; see [Kraken_API::set_http2].
+
+    * `parquet` (which implies `json`) adds `write_parquet`, to go with
+      [write_csv], for writing candles, trades, spreads and ledger entries to
+      Parquet files.
+
+    * `arrow` (which implies `json`) adds `record_batch`, which turns the same
+      collections into Arrow record batches, for polars and other analysis
+      libraries which take Arrow data.
+
+    * `ctrlc` adds `Shutdown_Guard::catch_ctrl_c`, so that an interrupted
+      program still cancels its orders on the way out.
+
+    * `openapi` (which implies `json`) adds `compare_with_openapi` and
+      `openapi_coverage`, which set the crate's [supported_end_points]
+      against the exchange's published OpenAPI description, to show what
+      the crate does not yet cover.
+
+    * `async` (which implies `json`) adds `Async_Stream`, and the
+      `ticker_stream_async`, `spread_stream_async` and `trade_stream_async`
+      methods which give the polling streams as `futures_core::Stream`s.
+
     ##  Limitations / To do
 
     * The user needs to be familiar with the Kraken documentation to be able to
@@ -90,22 +126,188 @@
       almost certainly required to handle this.
 
     * We have currently implemented all of the *Market Data*, *User Data* and
-      *User Trading* end-points.  The *User Funding* and *User Staking*
-      end-points are not yet implemented, nor is the *Websockets
-      Authentication* end-point.
+      *User Trading* end-points, the *Websockets Authentication* end-point,
+      the deposit methods, deposit addresses, withdrawal, withdrawal status,
+      deposit status and wallet transfer end-points of *User Funding*, and
+      the strategies end-point of *User Earn*.  The rest of the
+      *User Funding* and *User Earn* end-points are not yet implemented.
 
     * Some specific strings which the exchange needs to see are not provided by
       the crate, and in particular the peculiarities of trading pairs like
       "ZUSDXBTC" have to be dealt with entirely by the user.  The exchange
       provides little consistency among these and coding for them is difficult
       and use-case specific.
+
+    * There is no WebSocket transport in the crate.  The [Subscriptions]
+      registry deals with the subscription side of the WebSockets protocol,
+      but the connection itself must be made and serviced with some other
+      crate.
+
+    * The crate is entirely blocking; there is no `async` interface, beyond
+      the polling streams which the `async` feature offers as async
+      streams, each of which is driven by a thread of its own.
 */
 
 
 
-use  openssl  as  SSL;
 use  std::collections::HashMap  as  Map;
+use  std::net::IpAddr;
 use  std::sync::{Arc, Mutex};
+use  std::sync::atomic::{AtomicBool, Ordering};
+use  std::time::Duration;
+
+mod  schedule;
+mod  queue;
+mod  rate;
+mod  flight;
+mod  cache;
+mod  retry;
+mod  order_description;
+mod  auth;
+mod  warnings;
+mod  userref;
+mod  signed;
+mod  signer;
+mod  credentials;
+mod  validation;
+mod  coverage;
+mod  transport;
+mod  hedge;
+mod  latency;
+mod  guardrails;
+mod  trading_mode;
+mod  store;
+mod  fleet;
+mod  shutdown;
+#[cfg(feature = "json")]  mod  reply;
+#[cfg(feature = "json")]  mod  status;
+#[cfg(feature = "json")]  mod  pairs;
+#[cfg(feature = "json")]  mod  assets;
+#[cfg(feature = "json")]  mod  dca;
+#[cfg(feature = "json")]  mod  iceberg;
+#[cfg(feature = "json")]  mod  grid;
+#[cfg(feature = "json")]  mod  oco;
+#[cfg(feature = "json")]  mod  positions;
+#[cfg(feature = "json")]  mod  streams;
+#[cfg(feature = "json")]  mod  candles;
+#[cfg(feature = "json")]  mod  book;
+#[cfg(feature = "json")]  mod  websocket;
+#[cfg(feature = "json")]  mod  ws_message;
+#[cfg(feature = "json")]  mod  session;
+#[cfg(feature = "json")]  mod  orders;
+#[cfg(feature = "json")]  mod  history;
+#[cfg(feature = "json")]  mod  account;
+#[cfg(feature = "json")]  mod  json_calls;
+#[cfg(feature = "json")]  mod  ledger;
+#[cfg(feature = "json")]  mod  funding;
+#[cfg(feature = "json")]  mod  conversion;
+#[cfg(feature = "json")]  mod  export;
+#[cfg(feature = "json")]  mod  graph;
+#[cfg(feature = "json")]  mod  rewards;
+#[cfg(feature = "json")]  mod  earn;
+#[cfg(feature = "json")]  mod  series;
+#[cfg(feature = "json")]  mod  incremental;
+#[cfg(feature = "json")]  mod  strictness;
+#[cfg(feature = "json")]  mod  events;
+#[cfg(feature = "json")]  mod  journal;
+#[cfg(feature = "json")]  mod  performance;
+#[cfg(feature = "json")]  mod  equity;
+#[cfg(feature = "json")]  mod  consistency;
+#[cfg(all(test, feature = "json"))]  mod  test_fixtures;
+
+pub  use  schedule::Schedule;
+pub  use  queue::{Message_Queue, Overflow};
+pub  use  rate::Account_Tier;
+pub  use  retry::UNKNOWN_OUTCOME;
+pub  use  order_description::Order_Description;
+pub  use  auth::{Auth_Error, Permission};
+pub  use  fleet::Fleet;
+pub  use  shutdown::Shutdown_Guard;
+use  auth::Nonces;
+pub  use  warnings::{Deprecation, deprecation};
+pub  use  userref::Userref;
+pub  use  signed::Signed_Request;
+pub  use  signer::{External_Signer, Request_Signer};
+pub  use  validation::Invalid_Option;
+pub  use  coverage::{Coverage_Report, Supported_End_Point,
+                     supported_end_points};
+#[cfg(feature = "openapi")]
+pub  use  coverage::{compare_with_openapi, openapi_coverage};
+use  validation::Option_Validator;
+pub  use  credentials::{Passphrase, connect_encrypted, decrypt_credentials,
+                        encrypt_credentials, save_credentials};
+use  signer::{Secret_Signer, Shared_Signer, sign};
+pub  use  transport::{IP_Family, Reply_Too_Large};
+use  rate::Limiter;
+use  flight::Flights;
+use  cache::Cache;
+use  warnings::Warning_Callback;
+use  transport::Transport;
+use  hedge::Hedger;
+pub  use  latency::Latency_Stats;
+use  latency::Latencies;
+pub  use  guardrails::Withdrawal_Limits;
+use  guardrails::Withdrawal_Guard;
+pub  use  trading_mode::{System_Status, Mode_Refusal};
+use  trading_mode::Mode_Guard;
+pub  use  store::{State_Store, Shared_Store, Memory_Store, File_Store};
+#[cfg(feature = "json")]
+pub  use  status::{Health, Latency_Report, Status_Watcher};
+#[cfg(feature = "json")]
+pub  use  pairs::{Pair, Pair_Info, Fee_Schedule, Liquidity};
+#[cfg(feature = "json")]  pub  use  assets::{Asset, Asset_Info, common_symbol,
+                                              kraken_code};
+#[cfg(feature = "json")]  pub  use  dca::{DCA_Buy, DCA_Scheduler};
+#[cfg(feature = "json")]  pub  use  export::Export_Scheduler;
+#[cfg(feature = "json")]  pub  use  graph::Pair_Graph;
+#[cfg(feature = "json")]  pub  use  iceberg::Iceberg;
+#[cfg(feature = "json")]  pub  use  grid::{Grid, Grid_Level};
+#[cfg(feature = "json")]  pub  use  oco::{OCO, OCO_State};
+#[cfg(feature = "json")]
+pub  use  positions::{Position, Consolidated_Position, Trade_Balance,
+                      Margin_Alert, Margin_Monitor, net_exposure,
+                      unrealised_pnl, consolidate};
+#[cfg(feature = "json")]
+pub  use  streams::{Spread, Spread_Stats, Trade, Ticker_Stream,
+                    Cursor_Stream, Spread_Stream, Trade_Stream};
+#[cfg(feature = "async")]  pub  use  streams::Async_Stream;
+#[cfg(feature = "json")]  pub  use  candles::{Candle, Candle_Builder};
+#[cfg(feature = "json")]
+pub  use  book::{Book_Side, Book_Level, Depth, Depth_Band, Level_Change,
+                 Slippage};
+#[cfg(feature = "json")]
+pub  use  websocket::{Channel, Sub_Status, Subscriptions, Ws_Inbox};
+#[cfg(feature = "json")]
+pub  use  ws_message::{Ws_Message, Ticker, Own_Trade, Order_Update};
+#[cfg(feature = "json")]  pub  use  session::Kraken_Session;
+#[cfg(feature = "json")]
+pub  use  orders::{Order, Order_Status, Placed_Order, Fill_Outcome,
+                   Fill_Summary};
+#[cfg(feature = "json")]  pub  use  account::{Account, Account_Event};
+#[cfg(feature = "json")]  pub  use  ledger::{Ledger_Entry, Ledger_Type};
+#[cfg(feature = "json")]
+pub  use  rewards::{Reward_Period, Reward_Total};
+#[cfg(feature = "json")]  pub  use  earn::Earn_Strategy;
+#[cfg(feature = "json")]
+pub  use  strictness::{Strictness, set_strictness, unknown_fields,
+                       clear_unknown_fields};
+#[cfg(feature = "json")]
+pub  use  series::{Series_Row, Column_Type, Field, write_csv};
+#[cfg(feature = "parquet")]  pub  use  series::write_parquet;
+#[cfg(feature = "arrow")]  pub  use  series::record_batch;
+#[cfg(feature = "json")]
+pub  use  funding::{Withdrawal_Key, Withdrawal_Info, Transfer, Transfer_Ref,
+                    Transfer_Outcome, Funding_Direction, Funding_Record};
+#[cfg(feature = "json")]  pub  use  conversion::Conversion_Leg;
+#[cfg(feature = "json")]  pub  use  events::{Event, Event_Bus};
+#[cfg(feature = "json")]  pub  use  journal::{Journal_Entry, Trade_Journal};
+#[cfg(feature = "json")]
+pub  use  performance::{Round_Trip, Performance, round_trips,
+                        performance_by_pair};
+#[cfg(feature = "json")]  pub  use  equity::Equity_Point;
+#[cfg(feature = "json")]
+pub  use  consistency::{Data_Anomaly, check_cursor, stitch_candles,
+                        stitch_trades};
 
 
 
@@ -114,7 +316,7 @@ use  std::sync::{Arc, Mutex};
     always be strings; the comments below indicate how the strings will be
     interpreted by the exchange.  */
 
-#[derive(PartialEq,Eq,Hash)]
+#[derive(Clone,PartialEq,Eq,Hash)]
 pub  enum  API_Option
 {
     /** Information to be retrieved, one of "info", "leverage", "fees", or
@@ -260,6 +462,39 @@ pub  enum  API_Option
     /** Use pending replace, before complete replace (bool as str).  */
     CANCEL_RESPONSE,
 
+    /** The name of a withdrawal key, as set up on the Kraken web site. */
+    KEY,
+
+    /** An amount of an asset to move into or out of the account. */
+    AMOUNT,
+
+    /** A client order ID of the user's own choosing (a UUID, or free text of
+        up to 18 characters), by which an order can later be recognised. */
+    CL_ORD_ID,
+
+    /** One of "flex", "bonded", "timed" or "instant": the way in which an
+        earn strategy holds the funds allocated to it. */
+    LOCK_TYPE,
+
+    /** Where to carry on from in a list which is given a page at a time:
+        the 'next_cursor' of the previous page. */
+    CURSOR,
+
+    /** The name of a deposit or withdrawal method, e.g. "Bitcoin" or
+        "Bitcoin Lightning", as given by [Kraken_API::deposit_methods] and
+        [Kraken_API::withdraw_methods]. */
+    METHOD,
+
+    /** Whether to have the exchange make a new deposit address, rather than
+        list the existing ones (bool as str). */
+    NEW,
+
+    /** The wallet funds are moved out of, e.g. "Spot Wallet". */
+    FROM,
+
+    /** The wallet funds are moved into, e.g. "Futures Wallet". */
+    TO,
+
     #[doc(hidden)]
     REPORT,
     
@@ -275,6 +510,7 @@ use  API_Option  as  Opt;
 
 
 /**  When submitting a trade instruction, are we buying or selling?  */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub  enum  Instruction  {  /** We are buying. */
                            BUY,
 
@@ -354,8 +590,40 @@ impl  Order_Type
 
 
 
+/** One of the orders to be placed in a single call to
+    [Kraken_API::add_order_batch].
+
+    The order type, direction and volume are given when the object is made;
+    any of the optional arguments which [Kraken_API::add_order] understands,
+    apart from [API_Option::DEADLINE] and [API_Option::VALIDATE] which apply to
+    the batch as a whole, may then be set on the individual order.  */
+
+pub  struct  Batch_Order  {  order_type:  Order_Type,
+                             direction:   Instruction,
+                             volume:      String,
+                             options:     Map<Opt, String>  }
+
+impl  Batch_Order
+{
+    /** Describe an order of the given type, direction and volume. */
+    pub  fn  new<V: std::fmt::Display>  (order_type: Order_Type,
+                                         direction:  Instruction,
+                                         volume:  V)  ->  Self
+          {   Batch_Order {  order_type,  direction,
+                             volume:   volume.to_string (),
+                             options:  Map::new ()  }   }
+
+    /** Give a value to one of this order's optional arguments. */
+    pub  fn  set_opt<T: std::fmt::Display>  (mut self,  opt: API_Option,
+                                              value: T)  ->  Self
+          {   self.options.insert (opt, value.to_string ());   self   }
+}
+
+
+
 /** When exporting bulk data, we must specify the nature of the reporting
     format. */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub  enum  Report_Type  {  /** Trades. */ TRADES,  /** Ledgers. */ LEDGERS  }
 
 impl  Report_Type  {  fn  as_kraken_string (&self) -> &'static str
@@ -364,6 +632,51 @@ impl  Report_Type  {  fn  as_kraken_string (&self) -> &'static str
 
 
 
+/** A token which can be used to abort a request while it is in flight.
+
+    Install a clone of the token in a [Kraken_API] object with
+    [Kraken_API::set_cancel_token], and keep the other copy wherever the
+    decision to abandon the request will be made (typically another thread).
+    Calling [Cancel_Token::cancel] causes the current request, and any
+    subsequent ones, to return promptly with an error until the token is
+    [Cancel_Token::reset].  */
+
+#[derive(Clone, Default)]
+pub  struct  Cancel_Token  (Arc<AtomicBool>);
+
+impl  Cancel_Token
+{
+    /** Make a new token, in the un-cancelled state. */
+    pub  fn  new  ()  ->  Self   {   Self::default ()   }
+
+    /** Abort any request which is using this token. */
+    pub  fn  cancel  (&self)   {   self.0.store (true, Ordering::SeqCst);   }
+
+    /** Return the token to the un-cancelled state, so that it may be used
+        again. */
+    pub  fn  reset  (&self)   {   self.0.store (false, Ordering::SeqCst);   }
+
+    /** Determine if [Cancel_Token::cancel] has been called on this token (or
+        any of its clones). */
+    pub  fn  is_cancelled  (&self)  ->  bool
+          {   self.0.load (Ordering::SeqCst)   }
+
+    /*  Sleep for the given time, but wake up early if the token is
+     *  cancelled. */
+    pub(crate)  fn  pause  (&self,  time:  Duration)
+    {
+        let  wake  =  std::time::Instant::now () + time;
+        while  ! self.is_cancelled ()
+        {
+            let  now  =  std::time::Instant::now ();
+            if  now >= wake   {   break;   }
+            std::thread::sleep (Duration::from_millis (50).min (wake - now));
+        }
+    }
+}
+
+
+
 /** A handle on the connection to the Kraken exchange.
 
     This can be used multiple times, so should only be instantiated once,
@@ -388,6 +701,10 @@ impl  Report_Type  {  fn  as_kraken_string (&self) -> &'static str
     in your favour or against you depending on your work flow.  In any case, use
     the methods [Kraken_API::set_opt], [Kraken_API::clear_opt] and
     [Kraken_API::clear_all_options] to manipulate the current option set.
+    Standing preferences can instead be given with
+    [Kraken_API::set_default_opt], which are sent whenever the option has
+    not been set for the call, and are left alone when the options are
+    cleared.
 
     ## Errors
 
@@ -401,17 +718,74 @@ impl  Report_Type  {  fn  as_kraken_string (&self) -> &'static str
     `Result::Ok(String)`.
 
     A successful return of data from the exchange will be seen with a 'result'
-    section in the JSON string returned as `Result::Ok(String)`.  */
+    section in the JSON string returned as `Result::Ok(String)`.
 
-#[derive(Default)]
-pub  struct  Kraken_API  {  key:        String,
-                            secret:     String,
-                            query_url:  String,
-                            options:    Map<Opt, String>  }
+    ## Aborting requests
+
+    A request can be cut short, either by placing an overall time limit on it
+    with [Kraken_API::set_request_timeout], or by installing a [Cancel_Token]
+    with [Kraken_API::set_cancel_token] and cancelling it from elsewhere.  In
+    either case the method will return a `Result::Err(String)`.  Note that a
+    cancelled private request may or may not have been acted on by the
+    exchange.
 
+    A related safeguard is [Kraken_API::set_order_latency_budget], which causes
+    orders to carry a deadline after which the exchange will refuse them, so
+    that an order held up in transit does not execute at a stale price.
 
+    ## Rate limits
 
-const url_base: &str  =  "https://api.kraken.com/0";
+    Once the account's tier has been given with
+    [Kraken_API::set_account_tier], private requests wait until the exchange
+    would accept them, and [Kraken_API::remaining_budget] and
+    [Kraken_API::time_until] show how much room is left.
+
+    If several threads need to talk to the exchange, each can be given its own
+    handle from [Kraken_API::share], and they will then wait their turn on the
+    one counter.  Waiting calls go in order of importance rather than arrival:
+    order entry first, then order cancellation, then account enquiries, and
+    history queries and data exports last, so that a long history download
+    does not hold up trading.  Shared handles also pool their public
+    enquiries: if one thread asks for exactly the same public data (end-point
+    and arguments) as another thread is already waiting for, it is given the
+    other's reply rather than making a call of its own.  */
+
+#[derive(Default)]
+pub  struct  Kraken_API  {  key:                   String,
+                            secret:                String,
+                            query_url:             String,
+                            options:               Map<Opt, String>,
+                            default_options:       Map<Opt, String>,
+                            validators:            Map<Opt, Option_Validator>,
+                            cancel_token:          Option<Cancel_Token>,
+                            request_timeout:       Option<Duration>,
+                            order_latency_budget:  Option<Duration>,
+                            rate_limit:            Arc<Limiter>,
+                            flights:               Arc<Flights>,
+                            cache:                 Arc<Cache>,
+                            retries:               u32,
+                            warning:               Option<Warning_Callback>,
+                            transport:             Transport,
+                            signer:                Option<Shared_Signer>,
+                            hedge:                 Option<Arc<Hedger>>,
+                            latencies:             Arc<Latencies>,
+                            withdrawal_guard:      Arc<Withdrawal_Guard>,
+                            mode_guard:            Arc<Mode_Guard>,
+                            version:               Option<String>,
+                            nonces:                Arc<Nonces>,
+                            nonce_retries:         Option<u32>  }
+
+
+
+const url_host: &str  =  "https://api.kraken.com";
+
+/*  The version segment of the end-points' paths, unless the user says
+ *  otherwise. */
+const  DEFAULT_VERSION:  &str  =  "0";
+
+/*  How many times a private request refused for its nonce is tried again,
+ *  unless the user says otherwise. */
+const  DEFAULT_NONCE_RETRIES:  u32  =  1;
 
 
 
@@ -432,6 +806,34 @@ pub  fn  connect  (key:  String,  secret:  String)  ->  Kraken_API
 
 
 
+/** Check the crate's request signing against the worked example in
+    Kraken's [authentication
+    documentation](https://docs.kraken.com/rest/#section/Authentication),
+    giving `Ok` if the signature comes out as published.  This needs no
+    network or credentials, and is a quick way of ruling out a fault on this
+    side when the exchange answers with "EAPI:Invalid signature": if this
+    passes, look instead at the key and secret, and at the clock.  */
+
+pub  fn  verify_signing  ()  ->  Result<(), String>
+{
+    let  secret  =  "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18fWx\
+                     nyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg==";
+    let  expected  =  "4/dpxb3iT4tp/ZCVEwSnEsLxx0bqyhLpdfOpc6fn7OR8+UClSV5n\
+                       9E6aSS8MPtnRfp32bAb0nmbRn6H8ndwLUQ==";
+
+    let  got  =  sign (&Secret_Signer::new (secret) ?,
+                       "/0/private/AddOrder", "1616492376594",
+                       "nonce=1616492376594&ordertype=limit&pair=XBTUSD\
+                        &price=37500&type=buy&volume=1.25") ?;
+
+    if  got != expected
+        {   Err (format! ("signature of the documented example came out as \
+                           {}, not {}", got, expected)) ?   }
+    Ok (())
+}
+
+
+
 impl  Kraken_API
 {
 /********************  OPTIONAL ARGUMENT PROCESSING  **************************/
@@ -463,6 +865,383 @@ impl  Kraken_API
 
 
 
+/** Give a default value to the optional argument, which is sent to every
+    end-point which accepts the option, unless a value has been given to
+    the option with [Kraken_API::set_opt]; so standing preferences, such as
+    [API_Option::TRADES] being `true` or [API_Option::CLOSE_TIME] being
+    "both", need not be set again before every call.  Defaults are not
+    touched by [Kraken_API::clear_opt] or [Kraken_API::clear_all_options].  */
+
+    pub  fn  set_default_opt<T: std::fmt::Display>
+                                   (&mut  self,  opt:  API_Option,  value:  T)
+          {   self.default_options.insert (opt, value.to_string ());   }
+
+
+
+/** Remove the default value of an option. */
+
+    pub  fn  clear_default_opt  (&mut  self,  opt: API_Option)
+          {   self.default_options.remove (&opt);  }
+
+
+
+/** Remove the default values of all options. */
+
+    pub  fn  clear_all_default_options  (&mut  self)
+          {   self.default_options.clear ();   }
+
+
+
+    /*  The value the option will be sent with: the one set for the call, or
+     *  else the default. */
+    fn  option  (&self,  opt:  &Opt)  ->  Option<&String>
+          {   self.options.get (opt)
+                  .or_else (|| self.default_options.get (opt))   }
+
+
+
+    /*  Every option which will be sent, with its value, in no particular
+     *  order. */
+    fn  all_options  (&self)  ->  impl Iterator<Item = (&Opt, &String)>
+    {
+        self.options.iter ()
+            .chain (self.default_options.iter ()
+                         .filter (move |(O, _)|
+                                      ! self.options.contains_key (O)))
+    }
+
+
+
+
+/***********************  REQUEST CONTROL  **********************************/
+
+
+/** Install a token which, when cancelled, will abort the current request.  The
+    token stays in place for all subsequent requests until
+    [Kraken_API::clear_cancel_token] is called.  */
+
+    pub  fn  set_cancel_token  (&mut  self,  token:  Cancel_Token)
+          {   self.cancel_token  =  Some (token);   }
+
+
+
+/** Remove any cancellation token from this object. */
+
+    pub  fn  clear_cancel_token  (&mut  self)   {   self.cancel_token = None;  }
+
+
+
+/** Place a limit on the total time any one request may take, from the start of
+    the connection to the end of the reply; `None` (the default) means that we
+    will wait for as long as the transport will.  */
+
+    pub  fn  set_request_timeout  (&mut  self,  timeout:  Option<Duration>)
+          {   self.request_timeout  =  timeout;   }
+
+
+
+/** Use the given version of the REST API (the number in the path of every
+    end-point, e.g. "1" for `https://api.kraken.com/1/public/Time`) rather
+    than the crate's default of "0"; the signatures on private requests
+    follow suit.  A longer path such as "beta/0" may be given if the exchange opens
+    one.  `None` goes back to the default.  */
+
+    pub  fn  set_api_version  (&mut  self,  version:  Option<&str>)
+          {   self.version  =  version.map (|V| V.trim_matches ('/')
+                                                  .to_string ());   }
+
+
+
+    /*  The version segment in use. */
+    fn  version  (&self)  ->  &str
+          {   self.version.as_deref ().unwrap_or (DEFAULT_VERSION)   }
+
+
+
+/** Offer HTTP/2 to the exchange (`true`), or keep to HTTP/1.1 (`false`).
+    Until this is called the choice is libcurl's, and it offers HTTP/2
+    whenever it can; either way, HTTP/1.1 is used if the exchange declines,
+    or if libcurl cannot speak HTTP/2 ([Kraken_API::http2_available]).
+    HTTP/2 has leaner headers, but as each request is still made on a
+    connection of its own, calls made at the same time are not multiplexed
+    over one.  */
+
+    pub  fn  set_http2  (&mut  self,  enabled:  bool)
+          {   self.transport.http2  =  Some (enabled);   }
+
+
+
+/** Whether the libcurl the crate is linked against can speak HTTP/2; it can
+    always do so if the crate's `http2` feature is turned on. */
+
+    pub  fn  http2_available  ()  ->  bool
+          {   transport::http2_available ()   }
+
+
+
+/** Reach the exchange only over IPv4, or only over IPv6, rather than on
+    whichever address the system finds first; [IP_Family::ANY] goes back to
+    that.  */
+
+    pub  fn  set_ip_family  (&mut  self,  family:  IP_Family)
+          {   self.transport.ip_family  =  family;   }
+
+
+
+/** Send requests from the given local network interface ("eth1"), address
+    ("203.0.113.7") or host name, rather than letting the system choose; this
+    is needed when the API key only accepts calls from certain IP addresses
+    and the machine has more than one.  A name is tried first as an interface
+    and then as an address or host name; "if!eth1" or "host!my-host" says
+    which is meant.  `None` (the default) leaves the choice to the system.  */
+
+    pub  fn  set_local_address  (&mut  self,  local:  Option<&str>)
+          {   self.transport.interface  =  local.map (str::to_string);   }
+
+
+
+/** Look up the exchange's addresses at most once every `lifetime`, rather
+    than before every request, so that a slow resolver does not now and then
+    hold a request up; if a look-up fails, the addresses found last time go
+    on being used.  Handles from [Kraken_API::share] use the same cache.
+    `None` (the default) leaves the look-ups to libcurl.  */
+
+    pub  fn  set_dns_cache  (&mut  self,  lifetime:  Option<Duration>)
+          {   self.transport.dns_lifetime  =  lifetime;   }
+
+
+
+/** Send every request from this handle to the given addresses of
+    api.kraken.com, without looking the name up at all; `None` (the default)
+    goes back to looking it up.  */
+
+    pub  fn  set_exchange_addresses  (&mut  self,
+                                      addresses:  Option<Vec<IpAddr>>)
+          {   self.transport.pinned  =  addresses;   }
+
+
+
+/** Look up the addresses of api.kraken.com now (of the family given to
+    [Kraken_API::set_ip_family]), and send every request from this handle to
+    them from now on, as if they had been given to
+    [Kraken_API::set_exchange_addresses].  The addresses are also
+    returned.  */
+
+    pub  fn  pin_exchange_addresses  (&mut  self)
+               ->  Result<Vec<IpAddr>, String>
+    {
+        let  A  =  transport::resolve (self.transport.ip_family) ?;
+        self.transport.pinned  =  Some (A.clone ());
+        Ok (A)
+    }
+
+
+
+/** Refuse replies from the exchange of more than `limit` bytes, so that a
+    misbehaving proxy or an unexpectedly large answer cannot take all the
+    memory of an unattended program; such a request fails with a
+    [Reply_Too_Large] error as soon as the limit is passed (and is not
+    retried).  `None` (the default) takes replies of any size.  The limit
+    applies to data exports fetched with
+    [Kraken_API::retrieve_data_export_to_file] too.  */
+
+    pub  fn  set_max_reply_size  (&mut  self,  limit:  Option<usize>)
+          {   self.transport.max_reply  =  limit;   }
+
+
+
+/** Hedge public requests against a slow reply: once the exchange has taken
+    longer over a request than it took over `percentile` percent of recent
+    public requests (the last 200 of them), the same request is sent again,
+    and whichever answer comes back first is taken.  This trims the long tail
+    of reply times, at the cost of a few more calls.  Nothing is hedged until
+    20 requests have been timed, and private requests never are.  `None` (the
+    default) turns hedging off; handles from [Kraken_API::share] time their
+    requests together.  */
+
+    pub  fn  set_hedging  (&mut  self,  percentile:  Option<f64>)
+          {   self.hedge  =  percentile.map (|P|  Arc::new (Hedger::new (P)));  }
+
+
+
+/** Give every order placed with [Kraken_API::add_order],
+    [Kraken_API::add_order_batch] or [Kraken_API::edit_order] a deadline of
+    `budget` after the moment it is sent (that is, after any wait for the
+    rate limiter), so that the exchange rejects the order if it arrives any
+    later than that.  The deadline goes on that one request only, and an
+    [API_Option::DEADLINE] set by hand is sent instead; `None` (the default)
+    turns the feature off.  */
+
+    pub  fn  set_order_latency_budget  (&mut  self,  budget:  Option<Duration>)
+          {   self.order_latency_budget  =  budget;   }
+
+
+
+/** Tell the crate which verification tier the account is on, so that private
+    requests are held back until the exchange's call counter has room for
+    them, rather than being refused with a rate-limit error.  `None` (the
+    default) sends every request straight away, until the exchange first
+    complains of a rate-limit breach, at which point the crate assumes the
+    [Account_Tier::STARTER] tier.  See [Account_Tier] for the counting rules,
+    and how the crate slows down when the exchange says it is going too
+    fast.  */
+
+    pub  fn  set_account_tier  (&mut  self,  tier:  Option<Account_Tier>)
+          {   self.rate_limit.set_tier (tier);   }
+
+
+
+/** How much room is left on the account's call counter right now, in counter
+    units; a history query costs two of these, most other private calls one.
+    Infinite if no [Account_Tier] has been set.  */
+
+    pub  fn  remaining_budget  (&self)  ->  f64
+          {   self.rate_limit.remaining ()   }
+
+
+
+/** How long it will be before a call costing `cost` counter units can be made
+    without waiting; zero if it can go now, or if no [Account_Tier] has been
+    set.  A scheduler can use this to decide whether to send a low-priority
+    query now, or leave the room for orders.  */
+
+    pub  fn  time_until  (&self,  cost:  f64)  ->  Duration
+          {   self.rate_limit.time_until (cost)   }
+
+
+
+/** Sign private requests with the given [Request_Signer] rather than with
+    the secret given to [connect], which then need not be given at all (an
+    empty string will do); `None` (the default) goes back to the secret.
+    The key given to [connect] is still sent with every request.  Handles
+    from [Kraken_API::share] use the same signer.  */
+
+    pub  fn  set_signer  (&mut  self,
+                          signer:  Option<Arc<dyn Request_Signer>>)
+          {   self.signer  =  signer;   }
+
+
+
+/*  The signer to sign private requests with: the user's, or else one made
+ *  from the secret. */
+
+    fn  request_signer  (&self)  ->  Result<Shared_Signer, String>
+    {
+        match  &self.signer
+           {   Some (S)  =>  Ok (S.clone ()),
+               None  =>  Ok (Arc::new (Secret_Signer::new (&self.secret) ?))   }
+    }
+
+
+
+/** Get another handle on the same account, for use in another thread, which
+    shares this one's rate limiter (so that the two between them respect the
+    exchange's limits, and higher-priority calls on one can go ahead of
+    lower-priority calls on the other), its public enquiries in flight, its
+    cache, its nonce counter, its [Kraken_API::latency_stats], its
+    [Request_Signer], its [Kraken_API::on_warning] callback, its withdrawal
+    limits and the withdrawals counted against them (see
+    [Kraken_API::set_withdrawal_limits]), and the exchange's trading mode as
+    last seen (see [Kraken_API::trading_mode]).  The new handle starts with
+    no options, cancellation token, request timeout, retries or order
+    latency budget, though it does start with this one's default options
+    and option validators (see [Kraken_API::set_default_opt] and
+    [Kraken_API::set_option_validator]).  */
+
+    pub  fn  share  (&self)  ->  Kraken_API
+    {
+        Kraken_API {  key:         self.key.clone (),
+                      secret:      self.secret.clone (),
+                      rate_limit:  self.rate_limit.clone (),
+                      flights:     self.flights.clone (),
+                      cache:       self.cache.clone (),
+                      warning:     self.warning.clone (),
+                      transport:   self.transport.clone (),
+                      signer:      self.signer.clone (),
+                      hedge:       self.hedge.clone (),
+                      latencies:   self.latencies.clone (),
+                      withdrawal_guard:  self.withdrawal_guard.clone (),
+                      mode_guard:  self.mode_guard.clone (),
+                      version:     self.version.clone (),
+                      nonces:      self.nonces.clone (),
+                      default_options:  self.default_options.clone (),
+                      validators:  self.validators.clone (),
+                      ..Default::default ()  }
+    }
+
+
+
+/** Keep replies from the public `end_point` (named as in the Kraken
+    documentation, e.g. "AssetPairs") for `lifetime`, and answer repeated
+    enquiries with identical arguments from this store rather than going back
+    to the exchange; `None` (the default for every end-point) turns this off
+    again.  Replies carrying an error are never kept.  This is meant for
+    slowly-changing data like [Kraken_API::asset_info],
+    [Kraken_API::asset_pairs], [Kraken_API::system_status] and
+    [Kraken_API::server_time], which code might otherwise fetch over and over
+    again.  */
+
+    pub  fn  set_cache_lifetime  (&mut  self,  end_point:  &str,
+                                  lifetime:  Option<Duration>)
+          {   self.cache.set_lifetime (end_point, lifetime);   }
+
+
+
+/** Throw away all the replies kept under [Kraken_API::set_cache_lifetime],
+    so that the next enquiries go to the exchange. */
+
+    pub  fn  clear_cache  (&mut  self)   {   self.cache.clear ();   }
+
+
+
+/** Have requests which fail in the transport, or which the exchange says it
+    is momentarily too busy to handle, tried again up to `retries` more times
+    (none by default), waiting half a second before the first retry and
+    twice as long before each one after.
+
+    Only requests which can safely be repeated are retried: all the public
+    end-points, the private enquiries, and cancellation.  A request which
+    changes the state of the account, such as placing an order, is sent only
+    once, whatever the setting here; if its transport fails once it has left
+    us, the error message starts with [UNKNOWN_OUTCOME] and suggests how to
+    find out whether it took effect.  */
+
+    pub  fn  set_retries  (&mut  self,  retries:  u32)
+          {   self.retries  =  retries;   }
+
+
+
+/** When the exchange refuses a private request with "EAPI:Invalid nonce",
+    move the nonce counter a second ahead of where it was and send the
+    request again, up to `retries` times (once, by default; zero turns this
+    off).  This error nearly always means that another request with the same
+    key got in first with a later nonce, and as the exchange has not acted
+    on the refused request, it is safe to repeat whatever it was.  The
+    nonces are the time in microseconds, but never less than one more than
+    the last used by this handle or those [shared](Kraken_API::share) with
+    it.  */
+
+    pub  fn  set_nonce_retries  (&mut  self,  retries:  u32)
+          {   self.nonce_retries  =  Some (retries);   }
+
+
+
+/** Have `f` called with the end-point and the text of every warning the
+    exchange sends back, such as a notice that an end-point or argument is
+    deprecated.  Warnings come in the 'error' section of the reply, but with
+    a leading 'W' rather than 'E', alongside a good 'result'; the reply is
+    still returned as usual, and the typed helpers do not count warnings as
+    errors.
+
+    The callback also hears, once, of the first call the program makes to an
+    end-point which the crate knows Kraken has deprecated; see
+    [Deprecation].  */
+
+    pub  fn  on_warning<F>  (&mut  self,  f:  F)
+                where  F:  Fn (&str, &str) + Send + Sync + 'static
+          {   self.warning  =  Some (Arc::new (f));   }
+
+
 
 /***********************  USER DATA ENQUIRIES  ******************************/
 
@@ -700,6 +1479,55 @@ impl  Kraken_API
         self.set_opt (Opt::ID,  id);
         api_function (self, "RetrieveExport", &[Opt::ID],  query_private)
     }
+
+
+
+/** Retrieve a processed data export, as [Kraken_API::retrieve_data_export]
+    does, but write the ZIP archive straight into the file at `path` as it
+    arrives, rather than holding it all in memory; exports covering several
+    years of trading can run to hundreds of megabytes.
+
+    If the exchange sends back an error message instead of an archive, the
+    file is removed and the message is returned as the error.  */
+
+  pub  fn  retrieve_data_export_to_file  (&mut self,  id: &str,
+                                          path: impl AsRef<std::path::Path>)
+                   ->  Result<(), String>
+    {
+        use  std::io::{Read, Write};
+
+        let  path  =  path.as_ref ();
+        let  mut  F  =  std::fs::File::create (path)
+                            .map_err (|e| format! ("{}: {}",
+                                                   path.display (), e)) ?;
+
+        self.set_opt (Opt::ID,  id);
+        self.query_url  =  "RetrieveExport".to_string ();
+        query_add_options (self,  &[Opt::ID],  '?');
+
+        let  sink  =  move |data: &[u8]|  F.write_all (data)
+                                          .map_err (|e| e.to_string ());
+        let  outcome  =  private_request (self)
+                           .and_then (|C|  transfer (self, C, sink));
+
+        /*  A ZIP archive starts with "PK"; anything else is the exchange
+         *  telling us what went wrong. */
+        let  mut  magic  =  [0u8; 2];
+        let  is_zip  =  std::fs::File::open (path)
+                           .and_then (|mut F| F.read_exact (&mut magic))
+                           .is_ok ()
+                        &&  &magic == b"PK";
+
+        if  outcome.is_err ()  ||  ! is_zip
+        {
+            let  body  =  std::fs::read_to_string (path).unwrap_or_default ();
+            let  _  =  std::fs::remove_file (path);
+            outcome ?;
+            Err (body) ?;
+        }
+
+        Ok (())
+    }
     
 
 
@@ -736,12 +1564,16 @@ impl  Kraken_API
     [here](https://docs.kraken.com/rest/#operation/addOrder).
     
     The following optional arguments are considered by this end-point:
-    [API_Option::USERREF], [API_Option::PRICE], [API_Option::PRICE_2],
-    [API_Option::TRIGGER], [API_Option::LEVERAGE], [API_Option::OFLAGS],
-    [API_Option::TIME_IN_FORCE], [API_Option::START_TIME],
-    [API_Option::EXPIRE_TIME], [API_Option::CLOSE_TYPE],
-    [API_Option::CLOSE_PRICE_1], [API_Option::CLOSE_PRICE_2],
-    [API_Option::DEADLINE], and [API_Option::VALIDATE].  */
+    [API_Option::USERREF], [API_Option::CL_ORD_ID], [API_Option::PRICE],
+    [API_Option::PRICE_2], [API_Option::TRIGGER], [API_Option::LEVERAGE],
+    [API_Option::OFLAGS], [API_Option::TIME_IN_FORCE],
+    [API_Option::START_TIME], [API_Option::EXPIRE_TIME],
+    [API_Option::CLOSE_TYPE], [API_Option::CLOSE_PRICE_1],
+    [API_Option::CLOSE_PRICE_2], [API_Option::DEADLINE], and
+    [API_Option::VALIDATE].
+
+    An order which the exchange's current mode does not allow is refused
+    without being sent; see [Kraken_API::trading_mode].  */
 
   pub  fn  add_order<V: std::fmt::Display>  (&mut self,
                                              order_type: Order_Type,
@@ -750,6 +1582,9 @@ impl  Kraken_API
                                              pair:  &str)
                ->  Result<String, String>
     {
+        self.mode_guard.check (Some (order_type.as_kraken_string ()),
+                               self.option (&Opt::OFLAGS).map (|F| F.as_str ()))
+            .map_err (|E| E.to_string ()) ?;
         self.set_opt (Opt::ORDER_TYPE, order_type.as_kraken_string ());
         self.set_opt (Opt::TYPE, direction.as_kraken_string ());
         self.set_opt (Opt::VOLUME, volume);
@@ -762,12 +1597,77 @@ impl  Kraken_API
                          Opt::OFLAGS,           Opt::TIME_IN_FORCE,
                          Opt::START_TIME,       Opt::EXPIRE_TIME,
                          Opt::CLOSE_TYPE,       Opt::CLOSE_PRICE_1,
-                         Opt::CLOSE_PRICE_2,    Opt::DEADLINE,  Opt::VALIDATE],
+                         Opt::CLOSE_PRICE_2,    Opt::DEADLINE,  Opt::VALIDATE,
+                         Opt::CL_ORD_ID],
                        query_private)
     }
                          
 
 
+/** Place between two and fifteen orders, all on the same pair, in one go.
+
+    The upstream documentation is
+    [here](https://docs.kraken.com/rest/#operation/addOrderBatch).
+
+    The batch as a whole responds to the [API_Option::DEADLINE] and
+    [API_Option::VALIDATE] optional arguments set on this object; the
+    individual orders carry their own optional arguments, see [Batch_Order].
+    A batch of fewer than two or more than fifteen orders, or one carrying an
+    option value which does not pass its check, is refused without being
+    sent.  */
+
+  pub  fn  add_order_batch  (&mut self,  pair: &str,  orders: &[Batch_Order])
+               ->  Result<String, String>
+    {
+        if  ! (2 ..= 15).contains (&orders.len ())
+            {   Err (format! ("a batch must have from 2 to 15 orders, not {}",
+                              orders.len ())) ?   }
+
+        for  (i, O)  in  orders.iter ().enumerate ()
+        {
+            for  (o, V)  in  &O.options
+                {   validation::check (self, o, V)
+                        .map_err (|E|  format! ("{} (order {} of the batch)",
+                                                E, i)) ?;   }
+            self.mode_guard.check (Some (O.order_type.as_kraken_string ()),
+                                   O.options.get (&Opt::OFLAGS)
+                                            .map (|F| F.as_str ()))
+                .map_err (|E|  format! ("{} (order {} of the batch)", E, i)) ?;
+        }
+
+        self.set_opt (Opt::PAIR, pair);
+        let  options  =  [Opt::PAIR, Opt::DEADLINE, Opt::VALIDATE];
+        check_options (self, &options) ?;
+        self.query_url  =  "AddOrderBatch".to_string ();
+        query_add_options (self,  &options,  '?');
+
+        for  (i, O)  in  orders.iter ().enumerate ()
+        {
+            self.query_url += &format! ("&orders[{0}][ordertype]={1}\
+                                         &orders[{0}][type]={2}\
+                                         &orders[{0}][volume]={3}",
+                                        i,
+                                        O.order_type.as_kraken_string (),
+                                        O.direction.as_kraken_string (),
+                                        O.volume);
+
+            for  o  in  &[Opt::USERREF,     Opt::PRICE,     Opt::PRICE_2,
+                          Opt::TRIGGER,     Opt::LEVERAGE,  Opt::OFLAGS,
+                          Opt::START_TIME,  Opt::EXPIRE_TIME,
+                          Opt::CLOSE_TYPE,  Opt::TIME_IN_FORCE,
+                          Opt::CLOSE_PRICE_1,  Opt::CLOSE_PRICE_2,
+                          Opt::CL_ORD_ID]
+            {   if let Some (V) = O.options.get (o)
+                {   self.query_url
+                           +=  &format! ("&orders[{}][{}]={}",
+                                         i, kraken_argument (o), V);   }  }
+        }
+
+        dispatch (self, "AddOrderBatch", query_private)
+    }
+
+
+
 /** Edit an order on the exchange's order book.
 
     The upstream documentation is
@@ -783,6 +1683,9 @@ impl  Kraken_API
                                               pair:  &str)
                ->  Result<String, String>
     {
+        self.mode_guard.check (None,
+                               self.option (&Opt::OFLAGS).map (|F| F.as_str ()))
+            .map_err (|E| E.to_string ()) ?;
         self.set_opt (Opt::TXID, tx_id);
         self.set_opt (Opt::PAIR, pair);
         api_function  (self,
@@ -802,7 +1705,8 @@ impl  Kraken_API
     The documentation for this end-point is at
     [Kraken](https://docs.kraken.com/rest/#operation/cancelOrder).  Note that
     'txid' can actually be a 'userref', in which case all open orders for that
-    user are cancelled.  */
+    user are cancelled; [Kraken_API::cancel_by_txid] and
+    [Kraken_API::cancel_by_userref] say which is meant.  */
     
   pub  fn  cancel_order  (&mut self, txid:  &str)  ->  Result<String, String>
     {
@@ -812,6 +1716,31 @@ impl  Kraken_API
 
 
 
+/** Cancel the one open order with the given transaction ID, as
+    [Kraken_API::cancel_order] does, but refusing (without calling the
+    exchange) an ID made only of digits, which the exchange would take to be
+    a user reference.  */
+
+  pub  fn  cancel_by_txid  (&mut self,  txid:  &str)  ->  Result<String, String>
+    {
+      if  txid.is_empty ()  ||  txid.bytes ().all (|b| b.is_ascii_digit ())
+          {   Err (format! ("'{}' is not a transaction ID", txid)) ?   }
+      self.cancel_order (txid)
+    }
+
+
+
+/** Cancel every open order which was placed with the given
+    [API_Option::USERREF].  */
+
+  pub  fn  cancel_by_userref  (&mut self,  userref:  i32)
+               ->  Result<String, String>
+    {
+      self.cancel_order (&userref.to_string ())
+    }
+
+
+
 /** Cancel all orders open on this account.
 
     The documentation for this end-point is at
@@ -839,6 +1768,228 @@ impl  Kraken_API
 
 
 
+/**********************   USER FUNDING   **************************************/
+
+
+
+/** List the methods by which an asset may be deposited, with their limits
+    and fees, and whether each can generate new deposit addresses.
+
+    Upstream documentation is
+    [here](https://docs.kraken.com/rest/#operation/getDepositMethods).
+
+    The asset class may be given with the [API_Option::ACLASS] optional
+    argument.  */
+
+  pub  fn  deposit_methods  (&mut self,  asset: &str)
+               ->  Result<String, String>
+    {
+      self.set_opt (Opt::ASSET, asset);
+      api_function (self, "DepositMethods", &[Opt::ASSET, Opt::ACLASS],
+                    query_private)
+    }
+
+
+
+/** List the addresses to which `asset` may be deposited by the deposit
+    `method` (one of those given by [Kraken_API::deposit_methods], e.g.
+    "Bitcoin").  With the [API_Option::NEW] optional argument set to true, a
+    new address is made and given back instead, so that addresses can be
+    rotated; the exchange allows only so many of these for each method.
+
+    Upstream documentation is
+    [here](https://docs.kraken.com/rest/#operation/getDepositAddresses).  */
+
+  pub  fn  deposit_addresses  (&mut self,  asset: &str,  method: &str)
+               ->  Result<String, String>
+    {
+      self.set_opt (Opt::ASSET, asset);
+      self.set_opt (Opt::METHOD, method);
+      api_function (self, "DepositAddresses",
+                    &[Opt::ASSET, Opt::METHOD, Opt::NEW], query_private)
+    }
+
+
+
+/** List the methods by which an asset may be withdrawn, with their minimum
+    amounts and fees.
+
+    Upstream documentation is
+    [here](https://docs.kraken.com/rest/#operation/getWithdrawalMethods).
+
+    The asset class may be given with the [API_Option::ACLASS] optional
+    argument.  */
+
+  pub  fn  withdraw_methods  (&mut self,  asset: &str)
+               ->  Result<String, String>
+    {
+      self.set_opt (Opt::ASSET, asset);
+      api_function (self, "WithdrawMethods", &[Opt::ASSET, Opt::ACLASS],
+                    query_private)
+    }
+
+
+
+/** List the withdrawal addresses (keys) which have been set up for an asset.
+
+    Upstream documentation is
+    [here](https://docs.kraken.com/rest/#operation/getWithdrawalAddresses).
+
+    The asset class may be given with the [API_Option::ACLASS] optional
+    argument, and a single key picked out with [API_Option::KEY].  */
+
+  pub  fn  withdraw_addresses  (&mut self,  asset: &str)
+               ->  Result<String, String>
+    {
+      self.set_opt (Opt::ASSET, asset);
+      api_function (self, "WithdrawAddresses",
+                    &[Opt::ASSET, Opt::ACLASS, Opt::KEY], query_private)
+    }
+
+
+
+/** Find out the fee and limit which would apply to a withdrawal of `amount`
+    of `asset` to the address set up under the withdrawal `key`, without
+    making the withdrawal.
+
+    [Here](https://docs.kraken.com/rest/#operation/getWithdrawalInformation)
+    is the upstream documentation.  */
+
+  pub  fn  withdrawal_info  (&mut self,  asset: &str,  key: &str,
+                             amount: &str)
+               ->  Result<String, String>
+    {
+      self.set_opt (Opt::ASSET, asset);
+      self.set_opt (Opt::KEY, key);
+      self.set_opt (Opt::AMOUNT, amount);
+      api_function (self, "WithdrawInfo",
+                    &[Opt::ASSET, Opt::KEY, Opt::AMOUNT], query_private)
+    }
+
+
+
+/** Withdraw `amount` of `asset` to the address set up under the withdrawal
+    `key`.  This moves real funds out of the account, and is never retried
+    (see [Kraken_API::set_retries]); it fails without going to the exchange
+    if it would break the limits set with
+    [Kraken_API::set_withdrawal_limits].
+
+    Upstream documentation is
+    [here](https://docs.kraken.com/rest/#operation/withdrawFunds).  */
+
+  pub  fn  withdraw  (&mut self,  asset: &str,  key: &str,  amount: &str)
+               ->  Result<String, String>
+    {
+      self.set_opt (Opt::ASSET, asset);
+      self.set_opt (Opt::KEY, key);
+      self.set_opt (Opt::AMOUNT, amount);
+      let  serial  =  self.withdrawal_guard.check (asset, key, amount) ?;
+      let  reply  =  api_function (self, "Withdraw",
+                                   &[Opt::ASSET, Opt::KEY, Opt::AMOUNT],
+                                   query_private);
+      self.withdrawal_guard.settle (serial, &reply);
+      reply
+    }
+
+
+
+/** Get the status of recent deposits, of all assets or of the one given
+    with the [API_Option::ASSET] optional argument, and of all deposit
+    methods or of the one given with [API_Option::METHOD]; see
+    [Kraken_API::wait_for_deposit] for following one deposit through to
+    its end.
+
+    The upstream documentation is
+    [here](https://docs.kraken.com/rest/#operation/getStatusRecentDeposits).  */
+
+  pub  fn  deposit_status  (&mut self)  ->  Result<String, String>
+    {
+      api_function (self, "DepositStatus", &[Opt::ASSET, Opt::METHOD],
+                    query_private)
+    }
+
+
+
+/** Get the status of recent withdrawals, of all assets or of the one given
+    with the [API_Option::ASSET] optional argument, and of all withdrawal
+    methods or of the one given with [API_Option::METHOD]; see
+    [Kraken_API::wait_for_withdrawal] for following one withdrawal through
+    until it succeeds, with its reference and transaction IDs.
+
+    The upstream documentation is
+    [here](https://docs.kraken.com/rest/#operation/getStatusRecentWithdrawals).
+    */
+
+  pub  fn  withdraw_status  (&mut self)  ->  Result<String, String>
+    {
+      api_function (self, "WithdrawStatus", &[Opt::ASSET, Opt::METHOD],
+                    query_private)
+    }
+
+
+
+/** Move `amount` of `asset` from the wallet named `from` to the one named
+    `to`; at present the exchange only allows transfers from "Spot Wallet"
+    to "Futures Wallet", which is how collateral is put up for hedging on
+    Kraken Futures.  Like [Kraken_API::withdraw], this is never retried.
+
+    The upstream documentation is
+    [here](https://docs.kraken.com/rest/#operation/walletTransfer).  */
+
+  pub  fn  wallet_transfer  (&mut self,  asset: &str,  from: &str,  to: &str,
+                             amount: &str)
+               ->  Result<String, String>
+    {
+      self.set_opt (Opt::ASSET, asset);
+      self.set_opt (Opt::FROM, from);
+      self.set_opt (Opt::TO, to);
+      self.set_opt (Opt::AMOUNT, amount);
+      api_function (self, "WalletTransfer",
+                    &[Opt::ASSET, Opt::FROM, Opt::TO, Opt::AMOUNT],
+                    query_private)
+    }
+
+
+
+/************************   USER EARN   **************************************/
+
+
+
+/** List the earn strategies on offer, with their estimated yields, lock-up
+    terms and minimum allocations; the list is given a page at a time.
+
+    Upstream documentation is
+    [here](https://docs.kraken.com/rest/#operation/listStrategies).
+
+    The list may be narrowed down with the [API_Option::ASSET] and
+    [API_Option::LOCK_TYPE] optional arguments, and later pages got with
+    [API_Option::CURSOR].  */
+
+  pub  fn  earn_strategies  (&mut self)  ->  Result<String, String>
+    {
+      api_function (self, "Earn/Strategies",
+                    &[Opt::ASSET, Opt::LOCK_TYPE, Opt::CURSOR], query_private)
+    }
+
+
+
+/*****************   WEBSOCKETS AUTHENTICATION   ***************************/
+
+
+
+/** Get a token with which to subscribe to the private feeds of the WebSockets
+    API; it must be used within 15 minutes of being issued.
+
+    Upstream documentation is
+    [here](https://docs.kraken.com/rest/#operation/getWebsocketsToken).  */
+
+  pub  fn  websockets_token  (&mut self)  ->  Result<String, String>
+    {
+      api_function (self, "GetWebSocketsToken", &[], query_private)
+    }
+
+
+
 /**********************   MARKET DATA   **************************************/
 
 /** Get the server's time.
@@ -858,7 +2009,11 @@ impl  Kraken_API
     [here](https://docs.kraken.com/rest/#operation/getSystemStatus).  */
 
   pub  fn  system_status  (&mut self) ->  Result<String, String>
-    {  api_function (self, "SystemStatus", &[], query_public)  }
+    {
+      let  reply  =  api_function (self, "SystemStatus", &[], query_public);
+      if  let  Ok (R)  =  &reply   {   self.mode_guard.observe (R);   }
+      reply
+    }
 
 
 
@@ -896,9 +2051,10 @@ impl  Kraken_API
     The upstream documentation is
     [here](https://docs.kraken.com/rest/#operation/getTickerInformation).  */
 
-  pub  fn  ticker_info  (&mut self,  pair: String)  ->  Result<String, String>
+  pub  fn  ticker_info  (&mut self,  pair: impl Into<String>)
+               ->  Result<String, String>
     {  
-      self.set_opt (Opt::PAIR, pair);
+      self.set_opt (Opt::PAIR, pair.into ());
       api_function (self, "Ticker", &[Opt::PAIR], query_public)
     }
 
@@ -912,9 +2068,10 @@ impl  Kraken_API
     The method respects the optional arguments [API_Option::INTERVAL] and
     [API_Option::SINCE].  */
 
-  pub  fn  ohlc_data  (&mut self,  pair: String)  ->  Result<String, String>
+  pub  fn  ohlc_data  (&mut self,  pair: impl Into<String>)
+               ->  Result<String, String>
     {
-      self.set_opt (Opt::PAIR, pair);
+      self.set_opt (Opt::PAIR, pair.into ());
       api_function
            (self, "OHLC", &[Opt::PAIR, Opt::INTERVAL, Opt::SINCE], query_public)
     }
@@ -929,9 +2086,10 @@ impl  Kraken_API
     This end-point uses the optional argument [API_Option::COUNT] to limit the
     depth of data into the order book.  */
 
-  pub  fn  order_book  (&mut self,  pair: String)  ->  Result<String, String>
+  pub  fn  order_book  (&mut self,  pair: impl Into<String>)
+               ->  Result<String, String>
     {
-      self.set_opt (Opt::PAIR, pair);
+      self.set_opt (Opt::PAIR, pair.into ());
       api_function (self, "Depth", &[Opt::PAIR, Opt::COUNT], query_public)
     }
 
@@ -944,9 +2102,10 @@ impl  Kraken_API
 
     Allows the optional argument [API_Option::SINCE].  */
 
-  pub  fn  recent_trades  (&mut self,  pair: String)  ->  Result<String, String>
+  pub  fn  recent_trades  (&mut self,  pair: impl Into<String>)
+               ->  Result<String, String>
     {
-      self.set_opt (Opt::PAIR, pair);
+      self.set_opt (Opt::PAIR, pair.into ());
       api_function (self, "Trades", &[Opt::PAIR, Opt::SINCE], query_public)
     }
 
@@ -959,9 +2118,10 @@ impl  Kraken_API
 
     Respects the optional argument [API_Option::SINCE].  */
 
-  pub  fn  spread_data  (&mut self,  pair: String)  ->  Result<String, String>
+  pub  fn  spread_data  (&mut self,  pair: impl Into<String>)
+               ->  Result<String, String>
     {
-      self.set_opt (Opt::PAIR, pair);
+      self.set_opt (Opt::PAIR, pair.into ());
       api_function (self, "Spread", &[Opt::PAIR, Opt::SINCE], query_public)
     }
 }
@@ -974,105 +2134,311 @@ fn  api_function  (K: &mut Kraken_API,
                    do_query: fn(&Kraken_API)->Result<String,String>)
         ->  Result<String, String>
                 {
+                     check_options (K, options) ?;
                      K.query_url  =  end_point.to_string ();
                      query_add_options  (K,  options,  '?');
-                     do_query (K)
+                     dispatch (K, end_point, do_query)
                 }
 
 
 
-fn  query_public  (K:  &Kraken_API)  ->  Result<String, String>
+/*  Refuse any value set for the options which does not pass its check. */
+
+fn  check_options  (K:  &Kraken_API,  options:  &[Opt])  ->  Result<(), String>
 {
-    let  mut  C  =  curl::easy::Easy::new ();
+    for  o  in  options
+    {   if let Some (V) = K.option (o)
+            {   validation::check (K, o, V)
+                    .map_err (|E| E.to_string ()) ?;   }   }
+    Ok (())
+}
+
 
-    C.url (&(url_base.to_string () + "/public/" + &K.query_url)).unwrap ();
 
-    let  query_result  =  Arc::new (Mutex::new (String::new ()));
+/*  Send the query made up in K.query_url, warning of a deprecated end-point
+ *  beforehand, and passing any warnings in the reply to the callback. */
+
+fn  dispatch  (K:  &mut Kraken_API,
+               end_point:  &str,
+               do_query:  fn(&Kraken_API)->Result<String,String>)
+     ->  Result<String, String>
+{
+    warnings::check_deprecation (K.warning.as_ref (), end_point);
+    let  reply  =  do_query (K) ?;
+    if let Some (f) = &K.warning
+    {   for  W  in  warnings::warnings_in (&reply)
+            {   f (end_point, &W);   }   }
+    Ok (reply)
+}
 
-    let  qr  =  query_result.clone ();
-    C.write_function
-            (move |data|
-              {  *qr.lock ().unwrap () += std::str::from_utf8 (data).unwrap ();
-                  Ok (data.len ())  })
-        .map_err (|e| e.to_string ()) ?;
 
-    C.perform ().map_err (|e| e.to_string ()) ?;
 
-    let  x  =  Ok (query_result.lock ().unwrap ().to_string ());
-    x
+fn  query_public  (K:  &Kraken_API)  ->  Result<String, String>
+{
+    if let Some (R) = K.cache.get (&K.query_url)   {   return  Ok (R);   }
+
+    let  url  =  format! ("{}/{}/public/{}", url_host, K.version (),
+                          K.query_url);
+    let  attempt  =  ||  match  &K.hedge
+       {   Some (H)  =>  hedge::perform (K, H, &url),
+           None  =>  {  let  mut  C  =  curl::easy::Easy::new ();
+                        C.url (&url).unwrap ();
+                        perform (K, C)  }   };
+
+    let  reply  =  K.flights.run (&K.query_url,  K.cancel_token.as_ref (),
+                                  || with_retries (K, attempt)) ?;
+    K.cache.put (&K.query_url, &reply);
+    Ok (reply)
 }
 
 
 
+/*  Requests which are safe to repeat are retried as the user has asked;
+ *  others are sent only once, and if the transport fails after the request
+ *  has left us we cannot know whether the exchange acted on it, and say
+ *  so. */
+
 fn  query_private  (K:  &Kraken_API)  ->  Result<String, String>
 {
-    if  K.secret.len () != 88
-        {   Err ("private key must be 88 characters long".to_string ()) ?   }
+    let  attempt  =  ||  {
+        let  reply  =  perform (K, private_request (K) ?) ?;
+        K.rate_limit.observe (&reply);
+        Ok (reply)
+    };
+
+    if  retry::idempotent (&K.query_url)
+        {   return  with_nonce_retries (K,  || with_retries (K, attempt));   }
+
+    with_nonce_retries (K,  ||  {
+        let  C  =  private_request (K) ?;
+        if  K.cancel_token.as_ref ().is_some_and (Cancel_Token::is_cancelled)
+            {   Err ("request cancelled".to_string ()) ?   }
+
+        let  reply  =  perform (K, C)
+                         .map_err (|E| retry::unknown_outcome (&K.query_url,
+                                                               &E)) ?;
+        K.rate_limit.observe (&reply);
+        Ok (reply)
+    })
+}
 
-    let  nonce   =  std::time::SystemTime::now ()
-                             .duration_since (std::time::UNIX_EPOCH) .unwrap ()
-                             .as_micros ()
-                             .to_string ();
 
-    let  (query_url, post_data)  =  { let  mut  S  =  K.query_url.split ('?');
-                                      (S.next ().unwrap ().to_string (),
-                                       S.next ().unwrap_or ("").to_string ()) };
 
-    let  post_data  =  &format! ("{}{}nonce={}",
-                                 post_data,
-                                 if post_data.is_empty () {""} else {"&"},
-                                 nonce);
+/*  Send the private request, and if the exchange refuses its nonce, leap
+ *  the nonce counter ahead and send it again, as many times as K allows. */
 
-    let  mut  C  =  curl::easy::Easy::new ();
+fn  with_nonce_retries  (K:  &Kraken_API,
+                         mut  send:  impl FnMut () -> Result<String, String>)
+          ->  Result<String, String>
+{
+    let  mut  reply  =  send () ?;
 
-    C.url (&format! ("{}/private/{}", url_base, query_url)).unwrap ();
+    for  _  in  0 .. K.nonce_retries.unwrap_or (DEFAULT_NONCE_RETRIES)
+    {
+        if  Auth_Error::from_message (&reply)
+                  !=  Some (Auth_Error::INVALID_NONCE)
+            {   break;   }
+        K.nonces.leap ();
+        reply  =  send () ?;
+    }
+
+    Ok (reply)
+}
 
-    C.post (true).unwrap ();
-    C.post_fields_copy (post_data.as_bytes ()).unwrap ();
 
-    C.http_headers
-        ( {
-             let  mut  L  =  curl::easy::List::new ();
 
-             L.append (&format!("API-Key: {}", K.key)).unwrap ();
+/*  Make the attempt, and then as many more as K allows while it fails in a
+ *  way that might go away, waiting twice as long before each one as before
+ *  the last. */
 
-             let  key  =  SSL::pkey::PKey::hmac
-                             (&SSL::base64::decode_block (&K.secret).unwrap ())
-                           .unwrap ();
+fn  with_retries  (K:  &Kraken_API,
+                   mut  attempt:  impl FnMut () -> Result<String, String>)
+          ->  Result<String, String>
+{
+    let  mut  outcome  =  attempt ();
 
-             let  mut  signer  =  SSL::sign::Signer::new
-                                     (SSL::hash::MessageDigest::sha512 (), &key)
-                                   .unwrap ();
+    for  n  in  0 .. K.retries
+    {
+        if  ! retry::worth_retrying (&outcome)   {   break;   }
 
-             signer.update ("/0/private/".as_bytes ()).unwrap ();
-             signer.update (query_url.as_bytes ()).unwrap ();
-             signer.update (&SSL::hash::hash
-                                         (SSL::hash::MessageDigest::sha256 (),
-                                          (nonce + post_data).as_bytes ())
-                               .unwrap ())
-                   .unwrap ();
+        let  delay  =  Duration::from_millis (500)  *  2u32.pow (n.min (6));
+        match  &K.cancel_token
+        {
+            Some (T)  =>  {  T.pause (delay);
+                             if  T.is_cancelled ()   {   break;   }  }
+            None      =>  std::thread::sleep (delay)
+        }
 
-             L.append (&format!("API-Sign: {}",
-                                &SSL::base64::encode_block
-                                    (&signer.sign_to_vec ().unwrap ())))
-              .unwrap ();
+        outcome  =  attempt ();
+    }
+
+    outcome
+}
 
-             L
-        } ) .unwrap ();
-    
-    let  query_result  =  Arc::new  (Mutex::new (String::new ()));
 
-    let  qr  =  query_result.clone ();
-    C.write_function
-            (move |data|
-              {  *qr.lock ().unwrap () += std::str::from_utf8 (data).unwrap ();
-                  Ok (data.len ())  })
-     .unwrap ();
 
-    C.perform ().map_err (|e| e.to_string ()) ?;
+/*  Set up a signed request for the private end-point in K.query_url. */
+
+fn  private_request  (K:  &Kraken_API)  ->  Result<curl::easy::Easy, String>
+{
+    let  signer  =  K.request_signer () ?;
 
-    let  x  =  Ok (query_result.lock ().unwrap ().to_string ());
-    x
+    K.rate_limit.acquire (&K.query_url, K.cancel_token.as_ref ()) ?;
+
+    let  nonce  =  K.nonces.next ();
+
+    signed::prepare (&K.key, &*signer, K.version (),
+                     &with_deadline (K, std::time::SystemTime::now ()),
+                     nonce) ?
+        .easy ()
+}
+
+
+
+/*  The query in K.query_url, with a deadline of `now` plus K's order latency
+ *  budget added if it is an order and does not already carry a deadline of
+ *  the user's own. */
+
+fn  with_deadline  (K:  &Kraken_API,  now:  std::time::SystemTime)  ->  String
+{
+    let  query  =  &K.query_url;
+    let  (end_point, args)  =  query.split_once ('?').unwrap_or ((query, ""));
+
+    match  K.order_latency_budget
+    {
+        Some (B)  if  (end_point == "AddOrder"  ||  end_point == "AddOrderBatch"
+                                               ||  end_point == "EditOrder")
+                        &&  ! args.split ('&')
+                                  .any (|A| A.starts_with ("deadline="))
+            =>  format! ("{}{}deadline={}",
+                         query,
+                         if  query.contains ('?')  {  '&'  }  else  {  '?'  },
+                         rfc3339 (now + B)),
+        _   =>  query.clone ()
+    }
+}
+
+
+
+/*  Run the request set up in C to completion, and return the body of the
+ *  reply as a string.  The time taken is noted against the end-point in
+ *  K.query_url. */
+
+fn  perform  (K:  &Kraken_API,  C:  curl::easy::Easy)
+          ->  Result<String, String>
+{
+    let  body  =  Arc::new (Mutex::new (Vec::new ()));
+    let  b     =  body.clone ();
+
+    let  start  =  std::time::Instant::now ();
+    transfer (K, C,  move |data|  {  b.lock ().unwrap ()
+                                      .extend_from_slice (data);
+                                     Ok (())  }) ?;
+    K.latencies.record (&K.query_url, start.elapsed ());
+
+    let  body  =  std::mem::take (&mut *body.lock ().unwrap ());
+    String::from_utf8 (body).map_err (|_| "reply from exchange is not text"
+                                              .to_string ())
+}
+
+
+
+/*  Run the request set up in C to completion, unless it is cut short by the
+ *  cancellation token or time limit in K, passing the body of the reply to
+ *  the sink a piece at a time as it arrives.  We drive the transfer through
+ *  the multi interface so that we can look at the token every few
+ *  milliseconds, rather than wait for curl to give up. */
+
+fn  transfer  (K:  &Kraken_API,
+               mut  C:  curl::easy::Easy,
+               mut  sink:  impl FnMut (&[u8]) -> Result<(), String>
+                                                         + Send + 'static)
+          ->  Result<(), String>
+{
+    let  cancelled  =  || K.cancel_token.as_ref ()
+                                   .is_some_and (Cancel_Token::is_cancelled);
+
+    if  cancelled ()   {   Err ("request cancelled".to_string ()) ?   }
+
+    if let Some (T) = K.request_timeout
+        {   C.timeout (T).map_err (|e| e.to_string ()) ?;   }
+    K.transport.apply (&mut C) ?;
+
+    /*  If the sink fails, or the reply grows too big, curl is told to give
+     *  up, and we report our own error rather than curl's. */
+    let  failure  =  Arc::new (Mutex::new (None));
+    let  f  =  failure.clone ();
+    let  transport  =  K.transport.clone ();
+    let  mut  received  =  0;
+    C.write_function (move |data|  {
+                          received  +=  data.len ();
+                          let  taken  =  transport.admit (received)
+                                                  .and_then (|_| sink (data));
+                          match  taken
+                             {   Ok (())  =>  Ok (data.len ()),
+                                 Err (E)  =>  {  *f.lock ().unwrap ()
+                                                         =  Some (E);
+                                                 Ok (0)  }   }  })
+     .map_err (|e| e.to_string ()) ?;
+
+    let  M  =  curl::multi::Multi::new ();
+    let  H  =  M.add (C).map_err (|e| e.to_string ()) ?;
+
+    while  M.perform ().map_err (|e| e.to_string ()) ?  >  0
+    {
+        if  cancelled ()
+            {   M.remove (H).map_err (|e| e.to_string ()) ?;
+                return  Err ("request cancelled".to_string ());   }
+
+        M.wait (&mut [], Duration::from_millis (10))
+         .map_err (|e| e.to_string ()) ?;
+    }
+
+    let  mut  outcome  =  Ok (());
+    M.messages (|m|  if let Some (r) = m.result_for (&H)  {  outcome = r;  });
+    M.remove (H).map_err (|e| e.to_string ()) ?;
+
+    if let Some (E) = failure.lock ().unwrap ().take ()   {   Err (E) ?   }
+    outcome.map_err (|e| K.transport.failure (&e))
+}
+
+
+
+/*  Render the time in the RFC3339 form Kraken expects, e.g.
+ *  "2022-12-25T09:30:59.360Z", without recourse to a date-time crate. */
+
+fn  rfc3339  (t:  std::time::SystemTime)  ->  String
+{
+    let  since_epoch  =  t.duration_since (std::time::UNIX_EPOCH).unwrap ();
+    let  secs   =  since_epoch.as_secs ();
+    let  (year, month, day)  =  civil_date ((secs / 86400) as i64);
+    let  secs   =  secs % 86400;
+
+    format! ("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+             year, month, day,
+             secs / 3600, secs / 60 % 60, secs % 60,
+             since_epoch.subsec_millis ())
+}
+
+
+
+/*  Convert a count of days since the UNIX epoch into a (year, month, day)
+ *  triple, with months and days counting from one.  This is the civil-from-days
+ *  algorithm of Howard Hinnant. */
+
+pub(crate)  fn  civil_date  (days:  i64)  ->  (i64, i64, i64)
+{
+    let  z    =  days + 719468;
+    let  era  =  z.div_euclid (146097);
+    let  doe  =  z - era * 146097;
+    let  yoe  =  (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let  doy  =  doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let  mp   =  (5 * doy + 2) / 153;
+    let  day  =  doy - (153 * mp + 2) / 5 + 1;
+    let  month  =  if mp < 10 { mp + 3 } else { mp - 9 };
+    let  year   =  yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
 }
 
 
@@ -1118,6 +2484,15 @@ fn  kraken_argument  (O:  &Opt)  ->  &'static  str
                   Opt::CONSOLIDATION    =>  "consolidation",
                   Opt::ID               =>  "id",
                   Opt::CANCEL_RESPONSE  =>  "cancel_response",
+                  Opt::KEY              =>  "key",
+                  Opt::AMOUNT           =>  "amount",
+                  Opt::CL_ORD_ID        =>  "cl_ord_id",
+                  Opt::LOCK_TYPE        =>  "lock_type",
+                  Opt::CURSOR           =>  "cursor",
+                  Opt::METHOD           =>  "method",
+                  Opt::NEW              =>  "new",
+                  Opt::FROM             =>  "from",
+                  Opt::TO               =>  "to",
                   Opt::DESCRIPTION      =>  "description",
                   Opt::REPORT           =>  "report",
                   Opt::__CEILING        =>  ""    }
@@ -1129,7 +2504,7 @@ fn  query_add_options  (K: &mut Kraken_API,
                         permitted_options: &[Opt],
                         mut joiner: char)
 {   for  o  in  permitted_options
-    {   if let Some(K_O) = K.options.get (o)
+    {   if let Some(K_O) = K.option (o)
         {   K.query_url += &(std::mem::replace (&mut joiner, '&').to_string ()
                              + kraken_argument (o) + "=" + K_O);   }  }  }
 
@@ -1147,7 +2522,83 @@ mod  test
 
          let  res  =  v ["result"] ["rfc1123"].as_str ().ok_or ("") ?;
 
-         assert! (res.len () > 0);
+         assert! (! res.is_empty ());
 
          Ok (())
-     }  }
+     }
+
+     #[test]  fn  cancelled_request ()
+     {
+         let  mut  K  =  super::Kraken_API::default ();
+         let  T  =  super::Cancel_Token::new ();
+
+         K.set_cancel_token (T.clone ());
+         T.cancel ();
+
+         assert_eq! (K.server_time (),  Err ("request cancelled".to_string ()));
+     }
+
+     #[test]  fn  rfc3339 ()
+     {
+         let  t  =  std::time::UNIX_EPOCH
+                       +  std::time::Duration::from_millis (1671960659360);
+
+         assert_eq! (super::rfc3339 (t),  "2022-12-25T09:30:59.360Z");
+     }
+
+     #[test]  fn  txid_is_not_userref ()
+     {
+         let  mut  K  =  super::Kraken_API::default ();
+
+         assert! (K.cancel_by_txid ("12345").is_err ());
+         assert! (K.cancel_by_txid ("").is_err ());
+     }
+
+     #[test]  fn  default_options ()
+     {
+         use  super::API_Option  as  Opt;
+
+         let  mut  K  =  super::Kraken_API::default ();
+         K.set_default_opt (Opt::TRADES, true);
+         K.set_default_opt (Opt::CLOSE_TIME, "both");
+         K.set_opt (Opt::CLOSE_TIME, "open");
+         K.clear_all_options ();
+         K.set_opt (Opt::CLOSE_TIME, "close");
+
+         K.query_url  =  "ClosedOrders".to_string ();
+         super::query_add_options (&mut K,  &[Opt::TRADES, Opt::CLOSE_TIME],
+                                   '?');
+         assert_eq! (K.query_url,  "ClosedOrders?trades=true&closetime=close");
+         assert_eq! (K.all_options ().count (),  2);
+     }
+
+     #[test]  fn  order_deadline ()
+     {
+         use  std::time::{Duration, UNIX_EPOCH};
+
+         let  mut  K  =  super::Kraken_API::default ();
+         let  now  =  UNIX_EPOCH  +  Duration::from_millis (1671960659360);
+
+         K.query_url  =  "AddOrder?pair=XBTUSD".to_string ();
+         assert_eq! (super::with_deadline (&K, now),  "AddOrder?pair=XBTUSD");
+
+         K.set_order_latency_budget (Some (Duration::from_millis (500)));
+         assert_eq! (super::with_deadline (&K, now),
+                     "AddOrder?pair=XBTUSD&deadline=2022-12-25T09:30:59.860Z");
+
+         //  The handle's own options are left alone.
+         assert! (K.option (&super::API_Option::DEADLINE).is_none ());
+
+         K.query_url  =  "AddOrder?deadline=2023-01-01T00:00:00Z".to_string ();
+         assert_eq! (super::with_deadline (&K, now),  K.query_url);
+
+         K.query_url  =  "Balance".to_string ();
+         assert_eq! (super::with_deadline (&K, now),  "Balance");
+
+         K.set_order_latency_budget (None);
+         K.query_url  =  "AddOrder?pair=XBTUSD".to_string ();
+         assert_eq! (super::with_deadline (&K, now),  "AddOrder?pair=XBTUSD");
+     }
+
+     #[test]  fn  signing ()
+          {   assert_eq! (super::verify_signing (),  Ok (()));   }  }
@@ -0,0 +1,177 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  The times the exchange has recently taken to answer each end-point, kept
+ *  so that a program can see when it is slowing down. */
+
+
+
+use  crate::Kraken_API;
+use  std::collections::{BTreeMap, VecDeque};
+use  std::sync::Mutex;
+use  std::time::Duration;
+
+
+
+/*  How many of the most recent request times we keep for each end-point. */
+
+const  WINDOW:  usize  =  100;
+
+
+
+/** How long the exchange has been taking to answer requests to one
+    end-point, as given by [Kraken_API::latency_stats].  The time is from
+    the request being sent to the whole reply being back, for each attempt
+    which got a reply; time spent waiting on the rate limiter, or between
+    retries, is not counted.  The mean and 95th percentile are over the last
+    100 replies. */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub  struct  Latency_Stats
+{
+    /** The time taken by the most recent reply. */
+    pub  last:   Duration,
+
+    /** The mean time taken over the window. */
+    pub  mean:   Duration,
+
+    /** The time which 95 percent of the replies in the window took no
+        longer than. */
+    pub  p95:    Duration,
+
+    /** The number of replies timed since the statistics were started or
+        reset, including those no longer in the window. */
+    pub  count:  u64
+}
+
+
+
+#[derive(Default)]
+struct  Timings  {  recent:  VecDeque<Duration>,
+                    count:   u64  }
+
+impl  Timings
+{
+    fn  stats  (&self)  ->  Option<Latency_Stats>
+    {
+        let  last  =  *self.recent.back () ?;
+        let  mut  S  =  self.recent.iter ().copied ().collect::<Vec<_>> ();
+        S.sort ();
+        let  i  =  ((S.len () - 1) as f64 * 0.95).round () as usize;
+        Some (Latency_Stats {  last,
+                               mean:   S.iter ().sum::<Duration> ()
+                                           / S.len () as u32,
+                               p95:    S [i],
+                               count:  self.count  })
+    }
+}
+
+
+
+/*  The timings of every end-point used so far, keyed on the end-point's
+ *  name. */
+
+#[derive(Default)]
+pub(crate)  struct  Latencies  (Mutex<BTreeMap<String, Timings>>);
+
+impl  Latencies
+{
+    /*  Note the time taken by a reply to the query, which may carry
+     *  arguments after a '?'. */
+    pub(crate)  fn  record  (&self,  query:  &str,  latency:  Duration)
+    {
+        let  end_point  =  query.split ('?').next ().unwrap_or ("");
+        let  mut  M  =  self.0.lock ().unwrap ();
+        let  T  =  M.entry (end_point.to_string ()).or_default ();
+        if  T.recent.len () == WINDOW   {   T.recent.pop_front ();   }
+        T.recent.push_back (latency);
+        T.count  +=  1;
+    }
+}
+
+
+
+impl  Kraken_API
+{
+/** How long the exchange has lately been taking to answer requests to
+    `end_point` (named as in the Kraken documentation, e.g. "AddOrder" or
+    "CancelOrder"), or `None` if no reply from it has been timed.  A
+    strategy can use this to place orders less aggressively when the
+    exchange slows down.  Handles from [Kraken_API::share] keep their
+    statistics together.  */
+
+  pub  fn  latency_stats  (&self,  end_point:  &str)
+               ->  Option<Latency_Stats>
+    {
+      self.latencies.0.lock ().unwrap ().get (end_point)
+          .and_then (Timings::stats)
+    }
+
+
+
+/** The [Latency_Stats] of every end-point which has been timed, by name. */
+
+  pub  fn  all_latency_stats  (&self)  ->  BTreeMap<String, Latency_Stats>
+    {
+      self.latencies.0.lock ().unwrap ().iter ()
+          .filter_map (|(E, T)|  Some ((E.clone (), T.stats () ?)))
+          .collect ()
+    }
+
+
+
+/** Forget all the timings taken so far, on this handle and those shared
+    with it.  */
+
+  pub  fn  reset_latency_stats  (&self)
+          {   self.latencies.0.lock ().unwrap ().clear ();   }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  stats  ()
+    {
+        use  super::Latency_Stats;
+        use  std::time::Duration;
+
+        let  K  =  crate::Kraken_API::default ();
+        let  ms  =  Duration::from_millis;
+        for  n  in  1 ..= 150
+            {   K.latencies.record ("AddOrder", ms (n));   }
+        K.latencies.record ("Ticker?pair=XBTUSD", ms (7));
+
+        assert_eq! (K.latency_stats ("AddOrder"),
+                    Some (Latency_Stats {  last:   ms (150),
+                                           mean:   ms (100) + ms (1) / 2,
+                                           p95:    ms (145),
+                                           count:  150  }));
+        assert_eq! (K.latency_stats ("Ticker").map (|S| S.p95),  Some (ms (7)));
+        assert_eq! (K.latency_stats ("CancelOrder"),  None);
+        assert_eq! (K.all_latency_stats ().len (),  2);
+
+        K.reset_latency_stats ();
+        assert! (K.all_latency_stats ().is_empty ());
+    }
+}
@@ -0,0 +1,288 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::Kraken_API;
+use  crate::reply;
+use  crate::strictness;
+use  serde_json  as  JSN;
+
+
+
+/** The kind of a ledger entry.  The exchange adds new kinds from time to
+    time; those the crate does not know about arrive as `OTHER`, holding the
+    exchange's own word for them. */
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub  enum  Ledger_Type
+{
+    /** A trade, or one leg of one. */
+    TRADE,
+    /** Funds paid into the account. */
+    DEPOSIT,
+    /** Funds taken out of the account. */
+    WITHDRAWAL,
+    /** A movement between the account and another of the user's
+        wallets. */
+    TRANSFER,
+    /** A margin trade, or the settlement of a position. */
+    MARGIN,
+    /** The roll-over fee on an open position. */
+    ROLLOVER,
+    /** Funds spent by a "buy crypto" purchase. */
+    SPEND,
+    /** Funds received by a "buy crypto" purchase. */
+    RECEIVE,
+    /** The settlement of a position. */
+    SETTLED,
+    /** A credit made by the exchange. */
+    CREDIT,
+    /** Assets staked or unstaked. */
+    STAKING,
+    /** Kraken's "earn" products, which replaced staking. */
+    EARN,
+    /** Staking or earn rewards. */
+    REWARD,
+    /** A dividend. */
+    DIVIDEND,
+    /** A sale. */
+    SALE,
+    /** A conversion between assets. */
+    CONVERSION,
+    /** A correction made by the exchange. */
+    ADJUSTMENT,
+    /** Anything else. */
+    OTHER (String)
+}
+
+
+
+impl  Ledger_Type
+{
+    /** Interpret the exchange's word for the kind of an entry. */
+    pub  fn  from_kraken_string  (S:  &str)  ->  Self
+    {
+        match  S
+        {
+            "trade"       =>  Ledger_Type::TRADE,
+            "deposit"     =>  Ledger_Type::DEPOSIT,
+            "withdrawal"  =>  Ledger_Type::WITHDRAWAL,
+            "transfer"    =>  Ledger_Type::TRANSFER,
+            "margin"      =>  Ledger_Type::MARGIN,
+            "rollover"    =>  Ledger_Type::ROLLOVER,
+            "spend"       =>  Ledger_Type::SPEND,
+            "receive"     =>  Ledger_Type::RECEIVE,
+            "settled"     =>  Ledger_Type::SETTLED,
+            "credit"      =>  Ledger_Type::CREDIT,
+            "staking"     =>  Ledger_Type::STAKING,
+            "earn"        =>  Ledger_Type::EARN,
+            "reward"      =>  Ledger_Type::REWARD,
+            "dividend"    =>  Ledger_Type::DIVIDEND,
+            "sale"        =>  Ledger_Type::SALE,
+            "conversion"  =>  Ledger_Type::CONVERSION,
+            "adjustment"  =>  Ledger_Type::ADJUSTMENT,
+            _             =>  Ledger_Type::OTHER (S.to_string ())
+        }
+    }
+
+    /** Get the exact string Kraken uses for this kind of entry, as needed
+        for the [API_Option::TYPE](crate::API_Option::TYPE) setting. */
+    pub  fn  as_kraken_string  (&self)  ->  &str
+    {
+        match  self
+        {
+            Ledger_Type::TRADE       =>  "trade",
+            Ledger_Type::DEPOSIT     =>  "deposit",
+            Ledger_Type::WITHDRAWAL  =>  "withdrawal",
+            Ledger_Type::TRANSFER    =>  "transfer",
+            Ledger_Type::MARGIN      =>  "margin",
+            Ledger_Type::ROLLOVER    =>  "rollover",
+            Ledger_Type::SPEND       =>  "spend",
+            Ledger_Type::RECEIVE     =>  "receive",
+            Ledger_Type::SETTLED     =>  "settled",
+            Ledger_Type::CREDIT      =>  "credit",
+            Ledger_Type::STAKING     =>  "staking",
+            Ledger_Type::EARN        =>  "earn",
+            Ledger_Type::REWARD      =>  "reward",
+            Ledger_Type::DIVIDEND    =>  "dividend",
+            Ledger_Type::SALE        =>  "sale",
+            Ledger_Type::CONVERSION  =>  "conversion",
+            Ledger_Type::ADJUSTMENT  =>  "adjustment",
+            Ledger_Type::OTHER (S)   =>  S
+        }
+    }
+}
+
+
+
+/** One entry in the account's ledger, as given by the
+    [Kraken_API::ledgers_info] and [Kraken_API::query_ledgers] end-points. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Ledger_Entry
+{
+    /** The ledger ID. */
+    pub  id:        String,
+
+    /** The reference ID of the trade, deposit, etc. which made the
+        entry. */
+    pub  refid:     String,
+
+    /** UNIX time of the entry. */
+    pub  time:      f64,
+
+    /** What kind of entry this is. */
+    pub  kind:      Ledger_Type,
+
+    /** A finer classification, if the exchange gave one (it is often
+        empty). */
+    pub  subtype:   String,
+
+    /** The asset, e.g. "ZUSD". */
+    pub  asset:     String,
+
+    /** The amount credited (or, if negative, debited). */
+    pub  amount:    f64,
+
+    /** The fee charged. */
+    pub  fee:       f64,
+
+    /** The balance of the asset after the entry. */
+    pub  balance:   f64
+}
+
+
+
+impl  Ledger_Entry
+{
+    /** Interpret one member of the 'ledger' section of a Ledgers reply, or
+        of the 'result' section of a QueryLedgers reply; `id` is the key
+        under which the entry was found. */
+    pub  fn  from_json  (id:  &str,  V:  &JSN::Value)  ->  Result<Self, String>
+    {
+        strictness::check_fields ("Ledger_Entry", V,
+                                  &["refid", "time", "type", "subtype",
+                                    "aclass", "asset", "amount", "fee",
+                                    "balance"]) ?;
+
+        let  number  =  |f: &str|  reply::number (&V [f])
+                                     .map_err (|E| format! ("{}: {}", f, E));
+
+        Ok (Ledger_Entry {
+                id:       id.to_string (),
+                refid:    reply::string (V, "refid") ?,
+                time:     number ("time") ?,
+                kind:     Ledger_Type::from_kraken_string
+                                                 (&reply::string (V, "type") ?),
+                subtype:  V ["subtype"].as_str ().unwrap_or ("").to_string (),
+                asset:    reply::string (V, "asset") ?,
+                amount:   number ("amount") ?,
+                fee:      number ("fee") ?,
+                balance:  number ("balance") ?  })
+    }
+
+    /** Whether this entry is a staking or earn reward: an entry of the
+        "reward" kind, one of the "staking" or "earn" kind with the subtype
+        "reward", or (as the exchange used to record them) a credit of the
+        "staking" kind with no subtype. */
+    pub  fn  is_reward  (&self)  ->  bool
+    {
+        match  self.kind
+           {   Ledger_Type::REWARD  =>  true,
+               Ledger_Type::STAKING | Ledger_Type::EARN
+                   =>  self.subtype == "reward"
+                         ||  (self.kind == Ledger_Type::STAKING
+                                &&  self.subtype.is_empty ()
+                                &&  self.amount > 0.0),
+               _  =>  false   }
+    }
+}
+
+
+
+impl  Kraken_API
+{
+/** Get ledger entries as typed data, newest first.
+
+    This calls the [Kraken_API::ledgers_info] end-point, and so respects the
+    same optional arguments; in particular, the exchange gives at most 50
+    entries at a time, and [API_Option::OFS](crate::API_Option::OFS) picks
+    out later pages.  */
+
+  pub  fn  ledger_entries  (&mut self)  ->  Result<Vec<Ledger_Entry>, String>
+    {
+      let  R  =  reply::result_of (&self.ledgers_info () ?) ?;
+      let  mut  L  =  R ["ledger"].as_object ()
+                         .into_iter ()
+                         .flatten ()
+                         .map (|(id, V)| Ledger_Entry::from_json (id, V))
+                         .collect::<Result<Vec<_>, _>> () ?;
+      L.sort_by (|a, b| b.time.total_cmp (&a.time));
+      Ok (L)
+    }
+
+
+  /*  Every ledger entry the settings pick out, paging through them with the
+   *  OFS option, which is left set. */
+  pub(crate)  fn  all_ledger_entries  (&mut self)
+                   ->  Result<Vec<Ledger_Entry>, String>
+    {
+      let  mut  ledger  =  Vec::new ();
+      loop
+      {
+          self.set_opt (crate::API_Option::OFS, ledger.len ());
+          let  page  =  self.ledger_entries () ?;
+          if  page.is_empty ()   {   return  Ok (ledger);   }
+          ledger.extend (page);
+      }
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  entry  ()  ->  Result<(), String>
+    {
+        use  super::{Ledger_Entry, Ledger_Type};
+
+        let  V  =  serde_json::json! ({ "refid": "TJKLXX-PGMUI-4NTLXU",
+                                        "time": 1688464484.1787,
+                                        "type": "trade",
+                                        "subtype": "",
+                                        "aclass": "currency",
+                                        "asset": "XETH",
+                                        "amount": "-0.25",
+                                        "fee": "0.0000000000",
+                                        "balance": "1.5" });
+        let  E  =  Ledger_Entry::from_json ("L4UESK-KG3EQ-UFO4T5", &V) ?;
+        assert_eq! (E.kind, Ledger_Type::TRADE);
+        assert_eq! ((E.amount, E.balance), (-0.25, 1.5));
+
+        let  K  =  Ledger_Type::from_kraken_string ("nftrebate");
+        assert_eq! (K, Ledger_Type::OTHER ("nftrebate".into ()));
+        assert_eq! (K.as_kraken_string (), "nftrebate");
+        assert_eq! (Ledger_Type::from_kraken_string ("earn"),
+                    Ledger_Type::EARN);
+        Ok (())
+    }
+}
@@ -0,0 +1,345 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option as Opt, Cancel_Token, Instruction, Kraken_API,
+             Order_Type, State_Store};
+use  crate::reply;
+use  serde_json  as  JSN;
+use  std::time::Duration;
+
+
+
+/** Where a one-cancels-other pair of orders has got to. */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub  enum  OCO_State
+{
+    /** Both legs are still in the book. */
+    OPEN,
+
+    /** The take-profit leg has executed, and the stop-loss leg has been
+        cancelled. */
+    TAKE_PROFIT_FILLED,
+
+    /** The stop-loss leg has executed, and the take-profit leg has been
+        cancelled. */
+    STOP_LOSS_FILLED,
+
+    /** One of the legs was cancelled or expired without executing, so the other
+        has been cancelled too. */
+    CANCELLED
+}
+
+
+
+/** A take-profit order and a stop-loss order, either of which cancels the
+    other as soon as it executes.
+
+    The exchange has no such thing for spot trading, so this is emulated by
+    polling the state of the two legs from our end (see [OCO::poll]).  Both legs
+    carry the same user reference, which is what allows a program which has
+    been restarted to pick up the pieces with [Kraken_API::recover_oco];
+    alternatively the OCO can be kept in a [State_Store] with [OCO::save] and
+    brought back with [Kraken_API::restore_oco].
+
+    Note that a leg counts as executed as soon as any of it is filled; the
+    other leg is cancelled at that point, and the remainder of the executing
+    leg is left to complete on its own.  */
+
+#[derive(Clone, Debug)]
+pub  struct  OCO  {  userref:      i32,
+                     take_profit:  String,
+                     stop_loss:    String,
+                     state:        OCO_State  }
+
+
+
+impl  OCO
+{
+    /** The user reference shared by the two legs. */
+    pub  fn  userref  (&self)  ->  i32   {   self.userref   }
+
+    /** The transaction ID of the take-profit (limit order) leg. */
+    pub  fn  take_profit_txid  (&self)  ->  &str   {   &self.take_profit   }
+
+    /** The transaction ID of the stop-loss leg. */
+    pub  fn  stop_loss_txid  (&self)  ->  &str   {   &self.stop_loss   }
+
+    /** The state found at the last poll. */
+    pub  fn  state  (&self)  ->  OCO_State   {   self.state   }
+
+
+    /** Look at the two legs, and if either has executed or gone away cancel
+        the other.  */
+    pub  fn  poll  (&mut self,  K:  &mut Kraken_API)
+               ->  Result<OCO_State, String>
+    {
+        if  self.state != OCO_State::OPEN   {   return  Ok (self.state);   }
+
+        K.clear_all_options ();
+        let  R  =  reply::result_of
+                      (&K.query_orders (format! ("{},{}",
+                                                 self.take_profit,
+                                                 self.stop_loss)) ?) ?;
+
+        let  tp  =  leg_state (&R [&self.take_profit]) ?;
+        let  sl  =  leg_state (&R [&self.stop_loss]) ?;
+
+        self.state  =  match  (tp, sl)
+            {   (Leg::LIVE, Leg::LIVE)  =>  return  Ok (OCO_State::OPEN),
+                (Leg::EXECUTED, _)  =>  OCO_State::TAKE_PROFIT_FILLED,
+                (_, Leg::EXECUTED)  =>  OCO_State::STOP_LOSS_FILLED,
+                _                   =>  OCO_State::CANCELLED   };
+
+        for  (txid, leg)  in  [(&self.take_profit, tp),  (&self.stop_loss, sl)]
+        {   if  leg == Leg::LIVE
+                {   reply::result_of (&K.cancel_order (txid) ?) ?;   }   }
+
+        Ok (self.state)
+    }
+
+
+    /** Poll every `interval` until one of the legs goes, or `stop` is
+        cancelled. */
+    pub  fn  run  (&mut self,  K:  &mut Kraken_API,
+                   interval:  Duration,  stop:  &Cancel_Token)
+              ->  Result<OCO_State, String>
+    {
+        while  ! stop.is_cancelled ()  &&  self.poll (K) ? == OCO_State::OPEN
+        {
+            stop.pause (interval);
+        }
+        Ok (self.state)
+    }
+
+
+    /** Keep the user reference and the transaction IDs of the two legs in
+        `store` under `key`, for [Kraken_API::restore_oco] to pick up after
+        a restart; best done as soon as the OCO is placed.  */
+    pub  fn  save  (&self,  store:  &dyn State_Store,  key:  &str)
+              ->  Result<(), String>
+    {
+        store.put (key, format! ("{} {} {}", self.userref, self.take_profit,
+                                 self.stop_loss).as_bytes ())
+    }
+
+
+    /** Take both legs out of the book. */
+    pub  fn  cancel  (&mut self,  K:  &mut Kraken_API)  ->  Result<(), String>
+    {
+        reply::result_of (&K.cancel_order (&self.userref.to_string ()) ?) ?;
+        self.state  =  OCO_State::CANCELLED;
+        Ok (())
+    }
+}
+
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum  Leg  {  LIVE,  EXECUTED,  DEAD  }
+
+fn  leg_state  (O:  &JSN::Value)  ->  Result<Leg, String>
+{
+    let  executed  =  reply::number (&O ["vol_exec"]) ?  >  0.0;
+    Ok (match  (O ["status"].as_str ().unwrap_or (""),  executed)
+          {   (_, true)                     =>  Leg::EXECUTED,
+              ("pending", _) | ("open", _)  =>  Leg::LIVE,
+              _                             =>  Leg::DEAD   })
+}
+
+
+
+/*  The OCO kept under `key` as the `text` written by OCO::save. */
+
+fn  saved_oco  (key:  &str,  text:  &str)  ->  Result<OCO, String>
+{
+    let  bad  =  ||  format! ("'{}' is not a saved OCO", key);
+    let  F  =  text.split_whitespace ().collect::<Vec<_>> ();
+    let  [userref, take_profit, stop_loss]  =  F [..]
+        else   {   Err (bad ()) ?   };
+
+    Ok (OCO {  userref:  userref.parse ().map_err (|_| bad ()) ?,
+               take_profit:  take_profit.to_string (),
+               stop_loss:    stop_loss.to_string (),
+               state:        OCO_State::OPEN  })
+}
+
+
+
+impl  Kraken_API
+{
+/** Place a one-cancels-other pair of orders to close (part of) a position:
+    a limit order at `take_profit` and a stop-loss order triggered at
+    `stop_loss`, both of `volume` on `pair` in direction `side`.
+
+    Both legs are given the user reference `userref`, which should not be
+    shared with any other orders.  The optional arguments on this object are
+    cleared before the orders are sent.  */
+
+  pub  fn  place_oco<V: std::fmt::Display>  (&mut self,
+                                             pair: &str,
+                                             side: Instruction,
+                                             volume: V,
+                                             take_profit: f64,
+                                             stop_loss: f64,
+                                             userref: i32)
+               ->  Result<OCO, String>
+    {
+      let  P  =  self.pair_info (pair) ?;
+      let  volume  =  volume.to_string ();
+
+      let  place  =  |K: &mut Kraken_API,  order_type,  price|
+            ->  Result<String, String>
+          {
+            K.clear_all_options ();
+            K.set_opt (Opt::PRICE, P.format_price (price));
+            K.set_opt (Opt::USERREF, userref);
+            let  R  =  reply::result_of (&K.add_order (order_type, side,
+                                                       &volume, &P.name) ?) ?;
+            Ok (R ["txid"] [0].as_str ()
+                              .ok_or ("no txid in AddOrder reply") ?
+                              .to_string ())
+          };
+
+      let  take_profit  =  place (self, Order_Type::LIMIT, take_profit) ?;
+      let  stop_loss  =  match  place (self, Order_Type::STOP_LOSS, stop_loss)
+          {   Ok (T)  =>  T,
+              Err (E)  =>  {   let  _  =  self.cancel_order (&take_profit);
+                               Err (E) ?   }   };
+
+      self.clear_all_options ();
+
+      Ok (OCO {  userref,  take_profit,  stop_loss,  state: OCO_State::OPEN  })
+    }
+
+
+
+/** Rebuild an [OCO] kept in `store` under `key` with [OCO::save], or give
+    `None` if nothing is kept there.  The legs are polled straight away, as
+    with [Kraken_API::recover_oco], so if either has executed or gone while
+    we were away the other is cancelled.  */
+
+  pub  fn  restore_oco  (&mut self,  store:  &dyn State_Store,  key:  &str)
+               ->  Result<Option<OCO>, String>
+    {
+      let  Some (B)  =  store.get (key) ?   else   {   return  Ok (None)   };
+      let  mut  O  =  saved_oco (key, &String::from_utf8_lossy (&B)) ?;
+      O.poll (self) ?;
+      Ok (Some (O))
+    }
+
+
+
+/** Rebuild an [OCO] placed earlier with [Kraken_API::place_oco], from the
+    orders on the exchange which carry the given user reference.
+
+    If only one leg is still open, the other is taken to have executed (or been
+    cancelled) while we were away, and the open leg is cancelled straight
+    away, exactly as [OCO::poll] would have done.  */
+
+  pub  fn  recover_oco  (&mut self,  userref:  i32)  ->  Result<OCO, String>
+    {
+      let  mut  legs  =  [None, None];
+
+      for  closed  in  [false, true]
+      {
+          self.clear_all_options ();
+          self.set_opt (Opt::USERREF, userref);
+          let  R  =  if closed  { self.closed_orders () ? }
+                     else        { self.open_orders () ? };
+          let  R  =  reply::result_of (&R) ?;
+          let  section  =  if closed { "closed" } else { "open" };
+
+          for  (txid, O)  in  R [section].as_object ().into_iter ().flatten ()
+          {   let  i  =  match  O ["descr"] ["ordertype"].as_str ()
+                            {   Some ("limit")      =>  0,
+                                Some ("stop-loss")  =>  1,
+                                _                   =>  continue   };
+              if  legs [i].is_none ()
+                  {   legs [i]  =  Some (txid.clone ());   }   }
+      }
+
+      self.clear_all_options ();
+
+      match  legs
+        {   [Some (take_profit), Some (stop_loss)]
+                =>  {   let  mut  O  =  OCO {  userref,  take_profit,
+                                               stop_loss,
+                                               state: OCO_State::OPEN  };
+                        O.poll (self) ?;
+                        Ok (O)   },
+            _   =>  Err (format! ("cannot find both legs of OCO with \
+                                   userref {}",
+                                  userref))   }
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  legs  ()
+    {
+        use  super::{Leg, leg_state};
+        use  serde_json::json;
+
+        let  leg  =  |status: &str, vol_exec: &str|
+                        leg_state (&json! ({"status": status,
+                                            "vol_exec": vol_exec}));
+        assert_eq! (leg ("open", "0.00000000"),  Ok (Leg::LIVE));
+        assert_eq! (leg ("pending", "0"),  Ok (Leg::LIVE));
+        assert_eq! (leg ("open", "0.5"),  Ok (Leg::EXECUTED));
+        assert_eq! (leg ("canceled", "0.1"),  Ok (Leg::EXECUTED));
+        assert_eq! (leg ("closed", "1"),  Ok (Leg::EXECUTED));
+        assert_eq! (leg ("canceled", "0"),  Ok (Leg::DEAD));
+        assert_eq! (leg ("expired", "0"),  Ok (Leg::DEAD));
+        assert! (leg_state (&json! ({"status": "open"})).is_err ());
+    }
+
+
+    #[test]
+    fn  saved  ()
+    {
+        use  super::{OCO_State, saved_oco};
+        use  crate::{Memory_Store, State_Store};
+
+        let  O  =  saved_oco ("exit", "-42 OTP123-AAAAA-BBBBBB \
+                                       OSL456-CCCCC-DDDDDD\n")
+                       .unwrap ();
+        assert_eq! ((O.userref, O.take_profit.as_str (), O.stop_loss.as_str (),
+                     O.state),
+                    (-42, "OTP123-AAAAA-BBBBBB", "OSL456-CCCCC-DDDDDD",
+                     OCO_State::OPEN));
+
+        let  S  =  Memory_Store::new ();
+        O.save (&S, "exit").unwrap ();
+        let  text  =  S.get ("exit").unwrap ().unwrap ();
+        assert_eq! (saved_oco ("exit", &String::from_utf8 (text).unwrap ())
+                        .unwrap ().stop_loss,
+                    "OSL456-CCCCC-DDDDDD");
+
+        for  text  in  ["", "1 A", "1 A B C", "x A B"]
+            {   assert_eq! (saved_oco ("exit", text).unwrap_err (),
+                            "'exit' is not a saved OCO");   }
+    }
+}
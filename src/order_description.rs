@@ -0,0 +1,214 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::Instruction;
+
+
+
+/** The content of the human-readable description the exchange gives of an
+    order, in the `descr.order` member of the replies to order enquiries and
+    to [Kraken_API::add_order](crate::Kraken_API::add_order).
+
+    ```ignore
+    let  D  =  KKN::Order_Description::parse
+                   ("buy 1.25 XBTUSD @ limit 30000.0 with 2:1 leverage") ?;
+    assert_eq! (D.order_type, "limit");
+    assert_eq! (D.leverage, Some (2));
+    ```  */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Order_Description
+{
+    /** Whether the order buys or sells. */
+    pub  side:        Instruction,
+
+    /** The volume of the order. */
+    pub  volume:      f64,
+
+    /** The pair, e.g. "XBTUSD". */
+    pub  pair:        String,
+
+    /** The order type, in the form the exchange takes in
+        [API_Option::ORDER_TYPE](crate::API_Option::ORDER_TYPE), e.g.
+        "limit", "stop-loss" or "take-profit-limit". */
+    pub  order_type:  String,
+
+    /** The primary price as written, which may carry a leading `+` or `-`,
+        or a trailing `%`, for relative and trailing prices; `None` for
+        market orders. */
+    pub  price:       Option<String>,
+
+    /** The secondary price, for the "-limit" order types. */
+    pub  price2:      Option<String>,
+
+    /** The leverage, e.g. 2 for "with 2:1 leverage"; `None` if the order is
+        not on margin. */
+    pub  leverage:    Option<u32>
+}
+
+
+
+impl  Order_Description
+{
+    /** Interpret a description such as "sell 0.5 XBTUSD @ stop loss 25000.0
+        -> limit 24900.0".  Anything after a comma, such as a conditional
+        close, is ignored. */
+    pub  fn  parse  (text:  &str)  ->  Result<Self, String>
+    {
+        let  bad  =  || format! ("cannot understand order description '{}'",
+                                 text);
+
+        let  text_  =  text.split (',').next ().unwrap_or ("");
+        let  (text_, leverage)  =  match  text_.split_once (" with ")
+        {
+            Some ((T, L))  =>  (T,
+                                Some (L.trim ().trim_end_matches ("leverage")
+                                       .trim ().split (':').next ()
+                                       .and_then (|N| N.parse::<u32> ().ok ())
+                                       .ok_or_else (bad) ?)),
+            None  =>  (text_, None)
+        };
+
+        let  (order, terms)  =  text_.split_once (" @ ").ok_or_else (bad) ?;
+        let  mut  W  =  order.split_whitespace ();
+        let  side  =  match  W.next ()
+        {
+            Some ("buy")   =>  Instruction::BUY,
+            Some ("sell")  =>  Instruction::SELL,
+            _  =>  Err (bad ()) ?
+        };
+        let  volume  =  W.next ().and_then (|V| V.parse::<f64> ().ok ())
+                                 .ok_or_else (bad) ?;
+        let  pair  =  W.next ().ok_or_else (bad) ?.to_string ();
+
+        /*  The terms are either "type words price", or that followed by
+         *  "-> limit price2". */
+        let  (primary, secondary)  =  match  terms.split_once ("->")
+            {   Some ((P, S))  =>  (P, Some (S)),
+                None  =>  (terms, None)   };
+
+        let  (mut  order_type, price)  =  type_and_price (primary);
+        if  order_type.is_empty ()   {   Err (bad ()) ?   }
+
+        let  price2  =  match  secondary
+        {
+            Some (S)  =>  {  let  (T, P)  =  type_and_price (S);
+                             if  T != "limit"  ||  P.is_none ()
+                                 {   Err (bad ()) ?   }
+                             order_type  +=  "-limit";
+                             P  }
+            None  =>  None
+        };
+
+        Ok (Order_Description {  side,  volume,  pair,  order_type,  price,
+                                 price2,  leverage  })
+    }
+}
+
+
+
+/** Write the description out as the exchange would, so that
+    [Order_Description::parse] reads it back the same. */
+
+impl  std::fmt::Display  for  Order_Description
+{
+    fn  fmt  (&self,  f:  &mut std::fmt::Formatter)  ->  std::fmt::Result
+    {
+        let  order_type  =  match  self.price2
+           {   Some (_)  =>  self.order_type.trim_end_matches ("-limit"),
+               None  =>  &self.order_type   };
+
+        write! (f, "{} {} {} @ {}", self.side.as_kraken_string (),
+                self.volume, self.pair, order_type.replace ('-', " ")) ?;
+        if let Some (P) = &self.price    {   write! (f, " {}", P) ?;   }
+        if let Some (P) = &self.price2
+            {   write! (f, " -> limit {}", P) ?;   }
+        if let Some (L) = self.leverage
+            {   write! (f, " with {}:1 leverage", L) ?;   }
+        Ok (())
+    }
+}
+
+
+
+/*  Split e.g. "stop loss 25000.0" into ("stop-loss", Some ("25000.0")); the
+ *  price is the first word which starts with a digit, sign or '#'. */
+
+fn  type_and_price  (text:  &str)  ->  (String, Option<String>)
+{
+    let  mut  words  =  Vec::new ();
+    let  mut  price  =  None;
+
+    for  W  in  text.split_whitespace ()
+    {
+        if  W.starts_with (|c: char| c.is_ascii_digit () || "+-#.".contains (c))
+            {   price  =  Some (W.to_string ());   break;   }
+        words.push (W);
+    }
+
+    (words.join ("-"), price)
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  parse  ()
+    {
+        use  super::Order_Description  as  D;
+        use  crate::Instruction;
+
+        let  O  =  D::parse ("buy 1.25000000 XBTUSD @ limit 30000.0 \
+                               with 2:1 leverage").unwrap ();
+        assert_eq! (O,  D {  side:        Instruction::BUY,
+                             volume:      1.25,
+                             pair:        "XBTUSD".into (),
+                             order_type:  "limit".into (),
+                             price:       Some ("30000.0".into ()),
+                             price2:      None,
+                             leverage:    Some (2)  });
+
+        let  O  =  D::parse ("sell 0.5 XBTUSD @ stop loss 25000.0 -> limit \
+                               24900.0, close position @ limit 1").unwrap ();
+        assert_eq! (O.side, Instruction::SELL);
+        assert_eq! (O.order_type, "stop-loss-limit");
+        assert_eq! (O.price2.as_deref (), Some ("24900.0"));
+        assert_eq! (O.leverage, None);
+
+        let  O  =  D::parse ("sell 2.0 ETHUSD @ market").unwrap ();
+        assert_eq! ((O.order_type.as_str (), O.price), ("market", None));
+
+        let  O  =  D::parse ("buy 1 XBTUSD @ trailing stop +2.0%").unwrap ();
+        assert_eq! (O.order_type, "trailing-stop");
+        assert_eq! (O.price.as_deref (), Some ("+2.0%"));
+
+        for  text  in  ["buy 1.25 XBTUSD @ limit 30000.0 with 2:1 leverage",
+                        "sell 0.5 XBTUSD @ stop loss 25000.0 -> limit 24900.0",
+                        "sell 2 ETHUSD @ market",
+                        "buy 1 XBTUSD @ trailing stop +2.0%"].iter ()
+            {   assert_eq! (D::parse (text).unwrap ().to_string (),  *text);   }
+
+        assert! (D::parse ("hold 1 XBTUSD @ limit 3").is_err ());
+        assert! (D::parse ("buy 1 XBTUSD").is_err ());
+    }
+}
@@ -0,0 +1,672 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option, Batch_Order, Instruction, Kraken_API,
+             Order_Description, Order_Type, Own_Trade};
+use  crate::{history, reply};
+use  crate::strictness;
+use  serde_json  as  JSN;
+use  std::collections::BTreeMap;
+use  std::time::{Duration, Instant};
+
+
+
+/** The state of an order.  Any state the crate does not know about arrives
+    as `OTHER`, holding the exchange's own word for it. */
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub  enum  Order_Status
+{
+    /** Sent, but not yet in the book. */
+    PENDING,
+    /** In the book. */
+    OPEN,
+    /** Completely filled. */
+    CLOSED,
+    /** Cancelled, by the user or by the exchange. */
+    CANCELED,
+    /** Taken out of the book at its expiry time. */
+    EXPIRED,
+    /** Anything else. */
+    OTHER (String)
+}
+
+
+
+impl  Order_Status
+{
+    /** Interpret the exchange's word for the state of an order. */
+    pub  fn  from_kraken_string  (S:  &str)  ->  Self
+    {
+        match  S
+        {
+            "pending"   =>  Order_Status::PENDING,
+            "open"      =>  Order_Status::OPEN,
+            "closed"    =>  Order_Status::CLOSED,
+            "canceled"  =>  Order_Status::CANCELED,
+            "expired"   =>  Order_Status::EXPIRED,
+            _           =>  Order_Status::OTHER (S.to_string ())
+        }
+    }
+
+    /** Get the exact string Kraken uses for this state. */
+    pub  fn  as_kraken_string  (&self)  ->  &str
+    {
+        match  self
+        {
+            Order_Status::PENDING    =>  "pending",
+            Order_Status::OPEN       =>  "open",
+            Order_Status::CLOSED     =>  "closed",
+            Order_Status::CANCELED   =>  "canceled",
+            Order_Status::EXPIRED    =>  "expired",
+            Order_Status::OTHER (S)  =>  S
+        }
+    }
+
+    /** Whether an order in this state is in the book, or about to be. */
+    pub  fn  is_open  (&self)  ->  bool
+          {   matches! (self,  Order_Status::PENDING | Order_Status::OPEN)   }
+
+    /** Whether an order in this state is finished with, and will not change
+        again: closed, cancelled or expired.  A state the crate does not know
+        is not taken to be terminal. */
+    pub  fn  is_terminal  (&self)  ->  bool
+    {
+        matches! (self,  Order_Status::CLOSED | Order_Status::CANCELED
+                                              | Order_Status::EXPIRED)
+    }
+}
+
+
+
+/** An order, as reported by the [Kraken_API::open_orders],
+    [Kraken_API::closed_orders] and [Kraken_API::query_orders] end-points. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Order
+{
+    /** The order's transaction ID. */
+    pub  txid:        String,
+
+    /** The pair, as the exchange describes it (e.g. "XBTUSD"). */
+    pub  pair:        String,
+
+    /** Whether the order buys or sells. */
+    pub  side:        Instruction,
+
+    /** The order type, e.g. "limit" or "stop-loss". */
+    pub  order_type:  String,
+
+    /** The primary price of the order (zero for market orders). */
+    pub  price:       f64,
+
+    /** The volume of the order. */
+    pub  volume:      f64,
+
+    /** How much of the volume has been executed. */
+    pub  vol_exec:    f64,
+
+    /** The total cost of what has been executed, in the quote currency. */
+    pub  cost:        f64,
+
+    /** The total fee paid so far, in the quote currency. */
+    pub  fee:         f64,
+
+    /** The state of the order. */
+    pub  status:      Order_Status,
+
+    /** The user reference given to the order, if any. */
+    pub  userref:     Option<i64>,
+
+    /** UNIX time at which the order was placed. */
+    pub  open_time:   f64,
+
+    /** The exchange's description of the order, picked apart; `None` if
+        there was none, or it could not be understood. */
+    pub  description:  Option<Order_Description>
+}
+
+
+
+impl  Order
+{
+    /** Interpret one entry of the 'result' section of an order enquiry (or
+        of its 'open' or 'closed' member); `txid` is the key under which the
+        entry was found. */
+    pub  fn  from_json  (txid:  &str,  V:  &JSN::Value)
+              ->  Result<Self, String>
+    {
+        strictness::check_fields ("Order", V,
+                                  &["refid", "userref", "cl_ord_id", "status",
+                                    "opentm", "starttm", "expiretm", "descr",
+                                    "vol", "vol_exec", "cost", "fee", "price",
+                                    "stopprice", "limitprice", "trigger",
+                                    "margin", "misc", "oflags", "trades",
+                                    "closetm", "reason", "sender_sub_id",
+                                    "amended"]) ?;
+
+        let  number  =  |V: &JSN::Value, f: &str|
+                            reply::number (&V [f])
+                                  .map_err (|E| format! ("{}: {}", f, E));
+        let  D  =  &V ["descr"];
+
+        Ok (Order {  txid:        txid.to_string (),
+                     pair:        reply::string (D, "pair") ?,
+                     side:        if  D ["type"] == "sell"
+                                      { Instruction::SELL }
+                                  else  { Instruction::BUY },
+                     order_type:  reply::string (D, "ordertype") ?,
+                     price:       number (D, "price") ?,
+                     volume:      number (V, "vol") ?,
+                     vol_exec:    number (V, "vol_exec") ?,
+                     cost:        number (V, "cost") ?,
+                     fee:         number (V, "fee") ?,
+                     status:      Order_Status::from_kraken_string
+                                          (&reply::string (V, "status") ?),
+                     userref:     V ["userref"].as_i64 (),
+                     open_time:   number (V, "opentm") ?,
+                     description:  D ["order"].as_str ()
+                                     .and_then (|O| Order_Description::parse (O)
+                                                     .ok ())  })
+    }
+
+    /** The order in the form in which the exchange gives it, as
+        [Order::from_json] reads it. */
+    pub  fn  to_json  (&self)  ->  JSN::Value
+    {
+        let  mut  D  =  JSN::json! ({"pair": self.pair,
+                                     "type": self.side.as_kraken_string (),
+                                     "ordertype": self.order_type,
+                                     "price": self.price.to_string ()});
+        if let Some (O) = &self.description
+            {   D ["order"]  =  O.to_string ().into ();   }
+
+        JSN::json! ({"descr": D,
+                     "vol": self.volume.to_string (),
+                     "vol_exec": self.vol_exec.to_string (),
+                     "cost": self.cost.to_string (),
+                     "fee": self.fee.to_string (),
+                     "status": self.status.as_kraken_string (),
+                     "userref": self.userref,
+                     "opentm": self.open_time})
+    }
+
+    /** Whether the order is still in the book (or about to be). */
+    pub  fn  is_open  (&self)  ->  bool   {   self.status.is_open ()   }
+
+    /** Whether the order is finished with; see
+        [Order_Status::is_terminal]. */
+    pub  fn  is_terminal  (&self)  ->  bool   {   self.status.is_terminal ()   }
+
+    /** Whether some, but not all, of the order's volume has been executed,
+        whatever its state: an order can be partly filled while still open,
+        or when it was cancelled or expired. */
+    pub  fn  is_partially_filled  (&self)  ->  bool
+          {   self.vol_exec > 0.0  &&  self.vol_exec < self.volume   }
+}
+
+
+
+/** How the wait in [Kraken_API::wait_for_fill] ended; each carries the
+    order as last seen. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  enum  Fill_Outcome
+{
+    /** The order was completely filled. */
+    FILLED (Order),
+
+    /** At least the asked-for fraction of the order was filled, but not all
+        of it (yet). */
+    PARTIALLY_FILLED (Order),
+
+    /** The order was cancelled, or expired, before enough of it was
+        filled; some of it may have been. */
+    CANCELLED (Order),
+
+    /** Time ran out with not enough of the order filled. */
+    TIMED_OUT (Order)
+}
+
+
+
+/*  Has the wait for `fraction` of the order to be filled come to an end?  If
+ *  so, this gives the kind of outcome. */
+
+fn  fill_outcome  (O:  &Order,  fraction:  f64)
+          ->  Option<fn (Order) -> Fill_Outcome>
+{
+    if  O.vol_exec >= O.volume
+        {   Some (Fill_Outcome::FILLED)   }
+    else if  fraction < 1.0  &&  O.vol_exec >= fraction * O.volume
+        {   Some (Fill_Outcome::PARTIALLY_FILLED)   }
+    else if  ! O.is_open ()
+        {   Some (Fill_Outcome::CANCELLED)   }
+    else
+        {   None   }
+}
+
+
+
+/*  How often wait_for_fill asks after the order. */
+
+const  FILL_POLL:  Duration  =  Duration::from_secs (3);
+
+
+
+/** What has come of an order so far, worked out from its trades: see
+    [Fill_Summary::of].  */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Fill_Summary
+{
+    /** The volume executed. */
+    pub  executed:       f64,
+
+    /** The volume-weighted average price of the trades; zero if there have
+        been none. */
+    pub  average_price:  f64,
+
+    /** The total cost of the trades, in the quote currency. */
+    pub  cost:           f64,
+
+    /** The total fee paid on the trades. */
+    pub  fees:           f64,
+
+    /** The volume of the order still to be executed; zero once the order is
+        filled. */
+    pub  remaining:      f64,
+
+    /** How many trades went into the summary. */
+    pub  trades:         usize
+}
+
+
+
+impl  Fill_Summary
+{
+    /** Sum up those of the `trades` which belong to the order, as got (for
+        example) by passing the entries of [Kraken_API::trades_info_all] to
+        [Own_Trade::from_json], or from the WebSockets ownTrades feed; other
+        trades in the list are passed over.  The figures are only as complete
+        as the list of trades.  */
+    pub  fn  of  (order:  &Order,  trades:  &[Own_Trade])  ->  Self
+    {
+        let  mut  S  =  Fill_Summary {  executed: 0.0,  average_price: 0.0,
+                                       cost: 0.0,  fees: 0.0,
+                                       remaining: 0.0,  trades: 0  };
+
+        for  T  in  trades.iter ().filter (|T| T.order_txid == order.txid)
+        {
+            S.executed  +=  T.volume;
+            S.cost      +=  T.cost;
+            S.fees      +=  T.fee;
+            S.trades    +=  1;
+        }
+
+        if  S.executed > 0.0   {   S.average_price  =  S.cost / S.executed;   }
+        S.remaining  =  (order.volume - S.executed).max (0.0);
+        S
+    }
+}
+
+
+
+/** What became of one order sent with [Kraken_API::place_order] or
+    [Kraken_API::place_order_batch]: the transaction IDs the exchange gave
+    it, alongside the references it was sent with, which the exchange does
+    not echo back. */
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub  struct  Placed_Order
+{
+    /** The transaction IDs of the order (normally just one); empty if the
+        order was refused, or only validated. */
+    pub  txids:      Vec<String>,
+
+    /** The [API_Option::USERREF] the order was sent with, if any. */
+    pub  userref:    Option<i64>,
+
+    /** The [API_Option::CL_ORD_ID] the order was sent with, if any. */
+    pub  cl_ord_id:  Option<String>,
+
+    /** Why the exchange refused this order, when it took others in the same
+        batch. */
+    pub  error:      Option<String>
+}
+
+
+
+/*  The references given to an order among its options. */
+
+fn  references<'a>  (option:  impl Fn (&API_Option) -> Option<&'a String>)
+          ->  (Option<i64>, Option<String>)
+{
+    (option (&API_Option::USERREF).and_then (|U| U.parse ().ok ()),
+     option (&API_Option::CL_ORD_ID).cloned ())
+}
+
+
+
+/*  The transaction IDs in an AddOrder result, or one member of the 'orders'
+ *  of an AddOrderBatch result; the former gives a list, the latter a single
+ *  string. */
+
+fn  txids_of  (V:  &JSN::Value)  ->  Vec<String>
+{
+    match  &V ["txid"]
+    {
+        JSN::Value::String (T)  =>  vec! [T.clone ()],
+        T  =>  T.as_array ().into_iter ().flatten ()
+                .filter_map (|T| T.as_str ().map (str::to_string))
+                .collect ()
+    }
+}
+
+
+
+/*  Pair the 'orders' of an AddOrderBatch result with the references of the
+ *  orders sent, which the exchange reports in the same order. */
+
+fn  batch_from_json  (R:  &JSN::Value,  sent:  &[Batch_Order])
+          ->  Result<Vec<Placed_Order>, String>
+{
+    let  orders  =  R ["orders"].as_array ()
+                      .ok_or ("no orders in reply from exchange") ?;
+    if  orders.len () != sent.len ()
+        {   Err (format! ("{} orders sent but {} reported by the exchange",
+                          sent.len (), orders.len ())) ?   }
+
+    Ok (orders.iter ()
+              .zip (sent)
+              .map (|(V, O)|  {
+                   let  (userref, cl_ord_id)
+                          =  references (|o| O.options.get (o));
+                   Placed_Order {  txids:  txids_of (V),
+                                   userref,
+                                   cl_ord_id,
+                                   error:  V ["error"].as_str ()
+                                                      .map (str::to_string)  }
+              })
+              .collect ())
+}
+
+
+
+impl  Kraken_API
+{
+/** Place an order with [Kraken_API::add_order], and give back its
+    transaction IDs together with the [API_Option::USERREF] and
+    [API_Option::CL_ORD_ID] settings it was sent with.  */
+
+  pub  fn  place_order<V: std::fmt::Display>  (&mut self,
+                                               order_type: Order_Type,
+                                               direction: Instruction,
+                                               volume:  V,
+                                               pair:  &str)
+               ->  Result<Placed_Order, String>
+    {
+      let  (userref, cl_ord_id)  =  references (|o| self.option (o));
+      let  R  =  reply::result_of (&self.add_order (order_type, direction,
+                                                   volume, pair) ?) ?;
+      Ok (Placed_Order {  txids:  txids_of (&R),  userref,  cl_ord_id,
+                          error:  None  })
+    }
+
+
+
+/** Place a batch of orders with [Kraken_API::add_order_batch], and give
+    back the transaction IDs of each, in the order they were given, together
+    with the [API_Option::USERREF] and [API_Option::CL_ORD_ID] each was sent
+    with.  An order the exchange refused while taking the others has no
+    transaction IDs, and carries the reason in [Placed_Order::error].  */
+
+  pub  fn  place_order_batch  (&mut self,  pair:  &str,
+                               orders:  &[Batch_Order])
+               ->  Result<Vec<Placed_Order>, String>
+    {
+      let  R  =  reply::result_of (&self.add_order_batch (pair, orders) ?) ?;
+      batch_from_json (&R, orders)
+    }
+
+
+
+/** Wait until the order `txid` has been completely filled, or has been
+    cancelled or has expired, or until `timeout` has passed, whichever comes
+    first.  See [Kraken_API::wait_for_partial_fill].  */
+
+  pub  fn  wait_for_fill  (&mut self,  txid:  &str,  timeout:  Duration)
+               ->  Result<Fill_Outcome, String>
+    {
+      self.wait_for_partial_fill (txid, 1.0, timeout)
+    }
+
+
+
+/** Wait until at least `fraction` (between 0 and 1) of the volume of the
+    order `txid` has been filled, or the order has been cancelled or has
+    expired, or until `timeout` has passed, whichever comes first.
+
+    This asks after the order with the [Kraken_API::query_orders] end-point
+    every three seconds, clearing any [API_Option::TRADES] and
+    [API_Option::USERREF] settings; a cancellation token installed with
+    [Kraken_API::set_cancel_token](crate::Kraken_API::set_cancel_token) cuts
+    the wait short with an error.  */
+
+  pub  fn  wait_for_partial_fill  (&mut self,  txid:  &str,  fraction:  f64,
+                                   timeout:  Duration)
+               ->  Result<Fill_Outcome, String>
+    {
+      self.clear_opt (API_Option::TRADES);
+      self.clear_opt (API_Option::USERREF);
+      let  give_up  =  Instant::now () + timeout;
+
+      loop
+      {
+          let  R  =  reply::result_of (&self.query_orders (txid.to_string ())
+                                            ?) ?;
+          let  O  =  Order::from_json (txid,
+                                       R.get (txid)
+                                        .ok_or (format! ("no order '{}'",
+                                                         txid)) ?) ?;
+          if let Some (outcome) = fill_outcome (&O, fraction)
+              {   return  Ok (outcome (O));   }
+
+          let  now  =  Instant::now ();
+          if  now >= give_up   {   return  Ok (Fill_Outcome::TIMED_OUT (O));   }
+
+          let  delay  =  FILL_POLL.min (give_up - now);
+          match  &self.cancel_token
+          {
+              Some (T)  =>  {  T.pause (delay);
+                               if  T.is_cancelled ()
+                                   {   Err ("request cancelled") ?   }  }
+              None      =>  std::thread::sleep (delay)
+          }
+      }
+    }
+
+
+
+/** Get all the orders open on the account as typed data.
+
+    This calls the [Kraken_API::open_orders] end-point, respecting any
+    [API_Option::USERREF] setting.  */
+
+  pub  fn  current_orders  (&mut self)  ->  Result<Vec<Order>, String>
+    {
+      self.clear_opt (API_Option::TRADES);
+      let  R  =  reply::result_of (&self.open_orders () ?) ?;
+      R ["open"].as_object ()
+                .into_iter ()
+                .flatten ()
+                .map (|(txid, V)| Order::from_json (txid, V))
+                .collect ()
+    }
+
+
+
+/** Get the details of any number of orders, by transaction ID, as typed
+    data keyed by ID.
+
+    The [Kraken_API::query_orders] end-point only takes 50 IDs at a time, so
+    the list is split up and the end-point called as many times as needed, one
+    call after another.  Any [API_Option::USERREF] setting is respected, and
+    [API_Option::TRADES] is cleared.  */
+
+  pub  fn  query_orders_all  (&mut self,  txids:  &[&str])
+             ->  Result<BTreeMap<String, Order>, String>
+    {
+      self.clear_opt (API_Option::TRADES);
+
+      history::query_chunked (self, txids, QUERY_ORDERS_LIMIT,
+                              Kraken_API::query_orders) ?
+          .iter ()
+          .map (|(txid, V)|  Ok ((txid.clone (), Order::from_json (txid, V) ?)))
+          .collect ()
+    }
+}
+
+
+
+/*  The most transaction IDs the QueryOrders end-point will take at once. */
+
+const  QUERY_ORDERS_LIMIT:  usize  =  50;
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  fill  ()
+    {
+        use  super::{Fill_Outcome, Order, Order_Status, fill_outcome};
+        use  crate::Instruction;
+
+        let  order  =  |status: &str, vol_exec: f64|
+                          Order {  txid:  "OQCLML-BW3P3-BUCMWZ".into (),
+                                   pair:  "XBTUSD".into (),
+                                   side:  Instruction::BUY,
+                                   order_type:  "limit".into (),
+                                   price:  30000.0,
+                                   volume:  2.0,
+                                   vol_exec,
+                                   cost:  0.0,
+                                   fee:  0.0,
+                                   status:  Order_Status::from_kraken_string
+                                                                   (status),
+                                   userref:  None,
+                                   open_time:  0.0,
+                                   description:  None  };
+
+        let  outcome  =  |O: Order, fraction: f64|
+                            fill_outcome (&O, fraction).map (|F| F (O));
+
+        assert! (outcome (order ("open", 0.5), 1.0).is_none ());
+        assert! (matches! (outcome (order ("closed", 2.0), 1.0),
+                           Some (Fill_Outcome::FILLED (_))));
+        assert! (matches! (outcome (order ("open", 1.5), 0.75),
+                           Some (Fill_Outcome::PARTIALLY_FILLED (_))));
+        assert! (matches! (outcome (order ("canceled", 0.5), 0.75),
+                           Some (Fill_Outcome::CANCELLED (_))));
+        assert! (matches! (outcome (order ("expired", 0.0), 1.0),
+                           Some (Fill_Outcome::CANCELLED (_))));
+
+        assert! (order ("canceled", 0.5).is_terminal ());
+        assert! (order ("canceled", 0.5).is_partially_filled ());
+        assert! (! order ("pending", 0.0).is_terminal ());
+        assert! (! order ("closed", 2.0).is_partially_filled ());
+        assert_eq! (order ("amended", 0.0).status,
+                    Order_Status::OTHER ("amended".into ()));
+        assert! (! Order_Status::OTHER ("amended".into ()).is_terminal ());
+    }
+
+
+    #[test]
+    fn  summary  ()
+    {
+        use  super::{Fill_Summary, Order, Order_Status};
+        use  crate::Instruction;
+        use  crate::test_fixtures::own_trade;
+
+        let  O  =  Order {  txid:  "OQCLML-BW3P3-BUCMWZ".into (),
+                            pair:  "XBTUSD".into (),
+                            side:  Instruction::BUY,
+                            order_type:  "limit".into (),
+                            price:  30000.0,
+                            volume:  2.0,
+                            vol_exec:  1.5,
+                            cost:  0.0,
+                            fee:  0.0,
+                            status:  Order_Status::OPEN,
+                            userref:  None,
+                            open_time:  0.0,
+                            description:  None  };
+
+        let  trade  =  |order_txid: &str, volume: f64, price: f64|
+                          own_trade ("", order_txid, 0.0, Instruction::BUY,
+                                     price, volume);
+
+        let  S  =  Fill_Summary::of (&O, &[trade (&O.txid, 1.0, 30000.0),
+                                          trade ("OTHER", 5.0, 1.0),
+                                          trade (&O.txid, 0.5, 27000.0)]);
+        assert_eq! (S,  Fill_Summary {  executed:  1.5,
+                                        average_price:  29000.0,
+                                        cost:  43500.0,
+                                        fees:  43.5,
+                                        remaining:  0.5,
+                                        trades:  2  });
+
+        assert_eq! (Fill_Summary::of (&O, &[]).average_price,  0.0);
+    }
+
+
+    #[test]
+    fn  batch  ()  ->  Result<(), String>
+    {
+        use  super::{batch_from_json, txids_of};
+        use  crate::{API_Option, Batch_Order, Instruction, Order_Type};
+        use  serde_json::json;
+
+        assert_eq! (txids_of (&json! ({"descr": {"order": "buy 1 XBTUSD"},
+                                       "txid": ["OUF4EM-FRGI2-MQMWZD"]})),
+                    ["OUF4EM-FRGI2-MQMWZD"]);
+
+        let  sent  =  [Batch_Order::new (Order_Type::LIMIT, Instruction::BUY, 1)
+                            .set_opt (API_Option::USERREF, 42),
+                       Batch_Order::new (Order_Type::LIMIT, Instruction::BUY, 2)
+                            .set_opt (API_Option::CL_ORD_ID, "grid-2")];
+        let  P  =  batch_from_json
+                       (&json! ({"orders": [{"txid": "OS5CFR-MPXYB-ABC6CP"},
+                                            {"error": "EOrder:Margin level \
+                                                       too low"}]}),
+                        &sent) ?;
+
+        assert_eq! ((P [0].txids.len (), P [0].userref), (1, Some (42)));
+        assert! (P [1].txids.is_empty ());
+        assert_eq! (P [1].cl_ord_id.as_deref (), Some ("grid-2"));
+        assert! (P [1].error.is_some ());
+
+        assert! (batch_from_json (&json! ({"orders": []}), &sent).is_err ());
+        Ok (())
+    }
+}
@@ -0,0 +1,434 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option, Kraken_API};
+use  crate::reply;
+use  crate::strictness;
+use  serde_json  as  JSN;
+
+
+
+/** The facts about a trading pair which are needed to put together a valid
+    order, as given by the [Kraken_API::asset_pairs] end-point.  */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Pair_Info
+{
+    /** Kraken's canonical name for the pair, e.g. "XXBTZUSD". */
+    pub  name:           String,
+
+    /** The alternative name, e.g. "XBTUSD". */
+    pub  altname:        String,
+
+    /** The name used by the WebSocket API, e.g. "XBT/USD". */
+    pub  wsname:         String,
+
+    /** The asset being bought or sold, e.g. "XXBT". */
+    pub  base:           String,
+
+    /** The asset the price is expressed in, e.g. "ZUSD". */
+    pub  quote:          String,
+
+    /** Number of decimal places allowed in a price. */
+    pub  pair_decimals:  usize,
+
+    /** Number of decimal places allowed in a volume. */
+    pub  lot_decimals:   usize,
+
+    /** The smallest volume an order may have, if the exchange told us. */
+    pub  ordermin:       Option<f64>,
+
+    /** The fee for taking liquidity, in percent, at the lowest volume tier,
+        if the exchange told us. */
+    pub  taker_fee:      Option<f64>,
+
+    /** The fee for making liquidity, in percent, at the lowest volume tier,
+        if the exchange told us. */
+    pub  maker_fee:      Option<f64>
+}
+
+
+
+/*  The percentage in the first tier of a fee schedule, which is a list of
+ *  [volume, percent] pairs. */
+
+fn  first_tier  (V:  &JSN::Value)  ->  Result<Option<f64>, String>
+{
+    V.get (0).map (|T| reply::number (&T [1])).transpose ()
+}
+
+
+
+impl  Pair_Info
+{
+    /** Interpret one entry of the 'result' section of an AssetPairs reply;
+        `name` is the key under which the entry was found. */
+    pub  fn  from_json  (name:  &str,  V:  &JSN::Value)
+              ->  Result<Self, String>
+    {
+        strictness::check_fields ("Pair_Info", V,
+                                  &["altname", "wsname", "aclass_base", "base",
+                                    "aclass_quote", "quote", "lot",
+                                    "cost_decimals", "pair_decimals",
+                                    "lot_decimals", "lot_multiplier",
+                                    "leverage_buy", "leverage_sell", "fees",
+                                    "fees_maker", "fee_volume_currency",
+                                    "margin_call", "margin_stop", "ordermin",
+                                    "costmin", "tick_size", "status",
+                                    "long_position_limit",
+                                    "short_position_limit"]) ?;
+
+        let  decimals  =  |f: &str|  V [f].as_u64 ()
+                                          .map (|n| n as usize)
+                                          .ok_or (format! ("no '{}' for {}",
+                                                           f, name));
+        Ok (Pair_Info {
+                name:           name.to_string (),
+                altname:        reply::string (V, "altname") ?,
+                wsname:         V ["wsname"].as_str ().unwrap_or ("")
+                                             .to_string (),
+                base:           reply::string (V, "base") ?,
+                quote:          reply::string (V, "quote") ?,
+                pair_decimals:  decimals ("pair_decimals") ?,
+                lot_decimals:   decimals ("lot_decimals") ?,
+                ordermin:       V.get ("ordermin")
+                                 .map (reply::number).transpose () ?,
+                taker_fee:      first_tier (&V ["fees"]) ?,
+                maker_fee:      first_tier (&V ["fees_maker"]) ?  })
+    }
+
+    /** The pair in common symbols, e.g. "BTC/USD" for "XXBTZUSD", for
+        reports; see [common_symbol](crate::common_symbol). */
+    pub  fn  common_name  (&self)  ->  String
+    {
+        format! ("{}/{}", crate::common_symbol (&self.base),
+                          crate::common_symbol (&self.quote))
+    }
+
+    /** Does the `pair` string name this pair, in any of its forms? */
+    pub  fn  is_named  (&self,  pair:  &str)  ->  bool
+          {   pair == self.name  ||  pair == self.altname
+                                 ||  pair == self.wsname   }
+
+    /** Present a price with exactly the precision the exchange allows for
+        this pair. */
+    pub  fn  format_price  (&self,  price:  f64)  ->  String
+          {   format! ("{:.*}", self.pair_decimals, price)   }
+
+    /** Present a volume with exactly the precision the exchange allows for
+        this pair. */
+    pub  fn  format_volume  (&self,  volume:  f64)  ->  String
+          {   format! ("{:.*}", self.lot_decimals, volume)   }
+
+    /** Check that `volume` is not below the exchange's minimum order size for
+        this pair. */
+    pub  fn  check_volume  (&self,  volume:  f64)  ->  Result<(), String>
+    {
+        if  volume <= 0.0  ||  volume.is_nan ()
+            {   Err (format! ("order volume for {} must be positive",
+                              self.altname)) ?   }
+
+        match  self.ordermin
+           {   Some (M)  if  volume < M
+                   =>  Err (format! ("order volume {} for {} is below the \
+                                      minimum of {}",
+                                     volume, self.altname, M)),
+               _   =>  Ok (())   }
+    }
+}
+
+
+
+/** Which side of a trade an order is on, for the purposes of the fee
+    charged: making liquidity (resting in the book) or taking it. */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub  enum  Liquidity
+{
+    /** The order rested in the book, and someone else's order met it. */
+    MAKER,
+    /** The order met one already in the book. */
+    TAKER
+}
+
+
+
+/** The fee tiers of a trading pair, by 30-day trading volume, as given by
+    the [Kraken_API::asset_pairs] end-point; see
+    [Kraken_API::fee_schedule].  */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Fee_Schedule
+{
+    /** The (volume, percent) tiers for taking liquidity, in increasing order
+        of volume. */
+    pub  taker:              Vec<(f64, f64)>,
+
+    /** The (volume, percent) tiers for making liquidity; the exchange gives
+        none for some pairs, in which case makers pay the taker fees. */
+    pub  maker:              Vec<(f64, f64)>,
+
+    /** The currency in which the volume is reckoned, e.g. "ZUSD". */
+    pub  volume_currency:    String
+}
+
+
+
+impl  Fee_Schedule
+{
+    /** Interpret one entry of the 'result' section of an AssetPairs reply,
+        whether asked for with the "fees" [API_Option::INFO] or in full. */
+    pub  fn  from_json  (V:  &JSN::Value)  ->  Result<Self, String>
+    {
+        let  tiers  =  |f: &str|  -> Result<Vec<(f64, f64)>, String>  {
+            V [f].as_array ().into_iter ().flatten ()
+                 .map (|T|  Ok ((reply::number (&T [0]) ?,
+                                 reply::number (&T [1]) ?)))
+                 .collect ()
+        };
+
+        Ok (Fee_Schedule {  taker:  tiers ("fees") ?,
+                            maker:  tiers ("fees_maker") ?,
+                            volume_currency:  V ["fee_volume_currency"]
+                                                 .as_str ().unwrap_or ("")
+                                                 .to_string ()  })
+    }
+
+    /** The fee, in percent, for an account which has traded `volume_30d`
+        (in the [Fee_Schedule::volume_currency]) in the last 30 days; `None`
+        if the exchange gave no tiers at all. */
+    pub  fn  fee_for  (&self,  volume_30d:  f64,  side:  Liquidity)
+              ->  Option<f64>
+    {
+        let  tiers  =  match  side
+            {   Liquidity::MAKER  if  ! self.maker.is_empty ()  =>  &self.maker,
+                _  =>  &self.taker   };
+
+        tiers.iter ()
+             .take_while (|(volume, _)| *volume <= volume_30d)
+             .last ()
+             .or (tiers.first ())
+             .map (|(_, percent)| *percent)
+    }
+}
+
+
+
+/** The name of a trading pair which is known to exist on the exchange,
+    having been checked with [Kraken_API::pair] (or taken from a
+    [Pair_Info]), so that a mistyped pair is caught where it is written
+    rather than coming back from the exchange as "EQuery:Unknown asset pair"
+    in the middle of trading.  It holds Kraken's canonical name for the pair,
+    e.g. "XXBTZUSD", whatever form it was given in.
+
+    It can be handed to any method which takes a pair: as `&P` to those
+    taking `&str`, such as [Kraken_API::add_order], and as `&P` or `P` to
+    those taking `impl Into<String>`, such as [Kraken_API::ticker_info].
+
+    ```ignore
+    K.set_cache_lifetime ("AssetPairs", Some (Duration::from_secs (3600)));
+    let  P  =  K.pair ("XBTUSD") ?;
+    K.ticker_info (&P) ?;
+    K.add_order (KKN::Order_Type::MARKET, KKN::Instruction::BUY, 0.01, &P) ?;
+    ```  */
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub  struct  Pair  (String);
+
+
+
+impl  Pair
+{
+    /** Take `name` as a pair without checking it with the exchange; for
+        names which are known to be good, such as those in the exchange's own
+        replies. */
+    pub  fn  unchecked  (name:  impl Into<String>)  ->  Self
+          {   Pair (name.into ())   }
+
+    /** The pair's canonical name. */
+    pub  fn  as_str  (&self)  ->  &str   {   &self.0   }
+}
+
+
+
+impl  From<&Pair_Info>  for  Pair
+{
+    fn  from  (P:  &Pair_Info)  ->  Self   {   Pair (P.name.clone ())   }
+}
+
+impl  From<Pair>  for  String
+{
+    fn  from  (P:  Pair)  ->  Self   {   P.0   }
+}
+
+impl  From<&Pair>  for  String
+{
+    fn  from  (P:  &Pair)  ->  Self   {   P.0.clone ()   }
+}
+
+impl  std::ops::Deref  for  Pair
+{
+    type  Target  =  str;
+    fn  deref  (&self)  ->  &str   {   &self.0   }
+}
+
+impl  AsRef<str>  for  Pair
+{
+    fn  as_ref  (&self)  ->  &str   {   &self.0   }
+}
+
+impl  std::fmt::Display  for  Pair
+{
+    fn  fmt  (&self,  f:  &mut std::fmt::Formatter)  ->  std::fmt::Result
+          {   f.write_str (&self.0)   }
+}
+
+
+
+/*  Find the pair which `name` names, in any of its forms, among `pairs`. */
+
+fn  find_pair  (pairs:  &[Pair_Info],  name:  &str)  ->  Result<Pair, String>
+{
+    pairs.iter ()
+         .find (|P| P.is_named (name))
+         .map (Pair::from)
+         .ok_or (format! ("unknown asset pair '{}'", name))
+}
+
+
+
+impl  Kraken_API
+{
+/** Check that `name` is a trading pair the exchange knows, in any of the
+    forms it knows it by (e.g. "XXBTZUSD", "XBTUSD" or "XBT/USD"), and give
+    it back as a [Pair].
+
+    The check is made against the whole list from [Kraken_API::all_pairs],
+    so that code checking many pairs, having had the "AssetPairs" end-point
+    cached with [Kraken_API::set_cache_lifetime], needs only one call to the
+    exchange between them.  */
+
+  pub  fn  pair  (&mut self,  name:  &str)  ->  Result<Pair, String>
+    {
+      find_pair (&self.all_pairs () ?, name)
+    }
+
+
+
+/** Get the [Pair_Info] for a single trading pair, which may be named in any
+    of the forms the exchange knows it by.
+
+    This makes a call to the [Kraken_API::asset_pairs] end-point, and so
+    replaces any [API_Option::PAIR] setting and clears [API_Option::INFO].  */
+
+  pub  fn  pair_info  (&mut self,  pair:  &str)  ->  Result<Pair_Info, String>
+    {
+      self.clear_opt (API_Option::INFO);
+      self.set_opt (API_Option::PAIR, pair);
+      let  R  =  reply::result_of (&self.asset_pairs () ?) ?;
+
+      R.as_object ()
+       .and_then (|M| M.iter ().next ())
+       .ok_or (format! ("no information for pair '{}'", pair))
+       .and_then (|(name, V)| Pair_Info::from_json (name, V))
+    }
+
+
+
+/** Get the fee tiers of a trading pair, which may be named in any of the
+    forms the exchange knows it by; have the "AssetPairs" end-point cached
+    with [Kraken_API::set_cache_lifetime] to save asking each time.
+
+    This replaces any [API_Option::PAIR] and [API_Option::INFO] settings.  */
+
+  pub  fn  fee_schedule  (&mut self,  pair:  &str)
+             ->  Result<Fee_Schedule, String>
+    {
+      self.set_opt (API_Option::INFO, "fees");
+      self.set_opt (API_Option::PAIR, pair);
+      let  R  =  reply::result_of (&self.asset_pairs () ?);
+      self.clear_opt (API_Option::INFO);
+
+      R ?.as_object ()
+         .and_then (|M| M.values ().next ())
+         .ok_or (format! ("no fee schedule for pair '{}'", pair))
+         .and_then (Fee_Schedule::from_json)
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  pair  ()
+    {
+        use  super::{Pair, Pair_Info, find_pair};
+
+        let  pairs  =  [Pair_Info {  name:  "XXBTZUSD".into (),
+                                     altname:  "XBTUSD".into (),
+                                     wsname:  "XBT/USD".into (),
+                                     base:  "XXBT".into (),
+                                     quote:  "ZUSD".into (),
+                                     pair_decimals:  1,
+                                     lot_decimals:  8,
+                                     ordermin:  None,
+                                     taker_fee:  None,
+                                     maker_fee:  None  }];
+
+        assert_eq! (find_pair (&pairs, "XBT/USD"),
+                    Ok (Pair::unchecked ("XXBTZUSD")));
+        assert! (find_pair (&pairs, "XBTUSDD").unwrap_err ()
+                    .contains ("unknown asset pair"));
+
+        let  P  =  Pair::from (&pairs [0]);
+        let  as_str:  &str  =  &P;
+        assert_eq! ((as_str, String::from (&P)),
+                    ("XXBTZUSD", "XXBTZUSD".to_string ()));
+    }
+
+
+    #[test]
+    fn  fees  ()  ->  Result<(), String>
+    {
+        use  super::{Fee_Schedule, Liquidity};
+
+        let  V  =  serde_json::json! ({
+                       "fees":  [[0, 0.4], [10000, 0.35], [50000, 0.24]],
+                       "fees_maker":  [[0, 0.25], [10000, 0.2], [50000, 0.14]],
+                       "fee_volume_currency":  "ZUSD"  });
+        let  F  =  Fee_Schedule::from_json (&V) ?;
+
+        assert_eq! (F.fee_for (0.0, Liquidity::TAKER),  Some (0.4));
+        assert_eq! (F.fee_for (10000.0, Liquidity::TAKER),  Some (0.35));
+        assert_eq! (F.fee_for (49999.0, Liquidity::MAKER),  Some (0.2));
+        assert_eq! (F.fee_for (1e9, Liquidity::MAKER),  Some (0.14));
+
+        let  F  =  Fee_Schedule::from_json (&serde_json::json!
+                                               ({"fees": [[0, 0.26]]})) ?;
+        assert_eq! (F.fee_for (5.0, Liquidity::MAKER),  Some (0.26));
+        assert_eq! (Fee_Schedule::from_json (&V ["none"]) ?
+                        .fee_for (5.0, Liquidity::TAKER),
+                    None);
+        Ok (())
+    }
+}
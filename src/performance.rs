@@ -0,0 +1,289 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  The figures by which a trading strategy is usually judged, worked out
+ *  from the account's own trades: the trades are matched up, first in first
+ *  out, into round trips, and the realised profit of those is summed up. */
+
+
+
+use  crate::{Instruction, Own_Trade};
+use  std::collections::{BTreeMap, VecDeque};
+
+
+
+/** A position opened by one or more trades and closed (wholly or in part)
+    by a later trade on the other side, as found by [round_trips]. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Round_Trip
+{
+    /** The pair traded, as the trades give it. */
+    pub  pair:    String,
+
+    /** The side of the trades which opened the position: BUY for a long
+        position, SELL for a short one. */
+    pub  side:    Instruction,
+
+    /** UNIX time of the earliest trade which opened the volume closed. */
+    pub  opened:  f64,
+
+    /** UNIX time of the trade which closed it. */
+    pub  closed:  f64,
+
+    /** The volume closed. */
+    pub  volume:  f64,
+
+    /** The realised profit, or loss if negative, in the quote currency,
+        after fees. */
+    pub  pnl:     f64,
+
+    /** The fees paid on the volume, opening and closing, in the quote
+        currency. */
+    pub  fees:    f64
+}
+
+
+
+/*  Volume still open from one trade, with its price and the fee per unit
+ *  of volume. */
+
+struct  Lot  {  time:  f64,  price:  f64,  volume:  f64,  unit_fee:  f64  }
+
+
+
+/** Match the `trades` (in any order, of any pairs) up into round trips,
+    first in first out within each pair: each trade closes as much as it
+    can of the volume opened by earlier trades on the other side, making one
+    [Round_Trip], and any volume left over opens a position of its own.
+    Volume still open at the end is not in the list.  The round trips are
+    in the order in which they were closed.  The trades must go back far
+    enough to hold the openings of the positions they close; any that do not
+    only open positions. */
+
+pub  fn  round_trips  (trades:  &[Own_Trade])  ->  Vec<Round_Trip>
+{
+    const  DUST:  f64  =  1e-12;
+
+    let  mut  trades  =  trades.iter ().collect::<Vec<_>> ();
+    trades.sort_by (|A, B| A.time.total_cmp (&B.time));
+
+    let  mut  open  =  BTreeMap::<&str, (Instruction, VecDeque<Lot>)>::new ();
+    let  mut  trips  =  Vec::new ();
+
+    for  T  in  trades
+    {
+        if  T.volume <= 0.0   {   continue;   }
+        let  unit_fee  =  T.fee / T.volume;
+        let  (side, lots)  =  open.entry (&T.pair)
+                                  .or_insert ((T.side, VecDeque::new ()));
+
+        let  mut  remaining  =  T.volume;
+        if  *side != T.side  &&  ! lots.is_empty ()
+        {
+            let  sign  =  if *side == Instruction::BUY { 1.0 } else { -1.0 };
+            let  mut  trip  =  Round_Trip {  pair:    T.pair.clone (),
+                                             side:    *side,
+                                             opened:  lots [0].time,
+                                             closed:  T.time,
+                                             volume:  0.0,
+                                             pnl:     0.0,
+                                             fees:    0.0  };
+
+            while  remaining > DUST
+            {
+                let  Some (L)  =  lots.front_mut ()   else   {   break   };
+                let  V  =  remaining.min (L.volume);
+                let  fees  =  V * (L.unit_fee + unit_fee);
+                trip.volume  +=  V;
+                trip.fees    +=  fees;
+                trip.pnl     +=  sign * (T.price - L.price) * V - fees;
+                L.volume     -=  V;
+                remaining    -=  V;
+                if  L.volume <= DUST   {   lots.pop_front ();   }
+            }
+            trips.push (trip);
+        }
+
+        if  remaining > DUST
+        {
+            if  lots.is_empty ()   {   *side  =  T.side;   }
+            lots.push_back (Lot {  time:  T.time,  price:  T.price,
+                                   volume:  remaining,  unit_fee  });
+        }
+    }
+
+    trips
+}
+
+
+
+/** The standard measures of how a strategy has done, over a set of round
+    trips: see [Performance::of].  Money is in the quote currency of the
+    trips, so they should all be in pairs with the same quote currency;
+    [performance_by_pair] keeps the pairs apart. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Performance
+{
+    /** The number of round trips. */
+    pub  trips:          usize,
+
+    /** The fraction of the round trips which made a profit after fees;
+        zero if there were none. */
+    pub  win_rate:       f64,
+
+    /** The total profit of the winning trips. */
+    pub  gross_profit:   f64,
+
+    /** The total loss of the losing trips, as a positive number. */
+    pub  gross_loss:     f64,
+
+    /** The gross profit divided by the gross loss; `None` if nothing was
+        lost. */
+    pub  profit_factor:  Option<f64>,
+
+    /** The realised profit, or loss if negative, after fees, over all the
+        trips. */
+    pub  net_pnl:        f64,
+
+    /** The mean profit (or loss) of a round trip; zero if there were
+        none. */
+    pub  average_pnl:    f64,
+
+    /** The largest fall of the realised profit, added up trip by trip in the
+        order the trips were closed, from its highest point so far (which is
+        at least the starting point of nothing), as a positive number. */
+    pub  max_drawdown:   f64,
+
+    /** The total fees paid on the trips. */
+    pub  fees:           f64,
+
+    /** The fees as a fraction of the profit the trips would have made
+        without them; `None` if they would have made none. */
+    pub  fee_share:      Option<f64>
+}
+
+
+
+impl  Performance
+{
+    /** Sum up the round `trips`, taken in the order they were closed. */
+    pub  fn  of  (trips:  &[Round_Trip])  ->  Self
+    {
+        let  mut  trips  =  trips.iter ().collect::<Vec<_>> ();
+        trips.sort_by (|A, B| A.closed.total_cmp (&B.closed));
+
+        let  mut  P  =  Performance {  trips:  trips.len (),  win_rate:  0.0,
+                                      gross_profit:  0.0,  gross_loss:  0.0,
+                                      profit_factor:  None,  net_pnl:  0.0,
+                                      average_pnl:  0.0,  max_drawdown:  0.0,
+                                      fees:  0.0,  fee_share:  None  };
+        let  mut  wins  =  0;
+        let  mut  peak  =  0.0_f64;
+
+        for  T  in  &trips
+        {
+            if  T.pnl > 0.0
+                {   wins  +=  1;   P.gross_profit  +=  T.pnl;   }
+            else
+                {   P.gross_loss  -=  T.pnl;   }
+            P.net_pnl  +=  T.pnl;
+            P.fees     +=  T.fees;
+            peak  =  peak.max (P.net_pnl);
+            P.max_drawdown  =  P.max_drawdown.max (peak - P.net_pnl);
+        }
+
+        if  P.trips > 0
+        {
+            P.win_rate     =  wins as f64 / P.trips as f64;
+            P.average_pnl  =  P.net_pnl / P.trips as f64;
+        }
+        if  P.gross_loss > 0.0
+            {   P.profit_factor  =  Some (P.gross_profit / P.gross_loss);   }
+        if  P.net_pnl + P.fees > 0.0
+            {   P.fee_share  =  Some (P.fees / (P.net_pnl + P.fees));   }
+        P
+    }
+}
+
+
+
+/** The [Performance] of the `trades` in each pair, keyed by pair, from the
+    [round_trips] they make. */
+
+pub  fn  performance_by_pair  (trades:  &[Own_Trade])
+             ->  BTreeMap<String, Performance>
+{
+    let  mut  M  =  BTreeMap::<String, Vec<Round_Trip>>::new ();
+    for  T  in  round_trips (trades)
+        {   M.entry (T.pair.clone ()).or_default ().push (T);   }
+    M.into_iter ().map (|(pair, T)|  (pair, Performance::of (&T))).collect ()
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  performance  ()
+    {
+        use  super::{Performance, performance_by_pair, round_trips};
+        use  crate::Instruction::{self, BUY, SELL};
+        use  crate::test_fixtures::own_trade;
+
+        let  trade  =  |time: f64, side: Instruction, price: f64, volume: f64|
+                          own_trade ("", "", time, side, price, volume);
+
+        let  trades  =  [trade (1.0, BUY, 100.0, 2.0),
+                         trade (3.0, SELL, 120.0, 1.0),
+                         trade (2.0, BUY, 110.0, 1.0),
+                         trade (4.0, SELL, 90.0, 3.0),
+                         trade (5.0, BUY, 80.0, 1.0)];
+
+        let  R  =  round_trips (&trades);
+        assert_eq! (R.len (),  3);
+        assert_eq! ((R [0].side, R [0].volume, R [0].opened),
+                    (BUY, 1.0, 1.0));
+        assert! ((R [0].pnl - (20.0 - 0.1 - 0.12)).abs () < 1e-9);
+        assert_eq! ((R [1].volume, R [1].opened),  (2.0, 1.0));
+        assert! ((R [1].pnl - (-10.0 - 20.0 - 0.1 - 0.11 - 0.18)).abs ()
+                    < 1e-9);
+
+        /*  The last sell was one more than the position, and went short. */
+        assert_eq! ((R [2].side, R [2].volume),  (SELL, 1.0));
+        assert! ((R [2].pnl - (10.0 - 0.09 - 0.08)).abs () < 1e-9);
+
+        let  P  =  Performance::of (&R);
+        assert_eq! (P.trips,  3);
+        assert! ((P.win_rate - 2.0 / 3.0).abs () < 1e-9);
+        assert! ((P.profit_factor.unwrap () - (29.61 / 30.39)).abs () < 1e-9);
+        assert! ((P.max_drawdown - 30.39).abs () < 1e-9);
+        assert! ((P.fees - 0.78).abs () < 1e-9);
+        assert! ((P.net_pnl - (-0.78)).abs () < 1e-9);
+        assert! ((Performance::of (&R [.. 1]).fee_share.unwrap () - 0.011)
+                    .abs () < 1e-9);
+
+        assert_eq! (performance_by_pair (&trades) ["XXBTZUSD"],  P);
+        assert_eq! (Performance::of (&[]).win_rate,  0.0);
+    }
+}
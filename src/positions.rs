@@ -0,0 +1,615 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option, Cancel_Token, Instruction, Kraken_API};
+use  crate::reply;
+use  crate::strictness;
+use  serde_json  as  JSN;
+use  std::collections::BTreeMap;
+use  std::time::Duration;
+
+
+
+/** An open margin position, as reported by the
+    [Kraken_API::open_margin_positions] end-point. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Position
+{
+    /** The position's own transaction ID. */
+    pub  txid:           String,
+
+    /** The ID of the order which opened the position. */
+    pub  order_txid:     String,
+
+    /** The pair, by Kraken's canonical name. */
+    pub  pair:           String,
+
+    /** Long positions were opened by buying, short ones by selling. */
+    pub  side:           Instruction,
+
+    /** UNIX time at which the position was opened. */
+    pub  time:           f64,
+
+    /** The cost of opening the position, in the quote currency. */
+    pub  cost:           f64,
+
+    /** The fee paid to open the position, in the quote currency. */
+    pub  fee:            f64,
+
+    /** The volume of the position, in the base currency. */
+    pub  volume:         f64,
+
+    /** How much of the volume has been closed already. */
+    pub  volume_closed:  f64,
+
+    /** The margin used by the position, in the quote currency. */
+    pub  margin:         f64,
+
+    /** The current value of the remaining position, if the exchange was asked
+        to do the calculation (see [API_Option::DO_CALCS]). */
+    pub  value:          Option<f64>,
+
+    /** The unrealised profit (or loss) of the remaining position, if the
+        exchange was asked to calculate it. */
+    pub  net:            Option<f64>
+}
+
+
+
+impl  Position
+{
+    /** Interpret one entry of the 'result' section of an OpenPositions reply;
+        `txid` is the key under which the entry was found. */
+    pub  fn  from_json  (txid:  &str,  V:  &JSN::Value)
+              ->  Result<Self, String>
+    {
+        strictness::check_fields ("Position", V,
+                                  &["ordertxid", "posstatus", "pair", "time",
+                                    "type", "ordertype", "cost", "fee", "vol",
+                                    "vol_closed", "margin", "value", "net",
+                                    "terms", "rollovertm", "misc", "oflags"]) ?;
+
+        let  number  =  |f: &str|  reply::number (&V [f])
+                                       .map_err (|E| format! ("{}: {}", f, E));
+        let  optional  =  |f: &str|  V.get (f).map (reply::number)
+                                               .transpose ();
+
+        Ok (Position {
+                txid:           txid.to_string (),
+                order_txid:     reply::string (V, "ordertxid") ?,
+                pair:           reply::string (V, "pair") ?,
+                side:           if  V ["type"] == "sell"  { Instruction::SELL }
+                                else                      { Instruction::BUY  },
+                time:           number ("time") ?,
+                cost:           number ("cost") ?,
+                fee:            number ("fee") ?,
+                volume:         number ("vol") ?,
+                volume_closed:  number ("vol_closed") ?,
+                margin:         number ("margin") ?,
+                value:          optional ("value") ?,
+                net:            optional ("net") ?  })
+    }
+
+    /** The part of the position which is still open, positive for long
+        positions and negative for short ones. */
+    pub  fn  open_volume  (&self)  ->  f64
+    {
+        let  V  =  self.volume - self.volume_closed;
+        if  self.side == Instruction::SELL  {  -V  }  else  {  V  }
+    }
+}
+
+
+
+/** The open margin positions on one pair and side taken together, as the
+    [Kraken_API::open_margin_positions] end-point gives them when asked to
+    consolidate by market, and as the Kraken web site shows them; see
+    [Kraken_API::consolidated_positions] and [consolidate]. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Consolidated_Position
+{
+    /** The pair, by Kraken's canonical name. */
+    pub  pair:           String,
+
+    /** Long positions were opened by buying, short ones by selling. */
+    pub  side:           Instruction,
+
+    /** How many positions were taken together. */
+    pub  positions:      usize,
+
+    /** The total cost of opening the positions, in the quote currency. */
+    pub  cost:           f64,
+
+    /** The total fee paid to open the positions. */
+    pub  fee:            f64,
+
+    /** The total volume of the positions, in the base currency. */
+    pub  volume:         f64,
+
+    /** How much of the volume has been closed already. */
+    pub  volume_closed:  f64,
+
+    /** The total margin used by the positions. */
+    pub  margin:         f64,
+
+    /** The current value of the remaining positions, if it was calculated
+        for all of them. */
+    pub  value:          Option<f64>,
+
+    /** The unrealised profit (or loss) of the remaining positions, if it was
+        calculated for all of them. */
+    pub  net:            Option<f64>
+}
+
+
+
+impl  Consolidated_Position
+{
+    /** Interpret one entry of the 'result' section of an OpenPositions
+        reply made with [API_Option::CONSOLIDATION] set to "market". */
+    pub  fn  from_json  (V:  &JSN::Value)  ->  Result<Self, String>
+    {
+        strictness::check_fields ("Consolidated_Position", V,
+                                  &["pair", "positions", "type", "leverage",
+                                    "cost", "fee", "vol", "vol_closed",
+                                    "margin", "value", "net"]) ?;
+
+        let  number  =  |f: &str|  reply::number (&V [f])
+                                       .map_err (|E| format! ("{}: {}", f, E));
+        let  optional  =  |f: &str|  V.get (f).map (reply::number)
+                                               .transpose ();
+
+        Ok (Consolidated_Position {
+                pair:           reply::string (V, "pair") ?,
+                side:           if  V ["type"] == "sell"  { Instruction::SELL }
+                                else                      { Instruction::BUY  },
+                positions:      number ("positions") ? as usize,
+                cost:           number ("cost") ?,
+                fee:            number ("fee") ?,
+                volume:         number ("vol") ?,
+                volume_closed:  number ("vol_closed") ?,
+                margin:         number ("margin") ?,
+                value:          optional ("value") ?,
+                net:            optional ("net") ?  })
+    }
+
+    /** The part of the volume which is still open, positive for long
+        positions and negative for short ones. */
+    pub  fn  open_volume  (&self)  ->  f64
+    {
+        let  V  =  self.volume - self.volume_closed;
+        if  self.side == Instruction::SELL  {  -V  }  else  {  V  }
+    }
+}
+
+
+
+/** A summary of the account's standing for margin trading, as given by the
+    [Kraken_API::trade_balance] end-point.  All amounts are in the asset the
+    summary was asked for (by default, US dollars). */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Trade_Balance
+{
+    /** The combined value of all currency balances ('eb'). */
+    pub  equivalent_balance:  f64,
+
+    /** The combined value of all balances which count as margin collateral
+        ('tb'). */
+    pub  trade_balance:       f64,
+
+    /** The margin in use by open positions ('m'). */
+    pub  margin:              f64,
+
+    /** The unrealised profit or loss of open positions ('n'). */
+    pub  unrealised_pnl:      f64,
+
+    /** The cost basis of open positions ('c'). */
+    pub  cost_basis:          f64,
+
+    /** The current floating valuation of open positions ('v'). */
+    pub  valuation:           f64,
+
+    /** Equity: the trade balance plus the unrealised profit or loss ('e'). */
+    pub  equity:              f64,
+
+    /** Free margin: equity less the margin in use, which is what is
+        available for new positions ('mf'). */
+    pub  free_margin:         f64,
+
+    /** The margin level, as a percentage: equity over margin in use ('ml');
+        absent when there are no open positions. */
+    pub  margin_level:        Option<f64>,
+
+    /** The value of unfilled and partly filled orders ('uv'), if given. */
+    pub  unexecuted_value:    Option<f64>
+}
+
+impl  Trade_Balance
+{
+    /** Interpret the 'result' section of a TradeBalance reply. */
+    pub  fn  from_json  (V:  &JSN::Value)  ->  Result<Self, String>
+    {
+        strictness::check_fields ("Trade_Balance", V,
+                                  &["eb", "tb", "m", "uv", "n", "c", "v", "e",
+                                    "mf", "ml"]) ?;
+
+        let  number  =  |f: &str|  reply::number (&V [f])
+                                       .map_err (|E| format! ("{}: {}", f, E));
+        let  optional  =  |f: &str|  V.get (f).map (reply::number)
+                                               .transpose ();
+
+        Ok (Trade_Balance {  equivalent_balance:  number ("eb") ?,
+                             trade_balance:       number ("tb") ?,
+                             margin:              number ("m") ?,
+                             unrealised_pnl:      number ("n") ?,
+                             cost_basis:          number ("c") ?,
+                             valuation:           number ("v") ?,
+                             equity:              number ("e") ?,
+                             free_margin:         number ("mf") ?,
+                             margin_level:        optional ("ml") ?,
+                             unexecuted_value:    optional ("uv") ?  })
+    }
+}
+
+
+
+/** Add up the open volumes of the positions on each pair, longs positive and
+    shorts negative, to give the net exposure to each pair. */
+
+pub  fn  net_exposure  (positions:  &[Position])  ->  BTreeMap<String, f64>
+{
+    let  mut  M  =  BTreeMap::new ();
+    for  P  in  positions
+        {   *M.entry (P.pair.clone ()).or_insert (0.0) += P.open_volume ();   }
+    M
+}
+
+
+
+/** The total unrealised profit (or loss, if negative) of all the positions,
+    in their quote currencies, or `None` if the exchange was not asked to
+    calculate this for every position. */
+
+pub  fn  unrealised_pnl  (positions:  &[Position])  ->  Option<f64>
+      {   positions.iter ().map (|P| P.net).sum ()   }
+
+
+
+/** Take the positions together by pair and side, as the exchange does when
+    asked to consolidate by market, in order of pair with longs first; for
+    positions which are already to hand, such as those kept by an
+    [Account](crate::Account). */
+
+pub  fn  consolidate  (positions:  &[Position])  ->  Vec<Consolidated_Position>
+{
+    let  mut  M  =  BTreeMap::new ();
+    for  P  in  positions
+    {
+        let  C  =  M.entry ((P.pair.clone (), P.side == Instruction::SELL))
+                    .or_insert (Consolidated_Position {
+                                    pair:  P.pair.clone (),  side:  P.side,
+                                    positions:  0,  cost:  0.0,  fee:  0.0,
+                                    volume:  0.0,  volume_closed:  0.0,
+                                    margin:  0.0,  value:  Some (0.0),
+                                    net:  Some (0.0)  });
+        C.positions      +=  1;
+        C.cost           +=  P.cost;
+        C.fee            +=  P.fee;
+        C.volume         +=  P.volume;
+        C.volume_closed  +=  P.volume_closed;
+        C.margin         +=  P.margin;
+        C.value           =  C.value.zip (P.value).map (|(a, b)| a + b);
+        C.net             =  C.net.zip (P.net).map (|(a, b)| a + b);
+    }
+    M.into_values ().collect ()
+}
+
+
+
+impl  Kraken_API
+{
+/** Get all open margin positions as typed data, with the exchange's profit and
+    loss calculations included.
+
+    This uses the [Kraken_API::open_margin_positions] end-point, with
+    [API_Option::DO_CALCS] set and [API_Option::TXID] and
+    [API_Option::CONSOLIDATION] cleared.  */
+
+  pub  fn  positions  (&mut self)  ->  Result<Vec<Position>, String>
+    {
+      self.clear_opt (API_Option::TXID);
+      self.clear_opt (API_Option::CONSOLIDATION);
+      self.set_opt (API_Option::DO_CALCS, true);
+
+      let  R  =  reply::result_of (&self.open_margin_positions () ?) ?;
+      R.as_object ()
+       .into_iter ()
+       .flatten ()
+       .map (|(txid, V)| Position::from_json (txid, V))
+       .collect ()
+    }
+
+
+
+/** Get the open margin positions taken together by pair and side, with the
+    exchange's profit and loss calculations included.
+
+    This uses the [Kraken_API::open_margin_positions] end-point, with
+    [API_Option::DO_CALCS] set, [API_Option::CONSOLIDATION] set to "market"
+    and [API_Option::TXID] cleared.  */
+
+  pub  fn  consolidated_positions  (&mut self)
+             ->  Result<Vec<Consolidated_Position>, String>
+    {
+      self.clear_opt (API_Option::TXID);
+      self.set_opt (API_Option::CONSOLIDATION, "market");
+      self.set_opt (API_Option::DO_CALCS, true);
+
+      let  R  =  self.open_margin_positions ()
+                     .and_then (|R| reply::result_of (&R));
+      self.clear_opt (API_Option::CONSOLIDATION);
+
+      R ?.as_array ()
+         .into_iter ()
+         .flatten ()
+         .map (Consolidated_Position::from_json)
+         .collect ()
+    }
+
+
+
+/** The current margin level of the account, as a percentage, or `None` if
+    there are no open positions (when the level is undefined).
+
+    This comes from the [Kraken_API::trade_balance] end-point, with any
+    [API_Option::ASSET] setting being respected.  */
+
+  pub  fn  margin_level  (&mut self)  ->  Result<Option<f64>, String>
+    {
+      Ok (self.trade_balance_summary () ?.margin_level)
+    }
+
+
+
+/** Get the account's [Trade_Balance].
+
+    This comes from the [Kraken_API::trade_balance] end-point, with any
+    [API_Option::ASSET] setting being respected.  */
+
+  pub  fn  trade_balance_summary  (&mut self)  ->  Result<Trade_Balance, String>
+    {
+      Trade_Balance::from_json (&reply::result_of (&self.trade_balance () ?) ?)
+    }
+}
+
+
+
+/** A report from a [Margin_Monitor] that the account's margin level has
+    crossed one of the monitor's thresholds. */
+
+#[derive(Clone, Debug)]
+pub  struct  Margin_Alert
+{
+    /** The threshold which was crossed, as a percentage. */
+    pub  threshold:  f64,
+
+    /** The margin level now, as a percentage. */
+    pub  level:      f64,
+
+    /** True if the level has fallen below the threshold, false if it has come
+        back up above it. */
+    pub  falling:    bool,
+
+    /** The positions open at the time of the alert, if they could be got from
+        the exchange. */
+    pub  positions:  Vec<Position>
+}
+
+
+
+/** Keeps watch over the account's margin level, and calls back into user code
+    whenever it crosses one of a set of thresholds, in either direction.
+
+    This gives margin traders an early warning that they are approaching a
+    margin call or liquidation, and a hook in which to deleverage.  The monitor
+    can be driven by hand with [Margin_Monitor::poll], or left to poll in its
+    own thread with [Margin_Monitor::spawn].
+
+    ```ignore
+    let  mut  M  =  KKN::Margin_Monitor::new (&[200.0, 150.0, 110.0]);
+    M.on_alert (|A|  if A.falling { eprintln! ("margin at {}%", A.level) });
+    let  (thread, stop)  =  M.spawn (K, Duration::from_secs (30));
+    ```  */
+
+#[derive(Default)]
+pub  struct  Margin_Monitor  {  thresholds:  Vec<f64>,
+                                last:        Option<f64>,
+                                on_alert:    Vec<Alert_Callback>,
+                                on_error:    Vec<Error_Callback>  }
+
+type  Alert_Callback  =  Box<dyn FnMut (&Margin_Alert) + Send>;
+type  Error_Callback  =  Box<dyn FnMut (&str) + Send>;
+
+
+
+impl  Margin_Monitor
+{
+    /** A monitor which will raise alerts when the margin level, as a
+        percentage, crosses any of the `thresholds`. */
+    pub  fn  new  (thresholds:  &[f64])  ->  Self
+          {   Margin_Monitor {  thresholds: thresholds.to_vec (),
+                                ..Default::default ()  }   }
+
+    /** Register a function to be called with every alert. */
+    pub  fn  on_alert<F>  (&mut self,  f: F)  ->  &mut Self
+                 where  F:  FnMut (&Margin_Alert) + Send + 'static
+          {   self.on_alert.push (Box::new (f));   self   }
+
+    /** Register a function to be called with the error message when the
+        exchange cannot be asked for the margin level.  */
+    pub  fn  on_error<F>  (&mut self,  f: F)  ->  &mut Self
+                 where  F:  FnMut (&str) + Send + 'static
+          {   self.on_error.push (Box::new (f));   self   }
+
+    /** The margin level seen at the last poll, if there were any positions
+        open then. */
+    pub  fn  last_level  (&self)  ->  Option<f64>   {   self.last   }
+
+
+    /** Take note of the margin level (`None` meaning that there are no
+        positions open, which counts as an infinitely high level), and work out
+        which thresholds have been crossed since the last observation.  The
+        alerts are returned without any positions attached, and without the
+        callbacks being called. */
+    pub  fn  observe  (&mut self,  level:  Option<f64>)  ->  Vec<Margin_Alert>
+    {
+        let  before  =  self.last.unwrap_or (f64::INFINITY);
+        let  after   =  level.unwrap_or (f64::INFINITY);
+        self.last  =  level;
+
+        self.thresholds
+            .iter ()
+            .filter (|&&t|  (before >= t)  !=  (after >= t))
+            .map (|&t|  Margin_Alert {  threshold: t,
+                                        level: after,
+                                        falling: after < t,
+                                        positions: Vec::new ()  })
+            .collect ()
+    }
+
+
+    /** Get the margin level from the exchange, and call back with any alerts
+        arising.  Errors are passed to the `on_error` callbacks as well as
+        being returned. */
+    pub  fn  poll  (&mut self,  K:  &mut Kraken_API)
+               ->  Result<Vec<Margin_Alert>, String>
+    {
+        let  level  =  match  K.margin_level ()
+            {   Ok (L)  =>  L,
+                Err (E)  =>  {  for  f  in  &mut self.on_error  {  f (&E);  }
+                                return  Err (E);  }   };
+
+        let  mut  alerts  =  self.observe (level);
+
+        if  ! alerts.is_empty ()
+        {
+            let  positions  =  K.positions ().unwrap_or_default ();
+            for  A  in  &mut alerts
+            {   A.positions  =  positions.clone ();
+                for  f  in  &mut self.on_alert   {   f (A);   }   }
+        }
+
+        Ok (alerts)
+    }
+
+
+    /** Poll every `interval` until `stop` is cancelled. */
+    pub  fn  run  (&mut self,  K:  &mut Kraken_API,
+                   interval:  Duration,  stop:  &Cancel_Token)
+    {
+        while  ! stop.is_cancelled ()
+            {   let  _  =  self.poll (K);
+                stop.pause (interval);   }
+    }
+
+
+    /** Move the monitor and a [Kraken_API] handle into a thread of their own,
+        which polls every `interval`.  Cancel the returned token to bring the
+        thread to an end. */
+    pub  fn  spawn  (mut self,  mut K: Kraken_API,  interval: Duration)
+               ->  (std::thread::JoinHandle<()>, Cancel_Token)
+    {
+        let  stop  =  Cancel_Token::new ();
+        let  S     =  stop.clone ();
+        (std::thread::spawn (move || self.run (&mut K, interval, &S)),  stop)
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+  {  #[test]  fn  exposure ()  ->  Result<(), String>
+     {
+         use  serde_json  as  JSN;
+
+         let  V  =  JSN::json! ({
+                       "ordertxid": "O1", "pair": "XXBTZUSD", "time": 1.0,
+                       "type": "sell", "cost": "300", "fee": "1",
+                       "vol": "0.5", "vol_closed": "0.2", "margin": "60",
+                       "net": "-3.5" });
+
+         let  P  =  [super::Position::from_json ("T1", &V) ?,
+                     super::Position::from_json ("T2", &V) ?];
+
+         assert! ((super::net_exposure (&P) ["XXBTZUSD"] + 0.6).abs () < 1e-9);
+         assert_eq! (super::unrealised_pnl (&P),  Some (-7.0));
+
+         let  mut  M  =  super::Margin_Monitor::new (&[200.0, 150.0]);
+         assert! (M.observe (Some (300.0)).is_empty ());
+         assert_eq! (M.observe (Some (140.0)).len (),  2);
+         let  A  =  M.observe (Some (160.0));
+         assert! (A.len () == 1  &&  A [0].threshold == 150.0);
+         assert! (! A [0].falling);
+
+         Ok (())
+     }
+
+
+     #[test]  fn  consolidation ()  ->  Result<(), String>
+     {
+         use  super::{Consolidated_Position, Position, consolidate};
+         use  serde_json  as  JSN;
+
+         let  position  =  |pair: &str, side: &str, net: Option<&str>|  {
+             let  mut  V  =  JSN::json! ({
+                                 "ordertxid": "O1", "pair": pair,
+                                 "time": 1.0, "type": side, "cost": "300",
+                                 "fee": "1", "vol": "0.5",
+                                 "vol_closed": "0.2", "margin": "60"  });
+             if let Some (net) = net   {   V ["net"]  =  net.into ();   }
+             Position::from_json ("T", &V)
+         };
+
+         let  C  =  consolidate (&[position ("XXBTZUSD", "sell", Some ("-3")) ?,
+                                   position ("XETHZUSD", "buy", Some ("2")) ?,
+                                   position ("XXBTZUSD", "sell", Some ("4")) ?,
+                                   position ("XXBTZUSD", "buy", None) ?]);
+
+         assert_eq! (C.iter ().map (|C| (C.pair.as_str (), C.positions))
+                              .collect::<Vec<_>> (),
+                     [("XETHZUSD", 1), ("XXBTZUSD", 1), ("XXBTZUSD", 2)]);
+         assert_eq! ((C [1].net, C [2].net, C [2].cost),
+                     (None, Some (1.0), 600.0));
+         assert! ((C [2].open_volume () + 0.6).abs () < 1e-9);
+
+         let  R  =  Consolidated_Position::from_json (&JSN::json! ({
+                        "type": "buy", "pair": "XXBTZUSD", "positions": "2",
+                        "leverage": "5.00000", "cost": "600", "fee": "2",
+                        "vol": "1.0", "vol_closed": "0.4", "margin": "120",
+                        "value": "650", "net": "50"  })) ?;
+         assert_eq! ((R.positions, R.net),  (2, Some (50.0)));
+
+         Ok (())
+     }  }
@@ -0,0 +1,169 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  std::collections::VecDeque;
+use  std::sync::{Arc, Condvar, Mutex};
+use  std::time::{Duration, Instant};
+
+
+
+/** What a [Message_Queue] does with a message which arrives when it is
+    full. */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub  enum  Overflow
+{
+    /** Throw away the oldest message in the queue to make room; right for
+        market data, where only the latest state matters. */
+    DROP_OLDEST,
+
+    /** Throw away the new message. */
+    DROP_NEWEST,
+
+    /** Make the producer wait until the consumer has made room; right for
+        execution reports, none of which may be lost. */
+    BLOCK
+}
+
+
+
+/** A bounded first-in, first-out queue for passing messages from one thread
+    to another, with a fixed policy for what to do when the consumer falls
+    behind.
+
+    Clones of a queue are handles onto the same queue, so one can be kept by
+    the producer and another given to the consumer.  The number of messages
+    thrown away is counted, so that a consumer can tell that it has been too
+    slow.  */
+
+pub  struct  Message_Queue<T>  (Arc<(Mutex<Queue_State<T>>, Condvar)>);
+
+struct  Queue_State<T>  {  items:     VecDeque<T>,
+                           capacity:  usize,
+                           overflow:  Overflow,
+                           dropped:   u64  }
+
+impl<T>  Clone  for  Message_Queue<T>
+{   fn  clone  (&self)  ->  Self   {   Message_Queue (self.0.clone ())   }   }
+
+
+
+impl<T>  Message_Queue<T>
+{
+    /** A queue holding at most `capacity` messages (and at least one). */
+    pub  fn  new  (capacity:  usize,  overflow:  Overflow)  ->  Self
+    {
+        let  capacity  =  capacity.max (1);
+        Message_Queue (Arc::new ((Mutex::new (Queue_State {
+                                      items: VecDeque::with_capacity (capacity),
+                                      capacity,  overflow,  dropped: 0  }),
+                                  Condvar::new ())))
+    }
+
+
+    /** Add a message to the back of the queue, dealing with a full queue
+        according to the queue's [Overflow] policy.  Returns false if a
+        message had to be thrown away. */
+    pub  fn  push  (&self,  item:  T)  ->  bool
+    {
+        let  (lock, signal)  =  &*self.0;
+        let  mut  Q  =  lock.lock ().unwrap ();
+        let  mut  kept  =  true;
+
+        if  Q.items.len () >= Q.capacity
+        {
+            match  Q.overflow
+               {   Overflow::DROP_OLDEST  =>  {  Q.items.pop_front ();
+                                                 Q.dropped += 1;
+                                                 kept = false;  }
+                   Overflow::DROP_NEWEST  =>  {  Q.dropped += 1;
+                                                 return  false;  }
+                   Overflow::BLOCK
+                       =>  while  Q.items.len () >= Q.capacity
+                               {   Q  =  signal.wait (Q).unwrap ();   }   }
+        }
+
+        Q.items.push_back (item);
+        signal.notify_all ();
+        kept
+    }
+
+
+    /** Take the message at the front of the queue, waiting up to `timeout`
+        for one to arrive. */
+    pub  fn  pop  (&self,  timeout:  Duration)  ->  Option<T>
+    {
+        let  (lock, signal)  =  &*self.0;
+        let  deadline  =  Instant::now () + timeout;
+        let  mut  Q  =  lock.lock ().unwrap ();
+
+        while  Q.items.is_empty ()
+        {
+            let  left  =  deadline.saturating_duration_since (Instant::now ());
+            if  left.is_zero ()   {   return  None;   }
+            Q  =  signal.wait_timeout (Q, left).unwrap ().0;
+        }
+
+        let  item  =  Q.items.pop_front ();
+        signal.notify_all ();
+        item
+    }
+
+    /** Take the message at the front of the queue, if there is one. */
+    pub  fn  try_pop  (&self)  ->  Option<T>   {   self.pop (Duration::ZERO)   }
+
+    /** The number of messages waiting in the queue. */
+    pub  fn  len  (&self)  ->  usize
+          {   self.0.0.lock ().unwrap ().items.len ()   }
+
+    /** Whether the queue is empty. */
+    pub  fn  is_empty  (&self)  ->  bool   {   self.len () == 0   }
+
+    /** The number of messages thrown away so far because the queue was
+        full. */
+    pub  fn  dropped  (&self)  ->  u64
+          {   self.0.0.lock ().unwrap ().dropped   }
+}
+
+
+
+#[cfg(test)]
+mod  test
+  {  #[test]  fn  overflow ()
+     {
+         use  super::{Message_Queue, Overflow};
+         use  std::time::Duration;
+
+         let  Q  =  Message_Queue::new (2, Overflow::DROP_OLDEST);
+         Q.push (1);   Q.push (2);   Q.push (3);
+         assert_eq! ((Q.try_pop (), Q.try_pop (), Q.try_pop ()),
+                     (Some (2), Some (3), None));
+         assert_eq! (Q.dropped (),  1);
+
+         let  Q  =  Message_Queue::new (1, Overflow::BLOCK);
+         Q.push (1);
+         let  P  =  Q.clone ();
+         let  T  =  std::thread::spawn (move ||  P.push (2));
+         assert_eq! (Q.pop (Duration::from_secs (1)),  Some (1));
+         assert! (T.join ().unwrap ());
+         assert_eq! (Q.pop (Duration::from_secs (1)),  Some (2));
+         assert_eq! (Q.dropped (),  0);
+     }  }
@@ -0,0 +1,390 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::Cancel_Token;
+use  std::collections::BTreeSet;
+use  std::sync::{Condvar, Mutex};
+use  std::time::{Duration, Instant};
+
+
+
+/** The verification tier of a Kraken account, which determines how quickly
+    the exchange lets private end-points be called.
+
+    Each account has a call counter which every private call raises (by two
+    for the ledger and trade history queries, by one for the rest), and which
+    falls away steadily with time; a call which would take the counter above
+    the tier's maximum is refused by the exchange.  The order placing and
+    cancelling end-points are governed by a separate limit in the matching
+    engine, and do not count here.
+
+    If the exchange nevertheless reports that a limit has been exceeded,
+    perhaps because the tier given is wrong or the key is shared with another
+    program, the crate halves the rate at which it lets the counter fall, and
+    counts order calls too, and then speeds up again a little with every call
+    which goes through; a wrong tier setting thus costs some speed rather than
+    getting the key locked out.  */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub  enum  Account_Tier
+{
+    /** Maximum counter 15, falling by one every three seconds. */
+    STARTER,
+
+    /** Maximum counter 20, falling by one every two seconds. */
+    INTERMEDIATE,
+
+    /** Maximum counter 20, falling by one every second. */
+    PRO
+}
+
+
+
+impl  Account_Tier
+{
+    fn  maximum  (self)  ->  f64
+    {
+        match  self  {  Account_Tier::STARTER       =>  15.0,
+                        Account_Tier::INTERMEDIATE  =>  20.0,
+                        Account_Tier::PRO           =>  20.0  }
+    }
+
+    /*  Counter units shed per second. */
+    fn  decay  (self)  ->  f64
+    {
+        match  self  {  Account_Tier::STARTER       =>  0.33,
+                        Account_Tier::INTERMEDIATE  =>  0.5,
+                        Account_Tier::PRO           =>  1.0  }
+    }
+}
+
+
+
+/*  How much a call to the private end-point adds to the counter, according to
+ *  the exchange. */
+
+fn  call_cost  (end_point:  &str)  ->  f64
+{
+    match  end_point.split ('?').next ().unwrap_or ("")
+    {
+        "Ledgers" | "QueryLedgers" | "TradesHistory"  =>  2.0,
+        "AddOrder" | "AddOrderBatch" | "EditOrder" | "CancelOrder"
+          | "CancelAll" | "CancelAllOrdersAfter"  =>  0.0,
+        _  =>  1.0
+    }
+}
+
+
+
+/*  When calls have to wait for room on the counter, they go in this order;
+ *  calls of equal priority go in the order they arrived.  Calls to the public
+ *  end-points never wait. */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum  Priority  {  ORDER_ENTRY,  ORDER_CANCEL,  ACCOUNT_DATA,  HISTORY  }
+
+
+
+fn  priority  (end_point:  &str)  ->  Priority
+{
+    match  end_point.split ('?').next ().unwrap_or ("")
+    {
+        "AddOrder" | "AddOrderBatch" | "EditOrder"  =>  Priority::ORDER_ENTRY,
+        "CancelOrder" | "CancelAll" | "CancelAllOrdersAfter"
+                                                   =>  Priority::ORDER_CANCEL,
+        "ClosedOrders" | "TradesHistory" | "QueryTrades" | "Ledgers"
+          | "QueryLedgers" | "AddExport" | "ExportStatus" | "RetrieveExport"
+          | "RemoveExport"                         =>  Priority::HISTORY,
+        _                                          =>  Priority::ACCOUNT_DATA
+    }
+}
+
+
+
+/*  Our model of the exchange's call counter for the account.  The decay is
+ *  multiplied by the scale, which drops below one after the exchange has
+ *  told us that we are going too fast. */
+
+pub(crate)  struct  Rate_Limit  {  maximum:  f64,
+                                   decay:    f64,
+                                   scale:    f64,
+                                   counter:  f64,
+                                   at:       Instant  }
+
+
+
+/*  The slowest we will go, as a fraction of the tier's nominal rate, and how
+ *  much faster each successful call lets us go. */
+
+const  MIN_SCALE:  f64  =  0.125;
+const  RECOVERY:   f64  =  1.1;
+
+
+
+impl  Rate_Limit
+{
+    pub(crate)  fn  new  (tier:  Account_Tier)  ->  Self
+    {
+        Rate_Limit {  maximum:  tier.maximum (),
+                      decay:    tier.decay (),
+                      scale:    1.0,
+                      counter:  0.0,
+                      at:       Instant::now ()  }
+    }
+
+
+    /*  The value of the counter at time `now`. */
+    fn  level  (&self,  now:  Instant)  ->  f64
+    {
+        let  elapsed  =  now.saturating_duration_since (self.at);
+        (self.counter  -  self.decay * self.scale * elapsed.as_secs_f64 ())
+            .max (0.0)
+    }
+
+
+    /*  The charge for a call to the end-point.  While we are recovering from
+     *  a rate-limit error, calls which do not normally count cost one. */
+    pub(crate)  fn  cost  (&self,  end_point:  &str)  ->  f64
+    {
+        let  cost  =  call_cost (end_point);
+        if  cost > 0.0  ||  self.scale >= 1.0   {   cost   }   else  {  1.0  }
+    }
+
+
+    pub(crate)  fn  remaining  (&self,  now:  Instant)  ->  f64
+          {   self.maximum  -  self.level (now)   }
+
+
+    /*  How long until a call of the given cost can be made without taking
+     *  the counter over the maximum; a cost larger than the maximum is
+     *  treated as the maximum, or it would never be allowed. */
+    pub(crate)  fn  time_until  (&self,  cost:  f64,  now:  Instant)
+                 ->  Duration
+    {
+        let  excess  =  self.level (now)  +  cost.min (self.maximum)
+                                          -  self.maximum;
+        if  excess <= 0.0   {   return  Duration::ZERO;   }
+        Duration::from_secs_f64 (excess / (self.decay * self.scale))
+    }
+
+
+    pub(crate)  fn  charge  (&mut self,  cost:  f64,  now:  Instant)
+    {
+        self.counter  =  self.level (now)  +  cost;
+        self.at  =  now;
+    }
+
+
+    /*  The exchange has refused a call as over the limit: take the counter
+     *  to be full, and slow down. */
+    pub(crate)  fn  penalise  (&mut self,  now:  Instant)
+    {
+        self.counter  =  self.maximum;
+        self.at  =  now;
+        self.scale  =  (self.scale / 2.0).max (MIN_SCALE);
+    }
+
+
+    /*  A call has gone through: speed up a little, if we had slowed down. */
+    pub(crate)  fn  relax  (&mut self,  now:  Instant)
+    {
+        if  self.scale >= 1.0   {   return;   }
+        self.counter  =  self.level (now);
+        self.at  =  now;
+        self.scale  =  (self.scale * RECOVERY).min (1.0);
+    }
+}
+
+
+
+/*  The rate limiter proper, which may be shared between several handles on
+ *  the same account: the counter model, if an account tier is known, and the
+ *  queue of calls waiting for room on it. */
+
+#[derive(Default)]
+pub(crate)  struct  Limiter  {  state:  Mutex<Limiter_State>,
+                               wake:   Condvar  }
+
+#[derive(Default)]
+struct  Limiter_State  {  limit:    Option<Rate_Limit>,
+                          waiting:  BTreeSet<(Priority, u64)>,
+                          tickets:  u64  }
+
+
+
+impl  Limiter
+{
+    pub(crate)  fn  set_tier  (&self,  tier:  Option<Account_Tier>)
+    {
+        self.state.lock ().unwrap ().limit  =  tier.map (Rate_Limit::new);
+        self.wake.notify_all ();
+    }
+
+
+    pub(crate)  fn  remaining  (&self)  ->  f64
+    {
+        self.state.lock ().unwrap ().limit.as_ref ()
+            .map_or (f64::INFINITY,  |L| L.remaining (Instant::now ()))
+    }
+
+
+    pub(crate)  fn  time_until  (&self,  cost:  f64)  ->  Duration
+    {
+        self.state.lock ().unwrap ().limit.as_ref ()
+            .map_or (Duration::ZERO,  |L| L.time_until (cost, Instant::now ()))
+    }
+
+
+    /*  Wait until the call to the end-point is at the head of the queue and
+     *  the counter has room for it, and then charge it.  The token is looked
+     *  at every few milliseconds while we wait. */
+    pub(crate)  fn  acquire  (&self,  end_point:  &str,
+                              token:  Option<&Cancel_Token>)
+                 ->  Result<(), String>
+    {
+        let  mut  S  =  self.state.lock ().unwrap ();
+        if  S.limit.is_none ()   {   return  Ok (());   }
+
+        S.tickets  +=  1;
+        let  ticket  =  (priority (end_point), S.tickets);
+        S.waiting.insert (ticket);
+
+        loop
+        {
+            if  token.is_some_and (Cancel_Token::is_cancelled)
+            {
+                S.waiting.remove (&ticket);
+                self.wake.notify_all ();
+                Err ("request cancelled".to_string ()) ?;
+            }
+
+            let  head  =  S.waiting.first () == Some (&ticket);
+            let  now   =  Instant::now ();
+            let  wait  =  match  S.limit.as_mut ()
+                          {
+                              None  =>  Duration::ZERO,
+                              Some (L)  =>  {
+                                  let  cost  =  L.cost (end_point);
+                                  let  wait  =  L.time_until (cost, now);
+                                  if  head  &&  wait.is_zero ()
+                                      {   L.charge (cost, now);   }
+                                  wait  }
+                          };
+
+            if  head  &&  wait.is_zero ()
+            {
+                S.waiting.remove (&ticket);
+                self.wake.notify_all ();
+                return  Ok (());
+            }
+
+            let  tick  =  Duration::from_millis (50);
+            let  nap   =  if  wait.is_zero ()  {  tick  }
+                          else  {  wait.min (tick)  };
+            S  =  self.wake.wait_timeout (S, nap).unwrap ().0;
+        }
+    }
+
+
+    /*  The exchange has answered a call: if it refused it for going too fast,
+     *  slow down, putting a limiter in place if there was none; otherwise
+     *  speed up a little. */
+    pub(crate)  fn  observe  (&self,  reply:  &str)
+    {
+        let  now  =  Instant::now ();
+        let  mut  S  =  self.state.lock ().unwrap ();
+
+        if  reply.contains ("EAPI:Rate limit exceeded")
+              ||  reply.contains ("EOrder:Rate limit exceeded")
+            {   S.limit.get_or_insert (Rate_Limit::new (Account_Tier::STARTER))
+                 .penalise (now);   }
+        else if let Some (L) = S.limit.as_mut ()
+            {   L.relax (now);   }
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  counter  ()
+    {
+        use  super::{Account_Tier, Rate_Limit};
+        use  std::time::{Duration, Instant};
+
+        let  t0  =  Instant::now ();
+        let  mut  L  =  Rate_Limit::new (Account_Tier::PRO);
+        L.at  =  t0;
+
+        assert_eq! (L.remaining (t0), 20.0);
+        for  _  in  0..9   {   L.charge (L.cost ("Ledgers?ofs=50"), t0);   }
+        assert_eq! (L.cost ("AddOrder"), 0.0);
+        assert_eq! (L.remaining (t0), 2.0);
+        assert_eq! (L.time_until (2.0, t0), Duration::ZERO);
+        assert_eq! (L.time_until (5.0, t0), Duration::from_secs (3));
+
+        let  t1  =  t0 + Duration::from_secs (4);
+        assert_eq! (L.remaining (t1), 6.0);
+        assert_eq! (L.remaining (t1 + Duration::from_secs (60)), 20.0);
+
+        //  After a refusal the counter is full and drains at half speed, and
+        //  orders count until we are back up to speed.
+        L.penalise (t1);
+        assert_eq! (L.remaining (t1), 0.0);
+        assert_eq! (L.time_until (1.0, t1), Duration::from_secs (2));
+        assert_eq! (L.cost ("AddOrder"), 1.0);
+        for  _  in  0..8   {   L.relax (t1);   }
+        assert_eq! (L.cost ("AddOrder"), 0.0);
+    }
+
+
+    #[test]
+    fn  queue  ()
+    {
+        use  super::{Account_Tier, Limiter};
+        use  std::sync::{Arc, Mutex};
+        use  std::time::Duration;
+
+        //  Fill the counter, then let a history query and an order compete
+        //  for the next slot: the order must go through first, even though it
+        //  came second.
+        let  L  =  Arc::new (Limiter::default ());
+        L.set_tier (Some (Account_Tier::PRO));
+        L.observe ("{\"error\":[\"EAPI:Rate limit exceeded\"]}");
+
+        let  order  =  Arc::new (Mutex::new (Vec::new ()));
+        let  spawn  =  |end_point: &'static str|  {
+                          let  (L, order)  =  (L.clone (), order.clone ());
+                          std::thread::spawn (move ||  {
+                              L.acquire (end_point, None).unwrap ();
+                              order.lock ().unwrap ().push (end_point);  })  };
+
+        let  history  =  spawn ("Ledgers");
+        std::thread::sleep (Duration::from_millis (100));
+        let  entry  =  spawn ("AddOrder");
+        entry.join ().unwrap ();
+
+        assert_eq! (*order.lock ().unwrap (), ["AddOrder"]);
+        assert! (! history.is_finished ());
+    }
+}
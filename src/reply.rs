@@ -0,0 +1,94 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  Helpers for picking apart the JSON strings the exchange sends back to us,
+ *  for the use of those parts of the crate which offer typed results. */
+
+
+
+use  crate::Auth_Error;
+use  crate::warnings::is_warning;
+use  serde_json  as  JSN;
+
+
+
+/*  Parse the exchange's reply, and return the 'result' section of it, unless
+ *  the 'error' section holds anything other than warnings, in which case the
+ *  error strings are returned joined together; the ones which reject the
+ *  credentials carry a hint as to what to do about them. */
+
+pub(crate)  fn  result_of  (reply:  &str)  ->  Result<JSN::Value, String>
+{
+    let  mut  V  =  JSN::from_str::<JSN::Value> (reply)
+                        .map_err (|E| E.to_string ()) ?;
+
+    check_errors (V ["error"].as_array ().into_iter ().flatten ()
+                             .map (|e| e.as_str ().unwrap_or (""))) ?;
+
+    match  V.get_mut ("result")
+       {   Some (R)  =>  Ok (R.take ()),
+           None  =>  Err ("no result in reply from exchange".to_string ())  }
+}
+
+
+
+/*  Fail with the messages in an 'error' section, joined together, unless
+ *  they are all warnings. */
+
+pub(crate)  fn  check_errors<'a>  (messages:  impl Iterator<Item = &'a str>)
+          ->  Result<(), String>
+{
+    let  E  =  messages.filter (|e| ! is_warning (e))
+                       .map (|e|  match  Auth_Error::from_message (e)
+                                     {   Some (A)  =>  A.to_string (),
+                                         None  =>  e.to_string ()   })
+                       .collect::<Vec<_>> ();
+    if  ! E.is_empty ()   {   Err (E.join (", ")) ?   }
+    Ok (())
+}
+
+
+
+/*  Kraken sends most numbers as strings, some as actual JSON numbers; either
+ *  way we want an f64. */
+
+pub(crate)  fn  number  (V:  &JSN::Value)  ->  Result<f64, String>
+{
+    match  V
+       {   JSN::Value::String (S)
+                  =>  S.parse::<f64> ().map_err (|E| E.to_string ()),
+           JSN::Value::Number (N)
+                  =>  N.as_f64 ().ok_or ("bad number".to_string ()),
+           _  =>  Err (format! ("expected a number, got {}", V))   }
+}
+
+
+
+/*  Extract a string field, with an error message naming the field if it is
+ *  absent. */
+
+pub(crate)  fn  string  (V:  &JSN::Value,  field:  &str)
+          ->  Result<String, String>
+{
+    V [field].as_str ()
+             .map (str::to_string)
+             .ok_or (format! ("missing '{}' in reply from exchange", field))
+}
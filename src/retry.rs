@@ -0,0 +1,134 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  Which requests may safely be sent again when the first attempt fails, and
+ *  what to say when one which may not be repeated fails part-way through. */
+
+
+
+use  crate::Reply_Too_Large;
+
+
+
+/** The start of the error message given when a request which changes the
+    state of the account, such as placing an order, failed in such a way that
+    we cannot tell whether the exchange acted on it or not.  Such requests are
+    never retried; the rest of the message says how to find out what
+    happened.  */
+
+pub  const  UNKNOWN_OUTCOME:  &str  =  "unknown outcome";
+
+
+
+/*  Can the request (an end-point, optionally followed by '?' and its
+ *  arguments) be repeated without risk of doing something twice?  All the
+ *  public end-points and the private enquiries can, except an enquiry after
+ *  deposit addresses which asks for a new one; cancelling is safe to repeat
+ *  too, as a second attempt can only find the order already gone. */
+
+pub(crate)  fn  idempotent  (query:  &str)  ->  bool
+{
+    let  (end_point, arguments)  =  query.split_once ('?')
+                                         .unwrap_or ((query, ""));
+    match  end_point
+    {
+        "AddOrder" | "AddOrderBatch" | "EditOrder" | "AddExport"
+          | "RemoveExport" | "Withdraw" | "WithdrawCancel"
+          | "WalletTransfer" | "Stake" | "Unstake"
+            =>  false,
+        "DepositAddresses"
+            =>  ! arguments.split ('&').any (|A| A == "new=true"),
+        _   =>  true
+    }
+}
+
+
+
+/*  Did the attempt fail in a way that another attempt might not?  That is
+ *  any failure of the transport other than cancellation or an over-large
+ *  reply, and the exchange saying it is too busy or down for a moment. */
+
+pub(crate)  fn  worth_retrying  (outcome:  &Result<String, String>)  ->  bool
+{
+    match  outcome
+    {
+        Ok (reply)  =>  reply.contains ("EService:Unavailable")
+                          ||  reply.contains ("EService:Busy"),
+        Err (E)     =>  E != "request cancelled"
+                          &&  Reply_Too_Large::from_message (E).is_none ()
+    }
+}
+
+
+
+/*  The message for a request which cannot be retried and whose fate we do
+ *  not know, with a hint as to how the user can find out. */
+
+pub(crate)  fn  unknown_outcome  (end_point:  &str,  error:  &str)  ->  String
+{
+    let  end_point  =  end_point.split ('?').next ().unwrap_or ("");
+    let  hint  =  match  end_point
+    {
+        "AddOrder" | "AddOrderBatch" | "EditOrder"
+            =>  "look for the order with open_orders and closed_orders, \
+                 ideally by the USERREF given to it, before sending it again",
+        "Withdraw" | "WithdrawCancel" | "WalletTransfer"
+            =>  "check the recent withdrawals before trying again",
+        "AddExport" | "RemoveExport"
+            =>  "check get_export_report_status before trying again",
+        _   =>  "check the state of the account before trying again"
+    };
+
+    format! ("{}: {} may or may not have been acted on ({}); {}",
+             UNKNOWN_OUTCOME, end_point, error, hint)
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  classification  ()
+    {
+        use  super::{UNKNOWN_OUTCOME, idempotent, unknown_outcome,
+                     worth_retrying};
+
+        assert! (idempotent ("Ticker?pair=XBTUSD"));
+        assert! (idempotent ("QueryOrders?txid=X"));
+        assert! (idempotent ("CancelOrder?txid=X"));
+        assert! (! idempotent ("AddOrder?ordertype=limit"));
+        assert! (! idempotent ("Withdraw"));
+        assert! (idempotent ("DepositAddresses?asset=XBT&method=Bitcoin"));
+        assert! (idempotent ("DepositAddresses?asset=XBT&new=false"));
+        assert! (! idempotent ("DepositAddresses?asset=XBT&new=true"));
+        assert! (! idempotent ("DepositAddresses?new=true"));
+
+        assert! (worth_retrying (&Err ("Timeout was reached".into ())));
+        assert! (! worth_retrying (&Err ("request cancelled".into ())));
+        assert! (worth_retrying (&Ok (r#"{"error":["EService:Busy"]}"#
+                                         .into ())));
+        assert! (! worth_retrying (&Ok (r#"{"error":[]}"#.into ())));
+
+        assert! (unknown_outcome ("AddOrder?pair=XBTUSD", "Timeout")
+                    .starts_with (&format! ("{}: AddOrder ", UNKNOWN_OUTCOME)));
+    }
+}
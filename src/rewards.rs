@@ -0,0 +1,319 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option as Opt, Asset, Kraken_API, Ledger_Entry, civil_date};
+use  crate::conversion::{rate, route};
+use  crate::reply;
+use  std::collections::{BTreeMap, BTreeSet};
+
+
+
+/** How [Kraken_API::rewards_report] groups rewards over time: by calendar
+    day, month or year, in UTC. */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub  enum  Reward_Period
+{
+    /** A calendar day. */
+    DAY,
+    /** A calendar month. */
+    MONTH,
+    /** A calendar year. */
+    YEAR
+}
+
+
+
+impl  Reward_Period
+{
+    /** The name of the period in which the UNIX time `time` falls, e.g.
+        "2024-03-05", "2024-03" or "2024"; the names sort into time
+        order. */
+    pub  fn  label  (self,  time:  f64)  ->  String
+    {
+        let  (year, month, day)  =  civil_date (day_of (time));
+        match  self
+           {   Reward_Period::DAY
+                   =>  format! ("{:04}-{:02}-{:02}", year, month, day),
+               Reward_Period::MONTH  =>  format! ("{:04}-{:02}", year, month),
+               Reward_Period::YEAR   =>  format! ("{:04}", year)   }
+    }
+}
+
+
+
+/** The staking or earn rewards received in one asset over one period, as
+    given by [Kraken_API::rewards_report]. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Reward_Total
+{
+    /** The asset the rewards were paid in, as named in the ledger, e.g.
+        "DOT.S". */
+    pub  asset:   String,
+
+    /** The period, as named by [Reward_Period::label]. */
+    pub  period:  String,
+
+    /** The number of rewards received. */
+    pub  count:   usize,
+
+    /** The total amount received. */
+    pub  amount:  f64,
+
+    /** The total of the fees charged on the rewards. */
+    pub  fee:     f64,
+
+    /** The total worth of the rewards in the quote currency, each reward
+        valued at the time it was received; `None` if no valuation was asked
+        for, or if any one of the rewards could not be valued. */
+    pub  value:   Option<f64>
+}
+
+
+
+impl  Reward_Total
+{
+    /** Total up the rewards ([Ledger_Entry::is_reward]) among `entries`, per
+        asset per period, in order of asset and then of period.  `price`
+        gives the worth of one unit of an entry's asset when it was received,
+        if that is known. */
+    pub  fn  from_entries  (entries:  &[Ledger_Entry],
+                            period:  Reward_Period,
+                            price:  impl Fn (&Ledger_Entry) -> Option<f64>)
+               ->  Vec<Self>
+    {
+        let  mut  M  =  BTreeMap::new ();
+
+        for  E  in  entries.iter ().filter (|E| E.is_reward ())
+        {
+            let  label  =  period.label (E.time);
+            let  T  =  M.entry ((E.asset.clone (), label.clone ()))
+                        .or_insert (Reward_Total {  asset:   E.asset.clone (),
+                                                    period:  label,
+                                                    count:   0,
+                                                    amount:  0.0,
+                                                    fee:     0.0,
+                                                    value:   Some (0.0)  });
+            T.count   +=  1;
+            T.amount  +=  E.amount;
+            T.fee     +=  E.fee;
+            T.value    =  T.value.and_then (|V|  Some (V + E.amount
+                                                              * price (E) ?));
+        }
+
+        M.into_values ().collect ()
+    }
+}
+
+
+
+/*  The number of the UTC day in which UNIX time `time` falls. */
+
+fn  day_of  (time:  f64)  ->  i64   {   (time / 86400.0).floor () as i64   }
+
+
+
+impl  Kraken_API
+{
+/** Get every staking or earn reward in the ledger (see
+    [Ledger_Entry::is_reward]), oldest first.
+
+    This pages through the [Kraken_API::ledgers_info] end-point, 50 entries
+    at a time, until the ledger is exhausted, so it may take many calls.  It
+    replaces any [API_Option::TYPE](crate::API_Option::TYPE) and
+    [API_Option::OFS](crate::API_Option::OFS) settings, and respects the
+    asset, start and end settings, which are the way to keep the number of
+    calls down.  */
+
+  pub  fn  reward_entries  (&mut self)  ->  Result<Vec<Ledger_Entry>, String>
+    {
+      self.clear_opt (Opt::TYPE);
+      let  mut  offset  =  0;
+      let  mut  rewards  =  Vec::new ();
+
+      loop
+      {
+          self.set_opt (Opt::OFS, offset);
+          let  page  =  self.ledger_entries () ?;
+          if  page.is_empty ()   {   break;   }
+          offset  +=  page.len ();
+          rewards.extend (page.into_iter ().filter (|E| E.is_reward ()));
+      }
+
+      self.clear_opt (Opt::OFS);
+      rewards.sort_by (|a, b| a.time.total_cmp (&b.time));
+      Ok (rewards)
+    }
+
+
+
+/** Total up the account's staking and earn rewards per asset and per
+    `period`, for income tax reporting.  If a `quote` asset is given (in
+    Kraken's canonical form, e.g. "ZUSD"), each reward is also valued in it at
+    the volume-weighted average price of the day it was received.
+
+    The rewards come from [Kraken_API::reward_entries], and so the same
+    settings apply.  Staked variants such as "DOT.S" are priced by their
+    underlying asset ([Kraken_API::underlying_asset]), converted into the
+    quote asset as for [Kraken_API::convert_amount].  The daily prices come
+    from the [Kraken_API::ohlc_data] end-point, which only reaches back 720
+    days; the value of any total including a reward older than that, or in an
+    asset with no route to the quote asset, is `None`.  Any
+    [API_Option::INTERVAL](crate::API_Option::INTERVAL) and
+    [API_Option::SINCE](crate::API_Option::SINCE) settings are cleared.  */
+
+  pub  fn  rewards_report  (&mut self,  period:  Reward_Period,
+                            quote:  Option<&str>)
+               ->  Result<Vec<Reward_Total>, String>
+    {
+      let  entries  =  self.reward_entries () ?;
+      let  prices  =  match  quote
+                         {   Some (Q)  =>  self.reward_prices (&entries, Q) ?,
+                             None  =>  BTreeMap::new ()   };
+
+      Ok (Reward_Total::from_entries
+              (&entries, period,
+               |E|  prices.get (&(E.asset.clone (), day_of (E.time)))
+                          .copied ()))
+    }
+
+
+
+/*  The price in `quote` of each asset among the `entries` on each day on
+ *  which there is an entry, keyed by asset and day number, as far as they can
+ *  be found. */
+
+  fn  reward_prices  (&mut self,  entries:  &[Ledger_Entry],  quote:  &str)
+         ->  Result<BTreeMap<(String, i64), f64>, String>
+    {
+      let  pairs  =  self.all_pairs () ?;
+      let  mut  daily  =  BTreeMap::<String, BTreeMap<i64, f64>>::new ();
+      let  mut  prices  =  BTreeMap::new ();
+
+      let  assets  =  entries.iter ()
+                             .map (|E| E.asset.as_str ())
+                             .collect::<BTreeSet<_>> ();
+
+      for  A  in  assets
+      {
+          let  U  =  self.underlying_asset (&Asset::unchecked (A)) ?;
+          let  legs  =  match  route (&pairs, &U, quote)
+                           {   Some (L)  =>  L,   None  =>  continue   };
+
+          for  L  in  &legs
+          {
+              if  ! daily.contains_key (&L.pair)
+                  {   daily.insert (L.pair.clone (),
+                                    self.daily_prices (&L.pair) ?);   }
+          }
+
+          for  E  in  entries.iter ().filter (|E| E.asset == A)
+          {
+              let  day  =  day_of (E.time);
+              let  mids  =  legs.iter ()
+                                .filter_map (|L|  Some ((L.pair.clone (),
+                                                         *daily [&L.pair]
+                                                              .get (&day) ?)))
+                                .collect ();
+              if let Ok (R) = rate (&legs, &pairs, &mids)
+                  {   prices.insert ((A.to_string (), day), R);   }
+          }
+      }
+
+      Ok (prices)
+    }
+
+
+
+/*  The volume-weighted average price of `pair` on each of the last 720 days
+ *  on which it traded, keyed by day number. */
+
+  fn  daily_prices  (&mut self,  pair:  &str)
+         ->  Result<BTreeMap<i64, f64>, String>
+    {
+      self.clear_opt (Opt::SINCE);
+      self.set_opt (Opt::INTERVAL, 1440);
+      let  R  =  self.ohlc_data (pair);
+      self.clear_opt (Opt::INTERVAL);
+      let  R  =  reply::result_of (&R ?) ?;
+
+      let  mut  M  =  BTreeMap::new ();
+      for  C  in  R.as_object ().into_iter ().flatten ()
+                   .filter (|(name, _)| *name != "last")
+                   .flat_map (|(_, V)| V.as_array ().into_iter ().flatten ())
+      {
+          let  vwap  =  reply::number (&C [5]) ?;
+          if  vwap > 0.0
+              {   M.insert (day_of (reply::number (&C [0]) ?), vwap);   }
+      }
+      Ok (M)
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  totals  ()
+    {
+        use  super::{Reward_Period, Reward_Total};
+        use  crate::{Ledger_Entry, Ledger_Type};
+        use  crate::test_fixtures::ledger_entry;
+
+        let  entry  =  |time: f64, kind: Ledger_Type, subtype: &str,
+                        asset: &str, amount: f64|
+                         Ledger_Entry {  subtype:  subtype.into (),
+                                         ..ledger_entry ("", time, kind, asset,
+                                                         amount, 0.0)  };
+
+        //  2024-03-05, 2024-03-31 and 2024-04-01.
+        let  E  =  [entry (1709640000.0, Ledger_Type::STAKING, "",
+                           "DOT.S", 0.5),
+                    entry (1711886400.0, Ledger_Type::EARN, "reward",
+                           "DOT.S", 0.25),
+                    entry (1711886400.0, Ledger_Type::EARN, "allocation",
+                           "DOT.S", -10.0),
+                    entry (1711972800.0, Ledger_Type::REWARD, "",
+                           "DOT.S", 1.0),
+                    entry (1711972800.0, Ledger_Type::STAKING, "",
+                           "XETH", 1.0),
+                    entry (1711972800.0, Ledger_Type::TRADE, "",
+                           "XETH", 2.0)];
+
+        assert_eq! (Reward_Period::DAY.label (E [0].time), "2024-03-05");
+        assert_eq! (Reward_Period::YEAR.label (E [0].time), "2024");
+
+        let  T  =  Reward_Total::from_entries
+                      (&E, Reward_Period::MONTH,
+                       |E|  if  E.asset == "DOT.S"  {  Some (8.0)  }
+                            else  {  None  });
+        assert_eq! (T.iter ()
+                     .map (|T| (T.asset.as_str (), T.period.as_str (),
+                                T.count, T.amount, T.value))
+                     .collect::<Vec<_>> (),
+                    vec! [("DOT.S", "2024-03", 2, 0.75, Some (6.0)),
+                          ("DOT.S", "2024-04", 1, 1.0, Some (8.0)),
+                          ("XETH", "2024-04", 1, 1.0, None)]);
+    }
+}
@@ -0,0 +1,202 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::civil_date;
+use  std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+
+
+/** A recurring schedule, written in the style of a crontab entry.
+
+    The specification has five space-separated fields: minute (0-59), hour
+    (0-23), day of the month (1-31), month (1-12) and day of the week (0-6,
+    with 0 being Sunday).  Each field may be `*`, a number, a range `a-b`, any
+    of those followed by a step `/n`, or a comma-separated list of such items.
+    As with cron, when both the day-of-month and day-of-week fields are
+    restricted the schedule fires on days matching *either* of them.
+
+    All times are taken to be UTC, which is what the exchange works in.
+
+    ```ignore
+    //  Ten past nine every Monday morning.
+    let  S  =  KKN::Schedule::parse ("10 9 * * 1") ?;
+    ```  */
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub  struct  Schedule  {  minutes:   u64,
+                          hours:     u64,
+                          days:      u64,
+                          months:    u64,
+                          weekdays:  u64,
+                          any_day:      bool,
+                          any_weekday:  bool  }
+
+
+
+impl  Schedule
+{
+    /** Interpret a crontab-style specification, as described above. */
+    pub  fn  parse  (spec:  &str)  ->  Result<Self, String>
+    {
+        let  F  =  spec.split_whitespace ().collect::<Vec<_>> ();
+
+        if  F.len () != 5
+            {   Err (format! ("schedule '{}' needs five fields", spec)) ?   }
+
+        Ok (Schedule {  minutes:      field (F [0], 0, 59) ?,
+                        hours:        field (F [1], 0, 23) ?,
+                        days:         field (F [2], 1, 31) ?,
+                        months:       field (F [3], 1, 12) ?,
+                        weekdays:     field (F [4], 0, 6) ?,
+                        any_day:      F [2] == "*",
+                        any_weekday:  F [4] == "*"  })
+    }
+
+
+    /** Determine if the schedule fires at some point during the minute
+        containing `t`. */
+    pub  fn  matches  (&self,  t:  SystemTime)  ->  bool
+    {
+        let  secs  =  t.duration_since (UNIX_EPOCH).unwrap ().as_secs ();
+        let  minute  =  secs / 60 % 60;
+        let  hour    =  secs / 3600 % 24;
+
+        self.day_matches ((secs / 86400) as i64)
+            &&  self.hours & (1 << hour) != 0
+            &&  self.minutes & (1 << minute) != 0
+    }
+
+
+    /** The first minute strictly after `t` at which the schedule fires, or
+        `None` if there is no such time in the next four years (which can only
+        happen with impossible dates like the 31st of February). */
+    pub  fn  next_after  (&self,  t:  SystemTime)  ->  Option<SystemTime>
+    {
+        let  secs   =  t.duration_since (UNIX_EPOCH).unwrap ().as_secs ();
+        let  start  =  secs / 60 + 1;
+        let  first_day  =  start / 1440;
+
+        for  day  in  first_day .. first_day + 4 * 366
+        {
+            if  ! self.day_matches (day as i64)   {   continue;   }
+
+            let  from  =  if day == first_day  { start % 1440 }  else  { 0 };
+
+            for  m  in  from .. 1440
+            {   if  self.hours & (1 << (m / 60)) != 0
+                    &&  self.minutes & (1 << (m % 60)) != 0
+                {   return  Some (UNIX_EPOCH
+                                    + Duration::from_secs ((day * 1440 + m)
+                                                           * 60));   }   }
+        }
+
+        None
+    }
+
+
+    fn  day_matches  (&self,  days:  i64)  ->  bool
+    {
+        let  (_, month, day)  =  civil_date (days);
+        let  weekday  =  (days + 4).rem_euclid (7);
+
+        let  dom  =  self.days & (1 << day) != 0;
+        let  dow  =  self.weekdays & (1 << weekday) != 0;
+
+        self.months & (1 << month) != 0
+            &&  match  (self.any_day, self.any_weekday)
+                   {   (true, true)    =>  true,
+                       (true, false)   =>  dow,
+                       (false, true)   =>  dom,
+                       (false, false)  =>  dom || dow   }
+    }
+}
+
+
+
+/*  Turn one field of the specification into a bit-set of the values it
+ *  allows. */
+
+fn  field  (spec:  &str,  low:  u64,  high:  u64)  ->  Result<u64, String>
+{
+    let  number  =  |s: &str|  ->  Result<u64, String>
+                    {   let  n  =  s.parse::<u64> ()
+                                    .map_err (|_| format! ("bad number '{}' in \
+                                                            schedule", s)) ?;
+                        if  n < low  ||  n > high
+                            {   Err (format! ("{} is out of range in schedule",
+                                              n)) ?   }
+                        Ok (n)   };
+
+    let  mut  bits  =  0u64;
+
+    for  item  in  spec.split (',')
+    {
+        let  (range, step)  =  match  item.split_once ('/')
+                                 {   Some ((r, s))  =>  (r, number_step (s) ?),
+                                     None           =>  (item, 1)   };
+
+        let  (a, b)  =  if  range == "*"  {  (low, high)  }
+                        else if let Some ((a, b)) = range.split_once ('-')
+                             {  (number (a) ?, number (b) ?)  }
+                        else {  let  n  =  number (range) ?;
+                                (n,  if step > 1 { high } else { n })  };
+
+        for  n  in  (a ..= b).step_by (step as usize)   {   bits |= 1 << n;   }
+    }
+
+    Ok (bits)
+}
+
+
+
+fn  number_step  (s:  &str)  ->  Result<u64, String>
+{
+    match  s.parse::<u64> ()
+       {   Ok (n)  if  n > 0  =>  Ok (n),
+           _  =>  Err (format! ("bad step '{}' in schedule", s))   }
+}
+
+
+
+#[cfg(test)]
+mod  test
+  {  #[test]  fn  next_after ()  ->  Result<(), String>
+     {
+         use  std::time::{Duration, UNIX_EPOCH};
+
+         //  2022-12-25T09:30:59Z was a Sunday.
+         let  t  =  UNIX_EPOCH + Duration::from_secs (1671960659);
+
+         let  S  =  super::Schedule::parse ("10 9 * * 1") ?;
+         assert_eq! (S.next_after (t),
+                     Some (UNIX_EPOCH + Duration::from_secs (1672045800)));
+
+         let  S  =  super::Schedule::parse ("*/15 * * * *") ?;
+         assert_eq! (S.next_after (t),
+                     Some (UNIX_EPOCH + Duration::from_secs (1671960600
+                                                             + 15 * 60)));
+
+         assert! (super::Schedule::parse ("0 0 31 2 *") ?.next_after (t)
+                         .is_none ());
+         assert! (super::Schedule::parse ("60 * * * *").is_err ());
+
+         Ok (())
+     }  }
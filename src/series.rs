@@ -0,0 +1,477 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{Candle, Equity_Point, Instruction, Ledger_Entry, Spread, Trade};
+use  std::io::Write;
+use  std::path::Path;
+
+
+
+/** The type of the values in one column of a [Series_Row] table. */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub  enum  Column_Type
+{
+    /** A floating-point number. */
+    FLOAT,
+    /** A whole number. */
+    INTEGER,
+    /** A piece of text. */
+    TEXT,
+    /** True or false. */
+    BOOLEAN
+}
+
+
+
+/** One value in a row of a [Series_Row] table. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  enum  Field
+{
+    /** A value in a [Column_Type::FLOAT] column. */
+    FLOAT (f64),
+    /** A value in a [Column_Type::INTEGER] column. */
+    INTEGER (i64),
+    /** A value in a [Column_Type::TEXT] column. */
+    TEXT (String),
+    /** A value in a [Column_Type::BOOLEAN] column. */
+    BOOLEAN (bool),
+    /** No value at all. */
+    NULL
+}
+
+
+
+/** Records which can be written out as the rows of a table, always with the
+    same columns, by [write_csv], (with the `parquet` feature)
+    `write_parquet` and (with the `arrow` feature) `record_batch`.  Times are
+    UNIX times in seconds, in a column called "time", which comes first.
+
+    The crate implements this for [Candle] (from [Kraken_API::candles]),
+    [Trade] (from [Kraken_API::trades]), [Spread] (from
+    [Kraken_API::spreads]), [Ledger_Entry] (from
+    [Kraken_API::ledger_entries]) and [Equity_Point] (from
+    [Kraken_API::equity_curve]).
+
+    [Kraken_API::candles]: crate::Kraken_API::candles
+    [Kraken_API::trades]: crate::Kraken_API::trades
+    [Kraken_API::spreads]: crate::Kraken_API::spreads
+    [Kraken_API::ledger_entries]: crate::Kraken_API::ledger_entries
+    [Kraken_API::equity_curve]: crate::Kraken_API::equity_curve  */
+
+pub  trait  Series_Row
+{
+    /** A name for what the table holds, e.g. "trade". */
+    const  NAME:  &'static str;
+
+    /** The name and type of each column, in order. */
+    const  COLUMNS:  &'static [(&'static str, Column_Type)];
+
+    /** The values in this row, in the order of the columns. */
+    fn  fields  (&self)  ->  Vec<Field>;
+}
+
+
+
+impl  Series_Row  for  Candle
+{
+    const  NAME:  &'static str  =  "ohlc";
+
+    const  COLUMNS:  &'static [(&'static str, Column_Type)]
+        =  &[("time", Column_Type::FLOAT),   ("open", Column_Type::FLOAT),
+             ("high", Column_Type::FLOAT),   ("low", Column_Type::FLOAT),
+             ("close", Column_Type::FLOAT),  ("vwap", Column_Type::FLOAT),
+             ("volume", Column_Type::FLOAT), ("count", Column_Type::INTEGER)];
+
+    fn  fields  (&self)  ->  Vec<Field>
+    {
+        vec! [Field::FLOAT (self.time),   Field::FLOAT (self.open),
+              Field::FLOAT (self.high),   Field::FLOAT (self.low),
+              Field::FLOAT (self.close),  Field::FLOAT (self.vwap),
+              Field::FLOAT (self.volume), Field::INTEGER (self.count as i64)]
+    }
+}
+
+
+
+impl  Series_Row  for  Trade
+{
+    const  NAME:  &'static str  =  "trade";
+
+    const  COLUMNS:  &'static [(&'static str, Column_Type)]
+        =  &[("time", Column_Type::FLOAT),    ("price", Column_Type::FLOAT),
+             ("volume", Column_Type::FLOAT),  ("side", Column_Type::TEXT),
+             ("market", Column_Type::BOOLEAN),
+             ("trade_id", Column_Type::INTEGER)];
+
+    fn  fields  (&self)  ->  Vec<Field>
+    {
+        vec! [Field::FLOAT (self.time),  Field::FLOAT (self.price),
+              Field::FLOAT (self.volume),
+              Field::TEXT (match  self.side
+                              {   Instruction::BUY   =>  "buy",
+                                  Instruction::SELL  =>  "sell"   }
+                                  .to_string ()),
+              Field::BOOLEAN (self.market),
+              self.trade_id.map_or (Field::NULL,
+                                    |I|  Field::INTEGER (I as i64))]
+    }
+}
+
+
+
+impl  Series_Row  for  Spread
+{
+    const  NAME:  &'static str  =  "spread";
+
+    const  COLUMNS:  &'static [(&'static str, Column_Type)]
+        =  &[("time", Column_Type::FLOAT),  ("bid", Column_Type::FLOAT),
+             ("ask", Column_Type::FLOAT)];
+
+    fn  fields  (&self)  ->  Vec<Field>
+    {
+        vec! [Field::FLOAT (self.time),  Field::FLOAT (self.bid),
+              Field::FLOAT (self.ask)]
+    }
+}
+
+
+
+impl  Series_Row  for  Ledger_Entry
+{
+    const  NAME:  &'static str  =  "ledger";
+
+    const  COLUMNS:  &'static [(&'static str, Column_Type)]
+        =  &[("time", Column_Type::FLOAT),    ("id", Column_Type::TEXT),
+             ("refid", Column_Type::TEXT),    ("type", Column_Type::TEXT),
+             ("subtype", Column_Type::TEXT),  ("asset", Column_Type::TEXT),
+             ("amount", Column_Type::FLOAT),  ("fee", Column_Type::FLOAT),
+             ("balance", Column_Type::FLOAT)];
+
+    fn  fields  (&self)  ->  Vec<Field>
+    {
+        vec! [Field::FLOAT (self.time),
+              Field::TEXT (self.id.clone ()),
+              Field::TEXT (self.refid.clone ()),
+              Field::TEXT (self.kind.as_kraken_string ().to_string ()),
+              Field::TEXT (self.subtype.clone ()),
+              Field::TEXT (self.asset.clone ()),
+              Field::FLOAT (self.amount),  Field::FLOAT (self.fee),
+              Field::FLOAT (self.balance)]
+    }
+}
+
+
+
+impl  Series_Row  for  Equity_Point
+{
+    const  NAME:  &'static str  =  "equity";
+
+    const  COLUMNS:  &'static [(&'static str, Column_Type)]
+        =  &[("time", Column_Type::FLOAT),  ("equity", Column_Type::FLOAT),
+             ("unpriced", Column_Type::TEXT)];
+
+    fn  fields  (&self)  ->  Vec<Field>
+    {
+        vec! [Field::FLOAT (self.time),  Field::FLOAT (self.equity),
+              Field::TEXT (self.unpriced.join (" "))]
+    }
+}
+
+
+
+/*  A field as it appears in a CSV file: text is quoted if it needs to be,
+ *  and a NULL is left empty. */
+
+fn  csv_field  (F:  &Field)  ->  String
+{
+    match  F
+    {
+        Field::FLOAT (x)    =>  x.to_string (),
+        Field::INTEGER (i)  =>  i.to_string (),
+        Field::BOOLEAN (b)  =>  b.to_string (),
+        Field::NULL         =>  String::new (),
+        Field::TEXT (S)
+            =>  if  S.contains ([',', '"', '\n', '\r'])
+                    {  format! ("\"{}\"", S.replace ('"', "\"\""))  }
+                else  {  S.clone ()  }
+    }
+}
+
+
+
+/*  The whole CSV text: a heading line with the column names, then one line
+ *  per row. */
+
+fn  csv_text<R: Series_Row>  (rows:  &[R])  ->  String
+{
+    let  mut  T  =  R::COLUMNS.iter ()
+                              .map (|(name, _)| *name)
+                              .collect::<Vec<_>> ()
+                              .join (",")  +  "\n";
+    for  row  in  rows
+    {
+        T  +=  &row.fields ().iter ().map (csv_field)
+                   .collect::<Vec<_>> ().join (",");
+        T.push ('\n');
+    }
+    T
+}
+
+
+
+/** Write the `rows` to a CSV file at `path`, with a heading line naming the
+    columns; any existing file is replaced.  */
+
+pub  fn  write_csv<R: Series_Row>  (rows:  &[R],  path:  impl AsRef<Path>)
+           ->  Result<(), String>
+{
+    let  path  =  path.as_ref ();
+    std::fs::File::create (path)
+        .and_then (|mut F| F.write_all (csv_text (rows).as_bytes ()))
+        .map_err (|E| format! ("{}: {}", path.display (), E))
+}
+
+
+
+/** Write the `rows` to a Parquet file at `path`, as a single row group with
+    one nullable column for each of the row type's columns; any existing file
+    is replaced.  Only available with the `parquet` feature.  */
+
+#[cfg(feature = "parquet")]
+pub  fn  write_parquet<R: Series_Row>  (rows:  &[R],  path:  impl AsRef<Path>)
+           ->  Result<(), String>
+{
+    use  parquet::column::writer::ColumnWriter;
+    use  parquet::data_type::ByteArray;
+    use  parquet::file::{properties::WriterProperties,
+                         writer::SerializedFileWriter};
+    use  parquet::schema::parser::parse_message_type;
+    use  std::sync::Arc;
+
+    let  path  =  path.as_ref ();
+    let  fail  =  |E: parquet::errors::ParquetError|
+                     format! ("{}: {}", path.display (), E);
+
+    let  schema  =  format! ("message {} {{ {} }}", R::NAME,
+                             R::COLUMNS.iter ()
+                               .map (|(name, T)|  match  T
+                                   {   Column_Type::FLOAT
+                                           =>  format! ("OPTIONAL DOUBLE {};",
+                                                        name),
+                                       Column_Type::INTEGER
+                                           =>  format! ("OPTIONAL INT64 {};",
+                                                        name),
+                                       Column_Type::TEXT
+                                           =>  format! ("OPTIONAL BYTE_ARRAY \
+                                                         {} (UTF8);", name),
+                                       Column_Type::BOOLEAN
+                                           =>  format! ("OPTIONAL BOOLEAN {};",
+                                                        name)   })
+                               .collect::<Vec<_>> ()
+                               .join (" "));
+
+    let  F  =  std::fs::File::create (path)
+                   .map_err (|E| format! ("{}: {}", path.display (), E)) ?;
+    let  mut  W  =  SerializedFileWriter::new
+                        (F,
+                         Arc::new (parse_message_type (&schema)
+                                       .map_err (fail) ?),
+                         Arc::new (WriterProperties::builder ().build ()))
+                      .map_err (fail) ?;
+
+    let  table  =  rows.iter ().map (Series_Row::fields).collect::<Vec<_>> ();
+    let  mut  G  =  W.next_row_group ().map_err (fail) ?;
+
+    /*  The values present in a column, and the definition levels which
+     *  say which rows have them. */
+    fn  split<T>  (values:  Vec<Option<T>>)  ->  (Vec<T>, Vec<i16>)
+    {
+        let  levels  =  values.iter ().map (|V| V.is_some () as i16).collect ();
+        (values.into_iter ().flatten ().collect (), levels)
+    }
+
+    let  mut  c  =  0;
+    while let Some (mut C) = G.next_column ().map_err (fail) ?
+    {
+        let  column  =  table.iter ().map (|row| &row [c]);
+        match  C.untyped ()
+        {
+            ColumnWriter::DoubleColumnWriter (w)  =>  {
+                let  (V, L)  =  split (column.map (|F|  match  F
+                                          {   Field::FLOAT (x)  =>  Some (*x),
+                                              _  =>  None   })
+                                        .collect ());
+                w.write_batch (&V, Some (&L), None)
+            },
+            ColumnWriter::Int64ColumnWriter (w)  =>  {
+                let  (V, L)  =  split (column.map (|F|  match  F
+                                          {   Field::INTEGER (i)  =>  Some (*i),
+                                              _  =>  None   })
+                                        .collect ());
+                w.write_batch (&V, Some (&L), None)
+            },
+            ColumnWriter::BoolColumnWriter (w)  =>  {
+                let  (V, L)  =  split (column.map (|F|  match  F
+                                          {   Field::BOOLEAN (b)  =>  Some (*b),
+                                              _  =>  None   })
+                                        .collect ());
+                w.write_batch (&V, Some (&L), None)
+            },
+            ColumnWriter::ByteArrayColumnWriter (w)  =>  {
+                let  (V, L)  =  split (column.map (|F|  match  F
+                                          {   Field::TEXT (S)
+                                                  =>  Some (ByteArray::from
+                                                                (S.as_str ())),
+                                              _  =>  None   })
+                                        .collect ());
+                w.write_batch (&V, Some (&L), None)
+            },
+            _  =>  unreachable! ("no other column types in the schema")
+        }
+        .map_err (fail) ?;
+
+        C.close ().map_err (fail) ?;
+        c  +=  1;
+    }
+
+    G.close ().map_err (fail) ?;
+    W.close ().map_err (fail) ?;
+    Ok (())
+}
+
+
+
+/** Put the `rows` into an Arrow record batch, with one nullable column for
+    each of the row type's columns, for handing straight to an analysis
+    library (polars, DataFusion, ...) which reads Arrow data.  Only
+    available with the `arrow` feature.
+
+    ```ignore
+    let  B  =  KKN::record_batch (&K.candles ("XBTUSD", 60) ?) ?;
+    assert_eq! (B.schema ().field (0).name (), "time");
+    ```  */
+
+#[cfg(feature = "arrow")]
+pub  fn  record_batch<R: Series_Row>  (rows:  &[R])
+           ->  Result<arrow_array::RecordBatch, String>
+{
+    use  arrow_array::{ArrayRef, BooleanArray, Float64Array, Int64Array,
+                       RecordBatch, StringArray};
+    use  arrow_schema::{DataType, Field as Column, Schema};
+    use  std::sync::Arc;
+
+    let  table  =  rows.iter ().map (Series_Row::fields).collect::<Vec<_>> ();
+
+    let  mut  columns  =  Vec::<ArrayRef>::new ();
+    let  mut  schema   =  Vec::new ();
+    for  (c, (name, T))  in  R::COLUMNS.iter ().enumerate ()
+    {
+        let  column  =  table.iter ().map (|row| &row [c]);
+        let  (array, kind) :  (ArrayRef, DataType)  =  match  T
+        {
+            Column_Type::FLOAT
+                =>  (Arc::new (column.map (|F|  match  F
+                                              {   Field::FLOAT (x)
+                                                      =>  Some (*x),
+                                                  _  =>  None   })
+                                     .collect::<Float64Array> ()),
+                     DataType::Float64),
+            Column_Type::INTEGER
+                =>  (Arc::new (column.map (|F|  match  F
+                                              {   Field::INTEGER (i)
+                                                      =>  Some (*i),
+                                                  _  =>  None   })
+                                     .collect::<Int64Array> ()),
+                     DataType::Int64),
+            Column_Type::TEXT
+                =>  (Arc::new (column.map (|F|  match  F
+                                              {   Field::TEXT (S)
+                                                      =>  Some (S.as_str ()),
+                                                  _  =>  None   })
+                                     .collect::<StringArray> ()),
+                     DataType::Utf8),
+            Column_Type::BOOLEAN
+                =>  (Arc::new (column.map (|F|  match  F
+                                              {   Field::BOOLEAN (b)
+                                                      =>  Some (*b),
+                                                  _  =>  None   })
+                                     .collect::<BooleanArray> ()),
+                     DataType::Boolean)
+        };
+        columns.push (array);
+        schema.push (Column::new (*name, kind, true));
+    }
+
+    RecordBatch::try_new (Arc::new (Schema::new (schema)), columns)
+        .map_err (|E| E.to_string ())
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  csv  ()
+    {
+        use  super::csv_text;
+        use  crate::{Instruction, Spread, Trade};
+
+        let  T  =  [Trade {  price: 30000.5,  volume: 0.25,  time: 1700000000.5,
+                             side: Instruction::SELL,  market: true,
+                             trade_id: Some (42)  },
+                    Trade {  price: 30001.0,  volume: 1.0,  time: 1700000001.0,
+                             side: Instruction::BUY,  market: false,
+                             trade_id: None  }];
+        assert_eq! (csv_text (&T),
+                    "time,price,volume,side,market,trade_id\n\
+                     1700000000.5,30000.5,0.25,sell,true,42\n\
+                     1700000001,30001,1,buy,false,\n");
+
+        assert_eq! (csv_text::<Spread> (&[]),  "time,bid,ask\n");
+    }
+
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn  record_batch  ()  ->  Result<(), String>
+    {
+        use  crate::{Instruction, Trade};
+        use  arrow_array::{Array, Int64Array};
+
+        let  T  =  [Trade {  price: 30000.5,  volume: 0.25,  time: 1700000000.5,
+                             side: Instruction::SELL,  market: true,
+                             trade_id: Some (42)  },
+                    Trade {  price: 30001.0,  volume: 1.0,  time: 1700000001.0,
+                             side: Instruction::BUY,  market: false,
+                             trade_id: None  }];
+        let  B  =  super::record_batch (&T) ?;
+
+        assert_eq! ((B.num_rows (), B.num_columns ()),  (2, 6));
+        assert_eq! (B.schema ().field (3).name (),  "side");
+        let  I  =  B.column (5).as_any ()
+                               .downcast_ref::<Int64Array> ().unwrap ();
+        assert_eq! ((I.value (0), I.is_null (1)),  (42, true));
+        Ok (())
+    }
+}
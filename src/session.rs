@@ -0,0 +1,252 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{Book_Level, Depth, Kraken_API, Pair_Info, Spread, Subscriptions,
+             Ticker, Ws_Message};
+use  crate::reply;
+use  serde_json  as  JSN;
+use  std::collections::HashMap;
+use  std::time::{Duration, Instant};
+
+
+
+/** One handle on the exchange which takes market data from a WebSockets
+    connection when it has it, and goes to the REST end-points when it does
+    not.
+
+    The session owns the [Kraken_API] handle and the [Subscriptions]
+    registry.  Since the crate has no WebSocket transport of its own, the
+    application reads frames from its connection and hands each one to
+    [Kraken_Session::deliver]; the session keeps the latest ticker, spread and
+    order book of each pair it hears about, and hands the typed message back
+    for any further processing.  Queries such as [Kraken_Session::ticker] are
+    then answered from that pushed data while it is fresh, and from a REST
+    call otherwise, so application code need not care which is in use.
+
+    Pairs are named in WebSockets form, e.g. "XBT/USD", throughout.  */
+
+pub  struct  Kraken_Session  {  api:            Kraken_API,
+                                subscriptions:  Subscriptions,
+                                max_age:        Duration,
+                                pairs:          HashMap<String, Pair_Info>,
+                                tickers:        HashMap<String,
+                                                        (Instant, Ticker)>,
+                                spreads:        HashMap<String,
+                                                        (Instant, Spread)>,
+                                books:          HashMap<String,
+                                                        (Instant, Depth, usize)>
+                             }
+
+
+
+impl  Kraken_Session
+{
+    /** A session around the given REST handle, with no subscriptions.  Pushed
+        data is trusted for five seconds after it arrives. */
+    pub  fn  new  (api:  Kraken_API)  ->  Self
+          {   Kraken_Session {  api,  subscriptions: Subscriptions::new (),
+                                max_age: Duration::from_secs (5),
+                                pairs: HashMap::new (),
+                                tickers: HashMap::new (),
+                                spreads: HashMap::new (),
+                                books: HashMap::new ()  }   }
+
+    /** Trust pushed data for `max_age` after it arrives; after that, the REST
+        end-points are asked instead. */
+    pub  fn  set_max_age  (&mut self,  max_age:  Duration)  ->  &mut Self
+          {   self.max_age  =  max_age;   self   }
+
+    /** The REST handle, for everything the session does not cover itself. */
+    pub  fn  api  (&mut self)  ->  &mut Kraken_API   {   &mut self.api   }
+
+    /** The WebSockets subscriptions. */
+    pub  fn  subscriptions  (&mut self)  ->  &mut Subscriptions
+          {   &mut self.subscriptions   }
+
+    /** Drop all pushed data, as when the WebSockets connection has been lost;
+        queries go to the REST end-points until new data arrives.  The
+        subscriptions are marked to be sent again. */
+    pub  fn  disconnected  (&mut self)
+    {
+        self.tickers.clear ();
+        self.spreads.clear ();
+        self.books.clear ();
+        self.subscriptions.reset ();
+    }
+
+
+    /** Take in a text frame from the WebSockets connection, and return it in
+        typed form. */
+    pub  fn  deliver  (&mut self,  frame:  &str)  ->  Result<Ws_Message, String>
+    {
+        let  V  =  JSN::from_str::<JSN::Value> (frame)
+                       .map_err (|E| E.to_string ()) ?;
+        self.subscriptions.observe (&V);
+
+        let  M    =  Ws_Message::from_json (&V) ?;
+        let  now  =  Instant::now ();
+
+        match  &M
+           {   Ws_Message::TICKER (pair, T)
+                   =>  {  self.tickers.insert (pair.clone (),
+                                               (now, T.clone ()));  }
+               Ws_Message::SPREAD (pair, S)
+                   =>  {  self.spreads.insert (pair.clone (), (now, *S));  }
+               Ws_Message::BOOK {  pair,  snapshot,  bids,  asks,  ..  }
+                   =>  self.update_book (pair, *snapshot, bids, asks, now),
+               _  =>  ()   }
+
+        Ok (M)
+    }
+
+    /*  Fold a book message into our copy of the book.  An update to a book
+     *  we have no snapshot of is ignored. */
+    fn  update_book  (&mut self,  pair:  &str,  snapshot:  bool,
+                      bids:  &[Book_Level],  asks:  &[Book_Level],
+                      now:  Instant)
+    {
+        if  snapshot
+        {
+            let  depth  =  bids.len ().max (asks.len ());
+            let  D  =  Depth {  pair: pair.to_string (),
+                                bids: bids.to_vec (),  asks: asks.to_vec ()  };
+            self.books.insert (pair.to_string (), (now, D, depth));
+            return;
+        }
+
+        let  Some ((time, D, depth))  =  self.books.get_mut (pair)
+                 else  {  return;  };
+
+        for  L  in  bids   {   apply_level (&mut D.bids, L, true);   }
+        for  L  in  asks   {   apply_level (&mut D.asks, L, false);   }
+        D.bids.truncate (*depth);
+        D.asks.truncate (*depth);
+        *time  =  now;
+    }
+
+
+    /*  Find the REST name of a pair given in WebSockets form. */
+    fn  rest_name  (&mut self,  pair:  &str)  ->  Result<String, String>
+    {
+        if  ! self.pairs.contains_key (pair)
+            {   let  info  =  self.api.pair_info (pair) ?;
+                self.pairs.insert (pair.to_string (), info);   }
+        Ok (self.pairs [pair].name.clone ())
+    }
+
+
+    /** The ticker of `pair`: the last one pushed, if fresh, else one got from
+        the [Kraken_API::ticker_info] end-point. */
+    pub  fn  ticker  (&mut self,  pair:  &str)  ->  Result<Ticker, String>
+    {
+        if let Some ((t, T)) = self.tickers.get (pair)
+            {   if  t.elapsed () <= self.max_age
+                    {   return  Ok (T.clone ());   }   }
+
+        let  name  =  self.rest_name (pair) ?;
+        let  R  =  reply::result_of (&self.api.ticker_info (name.clone ()) ?) ?;
+        Ticker::from_json (R.get (&name)
+                            .ok_or (format! ("no ticker for '{}'", pair)) ?)
+    }
+
+
+    /** The best bid and ask of `pair`: from the last pushed spread or book,
+        if fresh, else from the ticker. */
+    pub  fn  spread  (&mut self,  pair:  &str)  ->  Result<(f64, f64), String>
+    {
+        let  fresh  =  |t: &Instant|  t.elapsed () <= self.max_age;
+
+        if let Some ((t, S)) = self.spreads.get (pair)
+            {   if  fresh (t)   {   return  Ok ((S.bid, S.ask));   }   }
+
+        if let Some ((t, D, _)) = self.books.get (pair)
+            {   if let (true, Some (B)) = (fresh (t), D.best ())
+                    {   return  Ok (B);   }   }
+
+        let  T  =  self.ticker (pair) ?;
+        Ok ((T.bid, T.ask))
+    }
+
+
+    /** The order book of `pair` to `count` levels: our copy of the pushed
+        book, if fresh and deep enough, else one got from the
+        [Kraken_API::order_book] end-point. */
+    pub  fn  depth  (&mut self,  pair:  &str,  count:  usize)
+               ->  Result<Depth, String>
+    {
+        if let Some ((t, D, depth)) = self.books.get (pair)
+        {   if  t.elapsed () <= self.max_age  &&  *depth >= count
+            {   let  mut  D  =  D.clone ();
+                D.bids.truncate (count);
+                D.asks.truncate (count);
+                return  Ok (D);   }   }
+
+        let  name  =  self.rest_name (pair) ?;
+        self.api.depth (&name, count)
+    }
+}
+
+
+
+/*  Put a level from a book update into one side of a book, which is kept
+ *  ordered best first; a volume of zero takes the level out. */
+
+fn  apply_level  (side:  &mut Vec<Book_Level>,  L:  &Book_Level,  bids:  bool)
+{
+    let  at  =  side.iter ().position (|S|  if  bids  { S.price <= L.price }
+                                             else   { S.price >= L.price });
+
+    match  at
+       {   Some (i)  if  side [i].price == L.price
+               =>  if  L.volume == 0.0   {   side.remove (i);   }
+                   else                  {   side [i]  =  L.clone ();   },
+           _  if  L.volume == 0.0  =>  (),
+           Some (i)  =>  side.insert (i, L.clone ()),
+           None      =>  side.push (L.clone ())   }
+}
+
+
+
+#[cfg(test)]
+mod  test
+  {  #[test]  fn  book_updates ()  ->  Result<(), String>
+     {
+         let  K  =  crate::connect (String::new (), String::new ());
+         let  mut  S  =  super::Kraken_Session::new (K);
+
+         S.deliver (r#"[1, {"as": [["101.0", "1.0", "1"],
+                                   ["102.0", "1.0", "1"]],
+                            "bs": [["100.0", "1.0", "1"],
+                                   ["99.0", "1.0", "1"]]},
+                        "book-2", "XBT/USD"]"#) ?;
+         S.deliver (r#"[1, {"b": [["100.5", "2.0", "2"]]},
+                           {"a": [["101.0", "0.0", "2"]], "c": "0"},
+                        "book-2", "XBT/USD"]"#) ?;
+
+         let  D  =  S.depth ("XBT/USD", 2) ?;
+         assert_eq! (D.bids.iter ().map (|L| L.price).collect::<Vec<_>> (),
+                     vec! [100.5, 100.0]);
+         assert_eq! (D.asks.iter ().map (|L| L.price).collect::<Vec<_>> (),
+                     vec! [102.0]);
+         assert_eq! (S.spread ("XBT/USD") ?,  (100.5, 102.0));
+
+         Ok (())
+     }  }
@@ -0,0 +1,199 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::Kraken_API;
+use  std::sync::{Arc, Mutex};
+
+
+
+/*  What is still to be done at shutdown; this is taken out of the guard the
+ *  first time the work is done (or dismissed), so it is only ever done
+ *  once. */
+
+struct  Duties  {  K:      Kraken_API,
+                   hooks:  Vec<Box<dyn FnOnce () + Send>>  }
+
+type  Shared_Duties  =  Arc<Mutex<Option<Duties>>>;
+
+
+
+/** Puts the account into a safe state when a program stops: when the guard
+    is dropped, whether at the end of `main` or while a panic unwinds, it
+    cancels all open orders, disarms the exchange's dead man's switch (see
+    [Kraken_API::cancel_all_orders_after_x]) and then calls any functions
+    given to [Shutdown_Guard::on_shutdown], such as one which stops a thread
+    that keeps re-arming the switch, or one which flushes a log of what the
+    program has done.
+
+    With the `ctrlc` feature, `Shutdown_Guard::catch_ctrl_c` does the same
+    when the user interrupts the program, which otherwise would not drop
+    anything.  A program which means to leave its orders in place when it
+    stops must call [Shutdown_Guard::dismiss] first.
+
+    ```ignore
+    let  mut  G  =  KKN::Shutdown_Guard::new (K.share ());
+    G.on_shutdown (move ||  keeper_stop.cancel ());
+    G.catch_ctrl_c () ?;
+    run_bot (&mut K);
+    ```  */
+
+pub  struct  Shutdown_Guard  (Shared_Duties);
+
+
+
+impl  Shutdown_Guard
+{
+    /** A guard which will use the given handle (made with
+        [Kraken_API::share], typically) to talk to the exchange. */
+    pub  fn  new  (K:  Kraken_API)  ->  Self
+    {
+        let  duties  =  Duties {  K,  hooks: vec! []  };
+        Self (Arc::new (Mutex::new (Some (duties))))
+    }
+
+
+    /** Register a function to be called at shutdown, after the orders have
+        been cancelled; they are called in the order they were given. */
+    pub  fn  on_shutdown<F>  (&mut self,  f: F)  ->  &mut Self
+                 where  F:  FnOnce () + Send + 'static
+    {
+        if let Some (D) = self.0.lock ().unwrap ().as_mut ()
+            {   D.hooks.push (Box::new (f));   }
+        self
+    }
+
+
+    /** Do the shutdown work now, rather than waiting for the guard to be
+        dropped; it will not be done again.  All the steps are tried even if
+        one fails, and the failures are reported together. */
+    pub  fn  shutdown  (&self)  ->  Result<(), String>
+          {   perform (&self.0)   }
+
+
+    /** Give up the guard without doing anything, leaving the orders and the
+        dead man's switch as they are. */
+    pub  fn  dismiss  (&self)   {   self.0.lock ().unwrap ().take ();   }
+
+
+    /** Also do the shutdown work when the program is interrupted (ctrl-c, or
+        SIGINT, SIGTERM or SIGHUP on Unix), and then exit with status 130.
+        Only one handler can be installed in a program, so this fails if
+        another already has been. */
+    #[cfg(feature = "ctrlc")]
+    pub  fn  catch_ctrl_c  (&self)  ->  Result<(), String>
+    {
+        let  duties  =  self.0.clone ();
+        ctrlc::set_handler (move ||  {  let  _  =  perform (&duties);
+                                        std::process::exit (130);  })
+              .map_err (|E|  format! ("cannot catch ctrl-c: {}", E))
+    }
+}
+
+
+
+impl  Drop  for  Shutdown_Guard
+{
+    fn  drop  (&mut self)   {   let  _  =  perform (&self.0);   }
+}
+
+
+
+fn  perform  (duties:  &Shared_Duties)  ->  Result<(), String>
+{
+    /*  The lock is not held while the exchange is called, so that a ctrl-c
+     *  during a drop does not wait on it. */
+    let  Some (mut D)  =  duties.lock ().unwrap ().take ()
+                      else   {   return  Ok (());   };
+
+    let  failures
+           =  vec! [failure ("cancelling orders", D.K.cancel_all_orders ()),
+                    failure ("disarming dead man's switch",
+                             D.K.cancel_all_orders_after_x (0))]
+                  .into_iter ().flatten ().collect::<Vec<_>> ();
+    for  hook  in  D.hooks   {   hook ();   }
+
+    if  failures.is_empty ()   {   Ok (())   }
+    else   {   Err (failures.join ("; "))   }
+}
+
+
+
+/*  What went wrong with a step, if anything: the request may not have got
+ *  through, or the exchange may have refused it (a bad key, say), which
+ *  leaves the orders just as live. */
+
+fn  failure  (step:  &str,  reply:  Result<String, String>)  ->  Option<String>
+{
+    match  reply
+    {
+        Err (E)  =>  Some (format! ("{}: {}", step, E)),
+        Ok (R)  if  ! R.replace (' ', "").contains ("\"error\":[]")
+                 =>  Some (format! ("{}: {}", step, R.trim ())),
+        Ok (_)  =>  None
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  dismiss  ()
+    {
+        use  super::Shutdown_Guard;
+        use  std::sync::Arc;
+        use  std::sync::atomic::{AtomicBool, Ordering};
+
+        let  called  =  Arc::new (AtomicBool::new (false));
+        let  C  =  called.clone ();
+
+        let  mut  G  =  Shutdown_Guard::new (Default::default ());
+        G.on_shutdown (move ||  C.store (true, Ordering::SeqCst));
+        G.dismiss ();
+        assert_eq! (G.shutdown (),  Ok (()));
+        drop (G);
+
+        assert! (! called.load (Ordering::SeqCst));
+    }
+
+
+    #[test]
+    fn  failures  ()
+    {
+        use  super::failure;
+
+        assert_eq! (failure ("cancelling orders",
+                             Ok (r#"{"error":[],"result":{"count":2}}"#
+                                     .into ())),
+                    None);
+        assert_eq! (failure ("cancelling orders",
+                             Ok (r#"{"error":["EAPI:Invalid key"]}"#.into ())),
+                    Some (r#"cancelling orders: {"error":["EAPI:Invalid key"]}"#
+                              .into ()));
+        assert! (failure ("disarming",
+                          Ok (r#"{"error": ["EGeneral:Permission denied"]}"#
+                                  .into ()))
+                     .is_some ());
+        assert_eq! (failure ("disarming", Err ("timed out".into ())),
+                    Some ("disarming: timed out".into ()));
+    }
+}
@@ -0,0 +1,386 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  Requests to the private end-points, signed but not yet sent.  Building one
+ *  does no I/O at all, so that the signing can be done on one machine and the
+ *  sending on another. */
+
+
+
+use  crate::{Cancel_Token, Kraken_API, Request_Signer, kraken_argument,
+             perform, retry, sign, url_host};
+use  crate::warnings;
+
+
+
+/** A request to a private end-point, signed and ready to be sent.
+
+    These are made by [Kraken_API::sign_offline] on a machine which holds the
+    API secret, perhaps one never connected to the network, and sent with
+    [Kraken_API::submit] from a machine which need not hold the secret at all.
+    To get from one to the other, the request is written out as text with
+    [ToString::to_string] (it looks much like the HTTP request itself) and
+    read back with [str::parse].
+
+    The exchange rejects a nonce which is not larger than the last one it
+    saw on the API key, unless the key has been given a nonce window on the
+    Kraken web site; a signed request must therefore be submitted promptly,
+    and before any later nonce is used with the key.
+
+    ```ignore
+    //  On the offline machine.
+    let  mut  K  =  KKN::connect (key, secret);
+    K.set_opt (KKN::API_Option::ASSET, "XBT");
+    K.set_opt (KKN::API_Option::KEY, "cold wallet");
+    K.set_opt (KKN::API_Option::AMOUNT, "0.5");
+    std::fs::write ("withdraw.txt",
+                    K.sign_offline ("Withdraw", nonce) ?.to_string ());
+
+    //  On the online machine.
+    let  R  :  KKN::Signed_Request  =  std::fs::read_to_string ("withdraw.txt")
+                                            .map_err (|e| e.to_string ()) ?
+                                            .parse () ?;
+    println! ("{}", KKN::Kraken_API::default ().submit (&R) ?);
+    ```  */
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub  struct  Signed_Request
+{
+    /** The end-point the request is for, e.g. "AddOrder". */
+    pub  end_point:  String,
+
+    /** The version of the API the request is for, e.g. "0"; see
+        [Kraken_API::set_api_version]. */
+    pub  version:    String,
+
+    /** The nonce the request was signed with. */
+    pub  nonce:      u64,
+
+    /** The URL-encoded body of the request, starting with the nonce. */
+    pub  body:       String,
+
+    /** The value of the API-Key header. */
+    pub  api_key:    String,
+
+    /** The value of the API-Sign header. */
+    pub  api_sign:   String
+}
+
+
+
+impl  Signed_Request
+{
+    /** The URL to which the request is posted. */
+    pub  fn  url  (&self)  ->  String
+          {   url_host.to_string () + &path (&self.version, &self.end_point)   }
+
+
+    /** The request as a `curl` command which can be pasted into a shell to
+        send it again by hand, for reproducing a problem or reporting one to
+        the exchange.  The signature is included (the secret never is), but
+        the API key is left for the shell to fill in from the
+        `KRAKEN_API_KEY` environment variable, so that the command can be
+        shown to others.
+
+        The exchange will only take the request once, and then only if no
+        later nonce has been used with the key in the meantime. */
+    pub  fn  to_curl  (&self)  ->  String
+    {
+        let  quote  =  |S: &str|  format! ("'{}'", S.replace ('\'', "'\\''"));
+
+        [format! ("curl -X POST {}", quote (&self.url ())),
+         "-H \"API-Key: $KRAKEN_API_KEY\"".to_string (),
+         format! ("-H {}", quote (&format! ("API-Sign: {}", self.api_sign))),
+         format! ("--data-raw {}", quote (&self.body))]
+            .join (" \\\n     ")
+    }
+
+
+    /*  A curl handle ready to send the request. */
+    pub(crate)  fn  easy  (&self)  ->  Result<curl::easy::Easy, String>
+    {
+        let  fail  =  |E: curl::Error|  E.to_string ();
+        let  mut  C  =  curl::easy::Easy::new ();
+        C.url (&self.url ()).map_err (fail) ?;
+        C.post (true).map_err (fail) ?;
+        C.post_fields_copy (self.body.as_bytes ()).map_err (fail) ?;
+
+        let  mut  L  =  curl::easy::List::new ();
+        L.append (&format! ("API-Key: {}", self.api_key)).map_err (fail) ?;
+        L.append (&format! ("API-Sign: {}", self.api_sign)).map_err (fail) ?;
+        C.http_headers (L).map_err (fail) ?;
+
+        Ok (C)
+    }
+}
+
+
+
+/*  The path of the private end-point in the given version of the API. */
+
+fn  path  (version:  &str,  end_point:  &str)  ->  String
+      {   format! ("/{}/private/{}", version, end_point)   }
+
+
+
+/*  Sign the request in `query_url`, which is an end-point name optionally
+ *  followed by '?' and URL-encoded arguments, with the given nonce. */
+
+pub(crate)  fn  prepare  (key:  &str,  signer:  &dyn Request_Signer,
+                          version:  &str,  query_url:  &str,  nonce:  u64)
+               ->  Result<Signed_Request, String>
+{
+    let  (end_point, arguments)  =  match  query_url.split_once ('?')
+                                       {   Some (S)  =>  S,
+                                           None  =>  (query_url, "")   };
+
+    let  body  =  if  arguments.is_empty ()  {  format! ("nonce={}", nonce)  }
+                  else  {  format! ("nonce={}&{}", nonce, arguments)  };
+
+    Ok (Signed_Request {
+            end_point:  end_point.to_string (),
+            version:    version.to_string (),
+            nonce,
+            api_key:    key.to_string (),
+            api_sign:   sign (signer, &path (version, end_point),
+                              &nonce.to_string (), &body) ?,
+            body  })
+}
+
+
+
+impl  std::fmt::Display  for  Signed_Request
+{
+    fn  fmt  (&self,  f:  &mut std::fmt::Formatter)  ->  std::fmt::Result
+    {
+        write! (f, "POST {}\nAPI-Key: {}\nAPI-Sign: {}\n\n{}\n",
+                self.url (), self.api_key, self.api_sign, self.body)
+    }
+}
+
+
+
+impl  std::str::FromStr  for  Signed_Request
+{
+    type  Err  =  String;
+
+    fn  from_str  (S:  &str)  ->  Result<Self, String>
+    {
+        let  mut  L  =  S.lines ();
+        let  mut  line  =  |prefix: &str|
+                             L.next ()
+                              .and_then (|l| l.strip_prefix (prefix))
+                              .map (|l| l.trim ().to_string ())
+                              .ok_or (format! ("signed request has no '{}' \
+                                                line", prefix.trim ()));
+
+        let  url        =  line (&format! ("POST {}/", url_host)) ?;
+        let  (version, end_point)
+             =  url.split_once ("/private/")
+                   .map (|(V, E)|  (V.to_string (), E.to_string ()))
+                   .ok_or ("signed request is not for a private end-point") ?;
+        let  api_key    =  line ("API-Key:") ?;
+        let  api_sign   =  line ("API-Sign:") ?;
+        line ("") ?;
+        let  body       =  line ("") ?;
+
+        let  nonce  =  body.split ('&')
+                           .find_map (|a| a.strip_prefix ("nonce="))
+                           .and_then (|n| n.parse ().ok ())
+                           .ok_or ("signed request has no nonce") ?;
+
+        Ok (Signed_Request {  end_point,  version,  nonce,  body,  api_key,
+                              api_sign  })
+    }
+}
+
+
+
+impl  Kraken_API
+{
+/** Sign a request to the private `end_point` (named as in the Kraken
+    documentation, e.g. "AddOrder") with the given `nonce`, without sending
+    it; every option which has been set on this object goes into it.  This
+    does no I/O, and so may be done on a machine with no network connection;
+    the request is sent later with [Kraken_API::submit], from anywhere.
+
+    The nonce must be larger than any used before with the API key; the
+    number of microseconds since the UNIX epoch at the time the request will
+    be submitted is the usual choice, and is what the crate itself uses.  */
+
+  pub  fn  sign_offline  (&self,  end_point:  &str,  nonce:  u64)
+               ->  Result<Signed_Request, String>
+    {
+      let  mut  arguments  =  self.all_options ()
+                                  .map (|(O, V)|  format! ("{}={}",
+                                                           kraken_argument (O),
+                                                           V))
+                                  .collect::<Vec<_>> ();
+      arguments.sort ();
+      warnings::check_deprecation (self.warning.as_ref (), end_point);
+
+      let  query_url  =  if  arguments.is_empty ()  {  end_point.to_string ()  }
+                         else  {  end_point.to_string () + "?"
+                                      + &arguments.join ("&")  };
+
+      prepare (&self.key, &*self.request_signer () ?, self.version (),
+               &query_url, nonce)
+    }
+
+
+
+/** Send a request which was signed with [Kraken_API::sign_offline], and give
+    back the exchange's reply.  The key and secret of this object are not
+    used, but its cancellation token, time limit and rate limiting are.
+
+    The request is sent once only, as the exchange would refuse its nonce a
+    second time; if the transport fails after the request has left, the error
+    starts with [UNKNOWN_OUTCOME](crate::UNKNOWN_OUTCOME).
+
+    A withdrawal is held to this object's
+    [withdrawal limits](Kraken_API::set_withdrawal_limits) just as one made
+    with [Kraken_API::withdraw] is.  */
+
+  pub  fn  submit  (&mut self,  request:  &Signed_Request)
+               ->  Result<String, String>
+    {
+      let  serial  =  if  request.end_point == "Withdraw"
+                      {
+                          let  argument  =  |name: &str|
+                               request.body.split ('&')
+                                      .find_map (|A| A.strip_prefix (name)
+                                                      ?.strip_prefix ('='))
+                                      .unwrap_or ("");
+                          self.withdrawal_guard.check (argument ("asset"),
+                                                       argument ("key"),
+                                                       argument ("amount")) ?
+                      }
+                      else  {  None  };
+
+      let  reply  =  self.send_signed (request);
+      self.withdrawal_guard.settle (serial, &reply);
+      reply
+    }
+
+
+    /*  Send the request, once, under the rate limiter. */
+    fn  send_signed  (&mut self,  request:  &Signed_Request)
+               ->  Result<String, String>
+    {
+      self.query_url  =  request.end_point.clone ();
+      self.rate_limit.acquire (&self.query_url, self.cancel_token.as_ref ()) ?;
+      if  self.cancel_token.as_ref ().is_some_and (Cancel_Token::is_cancelled)
+          {   Err ("request cancelled".to_string ()) ?   }
+
+      let  reply  =  perform (self, request.easy () ?)
+                        .map_err (|E| if  retry::idempotent (&self.query_url)
+                                          {  E  }
+                                      else  {  retry::unknown_outcome
+                                                      (&self.query_url, &E)  })
+                        ?;
+      self.rate_limit.observe (&reply);
+      Ok (reply)
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  round_trip  ()  ->  Result<(), String>
+    {
+        use  super::Signed_Request;
+        use  crate::API_Option;
+
+        let  mut  K  =  crate::connect
+                           ("my key".into (),
+                            "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18\
+                             fWxnyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg==".into ());
+        K.set_opt (API_Option::ORDER_TYPE, "limit");
+        K.set_opt (API_Option::PAIR, "XBTUSD");
+        K.set_opt (API_Option::PRICE, "37500");
+        K.set_opt (API_Option::TYPE, "buy");
+        K.set_opt (API_Option::VOLUME, "1.25");
+
+        //  The example in Kraken's documentation.
+        let  R  =  K.sign_offline ("AddOrder", 1616492376594) ?;
+        assert_eq! (R.body, "nonce=1616492376594&ordertype=limit&pair=XBTUSD\
+                             &price=37500&type=buy&volume=1.25");
+        assert_eq! (R.api_sign, "4/dpxb3iT4tp/ZCVEwSnEsLxx0bqyhLpdfOpc6fn7OR8+\
+                                 UClSV5n9E6aSS8MPtnRfp32bAb0nmbRn6H8ndwLUQ==");
+
+        assert_eq! (R.to_string ().parse::<Signed_Request> () ?,  R);
+        assert_eq! (R.url (),  "https://api.kraken.com/0/private/AddOrder");
+
+        K.set_api_version (Some ("/beta/1/"));
+        let  B  =  K.sign_offline ("AddOrder", 1616492376594) ?;
+        assert_eq! (B.url (),
+                    "https://api.kraken.com/beta/1/private/AddOrder");
+        assert_ne! (B.api_sign,  R.api_sign);
+        assert_eq! (B.to_string ().parse::<Signed_Request> () ?,  B);
+        assert_eq! (R.to_curl ().lines ().last (),
+                    Some ("     --data-raw 'nonce=1616492376594&ordertype=limit\
+                           &pair=XBTUSD&price=37500&type=buy&volume=1.25'"));
+        assert! ("POST https://example.com/\n".parse::<Signed_Request> ()
+                       .is_err ());
+        Ok (())
+    }
+
+
+    #[test]
+    fn  withdrawal_limits  ()  ->  Result<(), String>
+    {
+        use  crate::{API_Option, Cancel_Token, Withdrawal_Limits};
+
+        let  mut  K  =  crate::connect
+                           ("my key".into (),
+                            "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18\
+                             fWxnyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg==".into ());
+        K.set_withdrawal_limits
+              (Some (Withdrawal_Limits {
+                         per_call:  [("XBT".into (), 0.5)].into (),
+                         keys:      Some (["cold".into ()].into ()),
+                         ..Default::default ()  }));
+        K.set_opt (API_Option::ASSET, "XBT");
+        K.set_opt (API_Option::KEY, "cold");
+        K.set_opt (API_Option::AMOUNT, "1.0");
+        let  R  =  K.sign_offline ("Withdraw", 1616492376594) ?;
+        assert! (K.submit (&R).unwrap_err ().contains ("per call"));
+
+        K.set_opt (API_Option::KEY, "hot");
+        K.set_opt (API_Option::AMOUNT, "0.1");
+        let  R  =  K.sign_offline ("Withdraw", 1616492376595) ?;
+        assert! (K.submit (&R).unwrap_err ().contains ("'hot' may not"));
+
+        /*  One within the limits gets as far as the (cancelled) transport,
+         *  and is not counted as the exchange never saw it. */
+        K.set_opt (API_Option::KEY, "cold");
+        let  R  =  K.sign_offline ("Withdraw", 1616492376596) ?;
+        let  stop  =  Cancel_Token::new ();
+        K.set_cancel_token (stop.clone ());
+        stop.cancel ();
+        assert_eq! (K.submit (&R),  Err ("request cancelled".to_string ()));
+        assert_eq! (K.withdrawn_today ("XBT"),  0.0);
+        Ok (())
+    }
+}
@@ -0,0 +1,274 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  The hashing behind the API-Sign header.  The crate builds the message to
+ *  be signed, but the hashing itself is done by a Request_Signer, which the
+ *  user may supply so that the secret need never be in the process. */
+
+
+
+use  openssl  as  SSL;
+use  std::sync::Arc;
+
+
+
+/** The keyed hash with which requests to the private end-points are
+    signed.  The crate puts together the message which Kraken asks to be
+    signed, and asks the signer only for the hashes; so a signer can be
+    backed by a hardware security module, a FIPS-validated library or a
+    remote signing service, and the API secret need never be seen by the
+    process at all.  Install one with [Kraken_API::set_signer]; without one
+    the crate signs with OpenSSL, using the secret given to [connect].
+
+    [Kraken_API::set_signer]: crate::Kraken_API::set_signer
+    [connect]: crate::connect
+
+    ```ignore
+    struct  HSM_Signer  {  slot:  hsm::Key  }
+
+    impl  KKN::Request_Signer  for  HSM_Signer
+    {
+        fn  hmac_sha512  (&self,  message:  &[u8])  ->  Result<Vec<u8>, String>
+              {   self.slot.hmac (message).map_err (|E| E.to_string ())   }
+    }
+
+    let  mut  K  =  KKN::connect (key, String::new ());
+    K.set_signer (Some (Arc::new (HSM_Signer {  slot  })));
+    ```  */
+
+pub  trait  Request_Signer  :  Send + Sync
+{
+    /** The HMAC-SHA512 of `message`, keyed with the API secret (decoded
+        from base 64), as raw bytes. */
+    fn  hmac_sha512  (&self,  message:  &[u8])  ->  Result<Vec<u8>, String>;
+
+    /** The SHA-256 hash of `data`, as raw bytes.  No secret goes into this,
+        and by default OpenSSL's is used. */
+    fn  sha256  (&self,  data:  &[u8])  ->  Result<Vec<u8>, String>
+    {
+        SSL::hash::hash (SSL::hash::MessageDigest::sha256 (), data)
+            .map (|D| D.to_vec ())
+            .map_err (|E| format! ("signing: {}", E))
+    }
+}
+
+
+
+pub(crate)  type  Shared_Signer  =  Arc<dyn Request_Signer>;
+
+
+
+/*  The signer used when the handle holds the secret itself. */
+
+pub(crate)  struct  Secret_Signer  (Vec<u8>);
+
+impl  Secret_Signer
+{
+    pub(crate)  fn  new  (secret:  &str)  ->  Result<Self, String>
+    {
+        if  secret.len () != 88
+            {   Err ("private key must be 88 characters long") ?   }
+
+        SSL::base64::decode_block (secret)
+            .map (Secret_Signer)
+            .map_err (|E| format! ("signing: {}", E))
+    }
+}
+
+impl  Request_Signer  for  Secret_Signer
+{
+    fn  hmac_sha512  (&self,  message:  &[u8])  ->  Result<Vec<u8>, String>
+    {
+        let  fail  =  |E: SSL::error::ErrorStack|  format! ("signing: {}", E);
+
+        let  key  =  SSL::pkey::PKey::hmac (&self.0).map_err (fail) ?;
+        let  mut  signer  =  SSL::sign::Signer::new
+                                       (SSL::hash::MessageDigest::sha512 (),
+                                        &key)
+                               .map_err (fail) ?;
+        signer.update (message).map_err (fail) ?;
+        signer.sign_to_vec ().map_err (fail)
+    }
+}
+
+
+
+/** A [Request_Signer] which hands the signing to something outside the
+    crate: a hardware security module, a signing service on another host,
+    or a program which talks to one.  It is given the message to be signed,
+    which is the end-point's path followed by the SHA-256 digest of the nonce
+    and request body, and gives back the HMAC-SHA512 of it under the API
+    secret; so the secret itself need never be on the trading host.
+
+    ```ignore
+    //  A program which reads the message, in base 64, on its standard input
+    //  and writes the signature, in base 64, on its standard output.
+    K.set_signer (Some (Arc::new (KKN::External_Signer::command
+                                        ("/usr/local/bin/kraken-sign",
+                                         &["--slot", "3"]))));
+
+    //  Any other means of getting the signature.
+    K.set_signer (Some (Arc::new (KKN::External_Signer::new
+                                        (move |M|  vault.hmac (M)))));
+    ```  */
+
+pub  struct  External_Signer  (Sign_Function);
+
+type  Sign_Function
+          =  Box<dyn Fn (&[u8]) -> Result<Vec<u8>, String> + Send + Sync>;
+
+impl  External_Signer
+{
+    /** A signer which calls `sign` with the message, and takes the raw
+        bytes of the HMAC-SHA512 it gives back. */
+    pub  fn  new<F>  (sign:  F)  ->  Self
+                 where  F:  Fn (&[u8]) -> Result<Vec<u8>, String>
+                                                   + Send + Sync + 'static
+          {   External_Signer (Box::new (sign))   }
+
+
+    /** A signer which runs `program` with the given `arguments` for each
+        request, writes the message in base 64 (and a newline) to its
+        standard input, and reads the signature in base 64 from its standard
+        output.  The program failing, or giving anything but base 64, makes
+        the request fail; what it wrote to its standard error goes into the
+        error. */
+    pub  fn  command  (program:  &str,  arguments:  &[&str])  ->  Self
+    {
+        let  program    =  program.to_string ();
+        let  arguments  =  arguments.iter ().map (|A| A.to_string ())
+                                    .collect::<Vec<_>> ();
+        External_Signer::new (move |message|  run (&program, &arguments,
+                                                   message))
+    }
+}
+
+impl  Request_Signer  for  External_Signer
+{
+    fn  hmac_sha512  (&self,  message:  &[u8])  ->  Result<Vec<u8>, String>
+          {   (self.0) (message)   }
+}
+
+
+
+/*  Run the signing program, giving it the message and taking the signature
+ *  from it, both in base 64. */
+
+fn  run  (program:  &str,  arguments:  &[String],  message:  &[u8])
+       ->  Result<Vec<u8>, String>
+{
+    use  std::io::Write;
+    use  std::process::{Command, Stdio};
+
+    let  fail  =  |E: std::io::Error|  format! ("signing with {}: {}",
+                                                program, E);
+
+    let  mut  child  =  Command::new (program).args (arguments)
+                            .stdin (Stdio::piped ())
+                            .stdout (Stdio::piped ())
+                            .stderr (Stdio::piped ())
+                            .spawn ()
+                            .map_err (fail) ?;
+
+    /*  Dropping the pipe closes it, so the program sees the end of its
+     *  input. */
+    child.stdin.take ().unwrap ()
+         .write_all ((SSL::base64::encode_block (message) + "\n").as_bytes ())
+         .map_err (fail) ?;
+
+    let  out  =  child.wait_with_output ().map_err (fail) ?;
+    if  ! out.status.success ()
+        {   Err (format! ("signing with {}: {}: {}", program, out.status,
+                          String::from_utf8_lossy (&out.stderr).trim ())) ?   }
+
+    SSL::base64::decode_block (String::from_utf8_lossy (&out.stdout).trim ())
+        .map_err (|_| format! ("signing with {}: the signature is not \
+                                base 64", program))
+}
+
+
+
+/*  The API-Sign header for a call to the private end-point at `path` (e.g.
+ *  "/0/private/Balance") with the given `nonce` and `post_data` (which
+ *  includes the nonce): the HMAC-SHA512, keyed with the secret, of the URI
+ *  path followed by the SHA256 hash of the nonce and post data, in base
+ *  64. */
+
+pub(crate)  fn  sign  (signer:  &dyn Request_Signer,  path:  &str,
+                       nonce:  &str,  post_data:  &str)
+                ->  Result<String, String>
+{
+    let  mut  message  =  path.as_bytes ().to_vec ();
+    message.extend (signer.sha256 ((nonce.to_string () + post_data)
+                                       .as_bytes ()) ?);
+    Ok (SSL::base64::encode_block (&signer.hmac_sha512 (&message) ?))
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  own_signer  ()
+    {
+        use  super::{Request_Signer, Secret_Signer};
+        use  std::sync::Arc;
+
+        const  SECRET:  &str
+                  =  "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18fWx\
+                      nyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg==";
+
+        struct  Wrapped  (Secret_Signer);
+        impl  Request_Signer  for  Wrapped
+        {
+            fn  hmac_sha512  (&self,  message:  &[u8])
+                   ->  Result<Vec<u8>, String>
+                  {   self.0.hmac_sha512 (message)   }
+        }
+
+        let  by_secret  =  crate::connect ("key".into (), SECRET.into ())
+                               .sign_offline ("Balance", 1);
+
+        let  mut  K  =  crate::connect ("key".into (), String::new ());
+        assert! (K.sign_offline ("Balance", 1).is_err ());
+        K.set_signer (Some (Arc::new (Wrapped (Secret_Signer::new (SECRET)
+                                                   .unwrap ()))));
+        assert_eq! (K.sign_offline ("Balance", 1),  by_secret);
+    }
+
+
+    #[cfg(unix)]
+    #[test]
+    fn  command  ()
+    {
+        use  super::{External_Signer, Request_Signer};
+
+        /*  A "signature" which is the message itself, echoed back. */
+        let  S  =  External_Signer::command ("cat", &[]);
+        assert_eq! (S.hmac_sha512 (b"/0/private/Balance"),
+                    Ok (b"/0/private/Balance".to_vec ()));
+
+        let  S  =  External_Signer::command ("sh",
+                                             &["-c", "echo no >&2; false"]);
+        assert! (S.hmac_sha512 (b"x").unwrap_err ().ends_with (": no"));
+    }
+}
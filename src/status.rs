@@ -0,0 +1,374 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{Cancel_Token, Kraken_API, System_Status};
+use  crate::reply;
+use  std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+
+
+/** The result of a [Kraken_API::health_check]. */
+
+#[derive(Clone, Debug)]
+pub  struct  Health
+{
+    /** The operating mode of the exchange. */
+    pub  status:       System_Status,
+
+    /** The amount by which the exchange's clock is ahead of ours, in seconds
+        (negative if it is behind).  Kraken only reports whole seconds, so
+        this is only good to about half a second either way. */
+    pub  clock_drift:  f64,
+
+    /** The time taken for a round-trip to the exchange's time end-point. */
+    pub  round_trip:   Duration
+}
+
+impl  Health
+{
+    /** Whether it makes sense to place new orders of any kind. */
+    pub  fn  trading_enabled  (&self)  ->  bool
+          {   self.status == System_Status::ONLINE
+                  ||  self.status == System_Status::POST_ONLY   }
+}
+
+
+
+impl  Kraken_API
+{
+/** Check the status of the exchange, the drift between its clock and ours,
+    and the time it takes to get an answer out of it.
+
+    This makes two calls to the public end-points, [Kraken_API::system_status]
+    and that behind [Kraken_API::server_time], and is intended to be polled
+    by anything which needs to decide whether trading should go ahead.  The
+    time is always asked of the exchange itself, never taken from the cache
+    or another thread's request.  */
+
+  pub  fn  health_check  (&mut self)  ->  Result<Health, String>
+    {
+      let  S  =  reply::result_of (&self.system_status () ?) ?;
+      let  S  =  reply::string (&S, "status") ?;
+      let  status
+             =  System_Status::from_kraken_string (&S)
+                   .ok_or (format! ("unrecognised system status '{}'", S)) ?;
+
+      let  (round_trip, clock_drift)  =  self.time_sample () ?;
+      Ok (Health {  status,  clock_drift,  round_trip })
+    }
+
+
+
+/** Ask the exchange for the time `samples` times, one after the other, and
+    report how long the round-trips took and how far its clock seemed to be
+    from ours each time; the spread of these, rather than a single
+    [Kraken_API::health_check], is what to go by when choosing request
+    deadlines and nonce windows, or judging where to host a program.
+
+    Requests which fail are counted but otherwise left out; if every one
+    fails the last error is returned.  The handle's rate limit, if any, is
+    kept to, which is worth bearing in mind when asking for many samples.  */
+
+  pub  fn  measure_latency  (&mut self,  samples:  usize)
+               ->  Result<Latency_Report, String>
+    {
+      let  mut  report  =  Latency_Report {  samples:  vec! [],  failures: 0  };
+      let  mut  last_error  =  None;
+      for  _  in  0 .. samples
+      {
+          match  self.time_sample ()
+             {   Ok (S)  =>  report.samples.push (S),
+                 Err (E)  =>  {  report.failures += 1;
+                                 last_error  =  Some (E);  }   }
+      }
+
+      match  last_error
+         {   Some (E)  if  report.samples.is_empty ()  =>  Err (E),
+             _  =>  Ok (report)   }
+    }
+
+
+  /*  One round-trip to the time end-point, and the amount by which the
+   *  exchange's clock then seemed to be ahead of ours, taking it that the
+   *  exchange read its clock half way through.  The request goes straight
+   *  to the exchange, as a reply from the cache, another thread's flight or
+   *  a hedge would tell us nothing about this one. */
+  fn  time_sample  (&mut self)  ->  Result<(Duration, f64), String>
+    {
+      let  start  =  Instant::now ();
+      let  sent   =  SystemTime::now ();
+      let  T  =  reply::result_of (&crate::api_function
+                                        (self, "Time", &[],
+                                         query_public_uncached) ?) ?;
+      let  round_trip  =  start.elapsed ();
+
+      let  server_time  =  reply::number (&T ["unixtime"]) ?;
+      let  our_time  =  (sent + round_trip / 2).duration_since (UNIX_EPOCH)
+                                                .unwrap ()
+                                                .as_secs_f64 ();
+
+      Ok ((round_trip,  server_time - our_time))
+    }
+}
+
+
+
+/*  Send the public query in K.query_url once, straight to the exchange: not
+ *  from the cache, not shared with any other thread's request, not hedged
+ *  and not retried. */
+
+fn  query_public_uncached  (K:  &Kraken_API)  ->  Result<String, String>
+{
+    let  mut  C  =  curl::easy::Easy::new ();
+    C.url (&format! ("{}/{}/public/{}",
+                     crate::url_host, K.version (), K.query_url))
+     .map_err (|e| e.to_string ()) ?;
+    crate::perform (K, C)
+}
+
+
+
+/** The result of [Kraken_API::measure_latency]. */
+
+#[derive(Clone, Debug, Default)]
+pub  struct  Latency_Report
+{
+    /** The round-trip time and clock offset (as [Health::clock_drift]) of
+        each request which succeeded, in the order they were made. */
+    pub  samples:   Vec<(Duration, f64)>,
+
+    /** The number of requests which failed. */
+    pub  failures:  usize
+}
+
+impl  Latency_Report
+{
+    /** The round-trip time which `percent` percent of the samples took no
+        longer than, e.g. 50 for the median, 99 for the slowest but one in a
+        hundred; zero if there are no samples. */
+    pub  fn  percentile  (&self,  percent:  f64)  ->  Duration
+    {
+        let  mut  T  =  self.samples.iter ().map (|S| S.0).collect::<Vec<_>> ();
+        if  T.is_empty ()   {   return  Duration::ZERO;   }
+        T.sort ();
+        let  i  =  (T.len () - 1) as f64 * percent.clamp (0.0, 100.0) / 100.0;
+        T [i.round () as usize]
+    }
+
+    /** The mean round-trip time; zero if there are no samples. */
+    pub  fn  mean  (&self)  ->  Duration
+    {
+        if  self.samples.is_empty ()   {   return  Duration::ZERO;   }
+        self.samples.iter ().map (|S| S.0).sum::<Duration> ()
+            / self.samples.len () as u32
+    }
+
+    /** The mean of the clock offsets.  The exchange only gives its time to
+        the second, so this is a better guess than any single sample when
+        the samples span several seconds.  NaN if there are no samples. */
+    pub  fn  mean_offset  (&self)  ->  f64
+    {
+        self.samples.iter ().map (|S| S.1).sum::<f64> ()
+            / self.samples.len () as f64
+    }
+
+    /** The smallest and largest clock offsets seen. */
+    pub  fn  offset_range  (&self)  ->  (f64, f64)
+    {
+        self.samples.iter ()
+            .fold ((f64::INFINITY, f64::NEG_INFINITY),
+                   |(lo, hi), S|  (lo.min (S.1), hi.max (S.1)))
+    }
+
+    /** The clock offset measured over the quickest round-trip, which was
+        least disturbed by the time taken getting there and back. */
+    pub  fn  best_offset  (&self)  ->  Option<f64>
+          {   self.samples.iter ().min_by_key (|S| S.0).map (|S| S.1)   }
+}
+
+
+
+/** Keeps an eye on the exchange's [System_Status], and calls back into user
+    code whenever it changes.
+
+    The typical use is to pull all quotes from the book when the exchange drops
+    into cancel-only or maintenance mode, and to put them back when it comes
+    on-line again.  The watcher can be driven by hand, by calling
+    [Status_Watcher::poll] whenever convenient, or left to get on with it in
+    its own thread with [Status_Watcher::spawn].  */
+
+#[derive(Default)]
+pub  struct  Status_Watcher
+{
+    last:         Option<System_Status>,
+    on_change:    Vec<Change_Callback>,
+    on_error:     Vec<Error_Callback>
+}
+
+type  Change_Callback
+          =  Box<dyn FnMut (Option<System_Status>, System_Status) + Send>;
+
+type  Error_Callback  =  Box<dyn FnMut (&str) + Send>;
+
+impl  Status_Watcher
+{
+    /** A watcher with no callbacks, which has not yet seen the exchange's
+        status. */
+    pub  fn  new  ()  ->  Self   {   Self::default ()   }
+
+    /** Register a function to be called with the previous and new status
+        whenever the status changes.  The first status seen is also reported,
+        with the previous status being `None`. */
+    pub  fn  on_change<F>  (&mut self,  f: F)  ->  &mut Self
+                 where  F:  FnMut (Option<System_Status>, System_Status)
+                                                            + Send + 'static
+          {   self.on_change.push (Box::new (f));   self   }
+
+    /** Register a function to be called with the error message when an
+        attempt to get the status from the exchange fails.  */
+    pub  fn  on_error<F>  (&mut self,  f: F)  ->  &mut Self
+                 where  F:  FnMut (&str) + Send + 'static
+          {   self.on_error.push (Box::new (f));   self   }
+
+    /** The status seen at the last poll, if any. */
+    pub  fn  last_status  (&self)  ->  Option<System_Status>   {   self.last   }
+
+    /** Take note of the given status, calling back if it is a change. */
+    pub  fn  observe  (&mut self,  status:  System_Status)
+    {
+        if  self.last == Some (status)   {   return;   }
+
+        let  previous  =  self.last.replace (status);
+        for  f  in  &mut self.on_change   {   f (previous, status);   }
+    }
+
+    /** Ask the exchange for its current status, and deal with it as
+        [Status_Watcher::observe] does.  Errors are passed to any `on_error`
+        callbacks as well as being returned. */
+    pub  fn  poll  (&mut self,  K: &mut Kraken_API)
+               ->  Result<System_Status, String>
+    {
+        let  status  =  K.system_status ()
+                         .and_then (|R| reply::result_of (&R))
+                         .and_then (|R| reply::string (&R, "status"))
+                         .and_then (|S| System_Status::from_kraken_string (&S)
+                                          .ok_or (format! ("unrecognised \
+                                                            system status '{}'",
+                                                           S)));
+        match  status
+           {   Ok (S)   =>  self.observe (S),
+               Err (ref E)  =>  for  f  in  &mut self.on_error  {  f (E);  }  }
+
+        status
+    }
+
+    /** Poll the exchange every `interval` until `stop` is cancelled.  Errors
+        do not stop the loop; they are reported through the `on_error`
+        callbacks. */
+    pub  fn  run  (&mut self,
+                   K: &mut Kraken_API,
+                   interval: Duration,
+                   stop: &Cancel_Token)
+    {
+        while  ! stop.is_cancelled ()
+        {
+            let  _  =  self.poll (K);
+
+            stop.pause (interval);
+        }
+    }
+
+    /** Move the watcher and a [Kraken_API] handle into a thread of their own,
+        which polls every `interval`.  Cancel the returned token to bring the
+        thread to an end. */
+    pub  fn  spawn  (mut self,  mut K: Kraken_API,  interval: Duration)
+               ->  (std::thread::JoinHandle<()>, Cancel_Token)
+    {
+        let  stop  =  Cancel_Token::new ();
+        let  S     =  stop.clone ();
+        (std::thread::spawn (move || self.run (&mut K, interval, &S)),  stop)
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+  {  #[test]  fn  watcher_transitions ()
+     {
+         use  super::{Status_Watcher, System_Status as SS};
+         use  std::sync::{Arc, Mutex};
+
+         let  seen  =  Arc::new (Mutex::new (Vec::new ()));
+         let  s  =  seen.clone ();
+
+         let  mut  W  =  Status_Watcher::new ();
+         W.on_change (move |a, b|  s.lock ().unwrap ().push ((a, b)));
+
+         W.observe (SS::ONLINE);
+         W.observe (SS::ONLINE);
+         W.observe (SS::CANCEL_ONLY);
+
+         assert_eq! (*seen.lock ().unwrap (),
+                     vec! [(None, SS::ONLINE),
+                           (Some (SS::ONLINE), SS::CANCEL_ONLY)]);
+     }
+
+     #[test]  fn  latency_report ()
+     {
+         use  super::Latency_Report;
+         use  std::time::Duration;
+
+         let  ms  =  Duration::from_millis;
+         let  R  =  Latency_Report {  samples:  vec! [(ms (40), 0.2),
+                                                      (ms (10), -0.3),
+                                                      (ms (30), 0.7),
+                                                      (ms (20), -0.2)],
+                                      failures:  1  };
+
+         assert_eq! (R.percentile (0.0),  ms (10));
+         assert_eq! (R.percentile (50.0),  ms (30));
+         assert_eq! (R.percentile (100.0),  ms (40));
+         assert_eq! (R.mean (),  ms (25));
+         assert! ((R.mean_offset () - 0.1).abs () < 1e-9);
+         assert_eq! (R.offset_range (),  (-0.3, 0.7));
+         assert_eq! (R.best_offset (),  Some (-0.3));
+         assert_eq! (Latency_Report::default ().percentile (90.0),
+                     Duration::ZERO);
+     }
+
+     #[test]  fn  uncached_probe ()
+     {
+         use  crate::Cancel_Token;
+         use  std::time::Duration;
+
+         /*  The cache could answer server_time, but the probe must still go
+          *  to the exchange, which the cancelled token stops it doing. */
+         let  mut  K  =  crate::connect (String::new (), String::new ());
+         K.set_cache_lifetime ("Time", Some (Duration::from_secs (60)));
+         K.cache.put ("Time", r#"{"error":[],"result":{"unixtime":1}}"#);
+         let  T  =  Cancel_Token::new ();
+         T.cancel ();
+         K.set_cancel_token (T);
+
+         assert! (K.server_time ().is_ok ());
+         assert_eq! (K.time_sample (),  Err ("request cancelled".to_string ()));
+     }  }
@@ -0,0 +1,195 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  Somewhere for the stateful helpers to keep what they need to pick up
+ *  where they left off after the program is restarted. */
+
+
+
+use  std::collections::BTreeMap;
+use  std::path::PathBuf;
+use  std::sync::{Arc, Mutex};
+
+
+
+/** Storage for the state of the crate's long-running helpers, such as the
+    cursor of a [Cursor_Stream], the orders of an [OCO] and the open orders
+    an [Account] has seen, so that a program which crashes or is restarted
+    can carry on where it was.  The state is kept as blobs of bytes under
+    keys of the user's choosing; the crate provides [Memory_Store] and
+    [File_Store], and anything else (a database, a key-value service) can be
+    used by implementing this.
+
+    [Cursor_Stream]: crate::Cursor_Stream
+    [OCO]: crate::OCO
+    [Account]: crate::Account  */
+
+pub  trait  State_Store  :  Send + Sync
+{
+    /** The blob kept under `key`, if there is one. */
+    fn  get  (&self,  key:  &str)  ->  Result<Option<Vec<u8>>, String>;
+
+    /** Keep `value` under `key`, in place of anything kept there before. */
+    fn  put  (&self,  key:  &str,  value:  &[u8])  ->  Result<(), String>;
+
+    /** Forget whatever is kept under `key`; it is not an error if there is
+        nothing. */
+    fn  remove  (&self,  key:  &str)  ->  Result<(), String>;
+}
+
+
+
+/** A store which can be handed to several helpers, in several threads. */
+
+pub  type  Shared_Store  =  Arc<dyn State_Store>;
+
+
+
+/** A [State_Store] which keeps everything in memory: it survives the loss
+    of a helper or thread, but not of the program.  Useful in tests, and
+    where helpers are started and stopped within one run. */
+
+#[derive(Debug, Default)]
+pub  struct  Memory_Store  (Mutex<BTreeMap<String, Vec<u8>>>);
+
+impl  Memory_Store
+{
+    /** An empty store. */
+    pub  fn  new  ()  ->  Self   {   Self::default ()   }
+}
+
+impl  State_Store  for  Memory_Store
+{
+    fn  get  (&self,  key:  &str)  ->  Result<Option<Vec<u8>>, String>
+          {   Ok (self.0.lock ().unwrap ().get (key).cloned ())   }
+
+    fn  put  (&self,  key:  &str,  value:  &[u8])  ->  Result<(), String>
+    {
+        self.0.lock ().unwrap ().insert (key.to_string (), value.to_vec ());
+        Ok (())
+    }
+
+    fn  remove  (&self,  key:  &str)  ->  Result<(), String>
+          {   self.0.lock ().unwrap ().remove (key);   Ok (())   }
+}
+
+
+
+/** A [State_Store] which keeps each blob in a file of its own in a
+    directory, named after its key (which may hold only letters, digits,
+    '-', '_' and '.', and may not start with '.').  A blob is written to a
+    temporary file which is then renamed over the old one, so a crash in the
+    middle of writing leaves the old blob in place. */
+
+#[derive(Clone, Debug)]
+pub  struct  File_Store  (PathBuf);
+
+impl  File_Store
+{
+    /** A store in the directory at `dir`, which is made if it does not yet
+        exist. */
+    pub  fn  new  (dir:  impl Into<PathBuf>)  ->  Result<Self, String>
+    {
+        let  dir  =  dir.into ();
+        std::fs::create_dir_all (&dir)
+            .map_err (|E| format! ("{}: {}", dir.display (), E)) ?;
+        Ok (File_Store (dir))
+    }
+
+    /*  The file the blob under the key is kept in. */
+    fn  path  (&self,  key:  &str)  ->  Result<PathBuf, String>
+    {
+        if  key.is_empty ()  ||  key.starts_with ('.')
+              ||  ! key.chars ().all (|C|  C.is_ascii_alphanumeric ()
+                                             ||  "-_.".contains (C))
+            {   Err (format! ("'{}' cannot be used as a store key", key)) ?   }
+        Ok (self.0.join (key))
+    }
+}
+
+impl  State_Store  for  File_Store
+{
+    fn  get  (&self,  key:  &str)  ->  Result<Option<Vec<u8>>, String>
+    {
+        let  path  =  self.path (key) ?;
+        match  std::fs::read (&path)
+        {
+            Ok (B)  =>  Ok (Some (B)),
+            Err (E)  if  E.kind () == std::io::ErrorKind::NotFound
+                     =>  Ok (None),
+            Err (E)  =>  Err (format! ("{}: {}", path.display (), E))
+        }
+    }
+
+    fn  put  (&self,  key:  &str,  value:  &[u8])  ->  Result<(), String>
+    {
+        let  path  =  self.path (key) ?;
+        let  temporary  =  self.0.join (format! (".{}.new", key));
+        std::fs::write (&temporary, value)
+            .and_then (|_| std::fs::rename (&temporary, &path))
+            .map_err (|E| format! ("{}: {}", path.display (), E))
+    }
+
+    fn  remove  (&self,  key:  &str)  ->  Result<(), String>
+    {
+        let  path  =  self.path (key) ?;
+        match  std::fs::remove_file (&path)
+        {
+            Err (E)  if  E.kind () != std::io::ErrorKind::NotFound
+                     =>  Err (format! ("{}: {}", path.display (), E)),
+            _  =>  Ok (())
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  stores  ()  ->  Result<(), String>
+    {
+        use  super::{File_Store, Memory_Store, State_Store};
+
+        let  dir  =  std::env::temp_dir ()
+                         .join (format! ("kraken-store-{}",
+                                         std::process::id ()));
+        let  F  =  File_Store::new (&dir) ?;
+
+        for  S  in  [&Memory_Store::new () as &dyn State_Store,  &F]
+        {
+            assert_eq! (S.get ("cursor") ?,  None);
+            S.put ("cursor", b"1688671200") ?;
+            S.put ("cursor", b"1688671260") ?;
+            assert_eq! (S.get ("cursor") ?.as_deref (),
+                        Some (&b"1688671260" [..]));
+            S.remove ("cursor") ?;
+            S.remove ("cursor") ?;
+            assert_eq! (S.get ("cursor") ?,  None);
+        }
+
+        assert! (F.put ("../escape", b"").is_err ());
+        assert! (F.get (".hidden").is_err ());
+        std::fs::remove_dir_all (&dir).unwrap ();
+        Ok (())
+    }
+}
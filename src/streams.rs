@@ -0,0 +1,808 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{API_Option, Cancel_Token, Data_Anomaly, Instruction, Kraken_API,
+             Shared_Store};
+use  crate::consistency::check_cursor;
+use  crate::reply;
+use  serde_json  as  JSN;
+use  std::collections::VecDeque;
+use  std::time::{Duration, Instant};
+#[cfg(feature = "async")]
+use  std::{pin::Pin, sync::{Arc, Mutex, mpsc},
+           task::{Context, Poll, Waker}};
+
+
+
+/** One entry from the [Kraken_API::spread_data] end-point: the best bid and
+    ask prices in the book at a moment in time. */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub  struct  Spread
+{
+    /** UNIX time of the observation. */
+    pub  time:  f64,
+
+    /** The best bid price. */
+    pub  bid:   f64,
+
+    /** The best ask price. */
+    pub  ask:   f64
+}
+
+impl  Spread
+{
+    /** Interpret one `[time, bid, ask]` entry of a Spread reply. */
+    pub  fn  from_json  (V:  &JSN::Value)  ->  Result<Self, String>
+    {
+        Ok (Spread {  time:  reply::number (&V [0]) ?,
+                      bid:   reply::number (&V [1]) ?,
+                      ask:   reply::number (&V [2]) ?  })
+    }
+
+    /** The gap between the ask and the bid. */
+    pub  fn  width  (&self)  ->  f64   {   self.ask - self.bid   }
+}
+
+
+
+/** Summary statistics of the width of the spread over a run of [Spread]
+    observations, as got with [Kraken_API::spread_stats].  */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub  struct  Spread_Stats
+{
+    /** The number of observations. */
+    pub  count:          usize,
+
+    /** UNIX time of the first observation. */
+    pub  start:          f64,
+
+    /** UNIX time of the last observation. */
+    pub  end:            f64,
+
+    /** The narrowest spread seen. */
+    pub  min:            f64,
+
+    /** The widest spread seen. */
+    pub  max:            f64,
+
+    /** The mean of the observations, each counting the same. */
+    pub  mean:           f64,
+
+    /** The mean over time, each observation counting for as long as it
+        stood, i.e. until the next one; the same as the plain mean if all
+        the observations were made at the same moment. */
+    pub  time_weighted:  f64
+}
+
+
+
+impl  Spread_Stats
+{
+    /** Work out the statistics of the observations, which must be in time
+        order (as the exchange gives them); `None` if there are none. */
+    pub  fn  from_spreads  (spreads:  &[Spread])  ->  Option<Self>
+    {
+        let  first  =  spreads.first () ?;
+        let  last   =  spreads.last () ?;
+        let  widths  =  spreads.iter ().map (Spread::width);
+
+        let  mean  =  widths.clone ().sum::<f64> () / spreads.len () as f64;
+        let  span  =  last.time - first.time;
+        let  time_weighted
+               =  if  span <= 0.0  {  mean  }
+                  else  {  spreads.windows (2)
+                                  .map (|W| W [0].width ()
+                                              * (W [1].time - W [0].time))
+                                  .sum::<f64> ()  /  span  };
+
+        Some (Spread_Stats {  count:  spreads.len (),
+                              start:  first.time,
+                              end:    last.time,
+                              min:    widths.clone ().fold (f64::INFINITY,
+                                                            f64::min),
+                              max:    widths.fold (f64::NEG_INFINITY,
+                                                   f64::max),
+                              mean,
+                              time_weighted  })
+    }
+}
+
+
+
+/** One entry from the [Kraken_API::recent_trades] end-point. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Trade
+{
+    /** The price at which the trade was made. */
+    pub  price:     f64,
+
+    /** The volume traded. */
+    pub  volume:    f64,
+
+    /** UNIX time of the trade. */
+    pub  time:      f64,
+
+    /** Whether the taker was buying or selling. */
+    pub  side:      Instruction,
+
+    /** Whether the taker's order was a market order (else it was a limit
+        order). */
+    pub  market:    bool,
+
+    /** The exchange's ID for the trade, if it gave one. */
+    pub  trade_id:  Option<u64>
+}
+
+impl  Trade
+{
+    /** Interpret one `[price, volume, time, side, type, misc, id]` entry of a
+        Trades reply. */
+    pub  fn  from_json  (V:  &JSN::Value)  ->  Result<Self, String>
+    {
+        Ok (Trade {  price:     reply::number (&V [0]) ?,
+                     volume:    reply::number (&V [1]) ?,
+                     time:      reply::number (&V [2]) ?,
+                     side:      if V [3] == "s"  { Instruction::SELL }
+                                else             { Instruction::BUY },
+                     market:    V [4] == "m",
+                     trade_id:  V [6].as_u64 ()  })
+    }
+}
+
+
+
+/*  The pacing common to both streams: no request goes out less than
+ *  `interval` after the one before it, and the wait is cut short if the
+ *  stream's stop token is cancelled. */
+
+struct  Throttle  {  interval:  Duration,
+                     last:      Option<Instant>,
+                     stop:      Option<Cancel_Token>  }
+
+impl  Throttle
+{
+    fn  new  (interval:  Duration)  ->  Self
+          {   Throttle {  interval,  last: None,  stop: None  }   }
+
+    /*  Wait until the next request may go out, returning false if the stream
+     *  has been stopped. */
+    fn  wait  (&mut self)  ->  bool
+    {
+        if let Some (L) = self.last
+        {   let  gap  =  self.interval.saturating_sub (L.elapsed ());
+            match  &self.stop
+               {   Some (S)  =>  S.pause (gap),
+                   None      =>  std::thread::sleep (gap)   }   }
+
+        self.last  =  Some (Instant::now ());
+        ! self.stop.as_ref ().is_some_and (Cancel_Token::is_cancelled)
+    }
+}
+
+
+
+/** A blocking [Iterator] over successive snapshots of a pair's ticker, got by
+    polling the [Kraken_API::ticker_info] end-point; made with
+    [Kraken_API::ticker_stream].
+
+    Each item is the pair's entry from the 'result' section of the reply, or
+    the error if a poll failed; errors do not end the stream.  The stream only
+    ends if it has been given a stop token with [Ticker_Stream::with_stop] and
+    that token is cancelled.  */
+
+pub  struct  Ticker_Stream<'a>  {  K:         &'a mut Kraken_API,
+                                   pair:      String,
+                                   throttle:  Throttle  }
+
+impl  Ticker_Stream<'_>
+{
+    /** End the stream (cutting short any wait) when `stop` is cancelled. */
+    pub  fn  with_stop  (mut self,  stop:  Cancel_Token)  ->  Self
+          {   self.throttle.stop  =  Some (stop);   self   }
+}
+
+impl  Iterator  for  Ticker_Stream<'_>
+{
+    type  Item  =  Result<JSN::Value, String>;
+
+    fn  next  (&mut self)  ->  Option<Self::Item>
+    {
+        if  ! self.throttle.wait ()   {   return  None;   }
+
+        Some (self.K.ticker_info (self.pair.clone ())
+                    .and_then (|R| reply::result_of (&R))
+                    .and_then (|R| R.as_object ()
+                                    .and_then (|M| M.values ().next ())
+                                    .cloned ()
+                                    .ok_or (format! ("no ticker for '{}'",
+                                                     self.pair))))
+    }
+}
+
+
+
+/** A blocking [Iterator] over the entries of one of the market data
+    end-points which take an [API_Option::SINCE] cursor; made with
+    [Kraken_API::spread_stream] or [Kraken_API::trade_stream].
+
+    Each poll asks only for the entries since the `last` cursor of the
+    previous reply, so that every entry is yielded exactly once, in order.  A
+    failed poll yields its error, and the next poll tries again from the same
+    cursor.  The stream only ends if it has been given a stop token with
+    [Cursor_Stream::with_stop] and that token is cancelled.  */
+
+pub  struct  Cursor_Stream<'a, T>  {  K:         &'a mut Kraken_API,
+                                      pair:      String,
+                                      fetch:     Fetch,
+                                      parse:     fn (&JSN::Value)
+                                                       ->  Result<T, String>,
+                                      since:     Option<String>,
+                                      pending:   VecDeque<T>,
+                                      throttle:  Throttle,
+                                      store:     Option<(Shared_Store,
+                                                         String)>,
+                                      anomalies: Vec<Data_Anomaly>  }
+
+type  Fetch  =  fn (&mut Kraken_API, String)  ->  Result<String, String>;
+
+/** A [Cursor_Stream] over the [Kraken_API::spread_data] end-point. */
+pub  type  Spread_Stream<'a>  =  Cursor_Stream<'a, Spread>;
+
+/** A [Cursor_Stream] over the [Kraken_API::recent_trades] end-point. */
+pub  type  Trade_Stream<'a>  =  Cursor_Stream<'a, Trade>;
+
+impl<'a, T>  Cursor_Stream<'a, T>
+{
+    fn  new  (K:  &'a mut Kraken_API,  pair:  &str,  fetch:  Fetch,
+              parse:  fn (&JSN::Value) -> Result<T, String>)  ->  Self
+          {   Cursor_Stream {  K,  pair: pair.to_string (),  fetch,  parse,
+                               since: None,  pending: VecDeque::new (),
+                               throttle: Throttle::new (Duration::from_secs
+                                                                        (5)),
+                               store: None,  anomalies: Vec::new ()  }   }
+
+    /** Poll no more often than every `interval`; the default is every five
+        seconds, which keeps well inside the exchange's limits on public
+        calls. */
+    pub  fn  with_interval  (mut self,  interval:  Duration)  ->  Self
+          {   self.throttle.interval  =  interval;   self   }
+
+    /** Start from the given cursor, rather than with the whole of the
+        exchange's recent history. */
+    pub  fn  since  (mut self,  since:  &str)  ->  Self
+          {   self.since  =  Some (since.to_string ());   self   }
+
+    /** End the stream (cutting short any wait) when `stop` is cancelled. */
+    pub  fn  with_stop  (mut self,  stop:  Cancel_Token)  ->  Self
+          {   self.throttle.stop  =  Some (stop);   self   }
+
+    /** The cursor the next poll will start from, which may be saved to
+        resume the stream later with [Cursor_Stream::since]. */
+    pub  fn  cursor  (&self)  ->  Option<&str>   {   self.since.as_deref ()   }
+
+    /** Keep the cursor in `store` under `key`, and start from the one kept
+        there if there is one, so that after a restart the stream carries on
+        where it was.  The cursor is saved once every entry before it has
+        been yielded, so an entry may be yielded again after a crash, but
+        none is missed. */
+    pub  fn  with_store  (mut self,  store:  Shared_Store,  key:  &str)
+              ->  Result<Self, String>
+    {
+        if  let  Some (C)  =  store.get (key) ?
+            {   self.since  =  Some (String::from_utf8_lossy (&C).into ());   }
+        self.store  =  Some ((store, key.to_string ()));
+        Ok (self)
+    }
+
+    /** Take the [Data_Anomaly] reports made since the last call: at present,
+        any reply whose `last` cursor was behind the one before, in which
+        case the stream passes over the reply's entries and keeps to the
+        cursor it had.
+        [stitch_trades](crate::stitch_trades) makes further checks on the
+        entries themselves. */
+    pub  fn  take_anomalies  (&mut self)  ->  Vec<Data_Anomaly>
+          {   std::mem::take (&mut self.anomalies)   }
+
+    /*  Make one call to the end-point, queueing up the new entries. */
+    fn  poll  (&mut self)  ->  Result<(), String>
+    {
+        if  let  (Some ((S, key)), Some (C))  =  (&self.store, &self.since)
+            {   S.put (key, C.as_bytes ()) ?;   }
+
+        match  &self.since
+           {   Some (S)  =>  self.K.set_opt (API_Option::SINCE, S),
+               None      =>  self.K.clear_opt (API_Option::SINCE)   }
+
+        let  R  =  (self.fetch) (self.K, self.pair.clone ()) ?;
+        let  R  =  reply::result_of (&R) ?;
+        let  M  =  R.as_object ().ok_or ("bad reply from exchange") ?;
+
+        let  mut  new  =  Vec::new ();
+        for  V  in  M.iter ().filter (|(key, _)| *key != "last")
+                             .filter_map (|(_, V)| V.as_array ())
+                             .flatten ()
+            {   new.push ((self.parse) (V) ?);   }
+
+        let  last  =  match  M.get ("last")
+                         {   Some (JSN::Value::String (S))
+                                       =>  Some (S.clone ()),
+                             Some (V)  =>  Some (V.to_string ()),
+                             None      =>  None   };
+        if  let  Some (L)  =  last
+        {
+            /*  The entries of a reply whose cursor went back are ones we
+             *  have had already, or will have again from the next poll. */
+            if  let  Some (A)  =  check_cursor (self.since.as_deref (), &L)
+                {   self.anomalies.push (A);   return  Ok (());   }
+            self.since  =  Some (L);
+        }
+        self.pending.extend (new);
+
+        Ok (())
+    }
+}
+
+impl<T>  Iterator  for  Cursor_Stream<'_, T>
+{
+    type  Item  =  Result<T, String>;
+
+    fn  next  (&mut self)  ->  Option<Self::Item>
+    {
+        while  self.pending.is_empty ()
+        {
+            if  ! self.throttle.wait ()   {   return  None;   }
+
+            if let Err (E) = self.poll ()
+                {   self.pending.clear ();   return  Some (Err (E));   }
+        }
+
+        self.pending.pop_front ().map (Ok)
+    }
+}
+
+
+
+/*  The most items an [Async_Stream] holds which have not been taken; the
+ *  polling thread waits while there are this many. */
+
+#[cfg(feature = "async")]
+const  ASYNC_BACKLOG:  usize  =  4096;
+
+
+
+/** An asynchronous [Stream](futures_core::Stream) over the items of one of
+    the blocking streams above; made with [Kraken_API::ticker_stream_async],
+    [Kraken_API::spread_stream_async] or [Kraken_API::trade_stream_async],
+    with the `async` feature.
+
+    The crate's calls all block, so the polling is done on a thread of its
+    own with a [Kraken_API::share] of the handle, and the items passed over
+    to the stream as they arrive, waking the task waiting on it.  Dropping
+    the stream stops the thread, cutting short any wait or request in
+    progress.  */
+
+#[cfg(feature = "async")]
+pub  struct  Async_Stream<T>  {  items:  mpsc::Receiver<Result<T, String>>,
+                                 waker:  Arc<Mutex<Option<Waker>>>,
+                                 stop:   Cancel_Token  }
+
+/*  What the polling thread hands its items to: returns false when the stream
+ *  has gone, and so there is no point in carrying on. */
+#[cfg(feature = "async")]
+type  Sink<'a, T>  =  &'a mut dyn FnMut (Result<T, String>) -> bool;
+
+#[cfg(feature = "async")]
+impl<T: Send + 'static>  Async_Stream<T>
+{
+    /*  Call `run` on a thread of its own, with a share of K and a token,
+     *  both of which are cancelled when the stream is dropped, and the sink
+     *  for its items; the stream ends when `run` returns. */
+    fn  spawn  (K:  &Kraken_API,
+                run:  impl FnOnce (&mut Kraken_API, Cancel_Token, Sink<T>)
+                           + Send + 'static)
+           ->  Self
+    {
+        let  (sender, items)  =  mpsc::sync_channel (ASYNC_BACKLOG);
+        let  waker  =  Arc::new (Mutex::new (None));
+        let  stop  =  Cancel_Token::new ();
+
+        let  mut  K  =  K.share ();
+        K.set_cancel_token (stop.clone ());
+        let  (W, S)  =  (waker.clone (), stop.clone ());
+
+        std::thread::spawn (move ||
+            {   run (&mut K,  S,
+                     &mut |item|  {  let  sent  =  sender.send (item).is_ok ();
+                                     wake (&W);
+                                     sent  });
+                drop (sender);
+                wake (&W);   });
+
+        Async_Stream {  items,  waker,  stop  }
+    }
+}
+
+#[cfg(feature = "async")]
+fn  wake  (waker:  &Mutex<Option<Waker>>)
+{
+    if let Some (W) = waker.lock ().unwrap ().take ()   {   W.wake ();   }
+}
+
+#[cfg(feature = "async")]
+impl<T>  futures_core::Stream  for  Async_Stream<T>
+{
+    type  Item  =  Result<T, String>;
+
+    fn  poll_next  (self:  Pin<&mut Self>,  cx:  &mut Context<'_>)
+             ->  Poll<Option<Self::Item>>
+    {
+        /*  The waker is left before looking, so that an item sent after the
+         *  look will wake us. */
+        *self.waker.lock ().unwrap ()  =  Some (cx.waker ().clone ());
+
+        match  self.items.try_recv ()
+           {   Ok (I)  =>  Poll::Ready (Some (I)),
+               Err (mpsc::TryRecvError::Empty)  =>  Poll::Pending,
+               Err (mpsc::TryRecvError::Disconnected)
+                       =>  Poll::Ready (None)   }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T>  Drop  for  Async_Stream<T>
+{
+    fn  drop  (&mut self)   {   self.stop.cancel ();   }
+}
+
+
+
+impl  Kraken_API
+{
+/** Poll the ticker of `pair` every `interval`, as a blocking [Iterator]; see
+    [Ticker_Stream].
+
+    This gives streaming semantics without the need for a WebSocket
+    connection, at the cost of one public call per item.  */
+
+  pub  fn  ticker_stream  (&mut self,  pair: &str,  interval: Duration)
+             ->  Ticker_Stream<'_>
+    {
+      Ticker_Stream {  K: self,  pair: pair.to_string (),
+                       throttle: Throttle::new (interval)  }
+    }
+
+
+
+/** Get the recent spreads of `pair` as typed data, oldest first.
+
+    This makes one call to the [Kraken_API::spread_data] end-point, and so
+    respects any [API_Option::SINCE] setting.  */
+
+  pub  fn  spreads  (&mut self,  pair:  &str)  ->  Result<Vec<Spread>, String>
+    {
+      let  R  =  reply::result_of (&self.spread_data (pair) ?) ?;
+      R.as_object ()
+       .into_iter ()
+       .flatten ()
+       .filter (|(key, _)| *key != "last")
+       .filter_map (|(_, V)| V.as_array ())
+       .flatten ()
+       .map (Spread::from_json)
+       .collect ()
+    }
+
+
+
+/** Get the recent trades made in `pair` as typed data, oldest first.
+
+    This makes one call to the [Kraken_API::recent_trades] end-point, which
+    gives up to 1000 trades, and so respects any [API_Option::SINCE]
+    setting.  */
+
+  pub  fn  trades  (&mut self,  pair:  &str)  ->  Result<Vec<Trade>, String>
+    {
+      let  R  =  reply::result_of (&self.recent_trades (pair) ?) ?;
+      R.as_object ()
+       .into_iter ()
+       .flatten ()
+       .filter (|(key, _)| *key != "last")
+       .filter_map (|(_, V)| V.as_array ())
+       .flatten ()
+       .map (Trade::from_json)
+       .collect ()
+    }
+
+
+
+/** Get the [Spread_Stats] of the recent spreads of `pair`, as given by
+    [Kraken_API::spreads]; an error if the exchange gave none.  */
+
+  pub  fn  spread_stats  (&mut self,  pair:  &str)
+             ->  Result<Spread_Stats, String>
+    {
+      Spread_Stats::from_spreads (&self.spreads (pair) ?)
+          .ok_or (format! ("no spreads for '{}'", pair))
+    }
+
+
+
+/** Follow the spreads of `pair` as a blocking [Iterator]; see
+    [Cursor_Stream].
+
+    The stream uses the [API_Option::SINCE] setting of this handle for its
+    cursor, so it should not be shared with other calls while the stream is
+    alive (the borrow checker will see to that).  */
+
+  pub  fn  spread_stream  (&mut self,  pair: &str)  ->  Spread_Stream<'_>
+    {
+      Cursor_Stream::new (self,  pair,
+                          Kraken_API::spread_data,  Spread::from_json)
+    }
+
+
+
+/** Follow the trades made in `pair` as a blocking [Iterator]; see
+    [Cursor_Stream].
+
+    As with [Kraken_API::spread_stream], the stream takes over the handle's
+    [API_Option::SINCE] setting.  */
+
+  pub  fn  trade_stream  (&mut self,  pair: &str)  ->  Trade_Stream<'_>
+    {
+      Cursor_Stream::new (self,  pair,
+                          Kraken_API::recent_trades,  Trade::from_json)
+    }
+}
+
+
+
+#[cfg(feature = "async")]
+impl  Kraken_API
+{
+/** As [Kraken_API::ticker_stream], but as an [Async_Stream], polled from a
+    thread of its own with a [Kraken_API::share] of this handle.  Only with
+    the `async` feature.  */
+
+  pub  fn  ticker_stream_async  (&self,  pair: &str,  interval: Duration)
+             ->  Async_Stream<JSN::Value>
+    {
+      let  pair  =  pair.to_string ();
+      Async_Stream::spawn (self,
+                           move |K, stop, send|
+                             {   K.ticker_stream (&pair, interval)
+                                  .with_stop (stop)
+                                  .all (send);   })
+    }
+
+
+
+/** As [Kraken_API::spread_stream], polling every `interval`, but as an
+    [Async_Stream]; the shared handle's [API_Option::SINCE] setting is its
+    own, so this one's is left alone.  Only with the `async` feature.  */
+
+  pub  fn  spread_stream_async  (&self,  pair: &str,  interval: Duration)
+             ->  Async_Stream<Spread>
+    {
+      let  pair  =  pair.to_string ();
+      Async_Stream::spawn (self,
+                           move |K, stop, send|
+                             {   K.spread_stream (&pair)
+                                  .with_interval (interval)
+                                  .with_stop (stop)
+                                  .all (send);   })
+    }
+
+
+
+/** As [Kraken_API::trade_stream], polling every `interval`, but as an
+    [Async_Stream]; see [Kraken_API::spread_stream_async].  Only with the
+    `async` feature.  */
+
+  pub  fn  trade_stream_async  (&self,  pair: &str,  interval: Duration)
+             ->  Async_Stream<Trade>
+    {
+      let  pair  =  pair.to_string ();
+      Async_Stream::spawn (self,
+                           move |K, stop, send|
+                             {   K.trade_stream (&pair)
+                                  .with_interval (interval)
+                                  .with_stop (stop)
+                                  .all (send);   })
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    use  crate::{API_Option, Kraken_API};
+
+    /*  A stand-in for the spread end-point, which fails once when asked for
+     *  the entries after the first two. */
+    fn  flaky  (K:  &mut Kraken_API,  _:  String)  ->  Result<String, String>
+    {
+        Ok (match  K.option (&API_Option::SINCE).map (String::as_str)
+        {
+            None  =>  r#"{"error":[],"result":{"XXBTZUSD":
+                          [[1,"10","11"],[2,"10","12"]],"last":2}}"#,
+            Some ("2")  if  K.option (&API_Option::COUNT).is_none ()
+                  =>  {  K.set_opt (API_Option::COUNT, 1);
+                         return  Err ("EService:Unavailable".into ())  },
+            Some ("2")  =>  r#"{"error":[],"result":{"XXBTZUSD":
+                                [[3,"10","13"]],"last":3}}"#,
+            _  =>  r#"{"error":[],"result":{"XXBTZUSD":[],"last":3}}"#
+        }.to_string ())
+    }
+
+
+
+    #[test]
+    fn  throttle  ()
+    {
+        use  super::Throttle;
+        use  crate::Cancel_Token;
+        use  std::time::{Duration, Instant};
+
+        let  mut  T  =  Throttle::new (Duration::from_millis (200));
+        let  start  =  Instant::now ();
+        assert! (T.wait ());
+        assert! (start.elapsed () < Duration::from_millis (200));
+        assert! (T.wait ());
+        assert! (start.elapsed () >= Duration::from_millis (200));
+
+        let  stop  =  Cancel_Token::new ();
+        T.stop  =  Some (stop.clone ());
+        stop.cancel ();
+        let  start  =  Instant::now ();
+        assert! (! T.wait ());
+        assert! (start.elapsed () < Duration::from_millis (200));
+    }
+
+
+
+    #[test]
+    fn  retry  ()
+    {
+        use  super::{Cursor_Stream, Spread};
+
+        let  mut  K  =  Kraken_API::default ();
+        let  mut  S  =  Cursor_Stream::new (&mut K, "XBTUSD", flaky,
+                                            Spread::from_json)
+                           .with_interval (std::time::Duration::ZERO);
+
+        assert_eq! (S.cursor (),  None);
+        assert_eq! (S.next ().map (|S| S.map (|S| S.time)),  Some (Ok (1.0)));
+        assert_eq! (S.cursor (),  Some ("2"));
+        assert_eq! (S.next ().map (|S| S.map (|S| S.time)),  Some (Ok (2.0)));
+
+        //  The failed poll is tried again from the same cursor.
+        assert_eq! (S.next ().map (|S| S.map (|S| S.time)),
+                    Some (Err ("EService:Unavailable".into ())));
+        assert_eq! (S.cursor (),  Some ("2"));
+        assert_eq! (S.next ().map (|S| S.map (|S| S.time)),  Some (Ok (3.0)));
+        assert_eq! (S.cursor (),  Some ("3"));
+    }
+
+
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn  async_stream  ()
+    {
+        use  super::{Async_Stream, Cursor_Stream, Spread};
+        use  futures_core::Stream;
+        use  std::pin::Pin;
+        use  std::task::{Context, Poll, Waker};
+        use  std::time::Duration;
+
+        let  mut  S  =  Async_Stream::spawn
+                            (&Kraken_API::default (),
+                             |K, stop, send|
+                               {   Cursor_Stream::new (K, "XBTUSD", flaky,
+                                                       Spread::from_json)
+                                        .with_interval (Duration::ZERO)
+                                        .with_stop (stop)
+                                        .all (send);   });
+
+        let  mut  cx  =  Context::from_waker (Waker::noop ());
+        let  mut  items  =  Vec::new ();
+        let  tick  =  Duration::from_millis (1);
+        while  items.len () < 4
+        {
+            match  Pin::new (&mut S).poll_next (&mut cx)
+               {   Poll::Ready (Some (I))  =>  items.push (I.map (|S| S.time)),
+                   Poll::Ready (None)  =>  panic! ("stream ended early"),
+                   Poll::Pending  =>  std::thread::sleep (tick)   }
+        }
+
+        assert_eq! (items,  [Ok (1.0), Ok (2.0),
+                             Err ("EService:Unavailable".into ()), Ok (3.0)]);
+    }
+
+
+
+    #[test]
+    fn  spread_stats  ()
+    {
+        use  super::{Spread, Spread_Stats};
+
+        let  S  =  |time: f64, bid: f64, ask: f64|
+                      Spread {  time,  bid,  ask  };
+        let  stats  =  Spread_Stats::from_spreads (&[S (100.0, 10.0, 11.0),
+                                                      S (130.0, 10.0, 13.0),
+                                                      S (140.0, 10.0, 10.5)])
+                        .unwrap ();
+
+        assert_eq! ((stats.count, stats.start, stats.end), (3, 100.0, 140.0));
+        assert_eq! ((stats.min, stats.max, stats.mean), (0.5, 3.0, 1.5));
+        assert_eq! (stats.time_weighted, (1.0 * 30.0 + 3.0 * 10.0) / 40.0);
+
+        assert_eq! (Spread_Stats::from_spreads (&[S (5.0, 1.0, 2.0)])
+                        .unwrap ().time_weighted,
+                    1.0);
+        assert! (Spread_Stats::from_spreads (&[]).is_none ());
+    }
+
+
+
+    #[test]
+    fn  regressed_cursor  ()
+    {
+        use  super::{Cursor_Stream, Spread};
+        use  crate::Data_Anomaly;
+
+        /*  A stand-in for the spread end-point, which goes back to an
+         *  earlier cursor once. */
+        fn  fetch  (K:  &mut Kraken_API,  _:  String)
+               ->  Result<String, String>
+        {
+            Ok (match  K.option (&API_Option::SINCE).map (String::as_str)
+            {
+                None  =>  r#"{"error":[],"result":{"XXBTZUSD":
+                              [[1,"10","11"],[2,"10","12"]],"last":2}}"#,
+                Some ("2")  if  K.option (&API_Option::COUNT).is_none ()
+                      =>  {  K.set_opt (API_Option::COUNT, 1);
+                             r#"{"error":[],"result":{"XXBTZUSD":
+                                 [[1,"10","11"],[2,"10","12"]],"last":1}}"#  },
+                _  =>  r#"{"error":[],"result":{"XXBTZUSD":
+                              [[3,"10","13"]],"last":3}}"#
+            }.to_string ())
+        }
+
+        let  mut  K  =  Kraken_API::default ();
+        let  mut  S  =  Cursor_Stream::new (&mut K, "XBTUSD", fetch,
+                                            Spread::from_json)
+                           .with_interval (std::time::Duration::ZERO);
+
+        let  times  =  S.by_ref ().take (3)
+                        .map (|S| S.map (|S| S.time))
+                        .collect::<Result<Vec<_>, _>> ();
+        assert_eq! (times,  Ok (vec! [1.0, 2.0, 3.0]));
+        assert_eq! (S.take_anomalies (),
+                    [Data_Anomaly::CURSOR_REGRESSED {  previous:  "2".into (),
+                                                       next:  "1".into ()  }]);
+        assert_eq! (S.cursor (),  Some ("3"));
+    }
+}
@@ -0,0 +1,134 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  serde_json  as  JSN;
+use  std::collections::BTreeSet;
+use  std::sync::Mutex;
+use  std::sync::atomic::{AtomicBool, Ordering};
+
+
+
+/** How the typed models (e.g. [Order](crate::Order),
+    [Pair_Info](crate::Pair_Info)) treat fields in the exchange's replies
+    which they do not know about; see [set_strictness].  */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub  enum  Strictness
+{
+    /** Pass over unknown fields (the default), so that the exchange adding
+        to its replies does not break running programs. */
+    #[default]
+    LENIENT,
+
+    /** Fail on an unknown field, naming it, so that a test suite finds out
+        as soon as the exchange's replies change. */
+    STRICT
+}
+
+
+
+static  STRICT:  AtomicBool  =  AtomicBool::new (false);
+
+/*  The (model, field) pairs seen so far which the models do not know. */
+static  SEEN:  Mutex<BTreeSet<(String, String)>>
+                  =  Mutex::new (BTreeSet::new ());
+
+
+
+/** Choose how the typed models treat unknown fields from now on.  This is a
+    setting for the whole program rather than for one [Kraken_API] handle,
+    as the models are also built by code which has no handle, with their
+    `from_json` functions.
+
+    [Kraken_API]: crate::Kraken_API  */
+
+pub  fn  set_strictness  (strictness:  Strictness)
+      {   STRICT.store (strictness == Strictness::STRICT, Ordering::SeqCst);   }
+
+
+
+/** The fields met in the exchange's replies which the typed models do not
+    know about, as (model, field) pairs, e.g. ("Order", "amended"), in
+    order; they are noted whatever the [Strictness], so a lenient program
+    can still log them.  */
+
+pub  fn  unknown_fields  ()  ->  Vec<(String, String)>
+      {   SEEN.lock ().unwrap ().iter ().cloned ().collect ()   }
+
+
+
+/** Forget the fields which [unknown_fields] has noted so far. */
+
+pub  fn  clear_unknown_fields  ()   {   SEEN.lock ().unwrap ().clear ();   }
+
+
+
+/*  Note any field of V which is not among those `known` to the `model`, and
+ *  if we are being strict, fail on the first of them. */
+
+pub(crate)  fn  check_fields  (model:  &str,  V:  &JSN::Value,
+                               known:  &[&str])
+          ->  Result<(), String>
+      {   check (STRICT.load (Ordering::SeqCst), model, V, known)   }
+
+
+
+fn  check  (strict:  bool,  model:  &str,  V:  &JSN::Value,  known:  &[&str])
+      ->  Result<(), String>
+{
+    let  unknown  =  V.as_object ().into_iter ().flatten ()
+                      .map (|(field, _)| field)
+                      .filter (|field| ! known.contains (&field.as_str ()))
+                      .collect::<Vec<_>> ();
+    if  unknown.is_empty ()   {   return  Ok (());   }
+
+    let  mut  S  =  SEEN.lock ().unwrap ();
+    for  field  in  &unknown
+        {   S.insert ((model.to_string (), field.to_string ()));   }
+
+    if  strict
+        {   Err (format! ("unknown field '{}' in {}", unknown [0], model)) ?   }
+    Ok (())
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  unknown_fields  ()
+    {
+        use  super::check;
+
+        let  V  =  serde_json::json! ({"refid": "x", "test_only_field": 1});
+        assert! (check (false, "Test_Model", &V, &["refid"]).is_ok ());
+        assert_eq! (check (true, "Test_Model", &V, &["refid"]),
+                    Err ("unknown field 'test_only_field' in Test_Model"
+                             .to_string ()));
+        assert! (check (true, "Test_Model", &V,
+                        &["refid", "test_only_field"]).is_ok ());
+
+        assert! (super::unknown_fields ()
+                    .contains (&("Test_Model".to_string (),
+                                 "test_only_field".to_string ())));
+    }
+}
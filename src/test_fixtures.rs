@@ -0,0 +1,77 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+
+/*  Values for the tests of the modules which work over candles, trades and
+ *  ledger entries, with only the fields that matter to those tests given. */
+
+
+
+use  crate::{Candle, Instruction, Ledger_Entry, Ledger_Type, Own_Trade,
+             Trade};
+
+
+
+/*  A candle in which no trade moved the price off `close`. */
+
+pub(crate)  fn  candle  (time:  f64,  close:  f64)  ->  Candle
+{
+    Candle {  time,  open:  close,  high:  close,  low:  close,  close,
+              vwap:  close,  volume:  1.0,  count:  1  }
+}
+
+
+
+/*  A market buy of `volume` at `price`, as seen in the public trades. */
+
+pub(crate)  fn  trade  (time:  f64,  price:  f64,  volume:  f64,
+                        trade_id:  Option<u64>)
+               ->  Trade
+{
+    Trade {  price,  volume,  time,  side:  Instruction::BUY,  market:  true,
+             trade_id  }
+}
+
+
+
+/*  One of our own trades in XXBTZUSD, paying a fee of 0.1%. */
+
+pub(crate)  fn  own_trade  (txid:  &str,  order_txid:  &str,  time:  f64,
+                            side:  Instruction,  price:  f64,  volume:  f64)
+               ->  Own_Trade
+{
+    Own_Trade {  txid:  txid.into (),  order_txid:  order_txid.into (),
+                 pair:  "XXBTZUSD".into (),  time,  side,  price,  volume,
+                 cost:  price * volume,  fee:  price * volume * 0.001  }
+}
+
+
+
+/*  A ledger entry with no fee and no sub-type, whose ID is its `refid`. */
+
+pub(crate)  fn  ledger_entry  (refid:  &str,  time:  f64,  kind:  Ledger_Type,
+                               asset:  &str,  amount:  f64,  balance:  f64)
+               ->  Ledger_Entry
+{
+    Ledger_Entry {  id:  refid.into (),  refid:  refid.into (),  time,  kind,
+                    subtype:  String::new (),  asset:  asset.into (),
+                    amount,  fee:  0.0,  balance  }
+}
@@ -0,0 +1,257 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  The exchange's operating mode as last seen, and the orders it rules out,
+ *  so that an order which the exchange is bound to refuse is turned away
+ *  before it is sent, and costs nothing from the rate limit. */
+
+
+
+use  crate::Kraken_API;
+use  std::sync::Mutex;
+
+
+
+/** The operating mode of the exchange, as reported by the
+    [Kraken_API::system_status] end-point. */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub  enum  System_Status
+{
+    /** Operating normally; all order types may be submitted. */
+    ONLINE,
+
+    /** The exchange is off-line; no requests are honoured. */
+    MAINTENANCE,
+
+    /** Existing orders may be cancelled, but no new orders placed. */
+    CANCEL_ONLY,
+
+    /** Only new limit orders without immediate matches may be placed. */
+    POST_ONLY
+}
+
+impl  System_Status
+{
+    /** Interpret the status string the exchange uses. */
+    pub  fn  from_kraken_string  (status: &str)  ->  Option<Self>
+    {   match  status
+        {   "online"       =>  Some (System_Status::ONLINE),
+            "maintenance"  =>  Some (System_Status::MAINTENANCE),
+            "cancel_only"  =>  Some (System_Status::CANCEL_ONLY),
+            "post_only"    =>  Some (System_Status::POST_ONLY),
+            _              =>  None  }  }
+
+    /** Present the status precisely as the exchange does. */
+    pub  fn  as_kraken_string  (&self)  ->  &'static str
+    {   match  self
+        {   System_Status::ONLINE       =>  "online",
+            System_Status::MAINTENANCE  =>  "maintenance",
+            System_Status::CANCEL_ONLY  =>  "cancel_only",
+            System_Status::POST_ONLY    =>  "post_only"  }  }
+}
+
+
+
+/** An order turned away without being sent, because the exchange is in a
+    mode which does not allow it; given (as a string) by
+    [Kraken_API::add_order], [Kraken_API::add_order_batch] and
+    [Kraken_API::edit_order].  See [Kraken_API::trading_mode].
+
+    ```ignore
+    match  K.add_order (KKN::Order_Type::MARKET, KKN::Instruction::BUY,
+                        0.01, "XBTUSD")
+    {
+        Err (E)  if  KKN::Mode_Refusal::from_message (&E).is_some ()
+             =>  wait_for_the_exchange (),
+        R  =>  ...
+    }
+    ```  */
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub  struct  Mode_Refusal
+{
+    /** The mode the exchange was in. */
+    pub  mode:    System_Status,
+
+    /** What the mode does not allow. */
+    pub  reason:  String
+}
+
+
+
+impl  Mode_Refusal
+{
+    /** Recognise this error in an error message from the crate. */
+    pub  fn  from_message  (message:  &str)  ->  Option<Self>
+    {
+        let  rest  =  message.strip_prefix ("order refused in ") ?;
+        let  (mode, reason)  =  rest.split_once (" mode: ") ?;
+        Some (Mode_Refusal {  mode:  System_Status::from_kraken_string (mode) ?,
+                              reason:  reason.to_string ()  })
+    }
+}
+
+
+
+impl  std::fmt::Display  for  Mode_Refusal
+{
+    fn  fmt  (&self,  f:  &mut std::fmt::Formatter)  ->  std::fmt::Result
+          {   write! (f, "order refused in {} mode: {}",
+                      self.mode.as_kraken_string (), self.reason)   }
+}
+
+
+
+/*  Why the `mode` rules out an order of the type given (none for an edit,
+ *  which keeps the type of the order it amends) with the order flags. */
+
+fn  refusal  (mode:  System_Status,  order_type:  Option<&str>,
+              oflags:  Option<&str>)
+      ->  Option<String>
+{
+    let  post  =  oflags.is_some_and (|F|  F.split (',')
+                                           .any (|F| F.trim () == "post"));
+    match  mode
+    {
+        System_Status::ONLINE  =>  None,
+        System_Status::MAINTENANCE | System_Status::CANCEL_ONLY
+            =>  Some ("no new orders may be placed or amended".to_string ()),
+        System_Status::POST_ONLY
+            =>  match  order_type
+                {   Some (T)  if  T != "limit"
+                        =>  Some (format! ("{} orders may not be placed; only \
+                                            limit orders with the post flag",
+                                           T)),
+                    _  if  ! post
+                        =>  Some ("orders must carry the post flag in their \
+                                   order flags".to_string ()),
+                    _  =>  None   }
+    }
+}
+
+
+
+/*  The mode of the exchange as last seen by the handles which share it;
+ *  `None` until it has been seen. */
+
+#[derive(Default)]
+pub(crate)  struct  Mode_Guard  (Mutex<Option<System_Status>>);
+
+
+
+impl  Mode_Guard
+{
+    /*  Take note of the mode given in a reply from the SystemStatus
+     *  end-point; a reply which does not give one changes nothing. */
+    pub(crate)  fn  observe  (&self,  reply:  &str)
+    {
+        let  mode  =  reply.split_once ("\"status\":")
+                           .and_then (|(_, R)|  R.trim_start ()
+                                                 .strip_prefix ('"'))
+                           .and_then (|R|  R.split ('"').next ())
+                           .and_then (System_Status::from_kraken_string);
+        if  mode.is_some ()   {   *self.0.lock ().unwrap ()  =  mode;   }
+    }
+
+
+    /*  Refuse an order which the mode we last saw rules out. */
+    pub(crate)  fn  check  (&self,  order_type:  Option<&str>,
+                            oflags:  Option<&str>)
+                   ->  Result<(), Mode_Refusal>
+    {
+        let  Some (mode)  =  *self.0.lock ().unwrap ()
+            else   {   return  Ok (())   };
+        match  refusal (mode, order_type, oflags)
+           {   Some (reason)  =>  Err (Mode_Refusal {  mode,  reason  }),
+               None  =>  Ok (())   }
+    }
+}
+
+
+
+impl  Kraken_API
+{
+/** The operating mode of the exchange as last seen by this handle, or any
+    shared with it through [Kraken_API::share]: whenever a reply comes back
+    from [Kraken_API::system_status] (which [Kraken_API::health_check] and
+    a [Status_Watcher] call) the mode it gives is noted, or it can be set by
+    hand with [Kraken_API::set_trading_mode], say from the status messages
+    of a web-socket session.  `None` until the mode has been seen.
+
+    While the mode is [System_Status::MAINTENANCE] or
+    [System_Status::CANCEL_ONLY], [Kraken_API::add_order],
+    [Kraken_API::add_order_batch] and [Kraken_API::edit_order] fail at once
+    with a [Mode_Refusal], without sending anything; while it is
+    [System_Status::POST_ONLY] they do so for anything but limit orders
+    carrying the "post" flag in [API_Option::OFLAGS].  Cancelling is always
+    allowed.  The mode is only as fresh as the last look at it, so a program
+    which relies on this should keep a [Status_Watcher] running on a shared
+    handle.
+
+    [Status_Watcher]: crate::Status_Watcher
+    [API_Option::OFLAGS]: crate::API_Option::OFLAGS  */
+
+    pub  fn  trading_mode  (&self)  ->  Option<System_Status>
+          {   *self.mode_guard.0.lock ().unwrap ()   }
+
+
+
+/** Set the mode [Kraken_API::trading_mode] gives, for this handle and all
+    those shared with it; `None` forgets it, so that no orders are refused
+    until it is seen again. */
+
+    pub  fn  set_trading_mode  (&self,  mode:  Option<System_Status>)
+          {   *self.mode_guard.0.lock ().unwrap ()  =  mode;   }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  refusals  ()
+    {
+        use  super::{Mode_Guard, Mode_Refusal, System_Status as SS};
+
+        let  G  =  Mode_Guard::default ();
+        assert_eq! (G.check (Some ("market"), None),  Ok (()));
+
+        G.observe (r#"{"error":[],"result":{"status":"post_only",
+                                    "timestamp":"2023-07-06T18:52:00Z"}}"#);
+        assert_eq! (G.check (Some ("limit"), Some ("fcib,post")),  Ok (()));
+        assert_eq! (G.check (None, Some ("post")),  Ok (()));
+        let  E  =  G.check (Some ("market"), None).unwrap_err ();
+        assert_eq! (E.mode,  SS::POST_ONLY);
+        assert_eq! (Mode_Refusal::from_message (&E.to_string ()),  Some (E));
+        assert! (G.check (Some ("limit"), Some ("fciq")).is_err ());
+
+        G.observe (r#"{"error":["EService:Unavailable"]}"#);
+        G.observe (r#"{"error":[],"result":{"status":"cancel_only"}}"#);
+        assert! (G.check (Some ("limit"), Some ("post")).is_err ());
+        assert! (G.check (None, None).is_err ());
+
+        G.observe (r#"{"error":[],"result":{"status":"online"}}"#);
+        assert_eq! (G.check (Some ("market"), None),  Ok (()));
+        assert_eq! (Mode_Refusal::from_message ("invalid value"),  None);
+    }
+}
@@ -0,0 +1,285 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  The settings which govern how requests get to the exchange, as opposed to
+ *  what they say: they are applied to every curl handle just before it is
+ *  sent. */
+
+
+
+use  curl::easy::{Easy, HttpVersion, IpResolve, List};
+use  std::net::{IpAddr, ToSocketAddrs};
+use  std::sync::{Arc, Mutex};
+use  std::time::{Duration, Instant};
+
+
+
+/*  The exchange's host, as in the URLs of all the end-points. */
+
+pub(crate)  const  HOST:  &str  =  "api.kraken.com";
+
+
+
+/** Which kind of IP address to reach the exchange on; see
+    [Kraken_API::set_ip_family](crate::Kraken_API::set_ip_family). */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub  enum  IP_Family
+{
+    /** Whichever the system finds first (the default). */
+    #[default]
+    ANY,
+    /** IPv4 addresses only. */
+    V4,
+    /** IPv6 addresses only. */
+    V6
+}
+
+
+
+/** The error given when a reply from the exchange is larger than the limit
+    set with
+    [Kraken_API::set_max_reply_size](crate::Kraken_API::set_max_reply_size).
+    The transfer is abandoned as soon as the limit is passed, or before it
+    starts if the exchange says at the outset how long the reply will be.
+
+    ```ignore
+    match  K.asset_pairs ()
+    {
+        Err (E)  if  KKN::Reply_Too_Large::from_message (&E).is_some ()
+            =>  eprintln! ("the exchange sent far more than expected: {}", E),
+        R  =>  ...
+    }
+    ```  */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub  struct  Reply_Too_Large
+{
+    /** The limit which was passed, in bytes. */
+    pub  limit:  usize
+}
+
+
+
+impl  Reply_Too_Large
+{
+    /** Recognise this error in an error message from the crate. */
+    pub  fn  from_message  (message:  &str)  ->  Option<Self>
+    {
+        let  limit  =  message.strip_prefix ("reply too large: over ") ?
+                              .strip_suffix (" bytes") ?
+                              .parse ().ok () ?;
+        Some (Reply_Too_Large {  limit  })
+    }
+}
+
+
+
+impl  std::fmt::Display  for  Reply_Too_Large
+{
+    fn  fmt  (&self,  f:  &mut std::fmt::Formatter)  ->  std::fmt::Result
+          {   write! (f, "reply too large: over {} bytes", self.limit)   }
+}
+
+
+
+#[derive(Clone, Debug, Default)]
+pub(crate)  struct  Transport
+{
+    /*  Whether to offer HTTP/2 (true) or to keep to HTTP/1.1 (false); None
+     *  leaves the choice to libcurl, which offers HTTP/2 if it can. */
+    pub(crate)  http2:      Option<bool>,
+
+    pub(crate)  ip_family:  IP_Family,
+
+    /*  The local interface, address or host name to send requests from. */
+    pub(crate)  interface:  Option<String>,
+
+    /*  How long to keep using addresses of HOST once they have been looked
+     *  up; None leaves the look-ups to libcurl. */
+    pub(crate)  dns_lifetime:  Option<Duration>,
+
+    /*  The most bytes we will take in the body of a reply, if there is a
+     *  limit. */
+    pub(crate)  max_reply:  Option<usize>,
+
+    /*  Addresses to use for HOST no matter what, if any. */
+    pub(crate)  pinned:     Option<Vec<IpAddr>>,
+
+    /*  The last addresses looked up for HOST, and when; this is shared with
+     *  all the handles which share the rate limit. */
+    pub(crate)  resolved:   Arc<Mutex<Option<Lookup>>>
+}
+
+type  Lookup  =  (Vec<IpAddr>, Instant);
+
+
+
+/*  Can the libcurl we are linked against speak HTTP/2? */
+
+pub(crate)  fn  http2_available  ()  ->  bool
+      {   curl::Version::get ().feature_http2 ()   }
+
+
+
+/*  Look up the addresses of HOST, of the given family. */
+
+pub(crate)  fn  resolve  (family:  IP_Family)  ->  Result<Vec<IpAddr>, String>
+{
+    let  A  =  (HOST, 443).to_socket_addrs ()
+                          .map_err (|E| format! ("{}: {}", HOST, E)) ?
+                          .map (|A| A.ip ())
+                          .filter (|A|  match  family
+                                          {   IP_Family::ANY  =>  true,
+                                              IP_Family::V4
+                                                  =>  A.is_ipv4 (),
+                                              IP_Family::V6
+                                                  =>  A.is_ipv6 ()   })
+                          .collect::<Vec<_>> ();
+
+    if  A.is_empty ()
+        {   Err (format! ("{} has no usable address", HOST)) ?   }
+    Ok (A)
+}
+
+
+
+/*  The entry for libcurl's list of fixed name resolutions which sends
+ *  requests for HOST to the given addresses. */
+
+fn  resolve_entry  (addresses:  &[IpAddr])  ->  String
+{
+    let  A  =  addresses.iter ()
+                        .map (|A|  match  A
+                                     {   IpAddr::V4 (a)  =>  a.to_string (),
+                                         IpAddr::V6 (a)  =>  format! ("[{}]",
+                                                                      a)   })
+                        .collect::<Vec<_>> ();
+    format! ("{}:443:{}", HOST, A.join (","))
+}
+
+
+
+impl  Transport
+{
+    /*  The addresses to send requests to, if we are choosing them rather
+     *  than libcurl.  Once the cached ones are out of date they are looked up
+     *  again, but if that fails the old ones are used until it works, so
+     *  that trouble with DNS does not stop requests. */
+    fn  addresses  (&self)  ->  Option<Vec<IpAddr>>
+    {
+        if let Some (P) = &self.pinned   {   return  Some (P.clone ());   }
+        let  lifetime  =  self.dns_lifetime ?;
+
+        let  mut  R  =  self.resolved.lock ().unwrap ();
+        if let Some ((A, t)) = &*R
+        {
+            if  t.elapsed () < lifetime   {   return  Some (A.clone ());   }
+        }
+
+        match  resolve (self.ip_family)
+           {   Ok (A)   =>  {  *R  =  Some ((A.clone (), Instant::now ()));
+                               Some (A)  }
+               Err (_)  =>  R.as_ref ().map (|(A, _)| A.clone ())   }
+    }
+
+
+    /*  Fail if `received` bytes of a reply are more than we will take. */
+    pub(crate)  fn  admit  (&self,  received:  usize)  ->  Result<(), String>
+    {
+        match  self.max_reply
+           {   Some (limit)  if  received > limit
+                   =>  Err (Reply_Too_Large {  limit  }.to_string ()),
+               _   =>  Ok (())   }
+    }
+
+
+    /*  The message for a failed transfer: if curl was stopped because the
+     *  reply was too big, say so in our own terms. */
+    pub(crate)  fn  failure  (&self,  E:  &curl::Error)  ->  String
+    {
+        match  self.max_reply
+           {   Some (limit)  if  E.is_filesize_exceeded ()
+                   =>  Reply_Too_Large {  limit  }.to_string (),
+               _   =>  E.to_string ()   }
+    }
+
+
+    pub(crate)  fn  apply  (&self,  C:  &mut Easy)  ->  Result<(), String>
+    {
+        let  fail  =  |E: curl::Error|  E.to_string ();
+
+        match  self.http2
+           {   Some (true)  if  http2_available ()
+                   =>  C.http_version (HttpVersion::V2TLS).map_err (fail) ?,
+               Some (_)
+                   =>  C.http_version (HttpVersion::V11).map_err (fail) ?,
+               None  =>  ()   }
+
+        C.ip_resolve (match  self.ip_family
+                         {   IP_Family::ANY  =>  IpResolve::Any,
+                             IP_Family::V4   =>  IpResolve::V4,
+                             IP_Family::V6   =>  IpResolve::V6   })
+         .map_err (fail) ?;
+
+        if let Some (I) = &self.interface
+            {   C.interface (I).map_err (fail) ?;   }
+
+        if let Some (L) = self.max_reply
+            {   C.max_filesize (L as u64).map_err (fail) ?;   }
+
+        if let Some (A) = self.addresses ()
+        {
+            let  mut  L  =  List::new ();
+            L.append (&resolve_entry (&A)).map_err (fail) ?;
+            C.resolve (L).map_err (fail) ?;
+        }
+
+        Ok (())
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+  {  #[test]  fn  resolve_entry ()
+     {
+         let  A  =  ["104.16.10.8".parse ().unwrap (),
+                     "2606:4700::6810:a08".parse ().unwrap ()];
+
+         assert_eq! (super::resolve_entry (&A),
+                     "api.kraken.com:443:104.16.10.8,[2606:4700::6810:a08]");
+     }
+
+     #[test]  fn  reply_size ()
+     {
+         use  super::{Reply_Too_Large, Transport};
+
+         let  T  =  Transport {  max_reply: Some (1000),
+                                 ..Default::default ()  };
+         assert! (T.admit (1000).is_ok ());
+         let  E  =  T.admit (1001).unwrap_err ();
+         assert_eq! (Reply_Too_Large::from_message (&E),
+                     Some (Reply_Too_Large {  limit: 1000  }));
+         assert_eq! (Reply_Too_Large::from_message ("request cancelled"), None);
+         assert! (Transport::default ().admit (usize::MAX).is_ok ());
+     }  }
@@ -0,0 +1,136 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  std::sync::Mutex;
+use  std::time::{SystemTime, UNIX_EPOCH};
+
+
+
+/*  A user reference is a positive i32: the top eight of its 31 bits name the
+ *  namespace, and the other 23 hold a sequence number. */
+
+const  SEQUENCE_BITS:  u32  =  23;
+const  SEQUENCE_MASK:  u32  =  (1 << SEQUENCE_BITS) - 1;
+
+
+
+/** Hands out user references ([API_Option::USERREF](crate::API_Option)) for
+    orders, each different from the last, so that orders can be told apart
+    when they are reconciled against the exchange's records.
+
+    Each reference is a positive `i32` carrying a namespace (0-255), which
+    lets several strategies trading on one account recognise their own
+    orders, and a sequence number.  The sequence follows the clock, one step
+    per second, and moves on by one more whenever a reference is taken
+    faster than that; so references do not repeat within one run of the
+    program, nor across restarts unless orders were placed at more than one a
+    second on average, and they come round again only after some 97 days.
+
+    A generator can be shared between threads.
+
+    ```ignore
+    let  G  =  KKN::Userref::with_namespace (3);
+    K.set_opt (KKN::API_Option::USERREF, G.next ());
+    ...
+    if  order.userref.and_then (KKN::Userref::namespace_of) == Some (3)
+        { ... }
+    ```  */
+
+#[derive(Debug, Default)]
+pub  struct  Userref  {  namespace:  u32,
+                         last:       Mutex<Option<u32>>  }
+
+
+
+impl  Userref
+{
+    /** A generator in namespace 0. */
+    pub  const  fn  new  ()  ->  Self   {   Self::with_namespace (0)   }
+
+    /** A generator whose references are all marked with `namespace`. */
+    pub  const  fn  with_namespace  (namespace:  u8)  ->  Self
+          {   Userref {  namespace:  namespace as u32,
+                         last:  Mutex::new (None)  }   }
+
+    /** The next user reference. */
+    pub  fn  next  (&self)  ->  i32
+    {
+        let  now  =  SystemTime::now ().duration_since (UNIX_EPOCH)
+                                       .unwrap_or_default ().as_secs ();
+        self.next_at (now)
+    }
+
+    /** The namespace a user reference was made in, or `None` if it is not
+        one of ours (i.e. it is not positive). */
+    pub  fn  namespace_of  (userref:  i64)  ->  Option<u8>
+    {
+        if  userref <= 0  ||  userref > i32::MAX as i64   {   return  None;   }
+        Some ((userref as u32 >> SEQUENCE_BITS) as u8)
+    }
+
+    /*  The next reference, taking the time to be `secs` past the epoch. */
+    fn  next_at  (&self,  secs:  u64)  ->  i32
+    {
+        let  slot  =  (secs as u32) & SEQUENCE_MASK;
+        let  mut  last  =  self.last.lock ().unwrap ();
+
+        /*  Ahead of the clock, but not so far ahead that the clock has
+         *  wrapped round behind us. */
+        let  sequence  =  match  *last
+        {
+            Some (L)  if  L.wrapping_sub (slot) & SEQUENCE_MASK
+                              < SEQUENCE_MASK / 2
+                =>  (L + 1) & SEQUENCE_MASK,
+            _   =>  slot
+        };
+        *last  =  Some (sequence);
+
+        /*  Zero is not a user reference. */
+        let  R  =  (self.namespace << SEQUENCE_BITS | sequence) as i32;
+        if  R == 0   {   drop (last);   return  self.next_at (secs);   }
+        R
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  sequence  ()
+    {
+        use  super::{SEQUENCE_MASK, Userref};
+
+        let  G  =  Userref::with_namespace (200);
+        let  a  =  G.next_at (1000);
+        let  b  =  G.next_at (1000);
+        let  c  =  G.next_at (1000);
+        assert_eq! ((b - a, c - b), (1, 1));
+        assert_eq! (G.next_at (5000) & SEQUENCE_MASK as i32, 5000);
+        assert! (G.next_at (4000) > 5000);
+        assert_eq! (Userref::namespace_of (c as i64), Some (200));
+        assert_eq! (Userref::namespace_of (-4), None);
+
+        let  G  =  Userref::new ();
+        assert_eq! (G.next_at (SEQUENCE_MASK as u64 + 1), 1);
+    }
+}
@@ -0,0 +1,241 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  Checks on the values given to the optional arguments, so that a bad value
+ *  is caught where it is set, rather than coming back as an opaque refusal
+ *  from the exchange. */
+
+
+
+use  crate::{API_Option, API_Option  as  Opt, Kraken_API, kraken_argument};
+use  std::sync::Arc;
+
+
+
+/*  A check on the value of an option, giving the reason it is refused. */
+
+pub(crate)  type  Option_Validator
+                   =  Arc<dyn Fn (&str) -> Result<(), String> + Send + Sync>;
+
+
+
+/** A value given to an optional argument which the argument cannot take,
+    as reported by [Kraken_API::try_set_opt], and (as a string) by any call
+    which would have sent it. */
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub  struct  Invalid_Option
+{
+    /** The argument, as the exchange names it, e.g. "interval". */
+    pub  argument:  String,
+
+    /** The value which was refused. */
+    pub  value:     String,
+
+    /** Why it was refused. */
+    pub  reason:    String
+}
+
+
+
+impl  Invalid_Option
+{
+    /** Recognise this error in an error message from the crate. */
+    pub  fn  from_message  (message:  &str)  ->  Option<Self>
+    {
+        let  rest  =  message.strip_prefix ("invalid value '") ?;
+        let  (value, rest)  =  rest.rsplit_once ("' for ") ?;
+        let  (argument, reason)  =  rest.split_once (": ") ?;
+        Some (Invalid_Option {  argument:  argument.to_string (),
+                                value:     value.to_string (),
+                                reason:    reason.to_string ()  })
+    }
+}
+
+
+
+impl  std::fmt::Display  for  Invalid_Option
+{
+    fn  fmt  (&self,  f:  &mut std::fmt::Formatter)  ->  std::fmt::Result
+          {   write! (f, "invalid value '{}' for {}: {}",
+                      self.value, self.argument, self.reason)   }
+}
+
+
+
+/*  The check the crate makes on an option's value when the user has not
+ *  given one of their own; None for options which take values too varied
+ *  to check. */
+
+fn  built_in  (opt:  &Opt,  value:  &str)  ->  Option<Result<(), String>>
+{
+    let  one_of  =  |allowed: &[&str]|
+                        if  allowed.contains (&value)   {   Ok (())   }
+                        else   {   Err (format! ("must be one of {}",
+                                                 allowed.join (", ")))   };
+    let  between  =  |low: i64, high: i64|
+                        match  value.parse::<i64> ()
+                           {   Ok (n)  if  (low ..= high).contains (&n)
+                                   =>  Ok (()),
+                               _  =>  Err (format! ("must be a whole number \
+                                                     from {} to {}",
+                                                    low, high))   };
+
+    Some (match  opt
+    {
+        Opt::INTERVAL  =>  one_of (&["1", "5", "15", "30", "60", "240",
+                                     "1440", "10080", "21600"]),
+        Opt::TIMEOUT   =>  between (0, 86400),
+        Opt::USERREF   =>  between (i32::MIN as i64, i32::MAX as i64),
+        Opt::OFS | Opt::COUNT  =>  between (0, i64::MAX),
+        Opt::CLOSE_TIME  =>  one_of (&["open", "close", "both"]),
+        Opt::TRIGGER   =>  one_of (&["index", "last"]),
+        Opt::TIME_IN_FORCE  =>  one_of (&["GTC", "IOC", "GTD"]),
+        Opt::FORMAT    =>  one_of (&["CSV", "TSV"]),
+        Opt::INFO      =>  one_of (&["info", "leverage", "fees", "margin"]),
+        Opt::CONSOLIDATION  =>  one_of (&["market"]),
+        Opt::TRADES | Opt::DO_CALCS | Opt::FEE_INFO | Opt::VALIDATE
+          | Opt::NEW   =>  one_of (&["true", "false"]),
+        _  =>  return  None
+    })
+}
+
+
+
+/*  Check the value the option is to be given, with the user's validator if
+ *  there is one, or else the crate's own. */
+
+pub(crate)  fn  check  (K:  &Kraken_API,  opt:  &Opt,  value:  &str)
+                ->  Result<(), Invalid_Option>
+{
+    let  outcome  =  match  K.validators.get (opt)
+                        {   Some (V)  =>  V (value),
+                            None  =>  built_in (opt, value)
+                                          .unwrap_or (Ok (()))   };
+
+    outcome.map_err (|reason|
+                        Invalid_Option {  argument:  kraken_argument (opt)
+                                                         .to_string (),
+                                          value:     value.to_string (),
+                                          reason  })
+}
+
+
+
+impl  Kraken_API
+{
+/** Give a value to the optional argument, as [Kraken_API::set_opt] does,
+    but only if the argument can take it; otherwise the option is left as it
+    was, and the reason is given.  The crate knows the values which
+    [API_Option::INTERVAL], [API_Option::TIMEOUT] (0 to 86400 seconds),
+    [API_Option::USERREF], [API_Option::OFS], [API_Option::COUNT],
+    [API_Option::CLOSE_TIME], [API_Option::TRIGGER],
+    [API_Option::TIME_IN_FORCE], [API_Option::FORMAT], [API_Option::INFO],
+    [API_Option::CONSOLIDATION] and the boolean options can take; others
+    are only checked if a validator has been given with
+    [Kraken_API::set_option_validator].
+
+    Values given with [Kraken_API::set_opt] or
+    [Kraken_API::set_default_opt] are checked too, when a call which would
+    send them is made; the call then fails without going to the exchange.  */
+
+    pub  fn  try_set_opt<T: std::fmt::Display>  (&mut  self,
+                                                 opt:  API_Option,
+                                                 value:  T)
+               ->  Result<(), Invalid_Option>
+    {
+        let  value  =  value.to_string ();
+        check (self, &opt, &value) ?;
+        self.options.insert (opt, value);
+        Ok (())
+    }
+
+
+
+/** Check the values given to `opt` with the given function, in place of
+    the crate's own check (if any); it gives the reason a value is refused.
+    Handles from [Kraken_API::share] start with the same validators.  */
+
+    pub  fn  set_option_validator<F>  (&mut  self,  opt:  API_Option,  f:  F)
+                 where  F:  Fn (&str) -> Result<(), String>
+                                                   + Send + Sync + 'static
+          {   self.validators.insert (opt, Arc::new (f));   }
+
+
+
+/** Go back to the crate's own check on the values of `opt`. */
+
+    pub  fn  clear_option_validator  (&mut  self,  opt:  API_Option)
+          {   self.validators.remove (&opt);   }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  validation  ()
+    {
+        use  super::Invalid_Option;
+        use  crate::API_Option  as  Opt;
+
+        let  mut  K  =  crate::Kraken_API::default ();
+        assert_eq! (K.try_set_opt (Opt::INTERVAL, 60),  Ok (()));
+        assert_eq! (K.try_set_opt (Opt::TIMEOUT, 86400),  Ok (()));
+        assert_eq! (K.try_set_opt (Opt::PAIR, "anything"),  Ok (()));
+
+        let  E  =  K.try_set_opt (Opt::INTERVAL, 7).unwrap_err ();
+        assert_eq! (E.argument,  "interval");
+        assert_eq! (Invalid_Option::from_message (&E.to_string ()),  Some (E));
+        assert! (K.try_set_opt (Opt::TIMEOUT, 86401).is_err ());
+        assert! (K.try_set_opt (Opt::TRADES, "yes").is_err ());
+
+        K.set_option_validator (Opt::PAIR,
+                                |V|  if  V.contains ('/')  {  Ok (())  }
+                                     else  {  Err ("use a slash".into ())  });
+        assert! (K.try_set_opt (Opt::PAIR, "XBTUSD").is_err ());
+        K.clear_option_validator (Opt::PAIR);
+        assert_eq! (K.try_set_opt (Opt::PAIR, "XBTUSD"),  Ok (()));
+
+        K.set_opt (Opt::INTERVAL, 7);
+        let  E  =  K.ohlc_data ("XBTUSD").unwrap_err ();
+        assert_eq! (Invalid_Option::from_message (&E).map (|E| E.value),
+                    Some ("7".to_string ()));
+
+        use  crate::{Batch_Order, Instruction, Order_Type};
+        let  order  =  ||  Batch_Order::new (Order_Type::LIMIT,
+                                             Instruction::BUY, 1);
+        assert! (K.add_order_batch ("XBTUSD", &[order ()])
+                  .unwrap_err ().contains ("from 2 to 15 orders"));
+        assert! (K.add_order_batch ("XBTUSD",
+                                    &(0 .. 16).map (|_| order ())
+                                              .collect::<Vec<_>> ())
+                  .is_err ());
+        let  E  =  K.add_order_batch ("XBTUSD",
+                                      &[order (),
+                                        order ().set_opt (Opt::TIME_IN_FORCE,
+                                                          "GTX")])
+                    .unwrap_err ();
+        assert_eq! (Invalid_Option::from_message (&E).map (|E| E.value),
+                    Some ("GTX".to_string ()));
+    }
+}
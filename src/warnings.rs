@@ -0,0 +1,181 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  The exchange's 'error' section carries warnings as well as errors: the
+ *  first letter of each message is its severity, 'E' or 'W'.  This picks out
+ *  the warnings from a raw reply without needing a JSON parser, so that they
+ *  can be passed on whether or not the json feature is enabled. */
+
+
+
+use  std::collections::BTreeSet;
+use  std::sync::{Arc, Mutex};
+
+
+
+/*  What Kraken_API::on_warning installs: called with the end-point and the
+ *  text of each warning. */
+
+pub(crate)  type  Warning_Callback  =  Arc<dyn Fn (&str, &str) + Send + Sync>;
+
+
+
+/*  Is the message in the 'error' section only a warning? */
+
+pub(crate)  fn  is_warning  (message:  &str)  ->  bool
+      {   message.starts_with ('W')   }
+
+
+
+/*  The warnings in the 'error' section of the reply, in the order given. */
+
+pub(crate)  fn  warnings_in  (reply:  &str)  ->  Vec<String>
+{
+    let  Some (start)  =  reply.find ("\"error\"")
+        else   {   return  vec! []   };
+    let  rest  =  &reply [start + 7 ..];
+    let  rest  =  rest.trim_start ()
+                      .strip_prefix (':')
+                      .map (str::trim_start)
+                      .and_then (|R| R.strip_prefix ('['))
+                      .unwrap_or ("");
+    let  section  =  &rest [.. rest.find (']').unwrap_or (0)];
+
+    /*  The messages are the odd-numbered pieces between quotes. */
+    section.split ('"')
+           .skip (1)
+           .step_by (2)
+           .filter (|M| is_warning (M))
+           .map (str::to_string)
+           .collect ()
+}
+
+
+
+/** An end-point which Kraken has deprecated, and the one which replaces
+    it.  The crate keeps a table of these, consulted by [deprecation]; the
+    first call to one of them from a program is reported to the
+    [Kraken_API::on_warning](crate::Kraken_API::on_warning) callback, with
+    the end-point and the text of this notice, before the call is made.  */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub  struct  Deprecation
+{
+    /** The deprecated end-point, e.g. "Stake". */
+    pub  end_point:    &'static str,
+
+    /** The end-point to use instead, e.g. "Earn/Allocate". */
+    pub  replacement:  &'static str
+}
+
+
+
+/*  The end-points Kraken has deprecated: the legacy staking calls, replaced
+ *  by the Earn family. */
+
+const  DEPRECATED:  &[Deprecation]
+    =  &[Deprecation {  end_point: "Stake",  replacement: "Earn/Allocate"  },
+         Deprecation {  end_point: "Unstake",
+                        replacement: "Earn/Deallocate"  },
+         Deprecation {  end_point: "Staking/Assets",
+                        replacement: "Earn/Strategies"  },
+         Deprecation {  end_point: "Staking/Pending",
+                        replacement: "Earn/Allocations"  },
+         Deprecation {  end_point: "Staking/Transactions",
+                        replacement: "Earn/Allocations"  }];
+
+
+
+/** Look up the `end_point` (named as in the Kraken documentation, with or
+    without arguments) in the crate's table of deprecated end-points. */
+
+pub  fn  deprecation  (end_point:  &str)  ->  Option<Deprecation>
+{
+    let  name  =  end_point.split ('?').next ().unwrap_or ("");
+    DEPRECATED.iter ().find (|D| D.end_point == name).copied ()
+}
+
+
+
+impl  std::fmt::Display  for  Deprecation
+{
+    fn  fmt  (&self,  f:  &mut std::fmt::Formatter)  ->  std::fmt::Result
+    {
+        write! (f, "{} is deprecated by Kraken and will stop working; use {} \
+                    instead", self.end_point, self.replacement)
+    }
+}
+
+
+
+/*  The deprecated end-points which have been reported already. */
+
+static  REPORTED:  Mutex<BTreeSet<&str>>  =  Mutex::new (BTreeSet::new ());
+
+
+
+/*  If the end-point is deprecated and this is the first call to it, tell
+ *  the callback. */
+
+pub(crate)  fn  check_deprecation  (warning:  Option<&Warning_Callback>,
+                                    end_point:  &str)
+{
+    let  (Some (f), Some (D))  =  (warning, deprecation (end_point))
+        else   {   return   };
+    if  REPORTED.lock ().unwrap ().insert (D.end_point)
+        {   f (D.end_point, &D.to_string ());   }
+}
+
+
+
+#[cfg(test)]
+mod  test
+{
+    #[test]
+    fn  warnings  ()
+    {
+        use  super::warnings_in;
+
+        assert_eq! (warnings_in (r#"{"error": ["WGeneral:Deprecated:use v2",
+                                               "EGeneral:Invalid arguments",
+                                               "WAPI:Slow down"],
+                                    "result": {}}"#),
+                    ["WGeneral:Deprecated:use v2", "WAPI:Slow down"]);
+        assert! (warnings_in (r#"{"error":[],"result":{"W":"x"}}"#)
+                    .is_empty ());
+        assert! (warnings_in ("garbage").is_empty ());
+    }
+
+
+    #[test]
+    fn  deprecation  ()
+    {
+        use  super::deprecation;
+
+        assert_eq! (deprecation ("Staking/Assets?asset=DOT")
+                        .map (|D| D.replacement),
+                    Some ("Earn/Strategies"));
+        assert_eq! (deprecation ("Earn/Strategies"),  None);
+        assert_eq! (deprecation ("Stake").unwrap ().to_string (),
+                    "Stake is deprecated by Kraken and will stop working; \
+                     use Earn/Allocate instead");
+    }
+}
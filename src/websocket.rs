@@ -0,0 +1,492 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+/*  The crate does not itself hold WebSocket connections open; what is here
+ *  is the protocol, above the transport, for use with whichever WebSocket
+ *  crate the application already has. */
+
+
+
+use  crate::{Kraken_API, Message_Queue, Overflow, Ws_Message};
+use  crate::reply;
+use  serde_json  as  JSN;
+
+
+
+/** A feed of the WebSockets API. */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub  enum  Channel
+{
+    /** Ticker information for a pair. */
+    TICKER,
+
+    /** The order book of a pair, to the given depth (10, 25, 100, 500 or
+        1000). */
+    BOOK (u32),
+
+    /** Candles for a pair, of the given interval in minutes. */
+    OHLC (u32),
+
+    /** Trades made in a pair. */
+    TRADE,
+
+    /** Best bid and ask of a pair. */
+    SPREAD,
+
+    /** Our own trades; a private feed. */
+    OWN_TRADES,
+
+    /** Our own open orders; a private feed. */
+    OPEN_ORDERS
+}
+
+impl  Channel
+{
+    /** Get the exact string Kraken uses to name this feed. */
+    pub  fn  as_kraken_string  (&self)  ->  &'static str
+    {   match  self
+        {   Channel::TICKER       =>  "ticker",
+            Channel::BOOK (_)     =>  "book",
+            Channel::OHLC (_)     =>  "ohlc",
+            Channel::TRADE        =>  "trade",
+            Channel::SPREAD       =>  "spread",
+            Channel::OWN_TRADES   =>  "ownTrades",
+            Channel::OPEN_ORDERS  =>  "openOrders"  }  }
+
+    /** Whether this is one of the private feeds, which need a token from
+        [Kraken_API::websockets_token]. */
+    pub  fn  is_private  (&self)  ->  bool
+          {   matches! (self,  Channel::OWN_TRADES | Channel::OPEN_ORDERS)   }
+
+    /*  The 'subscription' object of a subscribe or unsubscribe frame. */
+    fn  subscription  (&self,  token:  Option<&str>)  ->  JSN::Value
+    {
+        let  mut  S  =  JSN::json! ({ "name": self.as_kraken_string () });
+        match  self
+           {   Channel::BOOK (D)  =>  S ["depth"]     =  JSN::json! (D),
+               Channel::OHLC (I)  =>  S ["interval"]  =  JSN::json! (I),
+               _  =>  ()   }
+        if  self.is_private ()
+            {   S ["token"]  =  JSN::json! (token.unwrap_or (""));   }
+        S
+    }
+
+    /*  Does the 'subscription' object of a status event refer to this
+     *  channel? */
+    fn  is_described_by  (&self,  S:  &JSN::Value)  ->  bool
+    {
+        let  (field, value)  =  match  self
+                                   {   Channel::BOOK (D)  =>  ("depth", *D),
+                                       Channel::OHLC (I)  =>  ("interval", *I),
+                                       _  =>  ("", 0)   };
+
+        S ["name"] == self.as_kraken_string ()
+            &&  S [field].as_u64 ().is_none_or (|v| v == u64::from (value))
+    }
+}
+
+
+
+/** The state of one subscription in a [Subscriptions] registry. */
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub  enum  Sub_Status
+{
+    /** The subscription is wanted, but has not yet been sent to the
+        exchange. */
+    WANTED,
+
+    /** The subscription has been sent, but not yet confirmed. */
+    PENDING,
+
+    /** The exchange has confirmed the subscription. */
+    SUBSCRIBED,
+
+    /** The subscription has been asked to be dropped, but this has not yet
+        been confirmed. */
+    CLOSING,
+
+    /** The exchange refused the subscription, with the given message. */
+    FAILED (String)
+}
+
+
+
+/** A registry of the feeds an application wants from the WebSockets API,
+    which produces the frames to send to the exchange and keeps track of the
+    exchange's replies to them.
+
+    The registry is built up with its chainable methods, and then
+    [Subscriptions::pending_frames] gives the frames to send down the
+    connection.  Every frame which comes back should be offered to
+    [Subscriptions::observe], which picks out the subscription status events.
+    Feeds can be added and removed at any time; after a reconnection,
+    [Subscriptions::reset] marks everything as wanted again so that it is all
+    re-sent.
+
+    ```ignore
+    let  mut  S  =  KKN::Subscriptions::new ();
+    S.ticker (&["XBT/USD"]).book (&["XBT/USD"], 25).own_trades ();
+    S.set_token (&token);
+    for  F  in  S.pending_frames ()   {   socket.send (F) ?;   }
+    ```  */
+
+#[derive(Clone, Debug, Default)]
+pub  struct  Subscriptions  {  entries:  Vec<(Channel, Option<String>,
+                                               Sub_Status)>,
+                               token:    Option<String>,
+                               reqid:    u64,
+                               unsent:   Vec<String>  }
+
+
+
+impl  Subscriptions
+{
+    /** An empty registry. */
+    pub  fn  new  ()  ->  Self   {   Self::default ()   }
+
+    /** Set the token to use for private feeds, as got from
+        [Kraken_API::websockets_token]. */
+    pub  fn  set_token  (&mut self,  token:  &str)  ->  &mut Self
+          {   self.token  =  Some (token.to_string ());   self   }
+
+    /** Ask for a public `channel` on each of the `pairs` (named as the
+        WebSockets API names them, e.g. "XBT/USD"), or for a private channel
+        (in which case `pairs` is ignored). */
+    pub  fn  add  (&mut self,  channel:  Channel,  pairs:  &[&str])
+               ->  &mut Self
+    {
+        let  pairs  =  if  channel.is_private ()   {   vec! [None]   }
+                       else  {  pairs.iter ().map (|p| Some (p.to_string ()))
+                                              .collect ()  };
+
+        for  P  in  pairs
+        {   match  self.entries.iter_mut ()
+                                .find (|(c, p, _)|  *c == channel  &&  *p == P)
+               {   Some ((_, _, S))
+                         =>  if  ! matches! (S,  Sub_Status::PENDING
+                                                   | Sub_Status::SUBSCRIBED)
+                                 {   *S = Sub_Status::WANTED;   },
+                   None  =>  self.entries.push ((channel, P,
+                                                 Sub_Status::WANTED))   }   }
+        self
+    }
+
+    /** Ask for the ticker of each of the `pairs`. */
+    pub  fn  ticker  (&mut self,  pairs:  &[&str])  ->  &mut Self
+          {   self.add (Channel::TICKER, pairs)   }
+
+    /** Ask for the order book of each of the `pairs`, to the given `depth`. */
+    pub  fn  book  (&mut self,  pairs:  &[&str],  depth:  u32)  ->  &mut Self
+          {   self.add (Channel::BOOK (depth), pairs)   }
+
+    /** Ask for candles of `interval` minutes for each of the `pairs`. */
+    pub  fn  ohlc  (&mut self,  pairs:  &[&str],  interval:  u32)  ->  &mut Self
+          {   self.add (Channel::OHLC (interval), pairs)   }
+
+    /** Ask for the trades made in each of the `pairs`. */
+    pub  fn  trade  (&mut self,  pairs:  &[&str])  ->  &mut Self
+          {   self.add (Channel::TRADE, pairs)   }
+
+    /** Ask for the spread of each of the `pairs`. */
+    pub  fn  spread  (&mut self,  pairs:  &[&str])  ->  &mut Self
+          {   self.add (Channel::SPREAD, pairs)   }
+
+    /** Ask for our own trades. */
+    pub  fn  own_trades  (&mut self)  ->  &mut Self
+          {   self.add (Channel::OWN_TRADES, &[])   }
+
+    /** Ask for our own open orders. */
+    pub  fn  open_orders  (&mut self)  ->  &mut Self
+          {   self.add (Channel::OPEN_ORDERS, &[])   }
+
+
+    /** Drop the `channel` for each of the `pairs` (or the private channel).
+        An unsubscribe frame will be among the next
+        [Subscriptions::pending_frames] for any which had been sent to the
+        exchange. */
+    pub  fn  remove  (&mut self,  channel:  Channel,  pairs:  &[&str])
+               ->  &mut Self
+    {
+        let  wanted  =  |p: &Option<String>|
+                            channel.is_private ()
+                              ||  pairs.iter ()
+                                       .any (|q| p.as_deref () == Some (q));
+
+        let  mut  gone  =  Vec::new ();
+        self.entries.retain_mut (|(c, p, S)|
+              {   if  *c != channel  ||  ! wanted (p)   {   return  true;   }
+                  match  S
+                     {   Sub_Status::SUBSCRIBED | Sub_Status::PENDING
+                               =>  {  *S = Sub_Status::CLOSING;
+                                      gone.push (p.clone ());
+                                      true  }
+                         Sub_Status::CLOSING  =>  true,
+                         _  =>  false   }   });
+
+        if  ! gone.is_empty ()
+            {   let  frame  =  self.frame ("unsubscribe", channel, &gone);
+                self.unsent.push (frame);   }
+        self
+    }
+
+
+    /** The state of the subscription to `channel` for `pair` (`None` for a
+        private channel), if it is in the registry. */
+    pub  fn  status  (&self,  channel:  Channel,  pair:  Option<&str>)
+               ->  Option<&Sub_Status>
+    {
+        self.entries.iter ()
+                    .find (|(c, p, _)|  *c == channel
+                                            &&  p.as_deref () == pair)
+                    .map (|(_, _, S)| S)
+    }
+
+    /** Every subscription in the registry with its state. */
+    pub  fn  all  (&self)
+               ->  impl Iterator<Item = (Channel, Option<&str>, &Sub_Status)>
+    {
+        self.entries.iter ().map (|(c, p, S)|  (*c, p.as_deref (), S))
+    }
+
+
+    /** The frames which need to be sent to the exchange to bring it into line
+        with the registry: one subscribe frame for each channel with wanted
+        subscriptions, and any unsubscribe frames due from
+        [Subscriptions::remove].  Each frame is only given out once, and the
+        subscriptions in it become pending. */
+    pub  fn  pending_frames  (&mut self)  ->  Vec<String>
+    {
+        let  mut  frames  =  std::mem::take (&mut self.unsent);
+        let  mut  done  =  Vec::<Channel>::new ();
+
+        for  i  in  0 .. self.entries.len ()
+        {
+            let  (channel, _, S)  =  &self.entries [i];
+            if  *S != Sub_Status::WANTED  ||  done.contains (channel)
+                {   continue;   }
+            let  channel  =  *channel;
+            done.push (channel);
+
+            let  mut  pairs  =  Vec::new ();
+            for  (c, p, S)  in  &mut self.entries
+            {   if  *c == channel  &&  *S == Sub_Status::WANTED
+                    {   pairs.push (p.clone ());
+                        *S  =  Sub_Status::PENDING;   }   }
+
+            frames.push (self.frame ("subscribe", channel, &pairs));
+        }
+
+        frames
+    }
+
+    /*  Put together a subscribe or unsubscribe frame. */
+    fn  frame  (&mut self,  event:  &str,  channel:  Channel,
+                pairs:  &[Option<String>])  ->  String
+    {
+        self.reqid  +=  1;
+        let  mut  F  =  JSN::json! ({ "event": event,
+                                      "reqid": self.reqid,
+                                      "subscription":
+                                          channel.subscription
+                                                 (self.token.as_deref ()) });
+        if  ! channel.is_private ()
+            {   F ["pair"]  =  JSN::json! (pairs.iter ().flatten ()
+                                                 .collect::<Vec<_>> ());   }
+        F.to_string ()
+    }
+
+
+    /** Mark every subscription as wanted, so that they are all sent again by
+        [Subscriptions::pending_frames]; for use after the connection has been
+        re-established.  Subscriptions which were being closed are dropped. */
+    pub  fn  reset  (&mut self)
+    {
+        self.unsent.clear ();
+        self.entries.retain (|(_, _, S)|  *S != Sub_Status::CLOSING);
+        for  (_, _, S)  in  &mut self.entries
+            {   *S  =  Sub_Status::WANTED;   }
+    }
+
+
+    /** Look at a frame received from the exchange, and update the registry if
+        it is a subscription status event.  Returns whether it was one. */
+    pub  fn  observe  (&mut self,  frame:  &JSN::Value)  ->  bool
+    {
+        if  frame ["event"] != "subscriptionStatus"   {   return  false;   }
+
+        let  pair  =  frame ["pair"].as_str ();
+        let  sub   =  &frame ["subscription"];
+
+        let  Some (i)  =  self.entries.iter ().position (|(c, p, _)|
+                                  c.is_described_by (sub)
+                                      &&  (c.is_private ()
+                                             ||  p.as_deref () == pair))
+              else  {  return  true;  };
+
+        match  frame ["status"].as_str ().unwrap_or ("")
+           {   "subscribed"    =>  self.entries [i].2 = Sub_Status::SUBSCRIBED,
+               "unsubscribed"  =>  {  self.entries.remove (i);  }
+               _  =>  self.entries [i].2
+                         =  Sub_Status::FAILED
+                               (reply::string (frame, "errorMessage")
+                                      .unwrap_or_else (|E| E))   }
+
+        true
+    }
+}
+
+
+
+/** Sorts the frames arriving on a WebSockets connection, as typed
+    [Ws_Message]s, into bounded queues, so that the thread reading the
+    connection never waits on the consumers of market data, and so that a slow
+    consumer cannot make memory grow without limit.
+
+    Market data goes to a queue which throws away its oldest messages when
+    full, and the private feeds (our own trades and orders) to one which never
+    throws anything away, but holds up the connection instead.  Other events
+    (system status, replies to orders placed over the socket, errors) go to a
+    third queue, which drops the oldest; heartbeats are discarded.
+
+    ```ignore
+    let  I  =  KKN::Ws_Inbox::new (1000);
+    let  fills  =  I.private.clone ();   //  Hand this to the strategy thread.
+    loop  {  I.deliver (&socket.read () ?, &mut subscriptions) ?;  }
+    ```  */
+
+#[derive(Clone)]
+pub  struct  Ws_Inbox
+{
+    /** Messages from the public feeds. */
+    pub  market:   Message_Queue<Ws_Message>,
+
+    /** Messages from the private feeds. */
+    pub  private:  Message_Queue<Ws_Message>,
+
+    /** Event messages other than heartbeats and subscription status. */
+    pub  events:   Message_Queue<Ws_Message>
+}
+
+
+
+impl  Ws_Inbox
+{
+    /** An inbox whose queues hold up to `capacity` frames each. */
+    pub  fn  new  (capacity:  usize)  ->  Self
+    {
+        Ws_Inbox {  market:   Message_Queue::new (capacity,
+                                                  Overflow::DROP_OLDEST),
+                    private:  Message_Queue::new (capacity, Overflow::BLOCK),
+                    events:   Message_Queue::new (capacity,
+                                                  Overflow::DROP_OLDEST)  }
+    }
+
+    /** Parse a text frame from the exchange into a [Ws_Message] and put it on
+        the right queue, giving subscription status events to `subscriptions`
+        instead. */
+    pub  fn  deliver  (&self,  frame:  &str,
+                       subscriptions:  &mut Subscriptions)
+               ->  Result<(), String>
+    {
+        let  V  =  JSN::from_str::<JSN::Value> (frame)
+                       .map_err (|E| E.to_string ()) ?;
+
+        if  subscriptions.observe (&V)   {   return  Ok (());   }
+
+        match  Ws_Message::from_json (&V) ?
+           {   Ws_Message::HEARTBEAT  =>  (),
+               M @ (Ws_Message::OWN_TRADES (..) | Ws_Message::OPEN_ORDERS (..))
+                   =>  {  self.private.push (M);  }
+               M @ (Ws_Message::TICKER (..) | Ws_Message::BOOK { .. }
+                      | Ws_Message::OHLC (..) | Ws_Message::TRADE (..)
+                      | Ws_Message::SPREAD (..))
+                   =>  {  self.market.push (M);  }
+               M  =>  {  self.events.push (M);  }   }
+
+        Ok (())
+    }
+}
+
+
+
+impl  Kraken_API
+{
+/** Get a fresh token for the private WebSockets feeds, and give it to the
+    `subscriptions` registry.  */
+
+  pub  fn  authorize_subscriptions  (&mut self,
+                                     subscriptions:  &mut Subscriptions)
+             ->  Result<(), String>
+    {
+      let  R  =  reply::result_of (&self.websockets_token () ?) ?;
+      subscriptions.set_token (&reply::string (&R, "token") ?);
+      Ok (())
+    }
+}
+
+
+
+#[cfg(test)]
+mod  test
+  {  #[test]  fn  subscriptions ()
+     {
+         use  super::{Channel, Subscriptions, Sub_Status};
+         use  serde_json::json;
+
+         let  mut  S  =  Subscriptions::new ();
+         S.ticker (&["XBT/USD", "ETH/USD"]).book (&["XBT/USD"], 25);
+         S.set_token ("T").own_trades ();
+
+         let  F  =  S.pending_frames ();
+         assert_eq! (F.len (),  3);
+         assert! (F [0].contains (r#""pair":["XBT/USD","ETH/USD"]"#));
+         assert! (F [2].contains (r#""token":"T""#));
+         assert! (S.pending_frames ().is_empty ());
+
+         S.observe (&json! ({"event": "subscriptionStatus",
+                             "status": "subscribed", "pair": "XBT/USD",
+                             "subscription": {"name": "book", "depth": 25}}));
+         S.observe (&json! ({"event": "subscriptionStatus",
+                             "status": "error", "pair": "ETH/USD",
+                             "errorMessage": "nope",
+                             "subscription": {"name": "ticker"}}));
+
+         assert_eq! (S.status (Channel::BOOK (25), Some ("XBT/USD")),
+                     Some (&Sub_Status::SUBSCRIBED));
+         assert_eq! (S.status (Channel::TICKER, Some ("ETH/USD")),
+                     Some (&Sub_Status::FAILED ("nope".to_string ())));
+
+         S.remove (Channel::BOOK (25), &["XBT/USD"]);
+         assert! (S.pending_frames () [0].contains ("unsubscribe"));
+         assert_eq! (S.status (Channel::BOOK (25), Some ("XBT/USD")),
+                     Some (&Sub_Status::CLOSING));
+
+         let  I  =  super::Ws_Inbox::new (10);
+         for  F  in  [r#"{"event":"heartbeat"}"#,
+                      r#"[42,["1.0","1.1","1542057299.5"],"spread","XBT/USD"]"#,
+                      r#"[[{"O1":{"status":"open"}}],"openOrders",{}]"#,
+                      r#"{"event":"systemStatus","status":"online"}"#]
+             {   I.deliver (F, &mut S).unwrap ();   }
+         assert_eq! ((I.market.len (), I.private.len (), I.events.len ()),
+                     (1, 1, 1));
+     }  }
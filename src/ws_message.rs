@@ -0,0 +1,519 @@
+/*
+  dmbcs-kraken-api-rust  Kraken API client library in Rust
+  Copyright (C) 2022  Dale Mellor
+
+  This program is free software: you can redistribute it and/or modify it under
+  the terms of the GNU General Public License as published by the Free Software
+  Foundation, either version 3 of the License, or (at your option) any later
+  version.
+
+  This program is distributed in the hope that it will be useful, but WITHOUT
+  ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+  FOR A PARTICULAR PURPOSE.  See the GNU General Public License for more
+  details.
+
+  You should have received a copy of the GNU General Public License along with
+  this program: it is in a file called LICENSE.txt.  If not, see
+  <https://www.gnu.org/licenses/>.
+*/
+
+
+
+use  crate::{Book_Level, Candle, Instruction, Kraken_API, Spread,
+             System_Status, Trade};
+use  crate::reply;
+use  serde_json  as  JSN;
+use  std::collections::BTreeMap;
+
+
+
+/** A pair's ticker: best prices, last trade and the day's statistics.  This
+    is what the WebSockets ticker feed sends, and what the
+    [Kraken_API::ticker_info](crate::Kraken_API::ticker_info) end-point sends
+    for each pair. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Ticker
+{
+    /** The best bid price. */
+    pub  bid:          f64,
+
+    /** The volume at the best bid. */
+    pub  bid_volume:   f64,
+
+    /** The best ask price. */
+    pub  ask:          f64,
+
+    /** The volume at the best ask. */
+    pub  ask_volume:   f64,
+
+    /** The price of the last trade. */
+    pub  last:         f64,
+
+    /** The volume of the last trade. */
+    pub  last_volume:  f64,
+
+    /** The volume traded in the last 24 hours. */
+    pub  volume_24h:   f64,
+
+    /** The volume-weighted average price over the last 24 hours. */
+    pub  vwap_24h:     f64,
+
+    /** The lowest price in the last 24 hours. */
+    pub  low_24h:      f64,
+
+    /** The highest price in the last 24 hours. */
+    pub  high_24h:     f64,
+
+    /** The number of trades in the last 24 hours. */
+    pub  trades_24h:   u64,
+
+    /** Today's opening price. */
+    pub  open:         f64
+}
+
+impl  Ticker
+{
+    /** Interpret a ticker object, in which every field is an array of
+        values. */
+    pub  fn  from_json  (V:  &JSN::Value)  ->  Result<Self, String>
+    {
+        let  n  =  |f: &str, i: usize|
+                       reply::number (&V [f] [i])
+                             .map_err (|E| format! ("ticker '{}': {}", f, E));
+        let  open  =  match  &V ["o"]
+                        {   JSN::Value::Array (_)  =>  n ("o", 0) ?,
+                            O  =>  reply::number (O) ?   };
+
+        Ok (Ticker {  bid:          n ("b", 0) ?,
+                      bid_volume:   n ("b", 2) ?,
+                      ask:          n ("a", 0) ?,
+                      ask_volume:   n ("a", 2) ?,
+                      last:         n ("c", 0) ?,
+                      last_volume:  n ("c", 1) ?,
+                      volume_24h:   n ("v", 1) ?,
+                      vwap_24h:     n ("p", 1) ?,
+                      low_24h:      n ("l", 1) ?,
+                      high_24h:     n ("h", 1) ?,
+                      trades_24h:   n ("t", 1) ? as u64,
+                      open  })
+    }
+
+    /** The gap between the best ask and the best bid. */
+    pub  fn  spread  (&self)  ->  f64   {   self.ask - self.bid   }
+
+    /** The price half-way between the best bid and the best ask. */
+    pub  fn  mid  (&self)  ->  f64   {   (self.bid + self.ask) / 2.0   }
+
+    /** The spread as a percentage of the mid price. */
+    pub  fn  spread_percent  (&self)  ->  f64
+          {   100.0 * self.spread () / self.mid ()   }
+
+    /** The change in price since today's opening, in percent. */
+    pub  fn  change_percent  (&self)  ->  f64
+          {   100.0 * (self.last - self.open) / self.open   }
+}
+
+
+
+impl  Kraken_API
+{
+/** Get the [Ticker] of each of the `pairs`, keyed by the name the exchange
+    gives the pair in its reply (its canonical name, e.g. "XXBTZUSD"), with
+    one call to the [Kraken_API::ticker_info] end-point.  */
+
+  pub  fn  tickers  (&mut self,  pairs:  &[&str])
+               ->  Result<BTreeMap<String, Ticker>, String>
+    {
+      let  R  =  reply::result_of (&self.ticker_info (pairs.join (",")) ?) ?;
+      R.as_object ()
+       .into_iter ()
+       .flatten ()
+       .map (|(name, V)|  Ok ((name.clone (), Ticker::from_json (V) ?)))
+       .collect ()
+    }
+}
+
+
+
+/** One of our own trades, as sent by the WebSockets ownTrades feed; the
+    entries given by [Kraken_API::trades_info] and
+    [Kraken_API::trades_history] have the same form, but with the pair in the
+    REST form (e.g. "XXBTZUSD"). */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Own_Trade
+{
+    /** The trade's transaction ID. */
+    pub  txid:        String,
+
+    /** The transaction ID of the order which made the trade. */
+    pub  order_txid:  String,
+
+    /** The pair traded, in WebSockets form (e.g. "XBT/USD"). */
+    pub  pair:        String,
+
+    /** UNIX time of the trade. */
+    pub  time:        f64,
+
+    /** Whether we bought or sold. */
+    pub  side:        Instruction,
+
+    /** The price of the trade. */
+    pub  price:       f64,
+
+    /** The volume traded. */
+    pub  volume:      f64,
+
+    /** The total cost of the trade. */
+    pub  cost:        f64,
+
+    /** The fee paid. */
+    pub  fee:         f64
+}
+
+impl  Own_Trade
+{
+    /** Interpret one entry of an ownTrades frame; `txid` is the key under
+        which the entry was found. */
+    pub  fn  from_json  (txid:  &str,  V:  &JSN::Value)
+              ->  Result<Self, String>
+    {
+        let  number  =  |f: &str|  reply::number (&V [f]);
+        Ok (Own_Trade {  txid:        txid.to_string (),
+                         order_txid:  reply::string (V, "ordertxid") ?,
+                         pair:        reply::string (V, "pair") ?,
+                         time:        number ("time") ?,
+                         side:        if  V ["type"] == "sell"
+                                          { Instruction::SELL }
+                                      else  { Instruction::BUY },
+                         price:       number ("price") ?,
+                         volume:      number ("vol") ?,
+                         cost:        number ("cost") ?,
+                         fee:         number ("fee") ?  })
+    }
+}
+
+
+
+/** A change to one of our orders, as sent by the WebSockets openOrders feed.
+    The first frame of the feed holds the full state of every open order;
+    after that, only the fields which have changed are sent. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  struct  Order_Update
+{
+    /** The order's transaction ID. */
+    pub  txid:      String,
+
+    /** The order's new status ("pending", "open", "closed", "canceled" or
+        "expired"), if it has changed. */
+    pub  status:    Option<String>,
+
+    /** The volume executed so far, if it has changed. */
+    pub  vol_exec:  Option<f64>,
+
+    /** The fields of the update, as sent. */
+    pub  fields:    JSN::Value
+}
+
+
+
+/** A message received on a WebSockets connection, put into typed form by
+    [Ws_Message::from_json]. */
+
+#[derive(Clone, Debug, PartialEq)]
+pub  enum  Ws_Message
+{
+    /** A ticker update for a pair. */
+    TICKER  (String, Ticker),
+
+    /** Order book levels for a pair: the whole book (to the subscribed depth)
+        if `snapshot`, otherwise only the levels which have changed, where a
+        volume of zero means that the level has gone. */
+    BOOK
+    {
+        /** The pair, in WebSockets form. */
+        pair:      String,
+        /** Whether this is the initial snapshot. */
+        snapshot:  bool,
+        /** Bid levels. */
+        bids:      Vec<Book_Level>,
+        /** Ask levels. */
+        asks:      Vec<Book_Level>,
+        /** The exchange's CRC32 checksum of the top of the book, if sent. */
+        checksum:  Option<String>
+    },
+
+    /** The candle in progress for a pair. */
+    OHLC  (String, Candle),
+
+    /** Trades made in a pair. */
+    TRADE  (String, Vec<Trade>),
+
+    /** The best bid and ask of a pair. */
+    SPREAD  (String, Spread),
+
+    /** Our own trades, with the feed's sequence number. */
+    OWN_TRADES  (Vec<Own_Trade>, u64),
+
+    /** Changes to our own orders, with the feed's sequence number. */
+    OPEN_ORDERS  (Vec<Order_Update>, u64),
+
+    /** A reply to a subscribe or unsubscribe request. */
+    SUBSCRIPTION_STATUS
+    {
+        /** The channel name, e.g. "book-25"; absent on errors. */
+        channel:  Option<String>,
+        /** The pair, for public feeds. */
+        pair:     Option<String>,
+        /** "subscribed", "unsubscribed" or "error". */
+        status:   String,
+        /** The exchange's message, if the status is "error". */
+        error:    Option<String>
+    },
+
+    /** The exchange's operating mode. */
+    SYSTEM_STATUS  (System_Status),
+
+    /** A sign of life from the exchange, sent when there is no other
+        traffic. */
+    HEARTBEAT,
+
+    /** An error reported by the exchange outside of any subscription. */
+    ERROR  (String),
+
+    /** Anything else, as sent. */
+    OTHER  (JSN::Value)
+}
+
+
+
+impl  Ws_Message
+{
+    /** Interpret a frame received from the exchange. */
+    pub  fn  from_json  (V:  &JSN::Value)  ->  Result<Self, String>
+    {
+        if  V.is_object ()   {   return  Ok (event (V));   }
+
+        let  A  =  match  V.as_array ()
+                     {   Some (A)  if  A.len () >= 3  =>  A,
+                         _  =>  Err (format! ("unrecognised frame {}", V))?  };
+
+        /*  The channel name is always second from the end: [id, data...,
+         *  name, pair] for public feeds, [data, name, {sequence}] for private
+         *  ones. */
+        let  name  =  A [A.len () - 2].as_str ().unwrap_or ("");
+        let  (channel, param)  =  name.split_once ('-').unwrap_or ((name, ""));
+
+        if  channel == "ownTrades"  ||  channel == "openOrders"
+            {   return  private (channel, &A [0], &A [2]);   }
+
+        let  pair  =  A [A.len () - 1].as_str ().unwrap_or ("").to_string ();
+        let  data  =  &A [1 .. A.len () - 2];
+        let  D     =  &data [0];
+
+        let  n  =  |i: usize|  reply::number (&D [i]);
+
+        Ok (match  channel
+              {   "ticker"  =>  Ws_Message::TICKER (pair,
+                                                    Ticker::from_json (D) ?),
+                  "book"    =>  book (pair, data) ?,
+                  "ohlc"    =>  Ws_Message::OHLC
+                                    (pair,
+                                     candle (D, param.parse ().unwrap_or (1))?),
+                  "trade"   =>  Ws_Message::TRADE
+                                    (pair,  array_of (D, Trade::from_json) ?),
+                  "spread"  =>  Ws_Message::SPREAD
+                                    (pair,  Spread {  bid:   n (0) ?,
+                                                      ask:   n (1) ?,
+                                                      time:  n (2) ?  }),
+                  _  =>  Ws_Message::OTHER (V.clone ())   })
+    }
+}
+
+
+
+/*  Interpret an event frame (a JSON object rather than an array). */
+
+fn  event  (V:  &JSN::Value)  ->  Ws_Message
+{
+    let  text  =  |f: &str|  V [f].as_str ().map (str::to_string);
+
+    match  V ["event"].as_str ().unwrap_or ("")
+       {   "heartbeat"  =>  Ws_Message::HEARTBEAT,
+           "subscriptionStatus"
+               =>  Ws_Message::SUBSCRIPTION_STATUS
+                         {  channel:  text ("channelName"),
+                            pair:     text ("pair"),
+                            status:   text ("status").unwrap_or_default (),
+                            error:    text ("errorMessage")  },
+           "systemStatus"
+               =>  match  V ["status"].as_str ()
+                              .and_then (System_Status::from_kraken_string)
+                     {   Some (S)  =>  Ws_Message::SYSTEM_STATUS (S),
+                         None  =>  Ws_Message::OTHER (V.clone ())   },
+           "error"  =>  Ws_Message::ERROR (text ("errorMessage")
+                                               .unwrap_or_default ()),
+           _  =>  match  text ("errorMessage")
+                     {   Some (E)  =>  Ws_Message::ERROR (E),
+                         None  =>  Ws_Message::OTHER (V.clone ())   }   }
+}
+
+
+
+/*  Interpret the data of an ownTrades or openOrders frame: an array of
+ *  objects, each holding one entry keyed by transaction ID. */
+
+fn  private  (channel:  &str,  data:  &JSN::Value,  tail:  &JSN::Value)
+         ->  Result<Ws_Message, String>
+{
+    let  sequence  =  tail ["sequence"].as_u64 ().unwrap_or (0);
+    let  entries   =  data.as_array ()
+                          .ok_or (format! ("bad {} frame", channel)) ?
+                          .iter ()
+                          .filter_map (JSN::Value::as_object)
+                          .flatten ();
+
+    if  channel == "ownTrades"
+    {
+        Ok (Ws_Message::OWN_TRADES
+                (entries.map (|(txid, V)| Own_Trade::from_json (txid, V))
+                        .collect::<Result<_, _>> () ?,
+                 sequence))
+    }
+    else
+    {
+        Ok (Ws_Message::OPEN_ORDERS
+                (entries.map (|(txid, V)|
+                                 Ok (Order_Update {
+                                       txid:      txid.clone (),
+                                       status:    V ["status"]
+                                                     .as_str ()
+                                                     .map (str::to_string),
+                                       vol_exec:  V.get ("vol_exec")
+                                                   .map (reply::number)
+                                                   .transpose () ?,
+                                       fields:    V.clone ()  }))
+                        .collect::<Result<_, String>> () ?,
+                 sequence))
+    }
+}
+
+
+
+/*  Interpret the data of a book frame.  A snapshot has "as" and "bs" arrays;
+ *  an update has "a" and/or "b" arrays, possibly in two separate objects, and
+ *  a "c" checksum. */
+
+fn  book  (pair:  String,  data:  &[JSN::Value])  ->  Result<Ws_Message, String>
+{
+    let  snapshot  =  data [0].get ("as").is_some ()
+                          ||  data [0].get ("bs").is_some ();
+    let  mut  bids      =  Vec::new ();
+    let  mut  asks      =  Vec::new ();
+    let  mut  checksum  =  None;
+
+    for  D  in  data
+    {
+        if let Some (V) = D.get ("as").or (D.get ("a"))
+            {   asks.extend (array_of (V, Book_Level::from_json) ?);   }
+        if let Some (V) = D.get ("bs").or (D.get ("b"))
+            {   bids.extend (array_of (V, Book_Level::from_json) ?);   }
+
+        if let Some (C) = D ["c"].as_str ()
+            {   checksum  =  Some (C.to_string ());   }
+    }
+
+    Ok (Ws_Message::BOOK {  pair,  snapshot,  bids,  asks,  checksum  })
+}
+
+
+
+/*  Interpret the data of an ohlc frame: [time, etime, open, high, low, close,
+ *  vwap, volume, count], where etime is the end of the interval. */
+
+fn  candle  (V:  &JSN::Value,  interval:  u32)  ->  Result<Candle, String>
+{
+    let  n  =  |i: usize|  reply::number (&V [i]);
+    Ok (Candle {  time:    n (1) ? - f64::from (interval) * 60.0,
+                  open:    n (2) ?,
+                  high:    n (3) ?,
+                  low:     n (4) ?,
+                  close:   n (5) ?,
+                  vwap:    n (6) ?,
+                  volume:  n (7) ?,
+                  count:   n (8) ? as u64  })
+}
+
+
+
+fn  array_of<T>  (V:  &JSN::Value,
+                  f:  fn (&JSN::Value) -> Result<T, String>)
+             ->  Result<Vec<T>, String>
+{
+    V.as_array ()
+     .ok_or (format! ("expected an array, got {}", V)) ?
+     .iter ()
+     .map (f)
+     .collect ()
+}
+
+
+
+#[cfg(test)]
+mod  test
+  {  #[test]  fn  ticker ()  ->  Result<(), String>
+     {
+         let  T  =  super::Ticker::from_json (&serde_json::json! (
+                       {"a": ["30010.0", "1", "1.500"],
+                        "b": ["29990.0", "2", "2.000"],
+                        "c": ["30000.0", "0.01"],
+                        "v": ["100.0", "250.0"],
+                        "p": ["29900.0", "29800.0"],
+                        "t": [400, 1000],
+                        "l": ["29500.0", "29000.0"],
+                        "h": ["30100.0", "30500.0"],
+                        "o": "28500.0"})) ?;
+
+         assert_eq! ((T.bid_volume, T.ask_volume), (2.0, 1.5));
+         assert_eq! ((T.spread (), T.mid ()), (20.0, 30000.0));
+         assert! ((T.spread_percent () - 20.0 / 300.0).abs () < 1e-12);
+         assert! ((T.change_percent () - 1500.0 / 285.0).abs () < 1e-12);
+         assert_eq! ((T.volume_24h, T.trades_24h), (250.0, 1000));
+         Ok (())
+     }
+
+     #[test]  fn  messages ()  ->  Result<(), String>
+     {
+         use  super::Ws_Message as M;
+         use  serde_json::json;
+
+         let  B  =  M::from_json (&json! (
+                       [336, {"a": [["5541.3", "2.5", "1534614248.4", "r"]]},
+                        {"b": [["5541.2", "0.0", "1534614248.5"]],
+                         "c": "974942666"},
+                        "book-10", "XBT/USD"])) ?;
+         match  B
+           {   M::BOOK {  snapshot,  bids,  asks,  checksum,  ..  }
+                   =>  {  assert! (! snapshot);
+                          assert_eq! ((bids.len (), asks.len ()),  (1, 1));
+                          assert_eq! (bids [0].volume,  0.0);
+                          assert_eq! (checksum.as_deref (),
+                                      Some ("974942666"));  }
+               _  =>  panic! ("not a book")   }
+
+         let  O  =  M::from_json (&json! (
+                       [[{"OGTT3Y": {"status": "closed", "vol_exec": "1.0"}}],
+                        "openOrders", {"sequence": 7}])) ?;
+         match  O
+           {   M::OPEN_ORDERS (U, 7)
+                   =>  assert_eq! ((U [0].status.as_deref (), U [0].vol_exec),
+                                   (Some ("closed"), Some (1.0))),
+               _  =>  panic! ("not an order update")   }
+
+         assert_eq! (M::from_json (&json! ({"event": "heartbeat"})) ?,
+                     M::HEARTBEAT);
+
+         Ok (())
+     }  }